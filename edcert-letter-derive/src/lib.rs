@@ -0,0 +1,166 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! This crate provides `#[derive(Fingerprint)]` and `#[derive(Claims)]` for edcert-letter. Use
+//! it through the `derive` feature of edcert-letter instead of depending on it directly.
+
+#![deny(missing_docs)]
+
+extern crate proc_macro;
+extern crate proc_macro2;
+#[macro_use]
+extern crate quote;
+extern crate syn;
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use syn::Data;
+use syn::DeriveInput;
+use syn::Fields;
+use syn::Ident;
+use syn::Index;
+
+/// Implements `Fingerprint` and `canonical::Encode` for a struct. The fingerprint is the
+/// canonical encoding of all fields in declaration order, so every field must implement
+/// `canonical::Encode`.
+#[proc_macro_derive(Fingerprint)]
+pub fn derive_fingerprint(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("Failed to parse the derive input.");
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.data {
+        Data::Struct(ref data) => encode_fields(&data.fields),
+        _ => {
+            return quote! {
+                compile_error!("#[derive(Fingerprint)] is only supported on structs");
+            }
+            .into()
+        }
+    };
+
+    let expanded = quote! {
+        impl #impl_generics ::edcert_letter::canonical::Encode for #name #ty_generics #where_clause {
+            fn encode(&self, out: &mut Vec<u8>) {
+                #fields
+            }
+        }
+
+        impl #impl_generics ::edcert_letter::canonical::Fingerprint for #name #ty_generics #where_clause {
+            fn fingerprint(&self) -> Vec<u8> {
+                ::edcert_letter::canonical::to_bytes(self)
+            }
+        }
+    };
+
+    expanded.into()
+}
+
+/// Implements `claims::Claims` for a struct with named fields. Fields are marked with
+/// `#[claim(issuer)]`, `#[claim(subject)]`, `#[claim(audience)]` or `#[claim(expires)]`. String
+/// claims must implement `claims::StrClaim`, the expiry `claims::TimeClaim`.
+#[proc_macro_derive(Claims, attributes(claim))]
+pub fn derive_claims(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("Failed to parse the derive input.");
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let fields = match input.data {
+        Data::Struct(ref data) => {
+            match data.fields {
+                Fields::Named(ref fields) => fields,
+                _ => return error("#[derive(Claims)] needs a struct with named fields"),
+            }
+        }
+        _ => return error("#[derive(Claims)] is only supported on structs"),
+    };
+
+    let mut methods = Vec::new();
+    let mut seen = Vec::new();
+
+    for field in &fields.named {
+        for attr in field.attrs.iter().filter(|a| a.path.is_ident("claim")) {
+            let claim: Ident = match attr.parse_args() {
+                Ok(claim) => claim,
+                Err(_) => return error("expected #[claim(issuer|subject|audience|expires)]"),
+            };
+
+            if seen.contains(&claim) {
+                return error("a claim can only be given once");
+            }
+
+            let ident = &field.ident;
+            methods.push(match claim.to_string().as_str() {
+                "issuer" | "subject" | "audience" => {
+                    quote! {
+                        fn #claim(&self) -> Option<&str> {
+                            ::edcert_letter::claims::StrClaim::claim(&self.#ident)
+                        }
+                    }
+                }
+                "expires" => {
+                    quote! {
+                        fn expires(&self) -> Option<::edcert_letter::claims::Timestamp> {
+                            ::edcert_letter::claims::TimeClaim::claim(&self.#ident)
+                        }
+                    }
+                }
+                _ => return error("expected #[claim(issuer|subject|audience|expires)]"),
+            });
+            seen.push(claim);
+        }
+    }
+
+    let expanded = quote! {
+        impl #impl_generics ::edcert_letter::claims::Claims for #name #ty_generics #where_clause {
+            #(#methods)*
+        }
+    };
+
+    expanded.into()
+}
+
+fn error(message: &str) -> TokenStream {
+    quote!(compile_error!(#message);).into()
+}
+
+fn encode_fields(fields: &Fields) -> TokenStream2 {
+    match *fields {
+        Fields::Named(ref fields) => {
+            let names = fields.named.iter().map(|f| &f.ident);
+            quote! {
+                #(::edcert_letter::canonical::Encode::encode(&self.#names, out);)*
+            }
+        }
+        Fields::Unnamed(ref fields) => {
+            let indices = (0..fields.unnamed.len()).map(Index::from);
+            quote! {
+                #(::edcert_letter::canonical::Encode::encode(&self.#indices, out);)*
+            }
+        }
+        Fields::Unit => quote! {
+            let _ = out;
+        },
+    }
+}
@@ -0,0 +1,223 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed letters encrypted with age.
+//!
+//! An `AgeSealedLetter` is a serialized letter encrypted to one or more age recipients, either
+//! native X25519 recipients (`age1...`) or SSH public keys (`ssh-ed25519 ...`, `ssh-rsa ...`).
+//! The result is a plain age file, so it can also be decrypted with the `age` command line tool.
+//! Like with `SealedLetter`, the recipients are written into the header before signing, so a
+//! recipient can't decrypt a letter and pass it on as if it had been sealed for someone else.
+
+use std::error::Error;
+use std::fmt;
+use std::io::Read;
+use std::io::Write;
+use std::marker::PhantomData;
+
+use age::Decryptor;
+use age::Encryptor;
+use age::Identity;
+use age::Recipient;
+
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::Signer;
+
+/// The metadata key holding the recipients, one per line.
+pub const AGE_RECIPIENTS_KEY: &str = "age.recipients";
+
+/// This error is returned, if a letter can't be sealed or opened with age.
+#[derive(Clone, PartialEq, Debug)]
+pub enum AgeSealError {
+    /// The recipient isn't an age or SSH public key.
+    InvalidRecipient(String),
+    /// The signer has no private key.
+    NoPrivateKey,
+    /// The letter couldn't be encrypted.
+    Encrypt(String),
+    /// The letter isn't encrypted to this identity or has been modified.
+    Decrypt,
+    /// The decrypted bytes aren't a letter.
+    Decode(DecodeError),
+    /// The letter isn't validly signed.
+    Invalid(ValidationError),
+    /// The letter was signed for other recipients.
+    WrongRecipient,
+}
+
+impl fmt::Display for AgeSealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            AgeSealError::InvalidRecipient(ref r) => write!(f, "invalid age recipient {}", r),
+            AgeSealError::NoPrivateKey => write!(f, "the signer has no private key"),
+            AgeSealError::Encrypt(ref e) => write!(f, "can't encrypt letter: {}", e),
+            AgeSealError::Decrypt => write!(f, "can't decrypt sealed letter"),
+            AgeSealError::Decode(ref e) => write!(f, "can't decode sealed letter: {}", e),
+            AgeSealError::Invalid(ref e) => write!(f, "sealed letter is not valid: {:?}", e),
+            AgeSealError::WrongRecipient => write!(f, "sealed letter was signed for another recipient"),
+        }
+    }
+}
+
+impl Error for AgeSealError {}
+
+/// Parses an age or SSH recipient.
+fn parse_recipient(recipient: &str) -> Result<Box<dyn Recipient + Send>, AgeSealError> {
+    if let Ok(r) = recipient.trim().parse::<::age::x25519::Recipient>() {
+        return Ok(Box::new(r));
+    }
+
+    match recipient.trim().parse::<::age::ssh::Recipient>() {
+        Ok(r) => Ok(Box::new(r)),
+        Err(_) => Err(AgeSealError::InvalidRecipient(recipient.to_string())),
+    }
+}
+
+/// Returns the recipient in its canonical form, without an SSH key comment.
+fn normalize_recipient(recipient: &str) -> Result<String, AgeSealError> {
+    if let Ok(r) = recipient.trim().parse::<::age::x25519::Recipient>() {
+        return Ok(r.to_string());
+    }
+
+    match recipient.trim().parse::<::age::ssh::Recipient>() {
+        Ok(r) => Ok(r.to_string()),
+        Err(_) => Err(AgeSealError::InvalidRecipient(recipient.to_string())),
+    }
+}
+
+/// A letter that only the age recipients can read.
+#[derive(Clone, PartialEq, Debug)]
+pub struct AgeSealedLetter<T: FromFingerprint> {
+    ciphertext: Vec<u8>,
+    content: PhantomData<T>,
+}
+
+impl<T: FromFingerprint> AgeSealedLetter<T> {
+    /// Signs the content and encrypts the letter to the recipients.
+    pub fn seal(content: T,
+                mut header: Header,
+                signer: &Signer,
+                recipients: &[&str])
+                -> Result<AgeSealedLetter<T>, AgeSealError> {
+        let mut names = recipients.iter()
+                                  .map(|r| normalize_recipient(r))
+                                  .collect::<Result<Vec<String>, AgeSealError>>()?;
+        names.sort();
+        names.dedup();
+        header.set_meta(AGE_RECIPIENTS_KEY, &names.join("\n"));
+
+        let letter = Letter::sign(content, header, signer).map_err(|_| AgeSealError::NoPrivateKey)?;
+
+        let recipients = recipients.iter()
+                                   .map(|r| parse_recipient(r))
+                                   .collect::<Result<Vec<_>, AgeSealError>>()?;
+        let encryptor = Encryptor::with_recipients(recipients.iter().map(|r| &**r as &dyn Recipient))
+                            .map_err(|e| AgeSealError::Encrypt(e.to_string()))?;
+
+        let mut ciphertext = Vec::new();
+        let mut writer = encryptor.wrap_output(&mut ciphertext)
+                                  .map_err(|e| AgeSealError::Encrypt(e.to_string()))?;
+        writer.write_all(&letter.to_bytes())
+              .and_then(|_| writer.finish())
+              .map_err(|e| AgeSealError::Encrypt(e.to_string()))?;
+
+        Ok(AgeSealedLetter {
+            ciphertext,
+            content: PhantomData,
+        })
+    }
+
+    /// Decrypts the letter with the identity, checks that it was sealed for `recipient`, the
+    /// public key of the identity, and validates it.
+    pub fn open<V: Validator>(&self,
+                              identity: &dyn Identity,
+                              recipient: &str,
+                              cv: &V)
+                              -> Result<Letter<T>, AgeSealError> {
+        let decryptor = Decryptor::new_buffered(&self.ciphertext[..]).map_err(|_| AgeSealError::Decrypt)?;
+        let mut reader = decryptor.decrypt(::std::iter::once(identity)).map_err(|_| AgeSealError::Decrypt)?;
+
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(|_| AgeSealError::Decrypt)?;
+
+        let letter: Letter<T> = Letter::from_bytes(&bytes).map_err(AgeSealError::Decode)?;
+        cv.is_valid(&letter).map_err(AgeSealError::Invalid)?;
+
+        let recipient = normalize_recipient(recipient)?;
+        let sealed_for = letter.header().get_meta(AGE_RECIPIENTS_KEY).unwrap_or("");
+        if !sealed_for.lines().any(|r| r == recipient) {
+            return Err(AgeSealError::WrongRecipient);
+        }
+
+        Ok(letter)
+    }
+
+    /// Returns the age file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.ciphertext.clone()
+    }
+
+    /// Wraps an age file. The letter inside is only checked by `open`.
+    pub fn from_bytes(bytes: &[u8]) -> AgeSealedLetter<T> {
+        AgeSealedLetter {
+            ciphertext: bytes.to_vec(),
+            content: PhantomData,
+        }
+    }
+}
+
+#[test]
+fn test_age_seal_and_open() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let alice = ::age::x25519::Identity::generate();
+    let alice_pk = alice.to_public().to_string();
+    let bob = ::age::x25519::Identity::generate();
+    let bob_pk = bob.to_public().to_string();
+
+    let content = Fingerprintable("secret".to_string());
+    let sealed = AgeSealedLetter::seal(content, Header::new(), &Signer::PrivateKey(&msk), &[&alice_pk]).unwrap();
+    let sealed: AgeSealedLetter<Fingerprintable<String>> = AgeSealedLetter::from_bytes(&sealed.to_bytes());
+
+    assert_eq!("secret", sealed.open(&alice, &alice_pk, &cv).unwrap().as_str());
+    assert_eq!(Err(AgeSealError::Decrypt), sealed.open(&bob, &bob_pk, &cv));
+
+    // Alice passes the letter on to Bob.
+    let letter = sealed.open(&alice, &alice_pk, &cv).unwrap();
+    let forwarded = AgeSealedLetter::<Fingerprintable<String>>::from_bytes(&::age::encrypt(&bob.to_public(), &letter.to_bytes()).unwrap());
+    assert_eq!(Err(AgeSealError::WrongRecipient), forwarded.open(&bob, &bob_pk, &cv));
+
+    assert_eq!(true, AgeSealedLetter::seal(Fingerprintable("x".to_string()), Header::new(), &Signer::PrivateKey(&msk), &["nope"]).is_err());
+}
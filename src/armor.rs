@@ -0,0 +1,90 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! ASCII armor for serialized letters.
+//!
+//! Armored letters are the binary letter format in base64, wrapped in lines of 64 characters
+//! between a `BEGIN` and an `END` line, so they can be kept in text files and pasted into emails
+//! or configuration repositories. Other formats of this crate use the same armor with their own
+//! label.
+
+use rustc_serialize::base64::CharacterSet;
+use rustc_serialize::base64::Config;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::Newline;
+use rustc_serialize::base64::ToBase64;
+
+use format::DecodeError;
+
+/// The label of armored letters.
+pub const LETTER_LABEL: &str = "EDCERT LETTER";
+
+const BASE64: Config = Config {
+    char_set: CharacterSet::Standard,
+    newline: Newline::LF,
+    pad: true,
+    line_length: Some(64),
+};
+
+/// Armors the bytes of a serialized letter.
+pub fn armor(bytes: &[u8]) -> String {
+    armor_with_label(LETTER_LABEL, bytes)
+}
+
+/// Removes the armor and returns the bytes of the serialized letter. Whitespace around the armor
+/// is ignored.
+pub fn dearmor(text: &str) -> Result<Vec<u8>, DecodeError> {
+    dearmor_with_label(LETTER_LABEL, text)
+}
+
+/// Armors the bytes with the given label in the `BEGIN` and `END` lines.
+pub fn armor_with_label(label: &str, bytes: &[u8]) -> String {
+    format!("-----BEGIN {}-----\n{}\n-----END {}-----\n", label, bytes.to_base64(BASE64), label)
+}
+
+/// Removes the armor with the given label. Armor with another label yields
+/// `DecodeError::InvalidMagic`.
+pub fn dearmor_with_label(label: &str, text: &str) -> Result<Vec<u8>, DecodeError> {
+    let begin = format!("-----BEGIN {}-----", label);
+    let end = format!("-----END {}-----", label);
+    let text = text.trim();
+
+    if !text.starts_with(&begin) || !text.ends_with(&end) || text.len() < begin.len() + end.len() {
+        return Err(DecodeError::InvalidMagic);
+    }
+
+    text[begin.len()..text.len() - end.len()]
+        .from_base64()
+        .map_err(|_| DecodeError::InvalidContent)
+}
+
+#[test]
+fn test_armor() {
+    let bytes: Vec<u8> = (0..200).map(|i| i as u8).collect();
+    let text = armor(&bytes);
+
+    assert_eq!(true, text.lines().all(|line| line.len() <= 64 || line.starts_with("-----")));
+    assert_eq!(Ok(bytes.clone()), dearmor(&format!("\n  {}  \n", text)));
+    assert_eq!(Err(DecodeError::InvalidMagic), dearmor("EDL"));
+    assert_eq!(Err(DecodeError::InvalidMagic), dearmor(&armor_with_label("OTHER", &bytes)));
+}
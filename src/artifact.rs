@@ -0,0 +1,259 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Verifying plugins before they are loaded.
+//!
+//! An `ArtifactManifest` records the file name, length and hash of a single file, like a plugin
+//! library. `verify_artifact_before_load` validates the manifest letter and checks the file in
+//! one call, meant to gate `dlopen` or `libloading`.
+//!
+//! The file is opened once and hashed through that handle, which `VerifiedArtifact` keeps open.
+//! Loading the library by its path afterwards still races with someone replacing the file in
+//! between. On Linux, `VerifiedArtifact::fd_path` returns a `/proc/self/fd` path of the open
+//! handle, which always refers to the verified file.
+
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use codec::Reader;
+use codec::Writer;
+use digest::HashAlgorithm;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::Signer;
+
+/// The content type of artifact manifest letters.
+pub const ARTIFACT_CONTENT_TYPE: &str = "edcert-letter/artifact";
+
+/// This error is returned, if an artifact can't be signed or doesn't match its manifest.
+#[derive(Debug)]
+pub enum ArtifactError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The signer has no private key.
+    NoPrivateKey,
+    /// The manifest letter isn't validly signed or isn't an artifact manifest.
+    Invalid(ValidationError),
+    /// The file has another name than the manifest.
+    WrongName(String),
+    /// The file differs from the manifest.
+    Modified,
+}
+
+impl fmt::Display for ArtifactError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ArtifactError::Io(ref e) => write!(f, "can't read artifact: {}", e),
+            ArtifactError::NoPrivateKey => write!(f, "the signer has no private key"),
+            ArtifactError::Invalid(ref e) => write!(f, "invalid artifact manifest: {:?}", e),
+            ArtifactError::WrongName(ref name) => write!(f, "the manifest is for {}", name),
+            ArtifactError::Modified => write!(f, "the artifact has been modified"),
+        }
+    }
+}
+
+impl Error for ArtifactError {}
+
+/// The name, length and hash of a file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ArtifactManifest {
+    /// The file name, without directories.
+    pub name: String,
+    /// The length of the file.
+    pub length: u64,
+    /// The algorithm of the hash.
+    pub hash_algorithm: HashAlgorithm,
+    /// The hash of the file.
+    pub hash: Vec<u8>,
+}
+
+/// An `ArtifactManifest` signed with a letter.
+pub type ArtifactLetter = Letter<ArtifactManifest>;
+
+fn file_name(path: &Path) -> Result<String, ArtifactError> {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.to_string())
+        .ok_or_else(|| ArtifactError::WrongName(path.display().to_string()))
+}
+
+fn read_all(file: &mut File) -> Result<Vec<u8>, ArtifactError> {
+    let mut bytes = Vec::new();
+    file.read_to_end(&mut bytes).map_err(ArtifactError::Io)?;
+    Ok(bytes)
+}
+
+impl ArtifactManifest {
+    /// Describes the file with the given hash algorithm.
+    pub fn of_file<P: AsRef<Path>>(path: P, hash_algorithm: HashAlgorithm) -> Result<ArtifactManifest, ArtifactError> {
+        let bytes = read_all(&mut File::open(path.as_ref()).map_err(ArtifactError::Io)?)?;
+
+        Ok(ArtifactManifest {
+            name: file_name(path.as_ref())?,
+            length: bytes.len() as u64,
+            hash_algorithm,
+            hash: hash_algorithm.digest(&bytes),
+        })
+    }
+
+    /// Returns true, if the bytes have the length and hash of the manifest.
+    pub fn matches(&self, bytes: &[u8]) -> bool {
+        bytes.len() as u64 == self.length && self.hash_algorithm.digest(bytes) == self.hash
+    }
+}
+
+impl Fingerprint for ArtifactManifest {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(self.name.as_bytes());
+        w.u64(self.length);
+        w.u8(self.hash_algorithm.id());
+        w.bytes(&self.hash);
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for ArtifactManifest {
+    fn from_fingerprint(bytes: &[u8]) -> Result<ArtifactManifest, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let manifest = ArtifactManifest {
+            name: String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?,
+            length: r.u64()?,
+            hash_algorithm: HashAlgorithm::from_id(r.u8()?)?,
+            hash: r.bytes()?.to_vec(),
+        };
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(manifest)
+    }
+}
+
+/// A file that matched its manifest, kept open.
+#[derive(Debug)]
+pub struct VerifiedArtifact {
+    path: PathBuf,
+    file: File,
+}
+
+impl VerifiedArtifact {
+    /// Returns the path the file was opened at.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the open file.
+    pub fn file(&self) -> &File {
+        &self.file
+    }
+
+    /// Returns a path that refers to the open, verified file, for loading it without a race.
+    #[cfg(target_os = "linux")]
+    pub fn fd_path(&self) -> PathBuf {
+        use std::os::unix::io::AsRawFd;
+        PathBuf::from(format!("/proc/self/fd/{}", self.file.as_raw_fd()))
+    }
+}
+
+/// Describes the file and signs the manifest.
+pub fn sign_artifact<P: AsRef<Path>>(path: P, mut header: Header, signer: &Signer) -> Result<ArtifactLetter, ArtifactError> {
+    let manifest = ArtifactManifest::of_file(path, header.hash_algorithm())?;
+    header.set_content_type(ARTIFACT_CONTENT_TYPE);
+    Letter::sign(manifest, header, signer).map_err(|_| ArtifactError::NoPrivateKey)
+}
+
+/// Validates the manifest letter, opens the file and checks its name, length and hash. Load the
+/// file only if this returns `Ok`.
+pub fn verify_artifact_before_load<P, V>(path: P,
+                                         expected: &ArtifactLetter,
+                                         cv: &V)
+                                         -> Result<VerifiedArtifact, ArtifactError>
+    where P: AsRef<Path>,
+          V: Validator
+{
+    expected.validate_as(cv, ARTIFACT_CONTENT_TYPE).map_err(ArtifactError::Invalid)?;
+
+    let path = path.as_ref();
+    if file_name(path)? != expected.name {
+        return Err(ArtifactError::WrongName(expected.name.clone()));
+    }
+
+    let mut file = File::open(path).map_err(ArtifactError::Io)?;
+    if !expected.matches(&read_all(&mut file)?) {
+        return Err(ArtifactError::Modified);
+    }
+
+    Ok(VerifiedArtifact {
+        path: path.to_path_buf(),
+        file,
+    })
+}
+
+#[test]
+fn test_verify_artifact() {
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let dir = env::temp_dir().join(format!("edcert-letter-artifact-{}", process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let plugin = dir.join("libdemo.so");
+    fs::write(&plugin, b"\x7fELF demo").unwrap();
+
+    let letter = sign_artifact(&plugin, Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    let letter: ArtifactLetter = Letter::from_bytes(&letter.to_bytes()).unwrap();
+    let verified = verify_artifact_before_load(&plugin, &letter, &cv).unwrap();
+    assert_eq!(plugin.as_path(), verified.path());
+
+    let renamed = dir.join("libother.so");
+    fs::write(&renamed, b"\x7fELF demo").unwrap();
+    assert_eq!(true, verify_artifact_before_load(&renamed, &letter, &cv).is_err());
+
+    fs::write(&plugin, b"\x7fELF evil").unwrap();
+    match verify_artifact_before_load(&plugin, &letter, &cv) {
+        Err(ArtifactError::Modified) => {}
+        other => panic!("expected a modified artifact, got {:?}", other),
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,112 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Audience restriction.
+//!
+//! A letter meant for one service carries its name in the authenticated `audience` header field,
+//! see `Header::set_audience`. An `AudienceValidator` validates letters with another validator and
+//! additionally requires one of the audiences it expects, so a letter minted for `billing-api`
+//! is rejected when it is replayed to `shipping-api`. Letters without an audience are rejected
+//! as well.
+
+use std::collections::BTreeSet;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// A validator that only accepts letters for the expected audiences.
+pub struct AudienceValidator<V: Validator> {
+    inner: V,
+    audiences: BTreeSet<String>,
+}
+
+impl<V: Validator> AudienceValidator<V> {
+    /// Wraps the validator. Without expected audiences, no letter is accepted.
+    pub fn new(inner: V) -> AudienceValidator<V> {
+        AudienceValidator {
+            inner,
+            audiences: BTreeSet::new(),
+        }
+    }
+
+    /// Accepts letters for the audience. A service known under several names can expect each.
+    pub fn expect_audience(mut self, audience: &str) -> AudienceValidator<V> {
+        self.audiences.insert(audience.to_string());
+        self
+    }
+
+    /// Returns the expected audiences.
+    pub fn audiences(&self) -> &BTreeSet<String> {
+        &self.audiences
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Validates the letter with the wrapped validator and checks its audience. Letters for
+    /// another or without an audience yield `ValidationError::Other`.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.inner.is_valid(letter)?;
+
+        match letter.header().audience() {
+            Some(audience) if self.audiences.contains(audience) => Ok(()),
+            _ => {
+                trace_event!(info, "letter is meant for another audience");
+                Err(ValidationError::Other)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_audience() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use header::Header;
+    use signer::Signer;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let mut header = Header::new();
+    header.set_audience("billing-api");
+    let letter = Letter::sign("charge 10 EUR", header, &Signer::PrivateKey(&msk)).unwrap();
+
+    let billing = AudienceValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_audience("billing-api");
+    let shipping = AudienceValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_audience("shipping-api");
+
+    assert_eq!(Ok(()), billing.validate(&letter));
+    assert_eq!(Err(ValidationError::Other), shipping.validate(&letter));
+    assert_eq!(Err(ValidationError::Other), billing.validate(&Letter::with_private_key("x", &msk)));
+
+    // The audience is signed, so it can't be changed.
+    let mut header = letter.header().clone();
+    header.set_audience("shipping-api");
+    let replayed = Letter::from_parts(*letter.get(), header, letter.shared_signature().clone());
+    assert_eq!(true, shipping.validate(&replayed).is_err());
+}
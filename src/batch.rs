@@ -0,0 +1,149 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Validation of many letters at once.
+//!
+//! With the `rayon` feature, `verify_all_parallel` spreads the work across all cores. The
+//! results are in the order of the input either way. `VerifiedExt` adds `verified` and
+//! `partition_verified` to iterators over letters, to get at the content of the valid ones.
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// Validates every letter and returns the results in input order.
+pub fn verify_all<V: Validator, T: Fingerprint>(cv: &V,
+                                                letters: &[Letter<T>])
+                                                -> Vec<Result<(), ValidationError>> {
+    letters.iter().map(|letter| cv.is_valid(letter)).collect()
+}
+
+/// Validates every letter on the rayon thread pool and returns the results in input order.
+#[cfg(feature = "rayon")]
+pub fn verify_all_parallel<V, T>(cv: &V, letters: &[Letter<T>]) -> Vec<Result<(), ValidationError>>
+    where V: Validator + Sync,
+          T: Fingerprint + Sync
+{
+    letters.par_iter().map(|letter| cv.is_valid(letter)).collect()
+}
+
+/// An iterator over the content of the valid letters of another iterator. See
+/// `VerifiedExt::verified`.
+pub struct Verified<'v, I, V: 'v> {
+    inner: I,
+    cv: &'v V,
+    index: usize,
+    failures: Vec<(usize, ValidationError)>,
+}
+
+impl<'v, I, V> Verified<'v, I, V> {
+    /// Returns the positions and errors of the invalid letters seen so far.
+    pub fn failures(&self) -> &[(usize, ValidationError)] {
+        &self.failures
+    }
+
+    /// Returns the positions and errors of the invalid letters seen so far.
+    pub fn into_failures(self) -> Vec<(usize, ValidationError)> {
+        self.failures
+    }
+}
+
+impl<'a, 'v, I, V, T> Iterator for Verified<'v, I, V>
+    where I: Iterator<Item = &'a Letter<T>>,
+          V: Validator,
+          T: Fingerprint + 'a
+{
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        for letter in &mut self.inner {
+            let index = self.index;
+            self.index += 1;
+
+            match self.cv.is_valid(letter) {
+                Ok(()) => return Some(letter.get()),
+                Err(e) => self.failures.push((index, e)),
+            }
+        }
+
+        None
+    }
+}
+
+/// Validation of the letters of an iterator.
+pub trait VerifiedExt<'a, T: Fingerprint + 'a>: Iterator<Item = &'a Letter<T>> + Sized {
+    /// Yields the content of every valid letter. The invalid ones are skipped and recorded in
+    /// `Verified::failures`.
+    fn verified<'v, V: Validator>(self, cv: &'v V) -> Verified<'v, Self, V> {
+        Verified {
+            inner: self,
+            cv,
+            index: 0,
+            failures: Vec::new(),
+        }
+    }
+
+    /// Returns the content of the valid letters, and the positions and errors of the invalid
+    /// ones.
+    fn partition_verified<V: Validator>(self, cv: &V) -> (Vec<&'a T>, Vec<(usize, ValidationError)>) {
+        let mut verified = self.verified(cv);
+        let valid = verified.by_ref().collect();
+        (valid, verified.into_failures())
+    }
+}
+
+impl<'a, T: Fingerprint + 'a, I: Iterator<Item = &'a Letter<T>>> VerifiedExt<'a, T> for I {}
+
+#[test]
+fn test_batch() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (_, other) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let letters: Vec<Letter<&str>> = (0..100)
+                                         .map(|i| {
+                                             let key = if i % 10 == 3 { &other } else { &msk };
+                                             Letter::with_private_key("x", key)
+                                         })
+                                         .collect();
+
+    let results = verify_all(&cv, &letters);
+    assert_eq!(10, results.iter().filter(|r| r.is_err()).count());
+    assert_eq!(true, results[3].is_err());
+
+    #[cfg(feature = "rayon")]
+    assert_eq!(results, verify_all_parallel(&cv, &letters));
+
+    let (valid, failures) = letters.iter().partition_verified(&cv);
+    assert_eq!(90, valid.len());
+    assert_eq!(3, failures[0].0);
+    assert_eq!(90, letters.iter().verified(&cv).count());
+}
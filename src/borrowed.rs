@@ -0,0 +1,116 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letters over content the caller doesn't own.
+//!
+//! `Borrowed` wraps a reference to a byte buffer, so a `LetterRef` can sign and validate a
+//! multi-megabyte buffer without moving or cloning it into the letter. The letter only keeps the
+//! digest of the buffer. `Fingerprint` has to return an owned `Vec`, so `Letter::sign` and
+//! `Letter::from_parts` still copy the buffer once while hashing it; `Letter::sign_borrowed` and
+//! `Letter::from_borrowed_parts` hash it in place.
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+
+use clock::SystemClock;
+use format;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// A reference to content, which is fingerprinted as its bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Borrowed<'a, T: ?Sized + 'a>(pub &'a T);
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Fingerprint for Borrowed<'a, T> {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.0.as_ref().to_vec()
+    }
+}
+
+impl<'a, T: ?Sized> Deref for Borrowed<'a, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.0
+    }
+}
+
+/// A letter that borrows its content.
+pub type LetterRef<'a, T> = Letter<Borrowed<'a, T>>;
+
+impl<'a, T: AsRef<[u8]> + ?Sized> Letter<Borrowed<'a, T>> {
+    /// This method signs the borrowed content like `Letter::sign`, without copying it.
+    pub fn sign_borrowed(content: &'a T, header: Header, signer: &Signer) -> Result<LetterRef<'a, T>, SignError> {
+        let digest = header.content_digest(content.as_ref());
+        Letter::sign_digest(Borrowed(content), digest, header, signer, &SystemClock)
+    }
+
+    /// This method creates a letter from the borrowed content, its header and the signature like
+    /// `Letter::from_parts`, without copying the content.
+    pub fn from_borrowed_parts<S>(content: &'a T, header: Header, signature: S) -> LetterRef<'a, T>
+        where S: Into<Arc<Signature>>
+    {
+        let digest = header.content_digest(content.as_ref());
+        Letter::with_digest(Borrowed(content), digest, header, signature.into())
+    }
+
+    /// This method serializes the letter into the binary letter format. It can be read back as a
+    /// letter that owns its content.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(self.header(), self.get().0.as_ref(), self.signature())
+    }
+}
+
+#[test]
+fn test_borrowed() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let buffer = vec![42u8; 1 << 16];
+    let letter: LetterRef<[u8]> = Letter::sign_borrowed(&buffer[..], Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(buffer.len(), letter.len());
+
+    // Both ways of building the letter agree on the digest.
+    let copied = Letter::from_parts(Borrowed(&buffer[..]), letter.header().clone(), letter.shared_signature().clone());
+    assert_eq!(letter.content_digest(), copied.content_digest());
+
+    let other = vec![43u8; 1 << 16];
+    let forged = Letter::from_borrowed_parts(&other[..], letter.header().clone(), letter.signature().clone());
+    assert_eq!(false, cv.is_valid(&forged).is_ok());
+
+    let text = String::from("hello world");
+    let letter = Letter::sign(Borrowed(&text), Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    let (header, content, signature) = format::decode(&letter.to_bytes()).unwrap();
+    let decoded = Letter::from_parts(Borrowed(&content[..]), header, signature);
+    assert_eq!(true, cv.is_valid(&decoded).is_ok());
+}
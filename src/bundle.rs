@@ -0,0 +1,249 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Several letters in one blob.
+//!
+//! A `LetterBundle` frames a list of letters, for example the letters of a handshake, so they can
+//! be sent and validated together. Letters that were signed by the same certificate chain share
+//! one copy of it on the wire.
+
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use batch;
+use codec::Reader;
+use codec::Writer;
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+
+/// The bytes every serialized bundle starts with.
+pub const BUNDLE_MAGIC: &[u8] = b"EDB";
+
+/// A list of letters that travel together.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LetterBundle<T: Fingerprint> {
+    letters: Vec<Letter<T>>,
+}
+
+impl<T: Fingerprint> Default for LetterBundle<T> {
+    fn default() -> LetterBundle<T> {
+        LetterBundle::new()
+    }
+}
+
+impl<T: Fingerprint> LetterBundle<T> {
+    /// Creates an empty bundle.
+    pub fn new() -> LetterBundle<T> {
+        LetterBundle { letters: Vec::new() }
+    }
+
+    /// Creates a bundle of the given letters.
+    pub fn from_letters(letters: Vec<Letter<T>>) -> LetterBundle<T> {
+        LetterBundle { letters }
+    }
+
+    /// Appends a letter.
+    pub fn push(&mut self, letter: Letter<T>) {
+        self.letters.push(letter);
+    }
+
+    /// Returns the letters in order.
+    pub fn letters(&self) -> &[Letter<T>] {
+        &self.letters
+    }
+
+    /// Returns the letters.
+    pub fn into_letters(self) -> Vec<Letter<T>> {
+        self.letters
+    }
+
+    /// Returns the number of letters.
+    pub fn len(&self) -> usize {
+        self.letters.len()
+    }
+
+    /// Returns true, if the bundle has no letters.
+    pub fn is_empty(&self) -> bool {
+        self.letters.is_empty()
+    }
+
+    /// Validates every letter and returns the results in order.
+    pub fn verify_all<V: Validator>(&self, cv: &V) -> Vec<Result<(), ValidationError>> {
+        batch::verify_all(cv, &self.letters)
+    }
+
+    /// Validates every letter and returns the position and error of the first invalid one.
+    pub fn verify<V: Validator>(&self, cv: &V) -> Result<(), (usize, ValidationError)> {
+        for (i, letter) in self.letters.iter().enumerate() {
+            cv.is_valid(letter).map_err(|e| (i, e))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl<T: FromFingerprint> LetterBundle<T> {
+    /// Serializes the bundle. Every distinct parent certificate is written once and the letters
+    /// refer to it by position.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut parents: Vec<Vec<u8>> = Vec::new();
+        let mut refs = Vec::new();
+
+        for letter in &self.letters {
+            let index = match letter.signature().parent() {
+                None => 0,
+                Some(parent) => {
+                    let encoded = format::encode_certificate(parent);
+                    match parents.iter().position(|p| *p == encoded) {
+                        Some(i) => i + 1,
+                        None => {
+                            parents.push(encoded);
+                            parents.len()
+                        }
+                    }
+                }
+            };
+            refs.push(index);
+        }
+
+        let mut w = Writer::new();
+        w.raw(BUNDLE_MAGIC);
+        w.u8(1);
+
+        w.u32(parents.len() as u32);
+        for parent in &parents {
+            w.bytes(parent);
+        }
+
+        w.u32(self.letters.len() as u32);
+        for (letter, index) in self.letters.iter().zip(refs) {
+            let header = letter.header();
+            w.bytes(&header.to_bytes());
+            w.bytes(&header.compression().compress(&letter.fingerprint()));
+            w.bytes(letter.signature().hash());
+            w.u32(index as u32);
+        }
+
+        w.into_bytes()
+    }
+
+    /// Parses a serialized bundle with the default `DecodeLimits`. The letters are only checked
+    /// by `verify`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<LetterBundle<T>, DecodeError> {
+        LetterBundle::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// Parses a serialized bundle. The limits apply to every letter and parent certificate in it.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &DecodeLimits) -> Result<LetterBundle<T>, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(BUNDLE_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != BUNDLE_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let mut parents = Vec::new();
+        for _ in 0..r.u32()? {
+            parents.push(format::decode_parent(r.bytes()?, limits)?);
+        }
+
+        let mut letters = Vec::new();
+        for _ in 0..r.u32()? {
+            let header = Header::from_bytes(r.bytes()?)?;
+            let content = header.compression().decompress_limited(r.bytes()?, limits.max_content_size)?;
+            let hash = r.bytes()?.to_vec();
+
+            let signature = match r.u32()? as usize {
+                0 => Signature::new(hash),
+                i => {
+                    let parent = parents.get(i - 1).ok_or(DecodeError::InvalidCertificate)?;
+                    Signature::with_parent(Box::new(parent.clone()), hash)
+                }
+            };
+
+            letters.push(Letter::from_parts(T::from_fingerprint(&content)?, header, signature));
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(LetterBundle { letters })
+    }
+}
+
+#[test]
+fn test_bundle() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(90));
+    cert.sign_with_master(&msk);
+
+    let mut bundle = LetterBundle::new();
+    bundle.push(Letter::with_private_key(Fingerprintable("hello".to_string()), &msk));
+    for step in 0..3 {
+        bundle.push(Letter::with_certificate(Fingerprintable(format!("step {}", step)), &cert).unwrap());
+    }
+
+    // The certificate is written once for three letters.
+    let bytes = bundle.to_bytes();
+    let single = Letter::with_certificate(Fingerprintable("step 0".to_string()), &cert).unwrap().to_bytes();
+    assert_eq!(true, bytes.len() < 2 * single.len());
+
+    let decoded: LetterBundle<Fingerprintable<String>> = LetterBundle::from_bytes(&bytes).unwrap();
+    assert_eq!(bundle, decoded);
+    assert_eq!(Ok(()), decoded.verify(&cv));
+
+    let (_, other) = ed25519::generate_keypair();
+    let mut letters = decoded.into_letters();
+    letters.insert(2, Letter::with_private_key(Fingerprintable("evil".to_string()), &other));
+    let bundle = LetterBundle::from_letters(letters);
+    assert_eq!(Err((2, ValidationError::SignatureInvalid)), bundle.verify(&cv));
+    assert_eq!(1, bundle.verify_all(&cv).iter().filter(|r| r.is_err()).count());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Some(DecodeError::TrailingBytes),
+               LetterBundle::<Fingerprintable<String>>::from_bytes(&trailing).err());
+}
@@ -0,0 +1,247 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! A cache of validated letters.
+//!
+//! Peers often present the same identity letter on every request. A `ValidationCache` remembers
+//! letters that were validated successfully, so they are only validated again after a time to
+//! live. The cache is keyed by a digest of the signed bytes, the signature and the certificate
+//! chain, so a modified letter never hits a cached entry. Failed validations aren't cached. The
+//! cache holds at most `capacity` entries and drops the least recently used one when it is full.
+//! It can be shared between threads and connections, but not between validators that trust
+//! different keys, since a cached letter isn't validated again.
+
+use std::collections::BTreeMap;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+struct Entry {
+    validated_at: Instant,
+    last_used: u64,
+    signers: Vec<Vec<u8>>,
+}
+
+struct Entries {
+    map: HashMap<Vec<u8>, Entry>,
+    lru: BTreeMap<u64, Vec<u8>>,
+    tick: u64,
+}
+
+impl Entries {
+    fn touch(&mut self, key: &[u8]) {
+        self.tick += 1;
+        let tick = self.tick;
+
+        if let Some(entry) = self.map.get_mut(key) {
+            self.lru.remove(&entry.last_used);
+            entry.last_used = tick;
+            self.lru.insert(tick, key.to_vec());
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) {
+        if let Some(entry) = self.map.remove(key) {
+            self.lru.remove(&entry.last_used);
+        }
+    }
+}
+
+/// A bounded cache of successful validations.
+pub struct ValidationCache {
+    capacity: usize,
+    ttl: Duration,
+    entries: Mutex<Entries>,
+}
+
+/// Returns the cache key of the letter.
+fn cache_key<T: Fingerprint>(letter: &Letter<T>) -> Vec<u8> {
+    let mut bytes = letter.signed_bytes();
+    bytes.extend_from_slice(letter.signature().hash());
+
+    for cert in letter.signer_chain() {
+        bytes.extend_from_slice(cert.public_key());
+
+        if let Some(sig) = cert.signature() {
+            bytes.extend_from_slice(sig.hash());
+        }
+    }
+
+    sha512::hash(&bytes).0.to_vec()
+}
+
+impl ValidationCache {
+    /// Creates a cache that holds up to `capacity` letters for `ttl` each.
+    pub fn new(capacity: usize, ttl: Duration) -> ValidationCache {
+        ValidationCache {
+            capacity,
+            ttl,
+            entries: Mutex::new(Entries {
+                map: HashMap::new(),
+                lru: BTreeMap::new(),
+                tick: 0,
+            }),
+        }
+    }
+
+    /// Validates the letter, unless it was validated successfully within the time to live.
+    /// Letters whose signing certificate has expired since are validated again.
+    pub fn validate<T: Fingerprint, V: Validator>(&self, letter: &Letter<T>, cv: &V) -> Result<(), ValidationError> {
+        let key = cache_key(letter);
+
+        if self.is_cached(&key) && !letter.signer_chain().iter().any(|c| c.is_expired()) {
+            trace_event!(trace, "validation cache hit");
+            return Ok(());
+        }
+
+        cv.is_valid(letter)?;
+        self.insert(key, letter);
+        Ok(())
+    }
+
+    fn is_cached(&self, key: &[u8]) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+
+        let fresh = match entries.map.get(key) {
+            Some(entry) => entry.validated_at.elapsed() < self.ttl,
+            None => return false,
+        };
+
+        if fresh {
+            entries.touch(key);
+        } else {
+            entries.remove(key);
+        }
+
+        fresh
+    }
+
+    fn insert<T: Fingerprint>(&self, key: Vec<u8>, letter: &Letter<T>) {
+        if self.capacity == 0 {
+            return;
+        }
+
+        let signers = letter.signer_chain().iter().map(|c| c.public_key().clone()).collect();
+        let mut entries = self.entries.lock().unwrap();
+        entries.remove(&key);
+
+        while entries.map.len() >= self.capacity {
+            let oldest = match entries.lru.iter().next() {
+                Some((_, key)) => key.clone(),
+                None => break,
+            };
+            entries.remove(&oldest);
+        }
+
+        entries.tick += 1;
+        let tick = entries.tick;
+        entries.lru.insert(tick, key.clone());
+        entries.map.insert(key,
+                           Entry {
+                               validated_at: Instant::now(),
+                               last_used: tick,
+                               signers,
+                           });
+    }
+
+    /// Forgets all letters signed by the certificate with the public key, directly or through a
+    /// chain. Returns the number of letters forgotten.
+    pub fn invalidate_signer(&self, public_key: &[u8]) -> usize {
+        let mut entries = self.entries.lock().unwrap();
+        let keys: Vec<Vec<u8>> = entries.map
+                                        .iter()
+                                        .filter(|&(_, e)| e.signers.iter().any(|s| &s[..] == public_key))
+                                        .map(|(k, _)| k.clone())
+                                        .collect();
+
+        for key in &keys {
+            entries.remove(key);
+        }
+
+        keys.len()
+    }
+
+    /// Forgets all letters.
+    pub fn clear(&self) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.map.clear();
+        entries.lru.clear();
+    }
+
+    /// Returns the number of cached letters, including expired ones that weren't removed yet.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().map.len()
+    }
+
+    /// Returns true, if no letters are cached.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[test]
+fn test_validation_cache() {
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (_, other) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let cache = ValidationCache::new(2, Duration::from_secs(60));
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + ::chrono::Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let a = Letter::with_certificate("a", &cert).unwrap();
+    let b = Letter::with_private_key("b", &msk);
+    let c = Letter::with_private_key("c", &msk);
+
+    assert_eq!(Ok(()), cache.validate(&a, &cv));
+    assert_eq!(Ok(()), cache.validate(&b, &cv));
+    assert_eq!(Ok(()), cache.validate(&a, &cv));
+    assert_eq!(Ok(()), cache.validate(&c, &cv));
+    assert_eq!(2, cache.len());
+
+    // b was the least recently used one. Without the validator, only cached letters pass.
+    let (other_mpk, _) = ed25519::generate_keypair();
+    let wrong = RootValidator::new(&other_mpk, NoRevoker);
+    assert_eq!(Ok(()), cache.validate(&a, &wrong));
+    assert_eq!(true, cache.validate(&b, &wrong).is_err());
+
+    assert_eq!(true, cache.validate(&Letter::with_private_key("a", &other), &cv).is_err());
+    assert_eq!(1, cache.invalidate_signer(cert.public_key()));
+    assert_eq!(true, cache.validate(&a, &wrong).is_err());
+}
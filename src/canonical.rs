@@ -0,0 +1,366 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! A canonical byte encoding for building fingerprints.
+//!
+//! Every value is encoded the same way on every platform: integers as big-endian bytes of their
+//! full width, `bool` as one byte, strings and sequences prefixed with their length as u32,
+//! `Option` with a leading 0 or 1, times as their Unix timestamp and nanoseconds, and tuples
+//! and structs as their fields in order. Because
+//! lengths are always included, two different values never share an encoding.
+//!
+//! With the `derive` feature, `#[derive(Fingerprint)]` implements `Fingerprint` and `Encode`
+//! for a struct by encoding its fields in declaration order. Plain values like `String`,
+//! `Vec<u8>`, integers, tuples and `Option<T>` can be put into a letter by wrapping them into a
+//! `Fingerprintable`.
+
+use std::ops::Deref;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+pub use edcert::fingerprint::Fingerprint;
+
+use format::DecodeError;
+use format::FromFingerprint;
+
+/// Types that have a canonical byte encoding.
+pub trait Encode {
+    /// Appends the canonical encoding of this value to `out`.
+    fn encode(&self, out: &mut Vec<u8>);
+}
+
+/// Returns the canonical encoding of a value.
+pub fn to_bytes<E: Encode + ?Sized>(value: &E) -> Vec<u8> {
+    let mut out = Vec::new();
+    value.encode(&mut out);
+    out
+}
+
+fn encode_len(len: usize, out: &mut Vec<u8>) {
+    (len as u32).encode(out);
+}
+
+macro_rules! impl_encode_int {
+    ($($t:ty),*) => {
+        $(
+            impl Encode for $t {
+                fn encode(&self, out: &mut Vec<u8>) {
+                    let size = ::std::mem::size_of::<$t>();
+                    for i in (0..size).rev() {
+                        out.push((*self as u64 >> (i * 8)) as u8);
+                    }
+                }
+            }
+        )*
+    }
+}
+
+impl_encode_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Encode for bool {
+    fn encode(&self, out: &mut Vec<u8>) {
+        out.push(*self as u8);
+    }
+}
+
+impl Encode for str {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        out.extend_from_slice(self.as_bytes());
+    }
+}
+
+impl Encode for String {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_str().encode(out);
+    }
+}
+
+impl<T: Encode> Encode for [T] {
+    fn encode(&self, out: &mut Vec<u8>) {
+        encode_len(self.len(), out);
+        for item in self {
+            item.encode(out);
+        }
+    }
+}
+
+impl<T: Encode> Encode for Vec<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.as_slice().encode(out);
+    }
+}
+
+impl<T: Encode> Encode for Option<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Some(ref value) => {
+                out.push(1);
+                value.encode(out);
+            }
+            None => out.push(0),
+        }
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for &T {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+}
+
+impl<T: Encode + ?Sized> Encode for Box<T> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        (**self).encode(out);
+    }
+}
+
+impl Encode for DateTime<UTC> {
+    fn encode(&self, out: &mut Vec<u8>) {
+        self.timestamp().encode(out);
+        self.timestamp_subsec_nanos().encode(out);
+    }
+}
+
+macro_rules! impl_encode_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Encode),+> Encode for ($($name,)+) {
+            #[allow(non_snake_case)]
+            fn encode(&self, out: &mut Vec<u8>) {
+                let ($(ref $name,)+) = *self;
+                $($name.encode(out);)+
+            }
+        }
+    }
+}
+
+impl_encode_tuple!(A);
+impl_encode_tuple!(A, B);
+impl_encode_tuple!(A, B, C);
+impl_encode_tuple!(A, B, C, D);
+impl_encode_tuple!(A, B, C, D, E);
+impl_encode_tuple!(A, B, C, D, E, F);
+
+/// Types that can be read back from their canonical encoding.
+pub trait Decode: Sized {
+    /// Reads a value from the start of `input` and advances `input` past it.
+    fn decode(input: &mut &[u8]) -> Result<Self, DecodeError>;
+}
+
+/// Reads a value from its canonical encoding. Trailing bytes are an error.
+pub fn from_bytes<D: Decode>(mut input: &[u8]) -> Result<D, DecodeError> {
+    let value = D::decode(&mut input)?;
+
+    if input.is_empty() {
+        Ok(value)
+    } else {
+        Err(DecodeError::InvalidContent)
+    }
+}
+
+fn take<'a>(input: &mut &'a [u8], len: usize) -> Result<&'a [u8], DecodeError> {
+    if input.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+
+    let (head, tail) = input.split_at(len);
+    *input = tail;
+    Ok(head)
+}
+
+fn decode_len(input: &mut &[u8]) -> Result<usize, DecodeError> {
+    Ok(u32::decode(input)? as usize)
+}
+
+macro_rules! impl_decode_int {
+    ($($t:ty),*) => {
+        $(
+            impl Decode for $t {
+                fn decode(input: &mut &[u8]) -> Result<$t, DecodeError> {
+                    let bytes = take(input, ::std::mem::size_of::<$t>())?;
+                    Ok(bytes.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64) as $t)
+                }
+            }
+        )*
+    }
+}
+
+impl_decode_int!(u8, u16, u32, u64, i8, i16, i32, i64);
+
+impl Decode for bool {
+    fn decode(input: &mut &[u8]) -> Result<bool, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(false),
+            1 => Ok(true),
+            _ => Err(DecodeError::InvalidContent),
+        }
+    }
+}
+
+impl Decode for String {
+    fn decode(input: &mut &[u8]) -> Result<String, DecodeError> {
+        let len = decode_len(input)?;
+        let bytes = take(input, len)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| DecodeError::InvalidContent)
+    }
+}
+
+impl<T: Decode> Decode for Vec<T> {
+    fn decode(input: &mut &[u8]) -> Result<Vec<T>, DecodeError> {
+        let len = decode_len(input)?;
+
+        // Don't trust the length for the allocation, every item takes at least one byte.
+        let mut items = Vec::with_capacity(::std::cmp::min(len, input.len()));
+        for _ in 0..len {
+            items.push(T::decode(input)?);
+        }
+
+        Ok(items)
+    }
+}
+
+impl<T: Decode> Decode for Option<T> {
+    fn decode(input: &mut &[u8]) -> Result<Option<T>, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(None),
+            1 => Ok(Some(T::decode(input)?)),
+            _ => Err(DecodeError::InvalidContent),
+        }
+    }
+}
+
+impl Decode for DateTime<UTC> {
+    fn decode(input: &mut &[u8]) -> Result<DateTime<UTC>, DecodeError> {
+        let secs = i64::decode(input)?;
+        let nanos = u32::decode(input)?;
+        UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidContent)
+    }
+}
+
+impl<T: Decode> Decode for Box<T> {
+    fn decode(input: &mut &[u8]) -> Result<Box<T>, DecodeError> {
+        Ok(Box::new(T::decode(input)?))
+    }
+}
+
+macro_rules! impl_decode_tuple {
+    ($($name:ident),+) => {
+        impl<$($name: Decode),+> Decode for ($($name,)+) {
+            fn decode(input: &mut &[u8]) -> Result<($($name,)+), DecodeError> {
+                Ok(($($name::decode(input)?,)+))
+            }
+        }
+    }
+}
+
+impl_decode_tuple!(A);
+impl_decode_tuple!(A, B);
+impl_decode_tuple!(A, B, C);
+impl_decode_tuple!(A, B, C, D);
+impl_decode_tuple!(A, B, C, D, E);
+impl_decode_tuple!(A, B, C, D, E, F);
+
+/// An adapter that makes any `Encode` type usable as letter content. The fingerprint is the
+/// canonical encoding of the wrapped value.
+///
+/// ```ignore
+/// let letter = Letter::with_private_key(Fingerprintable("hello".to_string()), &msk);
+/// ```
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Fingerprintable<T>(pub T);
+
+impl<T> Fingerprintable<T> {
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.0
+    }
+}
+
+impl<T: Encode> Fingerprint for Fingerprintable<T> {
+    fn fingerprint(&self) -> Vec<u8> {
+        to_bytes(&self.0)
+    }
+}
+
+impl<T: Encode + Decode> FromFingerprint for Fingerprintable<T> {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Fingerprintable<T>, DecodeError> {
+        from_bytes(bytes).map(Fingerprintable)
+    }
+}
+
+impl<T> Deref for Fingerprintable<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> From<T> for Fingerprintable<T> {
+    fn from(value: T) -> Fingerprintable<T> {
+        Fingerprintable(value)
+    }
+}
+
+#[test]
+fn test_lengths_are_unambiguous() {
+    let a = to_bytes(&("ab".to_string(), "c".to_string()));
+    let b = to_bytes(&("a".to_string(), "bc".to_string()));
+    assert!(a != b);
+}
+
+#[test]
+fn test_integers_are_big_endian() {
+    assert_eq!(vec![0, 0, 1, 2], to_bytes(&0x0102u32));
+    assert_eq!(vec![0xff, 0xfe], to_bytes(&-2i16));
+}
+
+#[test]
+fn test_decode_roundtrip() {
+    let value = (Some(-5i32), vec!["a".to_string(), "b".to_string()], true);
+    let bytes = to_bytes(&value);
+    assert_eq!(value, from_bytes(&bytes).unwrap());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Err(DecodeError::InvalidContent), from_bytes::<(Option<i32>, Vec<String>, bool)>(&trailing));
+}
+
+#[test]
+fn test_fingerprintable_letter() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+    use edcert::validator::Validator;
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let letter = Letter::with_private_key(Fingerprintable("hello world".to_string()), &msk);
+
+    let decoded: Letter<Fingerprintable<String>> = Letter::from_bytes(&letter.to_bytes()).unwrap();
+
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    assert_eq!(true, cv.is_valid(&decoded).is_ok());
+    assert_eq!("hello world", decoded.as_str());
+}
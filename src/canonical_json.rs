@@ -0,0 +1,134 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letter content for any serde type.
+//!
+//! The fingerprint of a `CanonicalContent<T>` is the canonical JSON encoding of the value: object
+//! keys are sorted and there is no whitespace, so the same value always yields the same bytes,
+//! no matter in which order a `HashMap` iterates. Floating point numbers are written in their
+//! shortest form, but you should still prefer integers in signed content.
+
+use std::ops::Deref;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use edcert::fingerprint::Fingerprint;
+
+use format::DecodeError;
+use format::FromFingerprint;
+
+/// Wraps a serde type to sign it deterministically. The canonical encoding is computed once,
+/// when the value is wrapped.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CanonicalContent<T> {
+    value: T,
+    bytes: Vec<u8>,
+}
+
+impl<T: Serialize> CanonicalContent<T> {
+    /// Wraps the value. This fails, if the value can't be represented as JSON, for example a map
+    /// with non-string keys.
+    pub fn new(value: T) -> Result<CanonicalContent<T>, serde_json::Error> {
+        let bytes = to_canonical_json(&value)?;
+        Ok(CanonicalContent {
+            value,
+            bytes,
+        })
+    }
+}
+
+impl<T> CanonicalContent<T> {
+    /// Returns a reference to the wrapped value.
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the wrapped value.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+
+    /// Returns the canonical JSON encoding of the value.
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+}
+
+/// Returns the canonical JSON encoding of a value.
+pub fn to_canonical_json<T: Serialize>(value: &T) -> Result<Vec<u8>, serde_json::Error> {
+    // serde_json::Value keeps objects in a BTreeMap, which sorts the keys.
+    let value = serde_json::to_value(value)?;
+    serde_json::to_vec(&value)
+}
+
+impl<T> Fingerprint for CanonicalContent<T> {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> FromFingerprint for CanonicalContent<T> {
+    fn from_fingerprint(bytes: &[u8]) -> Result<CanonicalContent<T>, DecodeError> {
+        let value: T = serde_json::from_slice(bytes).map_err(|_| DecodeError::InvalidContent)?;
+        let content = CanonicalContent::new(value).map_err(|_| DecodeError::InvalidContent)?;
+
+        // Only accept the canonical form, otherwise the same value could be signed in several
+        // different encodings.
+        if content.bytes != bytes {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(content)
+    }
+}
+
+impl<T> Deref for CanonicalContent<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+#[test]
+fn test_map_order_does_not_matter() {
+    use std::collections::HashMap;
+
+    let mut a = HashMap::new();
+    let mut b = HashMap::new();
+    for i in 0..32u32 {
+        a.insert(format!("key{}", i), i);
+        b.insert(format!("key{}", 31 - i), 31 - i);
+    }
+
+    let a = CanonicalContent::new(a).unwrap();
+    let b = CanonicalContent::new(b).unwrap();
+    assert_eq!(a.fingerprint(), b.fingerprint());
+
+    let decoded: CanonicalContent<HashMap<String, u32>> =
+        CanonicalContent::from_fingerprint(&a.fingerprint()).unwrap();
+    assert_eq!(*a, *decoded);
+
+    assert!(CanonicalContent::<HashMap<String, u32>>::from_fingerprint(b"{ \"key0\": 0 }").is_err());
+}
@@ -0,0 +1,430 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Capability tokens that holders can restrict further before passing them on.
+//!
+//! A `Capability` starts as a letter with a `Grant`: a list of caveats and the public key of the
+//! holder. The holder can attenuate it by appending more caveats and the public key of the next
+//! holder, signed with its own key. Every attenuation is bound to the signature before it, so
+//! caveats can be added, but never removed. The current holder's private key travels with the
+//! token, like the key of a macaroon, so every holder can attenuate it again.
+//!
+//! A request is allowed, if the grant letter is valid and every caveat allows it.
+
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical;
+use canonical::Decode;
+use canonical::Encode;
+use clock::Clock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of grant letters.
+pub const GRANT_CONTENT_TYPE: &str = "edcert-letter/capability-grant";
+
+/// The bytes every serialized capability starts with.
+pub const CAPABILITY_MAGIC: &[u8] = b"EDC";
+
+const VERSION: u8 = 1;
+
+/// A restriction of a capability.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Caveat {
+    /// Only this scope and the scopes below it, separated by `/`, are allowed.
+    Scope(String),
+    /// The capability can't be used after this time.
+    ExpiresAt(DateTime<UTC>),
+    /// Only this audience may accept the capability.
+    Audience(String),
+}
+
+impl Caveat {
+    /// Returns true, if the caveat allows the request.
+    pub fn allows(&self, request: &Request) -> bool {
+        match *self {
+            Caveat::Scope(ref scope) => {
+                request.scope == *scope ||
+                (request.scope.starts_with(scope.as_str()) &&
+                 request.scope[scope.len()..].starts_with('/'))
+            }
+            Caveat::ExpiresAt(ref expires) => request.time <= *expires,
+            Caveat::Audience(ref audience) => request.audience == *audience,
+        }
+    }
+}
+
+impl Encode for Caveat {
+    fn encode(&self, out: &mut Vec<u8>) {
+        match *self {
+            Caveat::Scope(ref scope) => (0u8, scope).encode(out),
+            Caveat::ExpiresAt(ref expires) => (1u8, expires.timestamp()).encode(out),
+            Caveat::Audience(ref audience) => (2u8, audience).encode(out),
+        }
+    }
+}
+
+impl Decode for Caveat {
+    fn decode(input: &mut &[u8]) -> Result<Caveat, DecodeError> {
+        match u8::decode(input)? {
+            0 => Ok(Caveat::Scope(String::decode(input)?)),
+            1 => {
+                let secs = i64::decode(input)?;
+                UTC.timestamp_opt(secs, 0)
+                   .single()
+                   .map(Caveat::ExpiresAt)
+                   .ok_or(DecodeError::InvalidContent)
+            }
+            2 => Ok(Caveat::Audience(String::decode(input)?)),
+            _ => Err(DecodeError::InvalidContent),
+        }
+    }
+}
+
+/// What a capability is used for.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Request {
+    scope: String,
+    audience: String,
+    time: DateTime<UTC>,
+}
+
+impl Request {
+    /// Creates a request for the given scope, made to the given audience now.
+    pub fn new(scope: &str, audience: &str) -> Request {
+        Request {
+            scope: scope.to_string(),
+            audience: audience.to_string(),
+            time: UTC::now(),
+        }
+    }
+
+    /// Takes the time of the request from the given clock.
+    pub fn with_clock<C: Clock>(mut self, clock: &C) -> Request {
+        self.time = clock.now();
+        self
+    }
+}
+
+/// The content of the letter a capability starts with.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Grant {
+    caveats: Vec<Caveat>,
+    holder_key: Vec<u8>,
+}
+
+impl Grant {
+    /// Returns the caveats of the grant.
+    pub fn caveats(&self) -> &[Caveat] {
+        &self.caveats
+    }
+
+    /// Returns the public key of the first holder.
+    pub fn holder_key(&self) -> &[u8] {
+        &self.holder_key
+    }
+}
+
+impl Fingerprint for Grant {
+    fn fingerprint(&self) -> Vec<u8> {
+        canonical::to_bytes(&(&self.caveats, &self.holder_key))
+    }
+}
+
+impl FromFingerprint for Grant {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Grant, DecodeError> {
+        let (caveats, holder_key) = canonical::from_bytes(bytes)?;
+
+        Ok(Grant {
+            caveats,
+            holder_key,
+        })
+    }
+}
+
+/// Caveats added by a holder, signed with its key.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Attenuation {
+    caveats: Vec<Caveat>,
+    holder_key: Vec<u8>,
+    signature: Vec<u8>,
+}
+
+impl Attenuation {
+    /// Returns the added caveats.
+    pub fn caveats(&self) -> &[Caveat] {
+        &self.caveats
+    }
+
+    /// Returns the public key of the next holder.
+    pub fn holder_key(&self) -> &[u8] {
+        &self.holder_key
+    }
+}
+
+/// The bytes an attenuation signs: the signature before it, the caveats and the next key.
+fn attenuation_bytes(previous: &[u8], caveats: &[Caveat], holder_key: &[u8]) -> Vec<u8> {
+    let mut bytes = CAPABILITY_MAGIC.to_vec();
+    bytes.extend_from_slice(&canonical::to_bytes(&(previous, caveats, holder_key)));
+    bytes
+}
+
+/// This error is returned, if a capability doesn't allow a request.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CapabilityError {
+    /// The grant letter isn't valid.
+    Invalid(ValidationError),
+    /// The signature of the attenuation with this index is wrong.
+    Broken(usize),
+    /// The private key doesn't belong to the last holder.
+    WrongHolder,
+    /// This caveat doesn't allow the request.
+    Denied(Caveat),
+}
+
+impl fmt::Display for CapabilityError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CapabilityError::Invalid(ref e) => write!(f, "grant is not valid: {:?}", e),
+            CapabilityError::Broken(i) => write!(f, "attenuation {} has a wrong signature", i),
+            CapabilityError::WrongHolder => write!(f, "holder key doesn't match"),
+            CapabilityError::Denied(ref c) => write!(f, "denied by caveat {:?}", c),
+        }
+    }
+}
+
+impl Error for CapabilityError {}
+
+/// A capability token: a grant letter, the attenuations of its holders and the private key of
+/// the current holder.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Capability {
+    grant: Letter<Grant>,
+    attenuations: Vec<Attenuation>,
+    holder_private_key: Vec<u8>,
+}
+
+impl Capability {
+    /// Issues a capability with the given caveats. The header gets the grant content type. It
+    /// fails, if the signer is a certificate without a private key.
+    pub fn issue(caveats: Vec<Caveat>, mut header: Header, signer: &Signer) -> Result<Capability, SignError> {
+        header.set_content_type(GRANT_CONTENT_TYPE);
+        let (pk, sk) = ed25519::generate_keypair();
+        let grant = Grant {
+            caveats,
+            holder_key: pk,
+        };
+
+        Ok(Capability {
+            grant: Letter::sign(grant, header, signer)?,
+            attenuations: Vec::new(),
+            holder_private_key: sk,
+        })
+    }
+
+    /// Returns a copy of the capability that is restricted by the given caveats as well. The
+    /// copy gets a new holder key, so it can be passed on without the ability to lift the new
+    /// caveats.
+    pub fn attenuate(&self, caveats: Vec<Caveat>) -> Capability {
+        let (pk, sk) = ed25519::generate_keypair();
+        let bytes = attenuation_bytes(self.last_signature(), &caveats, &pk);
+
+        let mut attenuations = self.attenuations.clone();
+        attenuations.push(Attenuation {
+            signature: ed25519::sign(&bytes, &self.holder_private_key),
+            caveats,
+            holder_key: pk,
+        });
+
+        Capability {
+            grant: self.grant.clone(),
+            attenuations,
+            holder_private_key: sk,
+        }
+    }
+
+    /// Returns the grant letter.
+    pub fn grant(&self) -> &Letter<Grant> {
+        &self.grant
+    }
+
+    /// Returns the attenuations, oldest first.
+    pub fn attenuations(&self) -> &[Attenuation] {
+        &self.attenuations
+    }
+
+    /// Returns all caveats of the capability.
+    pub fn caveats(&self) -> Vec<&Caveat> {
+        self.grant
+            .caveats()
+            .iter()
+            .chain(self.attenuations.iter().flat_map(|a| a.caveats().iter()))
+            .collect()
+    }
+
+    fn last_signature(&self) -> &[u8] {
+        match self.attenuations.last() {
+            Some(a) => &a.signature,
+            None => self.grant.signature().hash(),
+        }
+    }
+
+    /// Checks the grant letter and every attenuation, and that every caveat allows the request.
+    pub fn verify<V: Validator>(&self, cv: &V, request: &Request) -> Result<(), CapabilityError> {
+        self.grant.validate_as(cv, GRANT_CONTENT_TYPE).map_err(CapabilityError::Invalid)?;
+
+        let mut holder_key = self.grant.holder_key();
+        let mut previous = self.grant.signature().hash().as_slice();
+
+        for (i, a) in self.attenuations.iter().enumerate() {
+            let bytes = attenuation_bytes(previous, &a.caveats, &a.holder_key);
+            if !ed25519::verify(&bytes, &a.signature, holder_key) {
+                return Err(CapabilityError::Broken(i));
+            }
+
+            holder_key = &a.holder_key;
+            previous = &a.signature;
+        }
+
+        // The public key is the second half of an ed25519 private key.
+        if self.holder_private_key.len() != 64 || &self.holder_private_key[32..] != holder_key {
+            return Err(CapabilityError::WrongHolder);
+        }
+
+        match self.caveats().into_iter().find(|c| !c.allows(request)) {
+            Some(c) => Err(CapabilityError::Denied(c.clone())),
+            None => Ok(()),
+        }
+    }
+
+    /// Serializes the capability, including the private key of the current holder.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(CAPABILITY_MAGIC);
+        w.u8(VERSION);
+        w.bytes(&self.grant.to_bytes());
+
+        w.u32(self.attenuations.len() as u32);
+        for a in &self.attenuations {
+            w.bytes(&canonical::to_bytes(&(&a.caveats, &a.holder_key, &a.signature)));
+        }
+
+        w.bytes(&self.holder_private_key);
+        w.into_bytes()
+    }
+
+    /// Reads a capability.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Capability, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(CAPABILITY_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != CAPABILITY_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            VERSION => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let grant = Letter::from_bytes(r.bytes()?)?;
+
+        let mut attenuations = Vec::new();
+        for _ in 0..r.u32()? {
+            let (caveats, holder_key, signature) = canonical::from_bytes(r.bytes()?)?;
+            attenuations.push(Attenuation {
+                caveats,
+                holder_key,
+                signature,
+            });
+        }
+
+        let holder_private_key = r.bytes()?.to_vec();
+
+        if !r.is_empty() {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(Capability {
+            grant,
+            attenuations,
+            holder_private_key,
+        })
+    }
+}
+
+#[test]
+fn test_capability() {
+    use chrono::Duration;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let cap = Capability::issue(vec![Caveat::Scope("files".to_string())],
+                                Header::new(),
+                                &Signer::PrivateKey(&msk))
+                  .unwrap();
+    assert_eq!(Ok(()), cap.verify(&cv, &Request::new("files/photos", "storage")));
+    assert_eq!(true, cap.verify(&cv, &Request::new("filesystem", "storage")).is_err());
+
+    let restricted = cap.attenuate(vec![Caveat::Scope("files/photos".to_string()),
+                                        Caveat::Audience("storage".to_string()),
+                                        Caveat::ExpiresAt(UTC::now() + Duration::hours(1))]);
+    let restricted = Capability::from_bytes(&restricted.to_bytes()).unwrap();
+    assert_eq!(Ok(()), restricted.verify(&cv, &Request::new("files/photos/2016", "storage")));
+    assert_eq!(Err(CapabilityError::Denied(Caveat::Scope("files/photos".to_string()))),
+               restricted.verify(&cv, &Request::new("files/music", "storage")));
+    assert_eq!(Err(CapabilityError::Denied(Caveat::Audience("storage".to_string()))),
+               restricted.verify(&cv, &Request::new("files/photos", "mail")));
+
+    let clock = ::clock::ManualClock::new(UTC::now() + Duration::hours(2));
+    assert_eq!(true, restricted.verify(&cv, &Request::new("files/photos", "storage").with_clock(&clock)).is_err());
+
+    // A letter over a grant is only accepted with the grant content type.
+    let mut untagged = cap.clone();
+    untagged.grant = Letter::with_private_key(cap.grant.get().clone(), &msk);
+    assert_eq!(Err(CapabilityError::Invalid(ValidationError::Other)),
+               untagged.verify(&cv, &Request::new("files", "storage")));
+
+    // Dropping an attenuation doesn't lift its caveats.
+    let mut lifted = restricted.attenuate(vec![]);
+    lifted.attenuations.remove(0);
+    assert_eq!(Err(CapabilityError::Broken(0)),
+               lifted.verify(&cv, &Request::new("files/music", "storage")));
+}
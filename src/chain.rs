@@ -0,0 +1,216 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Append-only logs of letters.
+//!
+//! Every letter in a `LetterChain` carries its position and the hash of the letter before it in
+//! its header metadata, so both are covered by its signature. Changing, removing or reordering
+//! entries breaks the chain. Cutting entries off the end can only be noticed by comparing with a
+//! length and head hash that were stored elsewhere, which `verify_head` does.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::hex::FromHex;
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The metadata key holding the position of an entry.
+pub const INDEX_KEY: &str = "chain.index";
+
+/// The metadata key holding the hash of the previous entry, hex encoded.
+pub const PREVIOUS_KEY: &str = "chain.previous";
+
+/// This error is returned, if a chain doesn't verify.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChainError {
+    /// The entry at this position isn't validly signed.
+    Invalid(usize, ValidationError),
+    /// The entry at this position doesn't link to the one before it.
+    Broken(usize),
+    /// The chain is shorter than expected or ends with a different entry.
+    Truncated,
+}
+
+impl fmt::Display for ChainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChainError::Invalid(i, ref e) => write!(f, "entry {} is invalid: {:?}", i, e),
+            ChainError::Broken(i) => write!(f, "entry {} doesn't link to the previous entry", i),
+            ChainError::Truncated => write!(f, "the chain has been truncated"),
+        }
+    }
+}
+
+impl Error for ChainError {}
+
+/// Returns the hash that identifies an entry: SHA-512 over its signed bytes and its signature.
+pub fn entry_hash<T: Fingerprint>(letter: &Letter<T>) -> Vec<u8> {
+    let mut bytes = letter.signed_bytes();
+    bytes.extend_from_slice(letter.signature().hash());
+    sha512::hash(&bytes).0.to_vec()
+}
+
+/// A tamper-evident sequence of letters.
+#[derive(PartialEq, Debug)]
+pub struct LetterChain<T: Fingerprint> {
+    entries: Vec<Letter<T>>,
+}
+
+impl<T: Fingerprint> LetterChain<T> {
+    /// Creates an empty chain.
+    pub fn new() -> LetterChain<T> {
+        LetterChain { entries: Vec::new() }
+    }
+
+    /// Creates a chain from stored letters. Call `verify` before trusting it.
+    pub fn from_letters(entries: Vec<Letter<T>>) -> LetterChain<T> {
+        LetterChain { entries }
+    }
+
+    /// Signs the content as the next entry. The chain metadata is added to the given header.
+    pub fn append(&mut self, content: T, mut header: Header, signer: &Signer) -> Result<(), SignError> {
+        header.set_meta(INDEX_KEY, &self.entries.len().to_string());
+        match self.head() {
+            Some(head) => header.set_meta(PREVIOUS_KEY, &head.to_hex()),
+            None => {
+                header.remove_meta(PREVIOUS_KEY);
+            }
+        }
+
+        let letter = Letter::sign(content, header, signer)?;
+        self.entries.push(letter);
+        Ok(())
+    }
+
+    /// Returns the hash of the last entry, or None for an empty chain.
+    pub fn head(&self) -> Option<Vec<u8>> {
+        self.entries.last().map(entry_hash)
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns true, if the chain has no entries.
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the entries in order.
+    pub fn entries(&self) -> &[Letter<T>] {
+        &self.entries
+    }
+
+    /// Returns the entries in order.
+    pub fn into_letters(self) -> Vec<Letter<T>> {
+        self.entries
+    }
+
+    /// Checks the signature of every entry and that every entry links to the one before it.
+    pub fn verify<V: Validator>(&self, cv: &V) -> Result<(), ChainError> {
+        let mut previous: Option<Vec<u8>> = None;
+
+        for (i, letter) in self.entries.iter().enumerate() {
+            cv.is_valid(letter).map_err(|e| ChainError::Invalid(i, e))?;
+
+            let index = letter.header().get_meta(INDEX_KEY);
+            let link = match letter.header().get_meta(PREVIOUS_KEY) {
+                Some(hex) => Some(hex.from_hex().map_err(|_| ChainError::Broken(i))?),
+                None => None,
+            };
+
+            if index != Some(&i.to_string()[..]) || link != previous {
+                return Err(ChainError::Broken(i));
+            }
+
+            previous = Some(entry_hash(letter));
+        }
+
+        Ok(())
+    }
+
+    /// Verifies the chain and checks that it contains an entry at position `len - 1` with the
+    /// hash `head`, as recorded earlier. Entries appended since then are allowed.
+    pub fn verify_head<V: Validator>(&self, cv: &V, len: usize, head: &[u8]) -> Result<(), ChainError> {
+        self.verify(cv)?;
+
+        if len == 0 {
+            return Ok(());
+        }
+
+        match self.entries.get(len - 1) {
+            Some(letter) if entry_hash(letter) == head => Ok(()),
+            _ => Err(ChainError::Truncated),
+        }
+    }
+}
+
+impl<T: Fingerprint> Default for LetterChain<T> {
+    fn default() -> LetterChain<T> {
+        LetterChain::new()
+    }
+}
+
+#[test]
+fn test_chain() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let signer = Signer::PrivateKey(&msk);
+
+    let mut chain = LetterChain::new();
+    chain.append("first", Header::new(), &signer).unwrap();
+    chain.append("second", Header::new(), &signer).unwrap();
+    let (len, head) = (chain.len(), chain.head().unwrap());
+    chain.append("third", Header::new(), &signer).unwrap();
+
+    assert_eq!(Ok(()), chain.verify(&cv));
+    assert_eq!(Ok(()), chain.verify_head(&cv, len, &head));
+
+    let mut letters = chain.into_letters();
+    letters.pop();
+    letters.pop();
+    let truncated = LetterChain::from_letters(letters);
+    assert_eq!(Ok(()), truncated.verify(&cv));
+    assert_eq!(Err(ChainError::Truncated), truncated.verify_head(&cv, len, &head));
+
+    let mut letters = truncated.into_letters();
+    let mut other = LetterChain::new();
+    other.append("a", Header::new(), &signer).unwrap();
+    other.append("b", Header::new(), &signer).unwrap();
+    letters.push(other.into_letters().pop().unwrap());
+    assert_eq!(Err(ChainError::Broken(1)), LetterChain::from_letters(letters).verify(&cv));
+}
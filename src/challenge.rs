@@ -0,0 +1,266 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Challenge-response authentication.
+//!
+//! To prove that a peer holds the private key of a certificate, the verifier sends a fresh
+//! `Challenge` and the peer answers with a letter whose content is that challenge. Because the
+//! challenge is the signed content, a response can't be replayed for another challenge. The
+//! optional context (for example the name of the service) keeps a response for one service from
+//! being relayed to another: the peer passes the context it expects to `answer_challenge` and
+//! refuses challenges for any other.
+
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+use sodiumoxide::randombytes::randombytes;
+
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of challenge responses.
+pub const CHALLENGE_CONTENT_TYPE: &str = "edcert-letter/challenge-response";
+
+/// The number of random bytes in a challenge.
+pub const CHALLENGE_SIZE: usize = 32;
+
+/// This error is returned, if a challenge can't be answered or a response is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ChallengeError {
+    /// The challenge is for another context than the one the peer expects.
+    WrongContext(String),
+    /// The challenge can't be signed.
+    Sign(SignError),
+    /// The response answers another challenge.
+    Mismatch,
+    /// The challenge is older than the allowed age.
+    Expired,
+    /// The response isn't validly signed.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for ChallengeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ChallengeError::WrongContext(ref c) => write!(f, "the challenge is for another context: {}", c),
+            ChallengeError::Sign(ref e) => write!(f, "can't answer the challenge: {}", e),
+            ChallengeError::Mismatch => write!(f, "the response answers another challenge"),
+            ChallengeError::Expired => write!(f, "the challenge has expired"),
+            ChallengeError::Invalid(ref e) => write!(f, "the response is not valid: {:?}", e),
+        }
+    }
+}
+
+impl Error for ChallengeError {}
+
+impl From<SignError> for ChallengeError {
+    fn from(e: SignError) -> ChallengeError {
+        ChallengeError::Sign(e)
+    }
+}
+
+/// A random challenge, issued by the verifier.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Challenge {
+    nonce: Vec<u8>,
+    context: String,
+    issued_at: DateTime<UTC>,
+}
+
+impl Challenge {
+    /// Creates a new random challenge without a context.
+    pub fn random() -> Challenge {
+        Challenge::with_context("")
+    }
+
+    /// Creates a new random challenge bound to the given context.
+    pub fn with_context(context: &str) -> Challenge {
+        Challenge::with_clock(context, &SystemClock)
+    }
+
+    /// Creates a new random challenge, issued at the time of the clock.
+    pub fn with_clock<C: Clock>(context: &str, clock: &C) -> Challenge {
+        Challenge {
+            nonce: randombytes(CHALLENGE_SIZE),
+            context: context.to_string(),
+            issued_at: clock.now(),
+        }
+    }
+
+    /// Returns the random bytes of the challenge.
+    pub fn nonce(&self) -> &[u8] {
+        &self.nonce
+    }
+
+    /// Returns the context of the challenge.
+    pub fn context(&self) -> &str {
+        &self.context
+    }
+
+    /// Returns when the challenge was issued.
+    pub fn issued_at(&self) -> &DateTime<UTC> {
+        &self.issued_at
+    }
+
+    /// Returns the challenge as it is sent to the peer.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.fingerprint()
+    }
+
+    /// Reads a challenge sent by the verifier.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Challenge, DecodeError> {
+        Challenge::from_fingerprint(bytes)
+    }
+}
+
+impl Fingerprint for Challenge {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(&self.nonce);
+        w.bytes(self.context.as_bytes());
+        w.u64(self.issued_at.timestamp() as u64);
+        w.u32(self.issued_at.timestamp_subsec_nanos());
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for Challenge {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Challenge, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let nonce = r.bytes()?.to_vec();
+        let context = String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?;
+        let secs = r.u64()? as i64;
+        let nanos = r.u32()?;
+        let issued_at = UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidContent)?;
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(Challenge {
+            nonce,
+            context,
+            issued_at,
+        })
+    }
+}
+
+impl Letter<Challenge> {
+    /// Answers the challenge by signing it. It fails, if the challenge isn't for the context the
+    /// peer expects, for example the name of the service it is logging in to, so a challenge
+    /// relayed from another service isn't answered.
+    pub fn answer_challenge(challenge: &Challenge,
+                            context: &str,
+                            signer: &Signer)
+                            -> Result<Letter<Challenge>, ChallengeError> {
+        if challenge.context() != context {
+            return Err(ChallengeError::WrongContext(challenge.context().to_string()));
+        }
+
+        let mut header = Header::new();
+        header.set_content_type(CHALLENGE_CONTENT_TYPE);
+        Ok(Letter::sign(challenge.clone(), header, signer)?)
+    }
+}
+
+/// Checks that the response is a valid answer to the challenge, and that the challenge was
+/// issued at most `max_age` ago.
+pub fn verify_challenge_response<V: Validator>(challenge: &Challenge,
+                                               response: &Letter<Challenge>,
+                                               max_age: Duration,
+                                               cv: &V)
+                                               -> Result<(), ChallengeError> {
+    verify_challenge_response_with_clock(challenge, response, max_age, cv, &SystemClock)
+}
+
+/// Like `verify_challenge_response`, but takes the current time from the clock.
+pub fn verify_challenge_response_with_clock<V: Validator, C: Clock>(challenge: &Challenge,
+                                                                    response: &Letter<Challenge>,
+                                                                    max_age: Duration,
+                                                                    cv: &V,
+                                                                    clock: &C)
+                                                                    -> Result<(), ChallengeError> {
+    response.validate_as(cv, CHALLENGE_CONTENT_TYPE).map_err(ChallengeError::Invalid)?;
+
+    if response.get() != challenge {
+        return Err(ChallengeError::Mismatch);
+    }
+
+    if clock.now() - *challenge.issued_at() > max_age {
+        return Err(ChallengeError::Expired);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn test_challenge_response() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use clock::ManualClock;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (_, other) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let clock = ManualClock::new(UTC::now());
+
+    let challenge = Challenge::with_clock("login.example.com", &clock);
+    let received = Challenge::from_bytes(&challenge.to_bytes()).unwrap();
+    assert_eq!(challenge, received);
+
+    let response = Letter::answer_challenge(&received, "login.example.com", &Signer::PrivateKey(&msk)).unwrap();
+    let response: Letter<Challenge> = Letter::from_bytes(&response.to_bytes()).unwrap();
+    let max_age = Duration::minutes(1);
+    assert_eq!(Ok(()), verify_challenge_response_with_clock(&challenge, &response, max_age, &cv, &clock));
+
+    let another = Challenge::with_clock("login.example.com", &clock);
+    assert_eq!(Err(ChallengeError::Mismatch), verify_challenge_response_with_clock(&another, &response, max_age, &cv, &clock));
+
+    let forged = Letter::answer_challenge(&challenge, "login.example.com", &Signer::PrivateKey(&other)).unwrap();
+    assert_eq!(true, verify_challenge_response_with_clock(&challenge, &forged, max_age, &cv, &clock).is_err());
+
+    // A service relaying the challenge of another one doesn't get an answer.
+    let relayed = Challenge::with_clock("evil.example.com", &clock);
+    assert_eq!(Err(ChallengeError::WrongContext("evil.example.com".to_string())),
+               Letter::answer_challenge(&relayed, "login.example.com", &Signer::PrivateKey(&msk)).map(|_| ()));
+
+    clock.advance(Duration::minutes(2));
+    assert_eq!(Err(ChallengeError::Expired), verify_challenge_response_with_clock(&challenge, &response, max_age, &cv, &clock));
+}
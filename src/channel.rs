@@ -0,0 +1,102 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Binding letters to a TLS session.
+//!
+//! A letter that proves an identity over TLS can be relayed by a man-in-the-middle, who
+//! terminates one TLS session and opens another. To prevent this, both ends take channel binding
+//! data from their TLS library, for example a keying material exporter (RFC 5705) or
+//! `tls-unique`, which differs between the two sessions of the attacker. The signer writes a
+//! digest of it into the header, and the verifier compares it with the binding of its own session.
+
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The metadata key holding the SHA-512 digest of the channel binding, hex encoded.
+pub const CHANNEL_BINDING_KEY: &str = "channel-binding";
+
+/// Returns the value stored in the header for the binding.
+fn binding_digest(binding: &[u8]) -> String {
+    sha512::hash(binding).0.to_hex()
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// Signs the content bound to the channel with the given binding data.
+    pub fn bound_to_channel(content: T, binding: &[u8], signer: &Signer) -> Result<Letter<T>, SignError> {
+        Letter::bound_to_channel_with_header(content, Header::new(), binding, signer)
+    }
+
+    /// Like `bound_to_channel`, but starts from the given header.
+    pub fn bound_to_channel_with_header(content: T,
+                                        mut header: Header,
+                                        binding: &[u8],
+                                        signer: &Signer)
+                                        -> Result<Letter<T>, SignError> {
+        header.set_meta(CHANNEL_BINDING_KEY, &binding_digest(binding));
+        Letter::sign(content, header, signer)
+    }
+
+    /// Returns true, if the letter is bound to the channel with the given binding data.
+    /// Letters that aren't bound to any channel return false.
+    pub fn is_bound_to_channel(&self, binding: &[u8]) -> bool {
+        self.header().get_meta(CHANNEL_BINDING_KEY) == Some(&binding_digest(binding)[..])
+    }
+
+    /// Validates the letter and checks that it is bound to the channel with the given binding
+    /// data. Letters for another or without a channel yield `ValidationError::Other`.
+    pub fn validate_for_channel<V: Validator>(&self, cv: &V, binding: &[u8]) -> Result<(), ValidationError> {
+        cv.is_valid(self)?;
+
+        if self.is_bound_to_channel(binding) {
+            Ok(())
+        } else {
+            Err(ValidationError::Other)
+        }
+    }
+}
+
+#[test]
+fn test_channel_binding() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let letter = Letter::bound_to_channel("it's me", b"exporter of session a", &Signer::PrivateKey(&msk)).unwrap();
+    assert_eq!(Ok(()), letter.validate_for_channel(&cv, b"exporter of session a"));
+    assert_eq!(Err(ValidationError::Other), letter.validate_for_channel(&cv, b"exporter of session b"));
+
+    let unbound = Letter::with_private_key("it's me", &msk);
+    assert_eq!(false, unbound.is_bound_to_channel(b"exporter of session a"));
+}
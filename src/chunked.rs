@@ -0,0 +1,264 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Signatures over large data that can be checked while it streams.
+//!
+//! The data is split into chunks of a fixed size and the letter signs the SHA-512 of every
+//! chunk. After validating the letter, a downloader wraps the data in a `VerifyingReader`, which
+//! reads one chunk ahead and only hands out bytes of chunks whose hash matches. Nothing has to be
+//! buffered beyond a single chunk.
+
+use std::io;
+use std::io::Read;
+
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of chunked letters.
+pub const CHUNKED_CONTENT_TYPE: &str = "edcert-letter/chunks";
+
+/// The chunk size, if none is given: 4 MiB.
+pub const DEFAULT_CHUNK_SIZE: u32 = 4 * 1024 * 1024;
+
+/// The signed description of chunked data: the chunk size, the total length and the hash of
+/// every chunk.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Chunks {
+    chunk_size: u32,
+    length: u64,
+    hashes: Vec<Vec<u8>>,
+}
+
+/// A letter over chunked data.
+pub type ChunkedLetter = Letter<Chunks>;
+
+impl Chunks {
+    /// Reads the data to its end and hashes it chunk by chunk.
+    pub fn from_reader<R: Read>(mut reader: R, chunk_size: u32) -> io::Result<Chunks> {
+        assert!(chunk_size > 0, "The chunk size must not be 0.");
+
+        let mut chunks = Chunks {
+            chunk_size,
+            length: 0,
+            hashes: Vec::new(),
+        };
+
+        let mut buf = Vec::with_capacity(chunk_size as usize);
+        loop {
+            buf.clear();
+            (&mut reader).take(chunk_size as u64).read_to_end(&mut buf)?;
+            if buf.is_empty() {
+                return Ok(chunks);
+            }
+
+            chunks.length += buf.len() as u64;
+            chunks.hashes.push(sha512::hash(&buf).0.to_vec());
+        }
+    }
+
+    /// Returns the chunk size.
+    pub fn chunk_size(&self) -> u32 {
+        self.chunk_size
+    }
+
+    /// Returns the total length of the data.
+    pub fn len(&self) -> u64 {
+        self.length
+    }
+
+    /// Returns true, if the data is empty.
+    pub fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns the hashes of the chunks.
+    pub fn hashes(&self) -> &[Vec<u8>] {
+        &self.hashes
+    }
+
+    /// Signs the chunks. The header gets the chunked content type.
+    pub fn sign(self, mut header: Header, signer: &Signer) -> Result<ChunkedLetter, SignError> {
+        header.set_content_type(CHUNKED_CONTENT_TYPE);
+        Letter::sign(self, header, signer)
+    }
+}
+
+impl Fingerprint for Chunks {
+    fn fingerprint(&self) -> Vec<u8> {
+        canonical::to_bytes(&(self.chunk_size, self.length, &self.hashes))
+    }
+}
+
+impl FromFingerprint for Chunks {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Chunks, DecodeError> {
+        let (chunk_size, length, hashes): (u32, u64, Vec<Vec<u8>>) = canonical::from_bytes(bytes)?;
+
+        if chunk_size == 0 {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        // The number of chunks must match the length, so the reader knows when the data ends.
+        let size = chunk_size as u64;
+        let expected = length / size + if length % size == 0 { 0 } else { 1 };
+        if hashes.len() as u64 != expected {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(Chunks {
+            chunk_size,
+            length,
+            hashes,
+        })
+    }
+}
+
+/// A reader that checks every chunk against its signed hash before handing out its bytes. A
+/// wrong chunk, missing data or extra data yield an error of kind `InvalidData`.
+pub struct VerifyingReader<'a, R: Read> {
+    inner: R,
+    chunks: &'a Chunks,
+    next: usize,
+    buf: Vec<u8>,
+    pos: usize,
+}
+
+impl<'a, R: Read> VerifyingReader<'a, R> {
+    /// Wraps the data. Validate the letter the chunks come from first.
+    pub fn new(inner: R, chunks: &'a Chunks) -> VerifyingReader<'a, R> {
+        VerifyingReader {
+            inner,
+            chunks,
+            next: 0,
+            buf: Vec::new(),
+            pos: 0,
+        }
+    }
+
+    fn fill(&mut self) -> io::Result<()> {
+        self.buf.clear();
+        self.pos = 0;
+
+        let expected = match self.chunks.hashes.get(self.next) {
+            Some(hash) => hash,
+            None => {
+                // All chunks have been read, the data must end here.
+                let mut extra = [0u8; 1];
+                return match self.inner.read(&mut extra)? {
+                    0 => Ok(()),
+                    _ => Err(invalid_data("data is longer than signed")),
+                };
+            }
+        };
+
+        let offset = self.next as u64 * self.chunks.chunk_size as u64;
+        let len = ::std::cmp::min(self.chunks.chunk_size as u64, self.chunks.length - offset);
+        (&mut self.inner).take(len).read_to_end(&mut self.buf)?;
+
+        if self.buf.len() as u64 != len {
+            self.buf.clear();
+            return Err(invalid_data("data is shorter than signed"));
+        }
+
+        if sha512::hash(&self.buf).0[..] != expected[..] {
+            self.buf.clear();
+            return Err(invalid_data("chunk hash mismatch"));
+        }
+
+        self.next += 1;
+        Ok(())
+    }
+}
+
+fn invalid_data(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg)
+}
+
+impl<'a, R: Read> Read for VerifyingReader<'a, R> {
+    fn read(&mut self, out: &mut [u8]) -> io::Result<usize> {
+        if self.pos == self.buf.len() {
+            self.fill()?;
+        }
+
+        let n = ::std::cmp::min(out.len(), self.buf.len() - self.pos);
+        out[..n].copy_from_slice(&self.buf[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+impl Letter<Chunks> {
+    /// This method validates the letter and checks that it has the chunked content type.
+    pub fn validate<V: Validator>(&self, cv: &V) -> Result<(), ValidationError> {
+        self.validate_as(cv, CHUNKED_CONTENT_TYPE)
+    }
+
+    /// This method wraps the data in a reader that checks it against the signed chunk hashes.
+    /// It doesn't check the signature of the letter, so `validate` the letter first.
+    pub fn verifying_reader<'a, R: Read>(&'a self, data: R) -> VerifyingReader<'a, R> {
+        VerifyingReader::new(data, self.get())
+    }
+}
+
+#[test]
+fn test_chunked() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let data: Vec<u8> = (0..1000u32).map(|i| i as u8).collect();
+    let chunks = Chunks::from_reader(&data[..], 64).unwrap();
+    let letter = chunks.sign(Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    let letter: ChunkedLetter = Letter::from_bytes(&letter.to_bytes()).unwrap();
+    assert_eq!(Ok(()), letter.validate(&cv));
+    assert_eq!(Err(ValidationError::Other), Letter::with_private_key(letter.get().clone(), &msk).validate(&cv));
+    assert_eq!(16, letter.hashes().len());
+
+    let mut out = Vec::new();
+    letter.verifying_reader(&data[..]).read_to_end(&mut out).unwrap();
+    assert_eq!(data, out);
+
+    let mut tampered = data.clone();
+    tampered[500] ^= 1;
+    let mut out = Vec::new();
+    let err = letter.verifying_reader(&tampered[..]).read_to_end(&mut out).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidData, err.kind());
+    assert_eq!(448, out.len());
+
+    let mut longer = data.clone();
+    longer.push(0);
+    assert_eq!(true, letter.verifying_reader(&longer[..]).read_to_end(&mut Vec::new()).is_err());
+    assert_eq!(true, letter.verifying_reader(&data[..999]).read_to_end(&mut Vec::new()).is_err());
+}
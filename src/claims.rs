@@ -0,0 +1,366 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Standard claims of token-like letters.
+//!
+//! Auth tokens tend to carry the same few fields: who issued them, who they are about, which
+//! service they are for and when they expire. A content type that implements `Claims` names those
+//! fields, and a `ClaimsValidator` checks them after the signature, so every token type doesn't
+//! need its own validation code. The validator also checks the `audience` and `expires` fields of
+//! the letter header: a letter expires at the earlier of both expiries, and an audience in either
+//! place has to be expected. Expiries are checked with the validator's clock skew, see
+//! `ClaimsValidator::with_skew`. `token::TokenClaims` implements the trait.
+//!
+//! With the `derive` feature, `#[derive(Claims)]` implements the trait from field attributes.
+//! Together with `#[derive(Fingerprint)]`, the claims are part of the fingerprint like every
+//! other field:
+//!
+//! ```ignore
+//! #[derive(Fingerprint, Claims)]
+//! struct Session {
+//!     #[claim(issuer)]
+//!     iss: String,
+//!     #[claim(subject)]
+//!     user: String,
+//!     #[claim(audience)]
+//!     aud: Option<String>,
+//!     #[claim(expires)]
+//!     exp: DateTime<UTC>,
+//!     roles: Vec<String>,
+//! }
+//! ```
+//!
+//! String claims can be `String` or `Option<String>`, the expiry `DateTime<UTC>` or
+//! `Option<DateTime<UTC>>`.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+use letter::Letter;
+
+/// The type of the expiry claim.
+pub type Timestamp = DateTime<UTC>;
+
+/// Content with standard claims. Every claim is optional.
+pub trait Claims: Fingerprint {
+    /// Returns who issued the content.
+    fn issuer(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns who the content is about.
+    fn subject(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns the service the content is meant for.
+    fn audience(&self) -> Option<&str> {
+        None
+    }
+
+    /// Returns when the content expires.
+    fn expires(&self) -> Option<Timestamp> {
+        None
+    }
+}
+
+/// Field types that can hold a string claim. Used by `#[derive(Claims)]`.
+pub trait StrClaim {
+    /// Returns the claim.
+    fn claim(&self) -> Option<&str>;
+}
+
+impl StrClaim for String {
+    fn claim(&self) -> Option<&str> {
+        Some(self)
+    }
+}
+
+impl StrClaim for Option<String> {
+    fn claim(&self) -> Option<&str> {
+        self.as_ref().map(|s| s.as_str())
+    }
+}
+
+/// Field types that can hold the expiry claim. Used by `#[derive(Claims)]`.
+pub trait TimeClaim {
+    /// Returns the claim.
+    fn claim(&self) -> Option<Timestamp>;
+}
+
+impl TimeClaim for Timestamp {
+    fn claim(&self) -> Option<Timestamp> {
+        Some(*self)
+    }
+}
+
+impl TimeClaim for Option<Timestamp> {
+    fn claim(&self) -> Option<Timestamp> {
+        *self
+    }
+}
+
+/// This error is returned, if the claims of a letter are not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum ClaimsError {
+    /// The letter isn't valid.
+    Invalid(ValidationError),
+    /// The letter has expired.
+    Expired,
+    /// A claim the validator needs is missing, like an expiry that is required.
+    Missing(&'static str),
+    /// A claim can't be read, like an expiry in the header that isn't a time.
+    Malformed(&'static str),
+    /// The letter was issued by somebody the validator doesn't expect.
+    WrongIssuer,
+    /// The letter is meant for another service.
+    WrongAudience,
+}
+
+impl fmt::Display for ClaimsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ClaimsError::Invalid(ref e) => write!(f, "invalid letter: {:?}", e),
+            ClaimsError::Expired => write!(f, "the letter has expired"),
+            ClaimsError::Missing(claim) => write!(f, "the {} claim is missing", claim),
+            ClaimsError::Malformed(claim) => write!(f, "the {} claim is malformed", claim),
+            ClaimsError::WrongIssuer => write!(f, "unexpected issuer"),
+            ClaimsError::WrongAudience => write!(f, "the letter is meant for another audience"),
+        }
+    }
+}
+
+impl Error for ClaimsError {}
+
+/// A validator that checks the claims of a letter after its signature.
+pub struct ClaimsValidator<V: Validator, C: Clock = SystemClock> {
+    inner: V,
+    issuers: BTreeSet<String>,
+    audiences: BTreeSet<String>,
+    require_expiry: bool,
+    skew: Duration,
+    clock: C,
+}
+
+impl<V: Validator> ClaimsValidator<V> {
+    /// Wraps the validator. Without expectations, it only checks the expiry, if there is one.
+    pub fn new(inner: V) -> ClaimsValidator<V> {
+        ClaimsValidator {
+            inner,
+            issuers: BTreeSet::new(),
+            audiences: BTreeSet::new(),
+            require_expiry: false,
+            skew: Duration::zero(),
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<V: Validator, C: Clock> ClaimsValidator<V, C> {
+    /// Replaces the clock the expiry is checked against.
+    pub fn with_clock<D: Clock>(self, clock: D) -> ClaimsValidator<V, D> {
+        ClaimsValidator {
+            inner: self.inner,
+            issuers: self.issuers,
+            audiences: self.audiences,
+            require_expiry: self.require_expiry,
+            skew: self.skew,
+            clock,
+        }
+    }
+
+    /// Tolerates clocks that are off by up to `skew`: letters are accepted until `skew` after
+    /// they expire.
+    pub fn with_skew(mut self, skew: Duration) -> ClaimsValidator<V, C> {
+        self.skew = skew;
+        self
+    }
+
+    /// Returns the tolerated clock skew.
+    pub fn skew(&self) -> Duration {
+        self.skew
+    }
+
+    /// Accepts letters of the issuer. Once an issuer is expected, letters of others and without
+    /// an issuer are rejected.
+    pub fn expect_issuer(mut self, issuer: &str) -> ClaimsValidator<V, C> {
+        self.issuers.insert(issuer.to_string());
+        self
+    }
+
+    /// Accepts letters for the audience. Once an audience is expected, letters for others and
+    /// without an audience are rejected.
+    pub fn expect_audience(mut self, audience: &str) -> ClaimsValidator<V, C> {
+        self.audiences.insert(audience.to_string());
+        self
+    }
+
+    /// Rejects letters without an expiry.
+    pub fn require_expiry(mut self) -> ClaimsValidator<V, C> {
+        self.require_expiry = true;
+        self
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Validates the letter with the wrapped validator and checks its claims.
+    pub fn validate<T: Claims>(&self, letter: &Letter<T>) -> Result<(), ClaimsError> {
+        self.inner.is_valid(letter).map_err(ClaimsError::Invalid)?;
+        self.check_claims(letter, self.clock.now())
+    }
+
+    /// Like `validate`, but also requires the content type, see `Letter::validate_as`.
+    pub fn validate_as<T: Claims>(&self, letter: &Letter<T>, content_type: &str) -> Result<(), ClaimsError> {
+        letter.validate_as(&self.inner, content_type).map_err(ClaimsError::Invalid)?;
+        self.check_claims(letter, self.clock.now())
+    }
+
+    /// Checks the claims and the header of an already validated letter at the given time.
+    pub(crate) fn check_claims<T: Claims>(&self, letter: &Letter<T>, now: Timestamp) -> Result<(), ClaimsError> {
+        let claims = letter.get();
+        let header = letter.header();
+
+        let expires = match (claims.expires(), header.expires().map_err(|_| ClaimsError::Malformed("expires"))?) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        };
+
+        match expires {
+            Some(expires) if expires + self.skew <= now => return Err(ClaimsError::Expired),
+            None if self.require_expiry => return Err(ClaimsError::Missing("expires")),
+            _ => {}
+        }
+
+        if !self.issuers.is_empty() && !claims.issuer().is_some_and(|i| self.issuers.contains(i)) {
+            return Err(ClaimsError::WrongIssuer);
+        }
+
+        if !self.audiences.is_empty() {
+            let audiences = [claims.audience(), header.audience()];
+
+            if audiences.iter().all(|a| a.is_none()) ||
+               audiences.iter().flatten().any(|a| !self.audiences.contains(*a)) {
+                return Err(ClaimsError::WrongAudience);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_claims_validator() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use canonical;
+    use clock::ManualClock;
+
+    struct Session {
+        iss: String,
+        aud: Option<String>,
+        exp: Timestamp,
+    }
+
+    impl Fingerprint for Session {
+        fn fingerprint(&self) -> Vec<u8> {
+            canonical::to_bytes(&(&self.iss, &self.aud, &self.exp))
+        }
+    }
+
+    impl Claims for Session {
+        fn issuer(&self) -> Option<&str> {
+            StrClaim::claim(&self.iss)
+        }
+
+        fn audience(&self) -> Option<&str> {
+            StrClaim::claim(&self.aud)
+        }
+
+        fn expires(&self) -> Option<Timestamp> {
+            TimeClaim::claim(&self.exp)
+        }
+    }
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let session = Session {
+        iss: "login".to_string(),
+        aud: Some("billing".to_string()),
+        exp: UTC::now() + Duration::hours(1),
+    };
+    let letter = Letter::with_private_key(session, &msk);
+
+    let cv = ClaimsValidator::new(RootValidator::new(&mpk, NoRevoker))
+                 .expect_issuer("login")
+                 .expect_audience("billing")
+                 .require_expiry();
+    assert_eq!(Ok(()), cv.validate(&letter));
+
+    let cv = cv.expect_audience("shipping").with_clock(ManualClock::new(UTC::now() + Duration::hours(2)));
+    assert_eq!(Err(ClaimsError::Expired), cv.validate(&letter));
+
+    let cv = cv.with_skew(Duration::hours(2));
+    assert_eq!(Ok(()), cv.validate(&letter));
+
+    let cv = ClaimsValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_issuer("sso");
+    assert_eq!(Err(ClaimsError::WrongIssuer), cv.validate(&letter));
+    let cv = ClaimsValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_audience("shipping");
+    assert_eq!(Err(ClaimsError::WrongAudience), cv.validate(&letter));
+
+    // The header audience and expiry are checked as well.
+    let session = Session {
+        iss: "login".to_string(),
+        aud: None,
+        exp: UTC::now() + Duration::hours(1),
+    };
+    let mut header = ::header::Header::new();
+    header.set_audience("shipping");
+    header.set_expires(UTC::now() + Duration::minutes(10));
+    let letter = Letter::sign(session, header, &::signer::Signer::PrivateKey(&msk)).unwrap();
+
+    let cv = ClaimsValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_audience("billing");
+    assert_eq!(Err(ClaimsError::WrongAudience), cv.validate(&letter));
+    let cv = ClaimsValidator::new(RootValidator::new(&mpk, NoRevoker)).expect_audience("shipping");
+    assert_eq!(Ok(()), cv.validate(&letter));
+
+    let cv = cv.with_clock(ManualClock::new(UTC::now() + Duration::minutes(20)));
+    assert_eq!(Err(ClaimsError::Expired), cv.validate(&letter));
+    let cv = cv.with_skew(Duration::minutes(15));
+    assert_eq!(Ok(()), cv.validate(&letter));
+}
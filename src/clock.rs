@@ -0,0 +1,98 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The source of the current time for time-dependent checks.
+//!
+//! Validators and letters take the current time from a `Clock`, so tests can move time around
+//! and targets without a real-time clock can supply their own time. `SystemClock` is used by
+//! default. The expiry check of certificates is done by edcert and always uses the system time.
+
+use std::sync::Mutex;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::UTC;
+
+/// A source of the current time.
+pub trait Clock {
+    /// Returns the current time.
+    fn now(&self) -> DateTime<UTC>;
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> DateTime<UTC> {
+        (**self).now()
+    }
+}
+
+/// The time of the operating system.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now(&self) -> DateTime<UTC> {
+        UTC::now()
+    }
+}
+
+/// A clock that only moves when it is told to, for tests.
+#[derive(Debug)]
+pub struct ManualClock {
+    now: Mutex<DateTime<UTC>>,
+}
+
+impl ManualClock {
+    /// Creates a clock that stands at the given time.
+    pub fn new(now: DateTime<UTC>) -> ManualClock {
+        ManualClock { now: Mutex::new(now) }
+    }
+
+    /// Sets the time.
+    pub fn set(&self, now: DateTime<UTC>) {
+        *self.now.lock().unwrap() = now;
+    }
+
+    /// Moves the time forward, or backward for a negative duration.
+    pub fn advance(&self, duration: Duration) {
+        let mut now = self.now.lock().unwrap();
+        *now = *now + duration;
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> DateTime<UTC> {
+        *self.now.lock().unwrap()
+    }
+}
+
+#[test]
+fn test_manual_clock() {
+    let start = UTC::now();
+    let clock = ManualClock::new(start);
+    assert_eq!(start, clock.now());
+
+    clock.advance(Duration::days(1));
+    assert_eq!(start + Duration::days(1), Clock::now(&&clock));
+
+    clock.set(start);
+    assert_eq!(start, clock.now());
+}
@@ -0,0 +1,153 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Compression of the content bytes in a serialized letter.
+//!
+//! The signature is made over the uncompressed content, so compression only changes what goes
+//! over the wire. The chosen algorithm is stored in the header, so it is authenticated like every
+//! other header field. Deflate and zstd need the features of the same name.
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+use std::io::Read;
+
+use format::DecodeError;
+
+/// Decompressing yields at most this many bytes, so a small letter can't exhaust the memory of
+/// the reader.
+pub const MAX_DECOMPRESSED_SIZE: u64 = 64 * 1024 * 1024;
+
+/// A compression algorithm for the content bytes.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum Compression {
+    /// The content is stored as it is, the default.
+    #[default]
+    None,
+    /// Deflate (RFC 1951).
+    #[cfg(feature = "deflate")]
+    Deflate,
+    /// Zstandard.
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+
+impl Compression {
+    /// Compresses the bytes.
+    pub fn compress(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            Compression::None => data.to_vec(),
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => {
+                let mut out = Vec::new();
+                ::flate2::read::DeflateEncoder::new(data, ::flate2::Compression::default())
+                    .read_to_end(&mut out)
+                    .expect("Compressing from memory can't fail.");
+                out
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                ::zstd::encode_all(data, 0).expect("Compressing from memory can't fail.")
+            }
+        }
+    }
+
+    /// Decompresses the bytes. Invalid input yields `DecodeError::InvalidContent`, output larger
+    /// than `MAX_DECOMPRESSED_SIZE` yields `DecodeError::ContentTooLarge`.
+    pub fn decompress(&self, data: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        self.decompress_limited(data, MAX_DECOMPRESSED_SIZE)
+    }
+
+    /// Decompresses the bytes like `decompress`, but with another size limit. The limit applies
+    /// to uncompressed content, too.
+    pub fn decompress_limited(&self, data: &[u8], limit: u64) -> Result<Vec<u8>, DecodeError> {
+        match *self {
+            Compression::None => {
+                if data.len() as u64 > limit {
+                    return Err(DecodeError::ContentTooLarge);
+                }
+
+                Ok(data.to_vec())
+            }
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => read_limited(::flate2::read::DeflateDecoder::new(data), limit),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => {
+                let decoder = ::zstd::Decoder::new(data).map_err(|_| DecodeError::InvalidContent)?;
+                read_limited(decoder, limit)
+            }
+        }
+    }
+
+    /// Returns the byte that identifies this algorithm in a letter.
+    pub fn id(&self) -> u8 {
+        match *self {
+            Compression::None => 0,
+            #[cfg(feature = "deflate")]
+            Compression::Deflate => 1,
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => 2,
+        }
+    }
+
+    /// Parses an algorithm id. Algorithms that are unknown or not compiled in yield
+    /// `DecodeError::UnsupportedCompression`.
+    pub fn from_id(id: u8) -> Result<Compression, DecodeError> {
+        match id {
+            0 => Ok(Compression::None),
+            #[cfg(feature = "deflate")]
+            1 => Ok(Compression::Deflate),
+            #[cfg(feature = "zstd")]
+            2 => Ok(Compression::Zstd),
+            id => Err(DecodeError::UnsupportedCompression(id)),
+        }
+    }
+}
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+fn read_limited<R: Read>(reader: R, limit: u64) -> Result<Vec<u8>, DecodeError> {
+    let mut out = Vec::new();
+    reader.take(limit.saturating_add(1))
+          .read_to_end(&mut out)
+          .map_err(|_| DecodeError::InvalidContent)?;
+
+    if out.len() as u64 > limit {
+        return Err(DecodeError::ContentTooLarge);
+    }
+
+    Ok(out)
+}
+
+#[cfg(any(feature = "deflate", feature = "zstd"))]
+#[test]
+fn test_roundtrip() {
+    let data = vec![b'a'; 10000];
+    let algorithms = [#[cfg(feature = "deflate")] Compression::Deflate,
+                      #[cfg(feature = "zstd")] Compression::Zstd];
+
+    for algorithm in algorithms.iter() {
+        let compressed = algorithm.compress(&data);
+        assert_eq!(true, compressed.len() < data.len());
+        assert_eq!(Ok(data.clone()), algorithm.decompress(&compressed));
+        assert_eq!(Err(DecodeError::InvalidContent), algorithm.decompress(b"garbage"));
+        assert_eq!(Err(DecodeError::ContentTooLarge), algorithm.decompress_limited(&compressed, 100));
+    }
+}
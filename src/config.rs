@@ -0,0 +1,150 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed configuration files.
+//!
+//! `sign_config` turns any serde type into an armored letter with the content type
+//! `CONFIG_CONTENT_TYPE`. `load_verified` reads such a file, validates it and returns the typed
+//! configuration, so a service can refuse to start with configuration that was tampered with.
+//! Because the content type is checked, no other letter with the same JSON shape is accepted.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use armor;
+use canonical_json::CanonicalContent;
+use format;
+use format::DecodeError;
+use header::Header;
+use letter::Letter;
+use signer::Signer;
+
+/// The content type of signed configuration.
+pub const CONFIG_CONTENT_TYPE: &str = "application/vnd.edcert-letter.config+json";
+
+/// This error is returned, if a configuration can't be signed or loaded.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The value couldn't be encoded as JSON.
+    Encode(serde_json::Error),
+    /// The file isn't an armored letter with a configuration of the expected type.
+    Decode(DecodeError),
+    /// The signer has no private key.
+    NoPrivateKey,
+    /// The letter isn't validly signed or isn't a configuration letter.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ConfigError::Io(ref e) => write!(f, "can't read configuration: {}", e),
+            ConfigError::Encode(ref e) => write!(f, "can't encode configuration: {}", e),
+            ConfigError::Decode(ref e) => write!(f, "malformed configuration: {}", e),
+            ConfigError::NoPrivateKey => write!(f, "the signer has no private key"),
+            ConfigError::Invalid(ref e) => write!(f, "invalid configuration signature: {:?}", e),
+        }
+    }
+}
+
+impl Error for ConfigError {}
+
+/// Signs the configuration and returns it as an armored letter.
+pub fn sign_config<T: Serialize>(config: T, signer: &Signer) -> Result<String, ConfigError> {
+    let content = CanonicalContent::new(config).map_err(ConfigError::Encode)?;
+
+    let mut header = Header::new();
+    header.set_content_type(CONFIG_CONTENT_TYPE);
+
+    let letter = Letter::sign(content, header, signer).map_err(|_| ConfigError::NoPrivateKey)?;
+
+    // `T` needn't be deserializable, so `to_bytes` isn't available.
+    Ok(armor::armor(&format::encode(letter.header(), &letter.fingerprint(), letter.signature())))
+}
+
+/// Parses an armored configuration letter, validates it and returns the configuration.
+pub fn from_verified_str<T, V>(text: &str, cv: &V) -> Result<T, ConfigError>
+    where T: Serialize + DeserializeOwned,
+          V: Validator
+{
+    let bytes = armor::dearmor(text).map_err(ConfigError::Decode)?;
+    let letter: Letter<CanonicalContent<T>> = Letter::from_bytes(&bytes).map_err(ConfigError::Decode)?;
+
+    letter.validate_as(cv, CONFIG_CONTENT_TYPE).map_err(ConfigError::Invalid)?;
+
+    Ok(letter.into_inner().into_inner())
+}
+
+/// Reads the armored configuration letter at the path, validates it and returns the
+/// configuration.
+pub fn load_verified<T, V, P>(path: P, cv: &V) -> Result<T, ConfigError>
+    where T: Serialize + DeserializeOwned,
+          V: Validator,
+          P: AsRef<Path>
+{
+    let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+    from_verified_str(&text, cv)
+}
+
+#[test]
+fn test_config() {
+    use std::collections::BTreeMap;
+
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut config = BTreeMap::new();
+    config.insert("listen".to_string(), "0.0.0.0:443".to_string());
+    let text = sign_config(&config, &Signer::PrivateKey(&msk)).unwrap();
+
+    let path = ::std::env::temp_dir().join(format!("edcert-letter-config-{}.edl", ::std::process::id()));
+    fs::write(&path, &text).unwrap();
+    let loaded: BTreeMap<String, String> = load_verified(&path, &cv).unwrap();
+    assert_eq!(config, loaded);
+    fs::remove_file(&path).unwrap();
+
+    // Other letters of the same shape aren't configuration.
+    let other = Letter::with_private_key(CanonicalContent::new(config.clone()).unwrap(), &msk);
+    let other = armor::armor(&other.to_bytes());
+    match from_verified_str::<BTreeMap<String, String>, _>(&other, &cv) {
+        Err(ConfigError::Invalid(ValidationError::Other)) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
@@ -0,0 +1,513 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! COSE_Sign1 (RFC 9052) with the `EdDSA` algorithm.
+//!
+//! Like a JWS, a COSE_Sign1 structure signs its own `Sig_structure`, so `Letter::to_cose`
+//! signs the content and header again. The payload is the content fingerprint. The protected
+//! header holds the algorithm (label 1, EdDSA = -8), the public key of the signing certificate as
+//! key id (label 4) and two private labels: `"edh"` with the encoded letter header and, if a
+//! certificate signed, `"edc"` with the edcert JSON encoding of the certificate. The unprotected
+//! header is an empty map; a key id in it has to match the certificate like the protected one.
+
+use std::error::Error;
+use std::fmt;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use header::Header;
+use letter;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+use strict;
+
+/// The COSE algorithm id of EdDSA.
+pub const ALG_EDDSA: i64 = -8;
+
+/// The CBOR tag of a COSE_Sign1 structure.
+pub const COSE_SIGN1_TAG: u64 = 18;
+
+const LABEL_ALG: i64 = 1;
+const LABEL_KID: i64 = 4;
+
+// Nested CBOR beyond this depth is rejected, so the recursive reader can't overflow the stack.
+const MAX_DEPTH: usize = 16;
+
+/// This error is returned, if a COSE_Sign1 structure can't be verified.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CoseError {
+    /// The input isn't a COSE_Sign1 structure.
+    Malformed,
+    /// The structure uses another algorithm than EdDSA.
+    UnsupportedAlgorithm(i64),
+    /// The letter header, the certificate or the payload can't be decoded.
+    Decode(DecodeError),
+    /// The signature or the certificate isn't valid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for CoseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CoseError::Malformed => write!(f, "malformed COSE_Sign1"),
+            CoseError::UnsupportedAlgorithm(alg) => write!(f, "unsupported COSE algorithm {}", alg),
+            CoseError::Decode(ref e) => write!(f, "can't decode COSE_Sign1: {}", e),
+            CoseError::Invalid(ref e) => write!(f, "COSE_Sign1 is not valid: {:?}", e),
+        }
+    }
+}
+
+impl Error for CoseError {}
+
+impl From<DecodeError> for CoseError {
+    fn from(e: DecodeError) -> CoseError {
+        CoseError::Decode(e)
+    }
+}
+
+/// The subset of CBOR needed for COSE.
+#[derive(Clone, PartialEq, Debug)]
+enum Cbor {
+    Int(i64),
+    Bytes(Vec<u8>),
+    Text(String),
+    Array(Vec<Cbor>),
+    Map(Vec<(Cbor, Cbor)>),
+    Tag(u64, Box<Cbor>),
+}
+
+impl Cbor {
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        self.write(&mut out);
+        out
+    }
+
+    fn write(&self, out: &mut Vec<u8>) {
+        match *self {
+            Cbor::Int(n) if n >= 0 => write_head(0, n as u64, out),
+            Cbor::Int(n) => write_head(1, (-1 - n) as u64, out),
+            Cbor::Bytes(ref b) => {
+                write_head(2, b.len() as u64, out);
+                out.extend_from_slice(b);
+            }
+            Cbor::Text(ref s) => {
+                write_head(3, s.len() as u64, out);
+                out.extend_from_slice(s.as_bytes());
+            }
+            Cbor::Array(ref items) => {
+                write_head(4, items.len() as u64, out);
+                for item in items {
+                    item.write(out);
+                }
+            }
+            Cbor::Map(ref entries) => {
+                write_head(5, entries.len() as u64, out);
+                for (key, value) in entries {
+                    key.write(out);
+                    value.write(out);
+                }
+            }
+            Cbor::Tag(tag, ref value) => {
+                write_head(6, tag, out);
+                value.write(out);
+            }
+        }
+    }
+
+    fn read(input: &mut &[u8], depth: usize) -> Result<Cbor, CoseError> {
+        if depth > MAX_DEPTH {
+            return Err(CoseError::Malformed);
+        }
+
+        let (major, arg) = read_head(input)?;
+        match major {
+            0 if arg <= i64::MAX as u64 => Ok(Cbor::Int(arg as i64)),
+            1 if arg <= i64::MAX as u64 => Ok(Cbor::Int(-1 - arg as i64)),
+            2 => Ok(Cbor::Bytes(take(input, arg)?.to_vec())),
+            3 => {
+                let text = take(input, arg)?.to_vec();
+                String::from_utf8(text).map(Cbor::Text).map_err(|_| CoseError::Malformed)
+            }
+            4 => {
+                // Every item takes at least one byte.
+                if arg > input.len() as u64 {
+                    return Err(CoseError::Malformed);
+                }
+                let mut items = Vec::new();
+                for _ in 0..arg {
+                    items.push(Cbor::read(input, depth + 1)?);
+                }
+                Ok(Cbor::Array(items))
+            }
+            5 => {
+                if arg > input.len() as u64 {
+                    return Err(CoseError::Malformed);
+                }
+                let mut entries = Vec::new();
+                for _ in 0..arg {
+                    let key = Cbor::read(input, depth + 1)?;
+                    let value = Cbor::read(input, depth + 1)?;
+                    entries.push((key, value));
+                }
+                Ok(Cbor::Map(entries))
+            }
+            6 => Ok(Cbor::Tag(arg, Box::new(Cbor::read(input, depth + 1)?))),
+            _ => Err(CoseError::Malformed),
+        }
+    }
+
+    fn get(&self, label: &Cbor) -> Option<&Cbor> {
+        match *self {
+            Cbor::Map(ref entries) => entries.iter().find(|e| e.0 == *label).map(|e| &e.1),
+            _ => None,
+        }
+    }
+
+    fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Cbor::Bytes(ref b) => Some(b),
+            _ => None,
+        }
+    }
+}
+
+fn write_head(major: u8, arg: u64, out: &mut Vec<u8>) {
+    let major = major << 5;
+    if arg < 24 {
+        out.push(major | arg as u8);
+    } else if arg <= 0xff {
+        out.push(major | 24);
+        out.push(arg as u8);
+    } else if arg <= 0xffff {
+        out.push(major | 25);
+        out.extend_from_slice(&[(arg >> 8) as u8, arg as u8]);
+    } else if arg <= 0xffff_ffff {
+        out.push(major | 26);
+        for i in (0..4).rev() {
+            out.push((arg >> (i * 8)) as u8);
+        }
+    } else {
+        out.push(major | 27);
+        for i in (0..8).rev() {
+            out.push((arg >> (i * 8)) as u8);
+        }
+    }
+}
+
+fn read_head(input: &mut &[u8]) -> Result<(u8, u64), CoseError> {
+    let first = take(input, 1)?[0];
+    let len = match first & 0x1f {
+        n if n < 24 => return Ok((first >> 5, n as u64)),
+        24 => 1,
+        25 => 2,
+        26 => 4,
+        27 => 8,
+        // Indefinite lengths and reserved values aren't used by COSE.
+        _ => return Err(CoseError::Malformed),
+    };
+
+    let arg = take(input, len)?.iter().fold(0u64, |acc, &b| (acc << 8) | b as u64);
+    Ok((first >> 5, arg))
+}
+
+fn take<'a>(input: &mut &'a [u8], len: u64) -> Result<&'a [u8], CoseError> {
+    if (input.len() as u64) < len {
+        return Err(CoseError::Malformed);
+    }
+
+    let (head, tail) = input.split_at(len as usize);
+    *input = tail;
+    Ok(head)
+}
+
+fn sig_structure(protected: &[u8], payload: &[u8]) -> Vec<u8> {
+    Cbor::Array(vec![Cbor::Text("Signature1".to_string()),
+                     Cbor::Bytes(protected.to_vec()),
+                     Cbor::Bytes(Vec::new()),
+                     Cbor::Bytes(payload.to_vec())])
+        .to_bytes()
+}
+
+/// Signs the content and header as a tagged COSE_Sign1 structure. It fails, if the signer is a
+/// certificate without a private key.
+pub fn sign<T: Fingerprint>(content: &T, header: &Header, signer: &Signer) -> Result<Vec<u8>, SignError> {
+    let mut protected = vec![(Cbor::Int(LABEL_ALG), Cbor::Int(ALG_EDDSA))];
+
+    if let Signer::Certificate(cert) = *signer {
+        protected.push((Cbor::Int(LABEL_KID), Cbor::Bytes(cert.public_key().clone())));
+        protected.push((Cbor::Text("edc".to_string()),
+                        Cbor::Bytes(format::encode_public_certificate(cert))));
+    }
+
+    protected.push((Cbor::Text("edh".to_string()), Cbor::Bytes(header.to_bytes())));
+
+    let protected = Cbor::Map(protected).to_bytes();
+    let payload = content.fingerprint();
+    let signature = signer.sign(&sig_structure(&protected, &payload))?;
+
+    let sign1 = Cbor::Array(vec![Cbor::Bytes(protected),
+                                 Cbor::Map(Vec::new()),
+                                 Cbor::Bytes(payload),
+                                 Cbor::Bytes(signature.hash().clone())]);
+    Ok(Cbor::Tag(COSE_SIGN1_TAG, Box::new(sign1)).to_bytes())
+}
+
+/// Verifies a COSE_Sign1 structure, tagged or not, and returns its content and letter header. The
+/// certificate and the payload are decoded within the default `DecodeLimits`, like letters.
+pub fn verify<T: FromFingerprint, V: Validator>(bytes: &[u8], cv: &V) -> Result<(T, Header), CoseError> {
+    let limits = DecodeLimits::default();
+    let mut input = bytes;
+    let value = match Cbor::read(&mut input, 0)? {
+        Cbor::Tag(COSE_SIGN1_TAG, value) => *value,
+        value => value,
+    };
+
+    if !input.is_empty() {
+        return Err(CoseError::Malformed);
+    }
+
+    let items = match value {
+        Cbor::Array(ref items) if items.len() == 4 => items,
+        _ => return Err(CoseError::Malformed),
+    };
+
+    let protected_bytes = items[0].as_bytes().ok_or(CoseError::Malformed)?;
+    let unprotected = &items[1];
+    let payload = items[2].as_bytes().ok_or(CoseError::Malformed)?;
+    let signature = items[3].as_bytes().ok_or(CoseError::Malformed)?;
+
+    let mut protected_input = protected_bytes;
+    let protected = Cbor::read(&mut protected_input, 0)?;
+
+    match (&protected, unprotected) {
+        (&Cbor::Map(_), &Cbor::Map(_)) if protected_input.is_empty() => {}
+        _ => return Err(CoseError::Malformed),
+    }
+
+    if payload.len() as u64 > limits.max_content_size {
+        return Err(CoseError::Decode(DecodeError::ContentTooLarge));
+    }
+
+    match protected.get(&Cbor::Int(LABEL_ALG)) {
+        Some(&Cbor::Int(ALG_EDDSA)) => {}
+        Some(&Cbor::Int(alg)) => return Err(CoseError::UnsupportedAlgorithm(alg)),
+        _ => return Err(CoseError::Malformed),
+    }
+
+    let to_verify = sig_structure(protected_bytes, payload);
+
+    match protected.get(&Cbor::Text("edc".to_string())) {
+        Some(cert) => {
+            let cert = format::decode_parent(cert.as_bytes().ok_or(CoseError::Malformed)?, &limits)?;
+            letter::check_parent_chain(Some(&cert), limits.max_chain_depth).map_err(CoseError::Invalid)?;
+
+            // The key id names the signing key, so it can't name another one.
+            let kid = Cbor::Int(LABEL_KID);
+            let key = Cbor::Bytes(cert.public_key().clone());
+            if protected.get(&kid) != Some(&key) || unprotected.get(&kid).is_some_and(|k| *k != key) {
+                return Err(CoseError::Invalid(ValidationError::SignatureInvalid));
+            }
+
+            strict::check_signature_bytes(signature, Some(&cert)).map_err(CoseError::Invalid)?;
+            cv.is_valid(&cert).map_err(|_| CoseError::Invalid(ValidationError::ParentInvalid))?;
+            if !cert.verify(&to_verify, signature) {
+                return Err(CoseError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+        None => {
+            strict::check_signature_bytes(signature, None).map_err(CoseError::Invalid)?;
+            if !cv.is_signature_valid(&to_verify, signature) {
+                return Err(CoseError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+    }
+
+    let header = protected.get(&Cbor::Text("edh".to_string()))
+                          .and_then(|h| h.as_bytes())
+                          .ok_or(CoseError::Malformed)?;
+    let header = Header::from_bytes(header)?;
+    let content = T::from_fingerprint(payload)?;
+
+    Ok((content, header))
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method signs the content and header of the letter again as a tagged COSE_Sign1
+    /// structure with the EdDSA algorithm. The signature of the letter itself can't be reused.
+    pub fn to_cose(&self, signer: &Signer) -> Result<Vec<u8>, SignError> {
+        sign(self.get(), self.header(), signer)
+    }
+}
+
+#[test]
+fn test_cbor_encoding() {
+    assert_eq!(vec![0x17], Cbor::Int(23).to_bytes());
+    assert_eq!(vec![0x18, 0x18], Cbor::Int(24).to_bytes());
+    assert_eq!(vec![0x27], Cbor::Int(-8).to_bytes());
+    assert_eq!(vec![0x43, 1, 2, 3], Cbor::Bytes(vec![1, 2, 3]).to_bytes());
+    assert_eq!(vec![0xd2, 0x80], Cbor::Tag(18, Box::new(Cbor::Array(vec![]))).to_bytes());
+
+    let value = Cbor::Map(vec![(Cbor::Int(1), Cbor::Int(-8)), (Cbor::Text("a".to_string()), Cbor::Int(1000))]);
+    assert_eq!(value, Cbor::read(&mut &value.to_bytes()[..], 0).unwrap());
+}
+
+#[test]
+fn test_cose_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let letter = Letter::sign(Fingerprintable(42u32), Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+
+    for signer in &[Signer::PrivateKey(&msk), Signer::Certificate(&cert)] {
+        let bytes = letter.to_cose(signer).unwrap();
+        let (content, header): (Fingerprintable<u32>, Header) = verify(&bytes, &cv).unwrap();
+        assert_eq!(42, *content);
+        assert_eq!(letter.header(), &header);
+
+        let mut tampered = bytes.clone();
+        let last = tampered.len() - 1;
+        tampered[last] ^= 1;
+        assert_eq!(Err(CoseError::Invalid(ValidationError::SignatureInvalid)),
+                   verify::<Fingerprintable<u32>, _>(&tampered, &cv));
+    }
+}
+
+#[test]
+fn test_cose_without_private_key() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let bytes = sign(&"hello", &Header::new(), &Signer::Certificate(&cert)).unwrap();
+    let protected = match Cbor::read(&mut &bytes[..], 0).unwrap() {
+        Cbor::Tag(COSE_SIGN1_TAG, value) => {
+            match *value {
+                Cbor::Array(items) => items[0].as_bytes().unwrap().to_vec(),
+                _ => panic!("COSE_Sign1 is an array"),
+            }
+        }
+        _ => panic!("COSE_Sign1 is tagged"),
+    };
+    let protected = Cbor::read(&mut &protected[..], 0).unwrap();
+    let embedded = protected.get(&Cbor::Text("edc".to_string())).unwrap().as_bytes().unwrap();
+    let embedded = format::decode_certificate(embedded).unwrap();
+
+    assert_eq!(cert.public_key(), embedded.public_key());
+    assert_eq!(false, embedded.has_private_key());
+}
+
+#[test]
+fn test_cose_structure() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(1);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), expires);
+    cert.sign_with_master(&msk);
+    let signer = Signer::Certificate(&cert);
+
+    // Builds a validly signed COSE_Sign1 from the parts.
+    let build = |protected: Vec<(Cbor, Cbor)>, trailing: &[u8], unprotected: Cbor, signer: &Signer| {
+        let mut protected = Cbor::Map(protected).to_bytes();
+        protected.extend_from_slice(trailing);
+        let payload = Fingerprintable(42u32).fingerprint();
+        let signature = signer.sign(&sig_structure(&protected, &payload)).unwrap();
+        Cbor::Array(vec![Cbor::Bytes(protected), unprotected, Cbor::Bytes(payload), Cbor::Bytes(signature.hash().clone())])
+            .to_bytes()
+    };
+    let protected = |kid: &[u8], cert: &Certificate| {
+        vec![(Cbor::Int(LABEL_ALG), Cbor::Int(ALG_EDDSA)),
+             (Cbor::Int(LABEL_KID), Cbor::Bytes(kid.to_vec())),
+             (Cbor::Text("edc".to_string()), Cbor::Bytes(format::encode_public_certificate(cert))),
+             (Cbor::Text("edh".to_string()), Cbor::Bytes(Header::new().to_bytes()))]
+    };
+    let verify = |bytes: &[u8]| verify::<Fingerprintable<u32>, _>(bytes, &cv).map(|_| ());
+
+    let bytes = build(protected(cert.public_key(), &cert), b"", Cbor::Map(vec![]), &signer);
+    assert_eq!(Ok(()), verify(&bytes));
+
+    let bytes = build(protected(cert.public_key(), &cert), &[0], Cbor::Map(vec![]), &signer);
+    assert_eq!(Err(CoseError::Malformed), verify(&bytes));
+
+    let bytes = build(protected(cert.public_key(), &cert), b"", Cbor::Array(vec![]), &signer);
+    assert_eq!(Err(CoseError::Malformed), verify(&bytes));
+
+    let (other_pk, _) = ed25519::generate_keypair();
+    let bytes = build(protected(&other_pk, &cert), b"", Cbor::Map(vec![]), &signer);
+    assert_eq!(Err(CoseError::Invalid(ValidationError::SignatureInvalid)), verify(&bytes));
+
+    let unprotected = Cbor::Map(vec![(Cbor::Int(LABEL_KID), Cbor::Bytes(other_pk.clone()))]);
+    let bytes = build(protected(cert.public_key(), &cert), b"", unprotected, &signer);
+    assert_eq!(Err(CoseError::Invalid(ValidationError::SignatureInvalid)), verify(&bytes));
+
+    // A key that signs its own parent is a cycle.
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&cert).unwrap();
+    let mut again = cert.clone();
+    again.sign_with_parent(&leaf).unwrap();
+    let bytes = build(protected(again.public_key(), &again), b"", Cbor::Map(vec![]), &Signer::Certificate(&again));
+    assert_eq!(Err(CoseError::Invalid(ValidationError::ParentInvalid)), verify(&bytes));
+
+    let mut deep = cert.clone();
+    for _ in 0..letter::MAX_CHAIN_DEPTH {
+        let mut child = Certificate::generate_random(Meta::new_empty(), expires);
+        child.sign_with_parent(&deep).unwrap();
+        deep = child;
+    }
+    let bytes = build(protected(deep.public_key(), &deep), b"", Cbor::Map(vec![]), &Signer::Certificate(&deep));
+    assert_eq!(Err(CoseError::Decode(DecodeError::ChainTooDeep)), verify(&bytes));
+}
@@ -0,0 +1,439 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! W3C Verifiable Credentials with `eddsa-jcs-2022` Data Integrity proofs.
+//!
+//! `Letter::to_credential` renders a validated letter as a credential: the letter metadata and
+//! the base64url of the content fingerprint become the `credentialSubject`, the signing time
+//! `validFrom` and the header expiry `validUntil`. `sign_credential` adds a proof to any
+//! credential and `verify_credential` checks the proof of an incoming one against the edcert
+//! trust chain.
+//!
+//! The proof signs `SHA-256(proof options) || SHA-256(credential)`, both in canonical JSON, see
+//! the `canonical_json` module. Its `verificationMethod` is the `did:key` of the signing key, so
+//! other verifiers can check it without knowing edcert. If a certificate signed, the proof also
+//! carries the certificate in `edcertCertificate`, the standard base64 of its edcert JSON
+//! encoding, so this crate can check it against the master key. Canonical JSON is the JSON
+//! Canonicalization Scheme (RFC 8785) for credentials whose numbers are integers and whose keys
+//! don't contain characters outside the Basic Multilingual Plane; other credentials may hash
+//! differently than in other implementations.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::STANDARD;
+use rustc_serialize::base64::URL_SAFE;
+use serde_json::Map;
+use serde_json::Value;
+use sodiumoxide::crypto::hash::sha256;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical_json::to_canonical_json;
+use clock::Clock;
+use clock::SystemClock;
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use letter;
+use letter::Letter;
+use signer::public_key_of;
+use signer::SignError;
+use signer::Signer;
+use strict;
+
+/// The JSON-LD context of version 2.0 credentials.
+pub const CREDENTIALS_CONTEXT: &str = "https://www.w3.org/ns/credentials/v2";
+
+/// The cryptosuite of the proofs.
+pub const CRYPTOSUITE: &str = "eddsa-jcs-2022";
+
+/// The proof property holding the certificate that signed.
+pub const CERTIFICATE_PROPERTY: &str = "edcertCertificate";
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// The multicodec prefix of Ed25519 public keys.
+const ED25519_PUB: [u8; 2] = [0xed, 0x01];
+
+/// This error is returned, if a credential can't be signed or verified.
+#[derive(Clone, PartialEq, Debug)]
+pub enum CredentialError {
+    /// The credential or its proof isn't a JSON object of the expected shape.
+    Malformed,
+    /// The proof is of another type or cryptosuite.
+    UnsupportedProof(String),
+    /// The certificate in the proof can't be decoded.
+    Decode(DecodeError),
+    /// The letter, the signature or the certificate isn't valid.
+    Invalid(ValidationError),
+    /// The credential can't be signed.
+    Sign(SignError),
+}
+
+impl fmt::Display for CredentialError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CredentialError::Malformed => write!(f, "malformed credential"),
+            CredentialError::UnsupportedProof(ref p) => write!(f, "unsupported proof {}", p),
+            CredentialError::Decode(ref e) => write!(f, "can't decode the certificate: {}", e),
+            CredentialError::Invalid(ref e) => write!(f, "the credential is not valid: {:?}", e),
+            CredentialError::Sign(ref e) => write!(f, "can't sign the credential: {}", e),
+        }
+    }
+}
+
+impl Error for CredentialError {}
+
+impl From<DecodeError> for CredentialError {
+    fn from(e: DecodeError) -> CredentialError {
+        CredentialError::Decode(e)
+    }
+}
+
+impl From<SignError> for CredentialError {
+    fn from(e: SignError) -> CredentialError {
+        CredentialError::Sign(e)
+    }
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method validates the letter and renders it as a credential of the given issuer, an
+    /// URL or a DID, with a proof made by the signer.
+    pub fn to_credential<V: Validator>(&self, cv: &V, issuer: &str, signer: &Signer) -> Result<Value, CredentialError> {
+        cv.is_valid(self).map_err(CredentialError::Invalid)?;
+
+        let mut meta = Map::new();
+        for (key, value) in self.meta() {
+            meta.insert(key.clone(), Value::String(value.clone()));
+        }
+
+        let mut subject = Map::new();
+        subject.insert("meta".to_string(), Value::Object(meta));
+        subject.insert("content".to_string(), Value::String(self.get().fingerprint().to_base64(URL_SAFE)));
+
+        let mut credential = Map::new();
+        credential.insert("@context".to_string(), Value::Array(vec![Value::String(CREDENTIALS_CONTEXT.to_string())]));
+        credential.insert("type".to_string(),
+                          Value::Array(vec![Value::String("VerifiableCredential".to_string()),
+                                            Value::String("EdcertLetter".to_string())]));
+        credential.insert("issuer".to_string(), Value::String(issuer.to_string()));
+        credential.insert("validFrom".to_string(), Value::String(self.signed_at().to_rfc3339()));
+        if let Some(expires) = self.header().expires()? {
+            credential.insert("validUntil".to_string(), Value::String(expires.to_rfc3339()));
+        }
+        credential.insert("credentialSubject".to_string(), Value::Object(subject));
+
+        sign_credential(Value::Object(credential), signer)
+    }
+}
+
+/// Adds an `eddsa-jcs-2022` proof to the credential, replacing any proof it has.
+pub fn sign_credential(credential: Value, signer: &Signer) -> Result<Value, CredentialError> {
+    sign_credential_with_clock(credential, signer, &SystemClock)
+}
+
+/// Like `sign_credential`, but takes the creation time of the proof from the clock.
+pub fn sign_credential_with_clock<C: Clock>(credential: Value,
+                                            signer: &Signer,
+                                            clock: &C)
+                                            -> Result<Value, CredentialError> {
+    let mut credential = match credential {
+        Value::Object(credential) => credential,
+        _ => return Err(CredentialError::Malformed),
+    };
+    credential.remove("proof");
+
+    let public_key = match *signer {
+        Signer::PrivateKey(private_key) => public_key_of(private_key)?.to_vec(),
+        Signer::Certificate(cert) => cert.public_key().clone(),
+    };
+
+    let mut proof = Map::new();
+    proof.insert("type".to_string(), Value::String("DataIntegrityProof".to_string()));
+    proof.insert("cryptosuite".to_string(), Value::String(CRYPTOSUITE.to_string()));
+    proof.insert("created".to_string(), Value::String(clock.now().to_rfc3339()));
+    proof.insert("verificationMethod".to_string(), Value::String(verification_method(&public_key)));
+    proof.insert("proofPurpose".to_string(), Value::String("assertionMethod".to_string()));
+    if let Some(context) = credential.get("@context") {
+        proof.insert("@context".to_string(), context.clone());
+    }
+    if let Some(cert) = signer.certificate() {
+        proof.insert(CERTIFICATE_PROPERTY.to_string(),
+                     Value::String(format::encode_public_certificate(cert).to_base64(STANDARD)));
+    }
+
+    let signature = signer.sign(&hash_data(&proof, &credential)?)?;
+    proof.insert("proofValue".to_string(), Value::String(format!("z{}", base58_encode(signature.hash()))));
+
+    credential.insert("proof".to_string(), Value::Object(proof));
+    Ok(Value::Object(credential))
+}
+
+/// Checks the `eddsa-jcs-2022` proof of the credential. It is valid, if it was made by the master
+/// key or by a certificate in the proof that the validator accepts, and the `verificationMethod`
+/// is the `did:key` of that key. Signatures and keys are checked strictly, see the `strict`
+/// module, and the certificate is decoded within the default `DecodeLimits`. The claims of the
+/// credential, like `validUntil`, are up to the caller.
+pub fn verify_credential<V: Validator>(credential: &Value, cv: &V) -> Result<(), CredentialError> {
+    let limits = DecodeLimits::default();
+    let mut unsecured = credential.as_object().ok_or(CredentialError::Malformed)?.clone();
+    let mut proof = match unsecured.remove("proof") {
+        Some(Value::Object(proof)) => proof,
+        _ => return Err(CredentialError::Malformed),
+    };
+
+    let proof_type = proof.get("type").and_then(|t| t.as_str()).ok_or(CredentialError::Malformed)?;
+    let cryptosuite = proof.get("cryptosuite").and_then(|c| c.as_str()).ok_or(CredentialError::Malformed)?;
+    if proof_type != "DataIntegrityProof" || cryptosuite != CRYPTOSUITE {
+        return Err(CredentialError::UnsupportedProof(format!("{} {}", proof_type, cryptosuite)));
+    }
+
+    if proof.get("@context").is_some() && proof.get("@context") != unsecured.get("@context") {
+        return Err(CredentialError::Malformed);
+    }
+
+    let signature = match proof.remove("proofValue") {
+        Some(Value::String(ref value)) if value.starts_with('z') => {
+            base58_decode(&value[1..]).ok_or(CredentialError::Malformed)?
+        }
+        _ => return Err(CredentialError::Malformed),
+    };
+
+    let method = proof.get("verificationMethod").and_then(|m| m.as_str()).ok_or(CredentialError::Malformed)?;
+    let public_key = public_key_of_method(method).ok_or(CredentialError::Malformed)?;
+    let data = hash_data(&proof, &unsecured)?;
+
+    match proof.get(CERTIFICATE_PROPERTY) {
+        Some(cert) => {
+            let cert = cert.as_str().ok_or(CredentialError::Malformed)?;
+            let cert = cert.from_base64().map_err(|_| CredentialError::Malformed)?;
+            let cert = format::decode_parent(&cert, &limits)?;
+            letter::check_parent_chain(Some(&cert), limits.max_chain_depth).map_err(CredentialError::Invalid)?;
+
+            strict::check_signature_bytes(&signature, Some(&cert)).map_err(CredentialError::Invalid)?;
+            cv.is_valid(&cert).map_err(|_| CredentialError::Invalid(ValidationError::ParentInvalid))?;
+            if *cert.public_key() != public_key || !cert.verify(&data, &signature) {
+                return Err(CredentialError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+        None => {
+            strict::check_signature_bytes(&signature, None).map_err(CredentialError::Invalid)?;
+            if strict::has_small_order(&public_key) || !ed25519::verify(&data, &signature, &public_key) ||
+               !cv.is_signature_valid(&data, &signature) {
+                return Err(CredentialError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the bytes the proof signs: the SHA-256 of the canonical proof options, followed by the
+/// SHA-256 of the canonical credential without its proof.
+fn hash_data(proof: &Map<String, Value>, credential: &Map<String, Value>) -> Result<Vec<u8>, CredentialError> {
+    let proof = to_canonical_json(proof).map_err(|_| CredentialError::Malformed)?;
+    let credential = to_canonical_json(credential).map_err(|_| CredentialError::Malformed)?;
+
+    let mut data = sha256::hash(&proof).0.to_vec();
+    data.extend_from_slice(&sha256::hash(&credential).0);
+    Ok(data)
+}
+
+/// Returns the `did:key` verification method of an Ed25519 public key.
+fn verification_method(public_key: &[u8]) -> String {
+    let mut key = ED25519_PUB.to_vec();
+    key.extend_from_slice(public_key);
+    let key = format!("z{}", base58_encode(&key));
+    format!("did:key:{}#{}", key, key)
+}
+
+/// Returns the Ed25519 public key of a `did:key` verification method.
+fn public_key_of_method(method: &str) -> Option<Vec<u8>> {
+    let did = method.split('#').next().unwrap_or("");
+    let key = did.strip_prefix("did:key:")?;
+
+    if method.len() > did.len() && method[did.len() + 1..] != *key {
+        return None;
+    }
+
+    let key = base58_decode(key.strip_prefix('z')?)?;
+    if key.len() != ED25519_PUB.len() + 32 || key[..2] != ED25519_PUB {
+        return None;
+    }
+
+    Some(key[2..].to_vec())
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    let zeros = bytes.iter().take_while(|b| **b == 0).count();
+    let mut digits: Vec<u8> = Vec::new();
+
+    for byte in &bytes[zeros..] {
+        let mut carry = *byte as u32;
+        for digit in &mut digits {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let mut s = "1".repeat(zeros);
+    s.extend(digits.iter().rev().map(|d| BASE58_ALPHABET[*d as usize] as char));
+    s
+}
+
+fn base58_decode(s: &str) -> Option<Vec<u8>> {
+    let zeros = s.bytes().take_while(|c| *c == b'1').count();
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for c in s.bytes().skip(zeros) {
+        let mut carry = BASE58_ALPHABET.iter().position(|a| *a == c)? as u32;
+        for byte in &mut bytes {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let mut result = vec![0u8; zeros];
+    result.extend(bytes.iter().rev());
+    Some(result)
+}
+
+#[test]
+fn test_base58() {
+    assert_eq!("", base58_encode(b""));
+    assert_eq!("2NEpo7TZRRrLZSi2U", base58_encode(b"Hello World!"));
+    assert_eq!("11233QC4", base58_encode(&[0, 0, 40, 127, 180, 205]));
+    assert_eq!(Some(vec![0, 0, 40, 127, 180, 205]), base58_decode("11233QC4"));
+    assert_eq!(None, base58_decode("0OIl"));
+}
+
+#[test]
+fn test_credential() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use header::Header;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut header = Header::new();
+    header.set_meta("degree", "MSc");
+    let letter = Letter::sign("diploma", header, &Signer::Certificate(&cert)).unwrap();
+
+    for signer in &[Signer::PrivateKey(&msk), Signer::Certificate(&cert)] {
+        let credential = letter.to_credential(&cv, "https://uni.example.com", signer).unwrap();
+        assert_eq!(Ok(()), verify_credential(&credential, &cv));
+        assert_eq!(Some("MSc"), credential["credentialSubject"]["meta"]["degree"].as_str());
+        if let Some(cert) = credential["proof"][CERTIFICATE_PROPERTY].as_str() {
+            let cert = format::decode_certificate(&cert.from_base64().unwrap()).unwrap();
+            assert_eq!(false, cert.has_private_key());
+        }
+
+        let mut tampered = credential.clone();
+        tampered["credentialSubject"]["meta"]["degree"] = Value::String("PhD".to_string());
+        assert_eq!(Err(CredentialError::Invalid(ValidationError::SignatureInvalid)),
+                   verify_credential(&tampered, &cv));
+
+        let mut other = credential.clone();
+        other["proof"]["cryptosuite"] = Value::String("ecdsa-jcs-2019".to_string());
+        assert_eq!(true, verify_credential(&other, &cv).is_err());
+    }
+
+    // A certificate can't claim the key of another one.
+    let (other_pk, _) = ed25519::generate_keypair();
+    let mut credential = letter.to_credential(&cv, "https://uni.example.com", &Signer::Certificate(&cert)).unwrap();
+    credential["proof"]["verificationMethod"] = Value::String(verification_method(&other_pk));
+    assert_eq!(true, verify_credential(&credential, &cv).is_err());
+
+    let (_, other_sk) = ed25519::generate_keypair();
+    let credential = letter.to_credential(&cv, "https://uni.example.com", &Signer::PrivateKey(&other_sk)).unwrap();
+    assert_eq!(Err(CredentialError::Invalid(ValidationError::SignatureInvalid)),
+               verify_credential(&credential, &cv));
+    assert_eq!(Some(other_sk[32..].to_vec()),
+               public_key_of_method(credential["proof"]["verificationMethod"].as_str().unwrap()));
+}
+
+#[test]
+fn test_credential_chain_limits() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(1);
+    let credential = || {
+        let mut credential = Map::new();
+        credential.insert("issuer".to_string(), Value::String("https://uni.example.com".to_string()));
+        Value::Object(credential)
+    };
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+
+    // A key that signs its own parent is a cycle.
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).unwrap();
+    let mut again = root.clone();
+    again.sign_with_parent(&leaf).unwrap();
+    let signed = sign_credential(credential(), &Signer::Certificate(&again)).unwrap();
+    assert_eq!(Err(CredentialError::Invalid(ValidationError::ParentInvalid)),
+               verify_credential(&signed, &cv));
+
+    let mut cert = root;
+    for _ in 0..letter::MAX_CHAIN_DEPTH {
+        let mut child = Certificate::generate_random(Meta::new_empty(), expires);
+        child.sign_with_parent(&cert).unwrap();
+        cert = child;
+    }
+    let signed = sign_credential(credential(), &Signer::Certificate(&cert)).unwrap();
+    assert_eq!(Err(CredentialError::Decode(DecodeError::ChainTooDeep)),
+               verify_credential(&signed, &cv));
+}
@@ -0,0 +1,255 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letters that let another key act in a role.
+//!
+//! A `Delegation` says "key X may act as role R until T". A delegation signed by the master key,
+//! or by a certificate the master key endorsed, lets the key sign in place of the master key. A
+//! delegated key can delegate the same role further, but not beyond its own end time.
+//!
+//! `DelegationValidator` wraps another validator and, in its `validate` method, accepts letters
+//! signed by the keys delegated for its role as well. It isn't a `Validator` itself: a delegated
+//! key may sign letters, but not certificates, and a `Validator` can't tell the two apart.
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical;
+use clock::Clock;
+use clock::SystemClock;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+use strict;
+
+/// The content type of delegation letters.
+pub const DELEGATION_CONTENT_TYPE: &str = "edcert-letter/delegation";
+
+/// The statement that a key may act in a role until some time.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Delegation {
+    key: Vec<u8>,
+    role: String,
+    until: DateTime<UTC>,
+}
+
+/// A letter that contains a delegation.
+pub type DelegationLetter = Letter<Delegation>;
+
+impl Delegation {
+    /// Creates a delegation of the role to the public key.
+    pub fn new(key: &[u8], role: &str, until: DateTime<UTC>) -> Delegation {
+        Delegation {
+            key: key.to_vec(),
+            role: role.to_string(),
+            until,
+        }
+    }
+
+    /// Returns the delegated public key.
+    pub fn key(&self) -> &[u8] {
+        &self.key
+    }
+
+    /// Returns the role the key may act in.
+    pub fn role(&self) -> &str {
+        &self.role
+    }
+
+    /// Returns the end of the delegation.
+    pub fn until(&self) -> &DateTime<UTC> {
+        &self.until
+    }
+
+    /// Signs the delegation. The header gets the delegation content type.
+    pub fn sign(self, mut header: Header, signer: &Signer) -> Result<DelegationLetter, SignError> {
+        header.set_content_type(DELEGATION_CONTENT_TYPE);
+        Letter::sign(self, header, signer)
+    }
+}
+
+impl Fingerprint for Delegation {
+    fn fingerprint(&self) -> Vec<u8> {
+        canonical::to_bytes(&(&self.key, &self.role, self.until.timestamp()))
+    }
+}
+
+impl FromFingerprint for Delegation {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Delegation, DecodeError> {
+        let (key, role, secs): (Vec<u8>, String, i64) = canonical::from_bytes(bytes)?;
+        let until = UTC.timestamp_opt(secs, 0).single().ok_or(DecodeError::InvalidContent)?;
+        Ok(Delegation::new(&key, &role, until))
+    }
+}
+
+/// A validator that also accepts letters signed by keys delegated for a role. Validate letters
+/// with `validate`; it doesn't implement `Validator`, see the module documentation.
+pub struct DelegationValidator<V: Validator, C: Clock = SystemClock> {
+    inner: V,
+    role: String,
+    delegations: Vec<DelegationLetter>,
+    clock: C,
+}
+
+impl<V: Validator> DelegationValidator<V> {
+    /// Creates a validator that accepts what `inner` accepts, and signatures of keys delegated
+    /// for the role.
+    pub fn new(inner: V, role: &str) -> DelegationValidator<V> {
+        DelegationValidator {
+            inner,
+            role: role.to_string(),
+            delegations: Vec::new(),
+            clock: SystemClock,
+        }
+    }
+}
+
+impl<V: Validator, C: Clock> DelegationValidator<V, C> {
+    /// Replaces the clock the end of the delegations is checked against.
+    pub fn with_clock<D: Clock>(self, clock: D) -> DelegationValidator<V, D> {
+        DelegationValidator {
+            inner: self.inner,
+            role: self.role,
+            delegations: self.delegations,
+            clock,
+        }
+    }
+
+    /// Adds a delegation letter. It is checked when it is used, so the order of the letters of a
+    /// chain doesn't matter.
+    pub fn add(&mut self, delegation: DelegationLetter) {
+        self.delegations.push(delegation);
+    }
+
+    /// Returns the delegation letters.
+    pub fn delegations(&self) -> &[DelegationLetter] {
+        &self.delegations
+    }
+
+    /// Returns the delegations for the role that have a valid chain to the master key and haven't
+    /// ended yet. Letters without the delegation content type are ignored.
+    pub fn accepted(&self) -> Vec<&Delegation> {
+        let now = self.clock.now();
+        let mut accepted: Vec<&Delegation> = Vec::new();
+
+        // Every round accepts the delegations signed by an accepted key, until nothing changes.
+        loop {
+            let before = accepted.len();
+
+            for letter in &self.delegations {
+                let d = letter.get();
+                if d.role != self.role || d.until < now || accepted.contains(&d) ||
+                   letter.header().content_type() != Some(DELEGATION_CONTENT_TYPE) {
+                    continue;
+                }
+
+                let bytes = letter.signed_bytes();
+                let by_delegate = letter.signature().is_signed_by_master() &&
+                                  strict::check_signature(letter.signature()).is_ok() &&
+                                  accepted.iter().any(|a| {
+                    d.until <= a.until && ed25519::verify(&bytes, letter.signature().hash(), &a.key)
+                });
+
+                if by_delegate || self.inner.is_valid(letter).is_ok() {
+                    accepted.push(d);
+                }
+            }
+
+            if accepted.len() == before {
+                return accepted;
+            }
+        }
+    }
+
+    /// Validates the letter. It is valid, if `inner` accepts it, or if it is signed directly by a
+    /// key delegated for the role. The delegations are checked once per call.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        let result = self.inner.is_valid(letter);
+        if result.is_ok() || !letter.signature().is_signed_by_master() {
+            return result;
+        }
+
+        strict::check_signature(letter.signature())?;
+
+        let bytes = letter.signed_bytes();
+        if self.accepted().iter().any(|d| ed25519::verify(&bytes, letter.signature().hash(), &d.key)) {
+            Ok(())
+        } else {
+            result
+        }
+    }
+}
+
+#[test]
+fn test_delegation() {
+    use chrono::Duration;
+    use edcert::certificate::Certificate;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (apk, ask) = ed25519::generate_keypair();
+    let (bpk, bsk) = ed25519::generate_keypair();
+    let until = UTC::now() + Duration::days(1);
+
+    let mut cv = DelegationValidator::new(RootValidator::new(&mpk, NoRevoker), "release");
+    let letter = Letter::with_private_key("v1.0", &bsk);
+    assert_eq!(false, cv.validate(&letter).is_ok());
+
+    // The chain master -> b -> a works in any order.
+    cv.add(Delegation::new(&apk, "release", until).sign(Header::new(), &Signer::PrivateKey(&bsk)).unwrap());
+    assert_eq!(false, cv.validate(&letter).is_ok());
+    cv.add(Delegation::new(&bpk, "release", until).sign(Header::new(), &Signer::PrivateKey(&msk)).unwrap());
+    assert_eq!(true, cv.validate(&letter).is_ok());
+    assert_eq!(true, cv.validate(&Letter::with_private_key("v1.0", &ask)).is_ok());
+    assert_eq!(2, cv.accepted().len());
+
+    // A delegated key signs letters, not certificates.
+    let mut cert = Certificate::generate_random(Meta::new_empty(), until);
+    cert.sign_with_master(&bsk);
+    assert_eq!(false, cv.validate(&Letter::with_certificate("v1.0", &cert).unwrap()).is_ok());
+
+    // Letters that aren't tagged as delegations don't count.
+    let mut untagged = DelegationValidator::new(RootValidator::new(&mpk, NoRevoker), "release");
+    untagged.add(Letter::with_private_key(Delegation::new(&bpk, "release", until), &msk));
+    assert_eq!(false, untagged.validate(&letter).is_ok());
+
+    // Other roles and ended delegations don't count.
+    let mut cv = DelegationValidator::new(RootValidator::new(&mpk, NoRevoker), "release");
+    cv.add(Delegation::new(&bpk, "nightly", until).sign(Header::new(), &Signer::PrivateKey(&msk)).unwrap());
+    assert_eq!(false, cv.validate(&letter).is_ok());
+
+    let clock = ::clock::ManualClock::new(until + Duration::seconds(1));
+    let mut cv = cv.with_clock(&clock);
+    cv.add(Delegation::new(&bpk, "release", until).sign(Header::new(), &Signer::PrivateKey(&msk)).unwrap());
+    assert_eq!(false, cv.validate(&letter).is_ok());
+}
@@ -0,0 +1,288 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signers identified by decentralized identifiers.
+//!
+//! A `did:key` identifier is the Ed25519 public key itself, encoded as multibase base58btc with
+//! the multicodec prefix `0xed 0x01`. A `did:web` identifier names a web server that publishes a
+//! DID document; with the `http` feature, `Did::resolve` fetches it and takes the first Ed25519
+//! key of its verification methods; without it, fetch the document from `web_document_url` and
+//! pass it to `Did::from_document`. Either way, the key can then be used like a master key to
+//! validate letters that were signed with the matching private key.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::json::Json;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::revoker::NoRevoker;
+use edcert::root_validator::RootValidator;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// The multicodec prefix of Ed25519 public keys.
+const ED25519_PREFIX: [u8; 2] = [0xed, 0x01];
+
+const BASE58_ALPHABET: &[u8] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+/// This error is returned, if a DID can't be resolved to an Ed25519 key.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DidError {
+    /// The DID method isn't supported.
+    UnsupportedMethod(String),
+    /// The DID or its document is malformed or has no Ed25519 key.
+    Invalid,
+    /// The DID document couldn't be fetched.
+    Resolve(String),
+}
+
+impl fmt::Display for DidError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DidError::UnsupportedMethod(ref method) => write!(f, "unsupported DID method {}", method),
+            DidError::Invalid => write!(f, "invalid DID or DID document"),
+            DidError::Resolve(ref e) => write!(f, "can't resolve DID: {}", e),
+        }
+    }
+}
+
+impl Error for DidError {}
+
+/// A DID together with the Ed25519 public key it resolved to.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Did {
+    id: String,
+    public_key: Vec<u8>,
+}
+
+impl Did {
+    /// Returns the `did:key` identifier of the public key.
+    pub fn key(public_key: &[u8]) -> Did {
+        let mut bytes = ED25519_PREFIX.to_vec();
+        bytes.extend_from_slice(public_key);
+
+        Did {
+            id: format!("did:key:z{}", base58_encode(&bytes)),
+            public_key: public_key.to_vec(),
+        }
+    }
+
+    /// Parses a `did:key` identifier. Other methods need to be resolved, see `resolve`.
+    pub fn parse(id: &str) -> Result<Did, DidError> {
+        if !id.starts_with("did:key:") {
+            return Err(DidError::UnsupportedMethod(method(id).to_string()));
+        }
+
+        let public_key = decode_multibase_key(&id["did:key:".len()..])?;
+        Ok(Did {
+            id: id.to_string(),
+            public_key,
+        })
+    }
+
+    /// Takes the first Ed25519 key from the verification methods of a DID document. The `id` of
+    /// the document must be the given DID.
+    pub fn from_document(id: &str, document: &str) -> Result<Did, DidError> {
+        let json = Json::from_str(document).map_err(|_| DidError::Invalid)?;
+
+        if json.find("id").and_then(|i| i.as_string()) != Some(id) {
+            return Err(DidError::Invalid);
+        }
+
+        let methods = json.find("verificationMethod").and_then(|m| m.as_array()).ok_or(DidError::Invalid)?;
+        let public_key = methods.iter().filter_map(method_key).next().ok_or(DidError::Invalid)?;
+
+        Ok(Did {
+            id: id.to_string(),
+            public_key,
+        })
+    }
+
+    /// Resolves a `did:key` or `did:web` identifier. `did:web` documents are fetched over HTTPS.
+    #[cfg(feature = "http")]
+    pub fn resolve(id: &str, timeout: ::std::time::Duration) -> Result<Did, DidError> {
+        if id.starts_with("did:key:") {
+            return Did::parse(id);
+        }
+
+        let url = web_document_url(id)?;
+        let document = ::ureq::AgentBuilder::new()
+                           .timeout(timeout)
+                           .build()
+                           .get(&url)
+                           .call()
+                           .map_err(|e| DidError::Resolve(e.to_string()))?
+                           .into_string()
+                           .map_err(|e| DidError::Resolve(e.to_string()))?;
+
+        Did::from_document(id, &document)
+    }
+
+    /// Returns the DID.
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns the Ed25519 public key.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns a validator that accepts letters signed with the private key of the DID.
+    pub fn validator(&self) -> RootValidator<NoRevoker> {
+        RootValidator::new(&self.public_key, NoRevoker)
+    }
+
+    /// Validates a letter signed with the private key of the DID.
+    pub fn verify<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.validator().is_valid(letter)
+    }
+}
+
+fn method(id: &str) -> &str {
+    id.split(':').nth(1).unwrap_or("")
+}
+
+/// Returns the URL of the DID document of a `did:web` identifier, for callers that fetch the
+/// document themselves and pass it to `Did::from_document`.
+pub fn web_document_url(id: &str) -> Result<String, DidError> {
+    if !id.starts_with("did:web:") {
+        return Err(DidError::UnsupportedMethod(method(id).to_string()));
+    }
+
+    let mut parts = id["did:web:".len()..].split(':').map(|p| p.replace("%3A", ":").replace("%3a", ":"));
+    let host = parts.next().filter(|h| !h.is_empty()).ok_or(DidError::Invalid)?;
+    let path: Vec<String> = parts.collect();
+
+    if path.is_empty() {
+        Ok(format!("https://{}/.well-known/did.json", host))
+    } else {
+        Ok(format!("https://{}/{}/did.json", host, path.join("/")))
+    }
+}
+
+/// Returns the Ed25519 key of a verification method, given as `publicKeyMultibase` or as an
+/// OKP `publicKeyJwk`.
+fn method_key(method: &Json) -> Option<Vec<u8>> {
+    if let Some(multibase) = method.find("publicKeyMultibase").and_then(|k| k.as_string()) {
+        return decode_multibase_key(multibase).ok();
+    }
+
+    let jwk = method.find("publicKeyJwk")?;
+    if jwk.find("kty").and_then(|k| k.as_string()) != Some("OKP") ||
+       jwk.find("crv").and_then(|c| c.as_string()) != Some("Ed25519") {
+        return None;
+    }
+
+    let key = jwk.find("x").and_then(|x| x.as_string())?.from_base64().ok()?;
+    if key.len() == 32 { Some(key) } else { None }
+}
+
+fn decode_multibase_key(multibase: &str) -> Result<Vec<u8>, DidError> {
+    if !multibase.starts_with('z') {
+        return Err(DidError::Invalid);
+    }
+
+    let bytes = base58_decode(&multibase[1..]).ok_or(DidError::Invalid)?;
+    if bytes.len() != 34 || bytes[..2] != ED25519_PREFIX {
+        return Err(DidError::Invalid);
+    }
+
+    Ok(bytes[2..].to_vec())
+}
+
+fn base58_encode(bytes: &[u8]) -> String {
+    // Little-endian digits in base 58.
+    let mut digits: Vec<u8> = Vec::new();
+
+    for &byte in bytes {
+        let mut carry = byte as u32;
+        for digit in digits.iter_mut() {
+            carry += (*digit as u32) << 8;
+            *digit = (carry % 58) as u8;
+            carry /= 58;
+        }
+        while carry > 0 {
+            digits.push((carry % 58) as u8);
+            carry /= 58;
+        }
+    }
+
+    let zeros = bytes.iter().take_while(|&&b| b == 0).count();
+    let mut out = "1".repeat(zeros);
+    out.extend(digits.iter().rev().map(|&d| BASE58_ALPHABET[d as usize] as char));
+    out
+}
+
+fn base58_decode(text: &str) -> Option<Vec<u8>> {
+    // Little-endian bytes.
+    let mut bytes: Vec<u8> = Vec::new();
+
+    for c in text.bytes() {
+        let mut carry = BASE58_ALPHABET.iter().position(|&a| a == c)? as u32;
+        for byte in bytes.iter_mut() {
+            carry += (*byte as u32) * 58;
+            *byte = carry as u8;
+            carry >>= 8;
+        }
+        while carry > 0 {
+            bytes.push(carry as u8);
+            carry >>= 8;
+        }
+    }
+
+    let zeros = text.bytes().take_while(|&c| c == b'1').count();
+    let mut out = vec![0; zeros];
+    out.extend(bytes.iter().rev());
+    Some(out)
+}
+
+#[test]
+fn test_did_key() {
+    use edcert::ed25519;
+
+    let (pk, sk) = ed25519::generate_keypair();
+    let did = Did::key(&pk);
+
+    // Every Ed25519 did:key starts like this, because of the multicodec prefix.
+    assert_eq!(true, did.id().starts_with("did:key:z6Mk"));
+    assert_eq!(did, Did::parse(did.id()).unwrap());
+    assert_eq!(Ok(()), did.verify(&Letter::with_private_key("hello", &sk)));
+
+    let (_, other) = ed25519::generate_keypair();
+    assert_eq!(true, did.verify(&Letter::with_private_key("hello", &other)).is_err());
+
+    assert_eq!(Some(vec![0, 0, 1, 2]), base58_decode(&base58_encode(&[0, 0, 1, 2])));
+    assert_eq!(Err(DidError::UnsupportedMethod("web".to_string())), Did::parse("did:web:example.com"));
+    assert_eq!(Ok("https://example.com:8443/user/alice/did.json".to_string()),
+               web_document_url("did:web:example.com%3A8443:user:alice"));
+
+    let document = format!("{{\"id\": \"did:web:example.com\", \"verificationMethod\": [{{\"publicKeyMultibase\": \"{}\"}}]}}",
+                           &did.id()["did:key:".len()..]);
+    assert_eq!(pk, Did::from_document("did:web:example.com", &document).unwrap().public_key());
+    assert_eq!(Err(DidError::Invalid), Did::from_document("did:web:evil.com", &document));
+}
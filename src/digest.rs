@@ -0,0 +1,87 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The hash algorithms that can be used to digest the content of a letter.
+//!
+//! The content's fingerprint is hashed once and only the digest is signed, so large contents
+//! don't have to be fed through the signature algorithm. The chosen algorithm is part of the
+//! signed bytes, so it can't be swapped by an attacker.
+
+use sodiumoxide::crypto::hash::sha512;
+
+#[cfg(feature = "sha3")]
+use sha3::Digest;
+
+use format::DecodeError;
+
+/// A hash algorithm for the content digest. SHA-512 is always available, the others need the
+/// features of the same name.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub enum HashAlgorithm {
+    /// SHA-512, the default.
+    #[default]
+    Sha512,
+    /// SHA3-512.
+    #[cfg(feature = "sha3")]
+    Sha3_512,
+    /// BLAKE3 with a 256 bit output.
+    #[cfg(feature = "blake3")]
+    Blake3,
+}
+
+
+impl HashAlgorithm {
+    /// Hashes the given bytes.
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        match *self {
+            HashAlgorithm::Sha512 => sha512::hash(data).0.to_vec(),
+            #[cfg(feature = "sha3")]
+            HashAlgorithm::Sha3_512 => ::sha3::Sha3_512::digest(data).to_vec(),
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => ::blake3::hash(data).as_bytes().to_vec(),
+        }
+    }
+
+    /// Returns the byte that identifies this algorithm in a letter.
+    pub fn id(&self) -> u8 {
+        match *self {
+            HashAlgorithm::Sha512 => 1,
+            #[cfg(feature = "sha3")]
+            HashAlgorithm::Sha3_512 => 2,
+            #[cfg(feature = "blake3")]
+            HashAlgorithm::Blake3 => 3,
+        }
+    }
+
+    /// Parses an algorithm id. Algorithms that are unknown or not compiled in yield
+    /// `DecodeError::UnsupportedHashAlgorithm`.
+    pub fn from_id(id: u8) -> Result<HashAlgorithm, DecodeError> {
+        match id {
+            1 => Ok(HashAlgorithm::Sha512),
+            #[cfg(feature = "sha3")]
+            2 => Ok(HashAlgorithm::Sha3_512),
+            #[cfg(feature = "blake3")]
+            3 => Ok(HashAlgorithm::Blake3),
+            id => Err(DecodeError::UnsupportedHashAlgorithm(id)),
+        }
+    }
+}
@@ -0,0 +1,250 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed directory trees.
+//!
+//! `DirectoryManifest::scan` walks a directory and records the hash of every file under its
+//! relative path, with `/` as the separator on every platform. Signed as a `DirectoryLetter`, the
+//! manifest lets `verify_directory` check a copy of the tree on disk, for example a plugin folder:
+//! every listed file must be there with the same content and no other files may be present.
+//! Symbolic links are rejected, so a tree can't point outside itself.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical;
+use digest::HashAlgorithm;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::Signer;
+
+/// This error is returned, if a directory can't be signed or doesn't match its manifest.
+#[derive(Debug)]
+pub enum DirectoryError {
+    /// The directory couldn't be read.
+    Io(io::Error),
+    /// The directory contains a symbolic link or a name that isn't valid UTF-8.
+    Unsupported(String),
+    /// The signer has no private key.
+    NoPrivateKey,
+    /// The manifest letter isn't validly signed.
+    Invalid(ValidationError),
+    /// A file listed in the manifest is missing.
+    Missing(String),
+    /// A file differs from the manifest.
+    Modified(String),
+    /// A file isn't listed in the manifest.
+    Unexpected(String),
+}
+
+impl fmt::Display for DirectoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DirectoryError::Io(ref e) => write!(f, "can't read directory: {}", e),
+            DirectoryError::Unsupported(ref path) => write!(f, "unsupported entry {}", path),
+            DirectoryError::NoPrivateKey => write!(f, "the signer has no private key"),
+            DirectoryError::Invalid(ref e) => write!(f, "invalid manifest signature: {:?}", e),
+            DirectoryError::Missing(ref path) => write!(f, "missing file {}", path),
+            DirectoryError::Modified(ref path) => write!(f, "modified file {}", path),
+            DirectoryError::Unexpected(ref path) => write!(f, "unexpected file {}", path),
+        }
+    }
+}
+
+impl Error for DirectoryError {}
+
+impl From<io::Error> for DirectoryError {
+    fn from(e: io::Error) -> DirectoryError {
+        DirectoryError::Io(e)
+    }
+}
+
+/// The relative paths and content hashes of the files in a directory tree.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct DirectoryManifest {
+    hash_algorithm: HashAlgorithm,
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+/// A `DirectoryManifest` signed with a letter.
+pub type DirectoryLetter = Letter<DirectoryManifest>;
+
+impl DirectoryManifest {
+    /// Hashes every file below the directory with the default hash algorithm.
+    pub fn scan<P: AsRef<Path>>(root: P) -> Result<DirectoryManifest, DirectoryError> {
+        DirectoryManifest::scan_with(root, HashAlgorithm::default())
+    }
+
+    /// Hashes every file below the directory with the given hash algorithm.
+    pub fn scan_with<P: AsRef<Path>>(root: P, hash_algorithm: HashAlgorithm) -> Result<DirectoryManifest, DirectoryError> {
+        let mut files = BTreeMap::new();
+        scan_into(root.as_ref(), "", hash_algorithm, &mut files)?;
+
+        Ok(DirectoryManifest {
+            hash_algorithm,
+            files,
+        })
+    }
+
+    /// Returns the hash algorithm of the file hashes.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Returns the files and their hashes, sorted by path.
+    pub fn files(&self) -> &BTreeMap<String, Vec<u8>> {
+        &self.files
+    }
+
+    /// Compares the directory on disk with this manifest and returns the first difference.
+    pub fn check<P: AsRef<Path>>(&self, root: P) -> Result<(), DirectoryError> {
+        let actual = DirectoryManifest::scan_with(root, self.hash_algorithm)?;
+
+        for (path, hash) in &self.files {
+            match actual.files.get(path) {
+                None => return Err(DirectoryError::Missing(path.clone())),
+                Some(h) if h != hash => return Err(DirectoryError::Modified(path.clone())),
+                Some(_) => {}
+            }
+        }
+
+        match actual.files.keys().find(|path| !self.files.contains_key(*path)) {
+            Some(path) => Err(DirectoryError::Unexpected(path.clone())),
+            None => Ok(()),
+        }
+    }
+}
+
+fn scan_into(dir: &Path,
+             prefix: &str,
+             hash_algorithm: HashAlgorithm,
+             files: &mut BTreeMap<String, Vec<u8>>)
+             -> Result<(), DirectoryError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name().into_string().map_err(|name| {
+            DirectoryError::Unsupported(format!("{}{}", prefix, name.to_string_lossy()))
+        })?;
+        let path = format!("{}{}", prefix, name);
+        let file_type = entry.file_type()?;
+
+        if file_type.is_dir() {
+            scan_into(&entry.path(), &format!("{}/", path), hash_algorithm, files)?;
+        } else if file_type.is_file() {
+            let content = fs::read(entry.path())?;
+            files.insert(path, hash_algorithm.digest(&content));
+        } else {
+            return Err(DirectoryError::Unsupported(path));
+        }
+    }
+
+    Ok(())
+}
+
+impl Fingerprint for DirectoryManifest {
+    fn fingerprint(&self) -> Vec<u8> {
+        let files: Vec<(&String, &Vec<u8>)> = self.files.iter().collect();
+        canonical::to_bytes(&(self.hash_algorithm.id(), files))
+    }
+}
+
+impl FromFingerprint for DirectoryManifest {
+    fn from_fingerprint(bytes: &[u8]) -> Result<DirectoryManifest, DecodeError> {
+        let (id, list): (u8, Vec<(String, Vec<u8>)>) = canonical::from_bytes(bytes)?;
+
+        let mut files = BTreeMap::new();
+        for (path, hash) in list {
+            // Paths must be unique and sorted, otherwise the encoding wouldn't be canonical.
+            if files.keys().next_back().is_some_and(|last: &String| *last >= path) {
+                return Err(DecodeError::InvalidContent);
+            }
+
+            files.insert(path, hash);
+        }
+
+        Ok(DirectoryManifest {
+            hash_algorithm: HashAlgorithm::from_id(id)?,
+            files,
+        })
+    }
+}
+
+/// Scans the directory and signs its manifest.
+pub fn sign_directory<P: AsRef<Path>>(root: P, header: Header, signer: &Signer) -> Result<DirectoryLetter, DirectoryError> {
+    let manifest = DirectoryManifest::scan(root)?;
+    Letter::sign(manifest, header, signer).map_err(|_| DirectoryError::NoPrivateKey)
+}
+
+/// Validates the manifest letter and checks the directory on disk against it.
+pub fn verify_directory<V: Validator, P: AsRef<Path>>(letter: &DirectoryLetter,
+                                                      cv: &V,
+                                                      root: P)
+                                                      -> Result<(), DirectoryError> {
+    cv.is_valid(letter).map_err(DirectoryError::Invalid)?;
+    letter.check(root)
+}
+
+#[test]
+fn test_directory() {
+    use std::fs::File;
+    use std::io::Write;
+
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let root = ::std::env::temp_dir().join(format!("edcert-letter-dir-{}", ::std::process::id()));
+    fs::create_dir_all(root.join("lib")).unwrap();
+    File::create(root.join("plugin.toml")).unwrap().write_all(b"name = \"demo\"").unwrap();
+    File::create(root.join("lib/demo.so")).unwrap().write_all(b"\x7fELF").unwrap();
+
+    let letter = sign_directory(&root, Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    let letter: DirectoryLetter = Letter::from_bytes(&letter.to_bytes()).unwrap();
+    assert_eq!(vec!["lib/demo.so", "plugin.toml"], letter.files().keys().collect::<Vec<_>>());
+    assert_eq!(true, verify_directory(&letter, &cv, &root).is_ok());
+
+    File::create(root.join("lib/demo.so")).unwrap().write_all(b"\x7fELF evil").unwrap();
+    match verify_directory(&letter, &cv, &root) {
+        Err(DirectoryError::Modified(ref path)) => assert_eq!("lib/demo.so", path),
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    File::create(root.join("lib/demo.so")).unwrap().write_all(b"\x7fELF").unwrap();
+    File::create(root.join("lib/extra.so")).unwrap();
+    assert_eq!(true, verify_directory(&letter, &cv, &root).is_err());
+
+    fs::remove_dir_all(&root).unwrap();
+}
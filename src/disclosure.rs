@@ -0,0 +1,326 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Selective disclosure of the fields of a letter.
+//!
+//! The issuer doesn't sign the fields themselves, but a list of salted digests, one for every
+//! field, like an SD-JWT. The holder keeps the `Disclosure`s, that is the salt, name and value of
+//! every field, next to the letter. To show only some fields, the holder passes the letter on with
+//! just their disclosures. The verifier checks the signature of the letter and that every
+//! disclosure hashes to one of the signed digests. The random salts keep the verifier from
+//! guessing the withheld fields from their digests. The digests are sorted, so their order
+//! doesn't tell which field is which.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use sodiumoxide::randombytes::randombytes;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The bytes every serialized selective disclosure letter starts with.
+pub const DISCLOSURE_MAGIC: &[u8] = b"EDD";
+
+/// The number of random bytes in the salt of a disclosure.
+pub const SALT_SIZE: usize = 16;
+
+/// This error is returned, if disclosed fields are not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DisclosureError {
+    /// The letter isn't valid.
+    Invalid(ValidationError),
+    /// A disclosure doesn't match any signed digest.
+    UnknownDisclosure(String),
+    /// A field was disclosed twice.
+    DuplicateField(String),
+    /// The holder has no field of that name.
+    UnknownField(String),
+}
+
+impl fmt::Display for DisclosureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DisclosureError::Invalid(ref e) => write!(f, "invalid letter: {:?}", e),
+            DisclosureError::UnknownDisclosure(ref n) => write!(f, "field {} wasn't signed", n),
+            DisclosureError::DuplicateField(ref n) => write!(f, "field {} was disclosed twice", n),
+            DisclosureError::UnknownField(ref n) => write!(f, "no field {}", n),
+        }
+    }
+}
+
+impl Error for DisclosureError {}
+
+/// The salt, name and value of a field.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Disclosure {
+    salt: Vec<u8>,
+    name: String,
+    value: String,
+}
+
+impl Disclosure {
+    /// Creates a disclosure with a random salt.
+    pub fn new(name: &str, value: &str) -> Disclosure {
+        Disclosure {
+            salt: randombytes(SALT_SIZE),
+            name: name.to_string(),
+            value: value.to_string(),
+        }
+    }
+
+    /// Returns the name of the field.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value of the field.
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the digest that is signed for this field.
+    pub fn digest(&self, header: &Header) -> Vec<u8> {
+        let mut w = Writer::new();
+        self.encode(&mut w);
+        header.hash_algorithm().digest(&w.into_bytes())
+    }
+
+    fn encode(&self, w: &mut Writer) {
+        w.bytes(&self.salt);
+        w.bytes(self.name.as_bytes());
+        w.bytes(self.value.as_bytes());
+    }
+
+    fn decode(r: &mut Reader) -> Result<Disclosure, DecodeError> {
+        let utf8 = |b: &[u8]| String::from_utf8(b.to_vec()).map_err(|_| DecodeError::InvalidContent);
+
+        Ok(Disclosure {
+            salt: r.bytes()?.to_vec(),
+            name: utf8(r.bytes()?)?,
+            value: utf8(r.bytes()?)?,
+        })
+    }
+}
+
+/// The signed content: the sorted digests of all fields.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Digests {
+    digests: Vec<Vec<u8>>,
+}
+
+impl Digests {
+    /// Returns the digests.
+    pub fn digests(&self) -> &[Vec<u8>] {
+        &self.digests
+    }
+}
+
+impl Fingerprint for Digests {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u32(self.digests.len() as u32);
+
+        for digest in &self.digests {
+            w.bytes(digest);
+        }
+
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for Digests {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Digests, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let mut digests = Vec::new();
+
+        for _ in 0..r.u32()? {
+            digests.push(r.bytes()?.to_vec());
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(Digests { digests })
+    }
+}
+
+/// A letter over field digests together with the disclosures of some or all fields.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SelectiveLetter {
+    letter: Letter<Digests>,
+    disclosures: Vec<Disclosure>,
+}
+
+impl SelectiveLetter {
+    /// Signs the fields. The result holds the disclosures of all of them and belongs to the
+    /// holder. It fails, if a name appears twice.
+    pub fn issue(fields: &[(&str, &str)], header: Header, signer: &Signer) -> Result<SelectiveLetter, SignError> {
+        let disclosures: Vec<Disclosure> = fields.iter().map(|&(n, v)| Disclosure::new(n, v)).collect();
+
+        let mut names: Vec<&str> = fields.iter().map(|&(n, _)| n).collect();
+        names.sort();
+        names.dedup();
+        if names.len() != fields.len() {
+            return Err(SignError::InvalidContent);
+        }
+
+        let mut digests: Vec<Vec<u8>> = disclosures.iter().map(|d| d.digest(&header)).collect();
+        digests.sort();
+
+        Ok(SelectiveLetter {
+            letter: Letter::sign(Digests { digests }, header, signer)?,
+            disclosures,
+        })
+    }
+
+    /// Returns the signed letter.
+    pub fn letter(&self) -> &Letter<Digests> {
+        &self.letter
+    }
+
+    /// Returns the disclosures this letter carries.
+    pub fn disclosures(&self) -> &[Disclosure] {
+        &self.disclosures
+    }
+
+    /// Returns a copy that discloses only the named fields.
+    pub fn disclose(&self, names: &[&str]) -> Result<SelectiveLetter, DisclosureError> {
+        let mut disclosures = Vec::new();
+
+        for name in names {
+            match self.disclosures.iter().find(|d| d.name == *name) {
+                Some(d) => disclosures.push(d.clone()),
+                None => return Err(DisclosureError::UnknownField(name.to_string())),
+            }
+        }
+
+        Ok(SelectiveLetter {
+            letter: self.letter.clone(),
+            disclosures,
+        })
+    }
+
+    /// Checks the letter and the disclosures and returns the disclosed fields.
+    pub fn verify<V: Validator>(&self, cv: &V) -> Result<BTreeMap<String, String>, DisclosureError> {
+        cv.is_valid(&self.letter).map_err(DisclosureError::Invalid)?;
+
+        let mut fields = BTreeMap::new();
+
+        for d in &self.disclosures {
+            if !self.letter.get().digests.contains(&d.digest(self.letter.header())) {
+                return Err(DisclosureError::UnknownDisclosure(d.name.clone()));
+            }
+
+            if fields.insert(d.name.clone(), d.value.clone()).is_some() {
+                return Err(DisclosureError::DuplicateField(d.name.clone()));
+            }
+        }
+
+        Ok(fields)
+    }
+
+    /// Serializes the letter and its disclosures.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(DISCLOSURE_MAGIC);
+        w.u8(1);
+        w.bytes(&self.letter.to_bytes());
+        w.u32(self.disclosures.len() as u32);
+
+        for d in &self.disclosures {
+            d.encode(&mut w);
+        }
+
+        w.into_bytes()
+    }
+
+    /// Parses a serialized letter. The disclosures are only checked by `verify`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SelectiveLetter, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(DISCLOSURE_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != DISCLOSURE_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let letter = Letter::from_bytes(r.bytes()?)?;
+
+        let mut disclosures = Vec::new();
+        for _ in 0..r.u32()? {
+            disclosures.push(Disclosure::decode(&mut r)?);
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(SelectiveLetter {
+            letter,
+            disclosures,
+        })
+    }
+}
+
+#[test]
+fn test_selective_disclosure() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let fields = [("name", "Alice"), ("birthdate", "1990-01-01"), ("nationality", "DE")];
+    let full = SelectiveLetter::issue(&fields, Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    assert_eq!(3, full.verify(&cv).unwrap().len());
+
+    let shown = full.disclose(&["nationality"]).unwrap();
+    let shown = SelectiveLetter::from_bytes(&shown.to_bytes()).unwrap();
+    let disclosed = shown.verify(&cv).unwrap();
+    assert_eq!(1, disclosed.len());
+    assert_eq!(Some("DE"), disclosed.get("nationality").map(|v| v.as_str()));
+
+    let mut forged = shown.clone();
+    forged.disclosures[0].value = "FR".to_string();
+    assert_eq!(Err(DisclosureError::UnknownDisclosure("nationality".to_string())),
+               forged.verify(&cv));
+    assert_eq!(Err(DisclosureError::UnknownField("email".to_string())),
+               full.disclose(&["email"]).map(|_| ()));
+}
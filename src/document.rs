@@ -0,0 +1,299 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Human-readable letter documents.
+//!
+//! `Letter::to_document` renders a letter as a TOML or YAML document for infrastructure
+//! repositories, where reviewers want to see what exactly was signed. A comment block describes
+//! the letter; below it, every value is a double quoted string on its own line:
+//!
+//! ```toml
+//! version = "1"
+//! content_sha512 = "<hex>"
+//! signed_at = "2016-08-01T12:00:00+00:00"
+//! expires = "2016-09-01T12:00:00+00:00"
+//! signer = "<hex public key of the signing certificate, or master>"
+//! header = "<base64>"
+//! content = "<base64>"
+//! signature = "<base64>"
+//! certificate = "<base64>"
+//! ```
+//!
+//! `header`, `content`, `signature` and `certificate` are the parts of the binary format.
+//! The other values are for humans, and `Letter::from_document` checks that they agree with the
+//! signed parts, so a document can't show one thing and sign another. Parsing is strict: only the
+//! lines shown above, comments and blank lines are accepted, each key exactly once. `expires` and
+//! `certificate` are left out, if the letter doesn't have them.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::STANDARD;
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::signature::Signature;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use format::LetterFormatVersion;
+use header::Header;
+use letter::Letter;
+use store;
+
+/// The syntax of a letter document.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum DocumentFormat {
+    /// `key = "value"`
+    Toml,
+    /// `key: "value"`
+    Yaml,
+}
+
+impl DocumentFormat {
+    fn separator(&self) -> &'static str {
+        match *self {
+            DocumentFormat::Toml => " = ",
+            DocumentFormat::Yaml => ": ",
+        }
+    }
+}
+
+/// This error is returned, if a letter document can't be parsed.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DocumentError {
+    /// The line with the given number isn't a comment or a `key = "value"` line.
+    Syntax(usize),
+    /// The key isn't part of a letter document.
+    UnknownKey(String),
+    /// The key appears more than once.
+    DuplicateKey(String),
+    /// The key is missing.
+    MissingKey(&'static str),
+    /// The value of the key doesn't agree with the signed parts of the letter.
+    Mismatch(&'static str),
+    /// A part of the letter can't be decoded.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for DocumentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DocumentError::Syntax(line) => write!(f, "syntax error in line {}", line),
+            DocumentError::UnknownKey(ref key) => write!(f, "unknown key {}", key),
+            DocumentError::DuplicateKey(ref key) => write!(f, "duplicate key {}", key),
+            DocumentError::MissingKey(key) => write!(f, "missing key {}", key),
+            DocumentError::Mismatch(key) => write!(f, "{} doesn't match the signed letter", key),
+            DocumentError::Decode(ref e) => write!(f, "malformed letter document: {}", e),
+        }
+    }
+}
+
+impl Error for DocumentError {}
+
+impl From<DecodeError> for DocumentError {
+    fn from(e: DecodeError) -> DocumentError {
+        DocumentError::Decode(e)
+    }
+}
+
+const KEYS: &[&str] = &["version",
+                                         "content_sha512",
+                                         "signed_at",
+                                         "expires",
+                                         "signer",
+                                         "header",
+                                         "content",
+                                         "signature",
+                                         "certificate"];
+
+/// The values shown to humans, derived from the signed parts.
+fn summary(header: &Header, content: &[u8], signature: &Signature) -> Vec<(&'static str, String)> {
+    let mut values = vec![("version", LetterFormatVersion::current().as_byte().to_string()),
+                          ("content_sha512", sha512::hash(content).0.to_hex()),
+                          ("signed_at", header.signed_at().to_rfc3339())];
+
+    if let Ok(Some(expires)) = header.expires() {
+        values.push(("expires", expires.to_rfc3339()));
+    }
+
+    let signer = match signature.parent() {
+        Some(cert) => cert.public_key().to_hex(),
+        None => store::MASTER_SIGNER.to_string(),
+    };
+    values.push(("signer", signer));
+    values
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// Renders the letter as a commented document.
+    pub fn to_document(&self, doc: DocumentFormat) -> String {
+        let content = self.get().fingerprint();
+        let mut out = String::new();
+
+        out.push_str("# A signed edcert letter.\n#\n");
+        out.push_str(&format!("# Signed at:       {}\n", self.signed_at()));
+        match self.signer_certificate() {
+            Some(cert) => out.push_str(&format!("# Signed by:       certificate {}\n", cert.public_key().to_hex())),
+            None => out.push_str("# Signed by:       the master key\n"),
+        }
+        match self.header().expires() {
+            Ok(Some(expires)) => out.push_str(&format!("# Expires:         {}\n", expires)),
+            Ok(None) => out.push_str("# Expires:         never\n"),
+            Err(_) => out.push_str("# Expires:         malformed\n"),
+        }
+        out.push_str(&format!("# Content length:  {} bytes\n", content.len()));
+
+        // Debug formatting escapes line breaks, so metadata can't end the comment.
+        for (key, value) in self.meta() {
+            out.push_str(&format!("# Metadata:        {:?} = {:?}\n", key, value));
+        }
+
+        out.push_str("#\n# Only the values below are read back. header, content, signature and certificate\n");
+        out.push_str("# are signed; the other values are checked against them.\n\n");
+
+        let mut values = summary(self.header(), &content, self.signature());
+        values.push(("header", self.header().to_bytes().to_base64(STANDARD)));
+        values.push(("content", content.to_base64(STANDARD)));
+        values.push(("signature", self.signature().hash().to_base64(STANDARD)));
+
+        if let Some(cert) = self.signer_certificate() {
+            values.push(("certificate", format::encode_certificate(cert).to_base64(STANDARD)));
+        }
+
+        for (key, value) in values {
+            out.push_str(&format!("{}{}\"{}\"\n", key, doc.separator(), value));
+        }
+
+        out
+    }
+
+    /// Parses a document written by `to_document`. The letter isn't validated. The default
+    /// `DecodeLimits` apply.
+    pub fn from_document(text: &str, doc: DocumentFormat) -> Result<Letter<T>, DocumentError> {
+        let mut values = BTreeMap::new();
+
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim_end();
+
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut parts = line.splitn(2, doc.separator());
+            let key = parts.next().unwrap_or("");
+            let value = parts.next().ok_or(DocumentError::Syntax(number + 1))?;
+
+            if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') ||
+               value[1..value.len() - 1].contains(&['"', '\\'][..]) {
+                return Err(DocumentError::Syntax(number + 1));
+            }
+
+            if !KEYS.contains(&key) {
+                return Err(DocumentError::UnknownKey(key.to_string()));
+            }
+
+            if values.insert(key, &value[1..value.len() - 1]).is_some() {
+                return Err(DocumentError::DuplicateKey(key.to_string()));
+            }
+        }
+
+        let base64 = |key: &'static str| -> Result<Option<Vec<u8>>, DocumentError> {
+            match values.get(key) {
+                Some(v) => v.from_base64().map(Some).map_err(|_| DocumentError::Mismatch(key)),
+                None => Ok(None),
+            }
+        };
+
+        let limits = DecodeLimits::default();
+        let header = Header::from_bytes(&base64("header")?.ok_or(DocumentError::MissingKey("header"))?)?;
+        let content = base64("content")?.ok_or(DocumentError::MissingKey("content"))?;
+        let hash = base64("signature")?.ok_or(DocumentError::MissingKey("signature"))?;
+
+        if content.len() as u64 > limits.max_content_size {
+            return Err(DocumentError::Decode(DecodeError::ContentTooLarge));
+        }
+
+        let signature = match base64("certificate")? {
+            Some(cert) => Signature::with_parent(Box::new(format::decode_parent(&cert, &limits)?), hash),
+            None => Signature::new(hash),
+        };
+
+        let expires = header.expires()?;
+        let summary = summary(&header, &content, &signature);
+        for &(key, ref expected) in &summary {
+            if values.get(key) != Some(&&expected[..]) {
+                return Err(DocumentError::Mismatch(key));
+            }
+        }
+
+        if values.contains_key("expires") && expires.is_none() {
+            return Err(DocumentError::Mismatch("expires"));
+        }
+
+        Ok(Letter::from_parts(T::from_fingerprint(&content)?, header, signature))
+    }
+}
+
+#[test]
+fn test_document_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+    use canonical::Fingerprintable;
+    use signer::Signer;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut header = Header::new();
+    header.set_expires(UTC::now() + Duration::days(30));
+    header.set_meta("note", "line\nsignature = \"forged\"");
+    let letter = Letter::sign(Fingerprintable("release 1.2".to_string()), header, &Signer::Certificate(&cert)).unwrap();
+
+    for &doc in &[DocumentFormat::Toml, DocumentFormat::Yaml] {
+        let text = letter.to_document(doc);
+        let parsed: Letter<Fingerprintable<String>> = Letter::from_document(&text, doc).unwrap();
+        assert_eq!("release 1.2", parsed.as_str());
+        assert_eq!(true, cv.is_valid(&parsed).is_ok());
+    }
+
+    let text = letter.to_document(DocumentFormat::Toml);
+    let lying = text.replace(&format!("signer = \"{}\"", cert.public_key().to_hex()), "signer = \"master\"");
+    assert_eq!(Err(DocumentError::Mismatch("signer")), Letter::<Fingerprintable<String>>::from_document(&lying, DocumentFormat::Toml).map(|_| ()));
+
+    let extra = format!("{}owner = \"me\"\n", text);
+    assert_eq!(Err(DocumentError::UnknownKey("owner".to_string())), Letter::<Fingerprintable<String>>::from_document(&extra, DocumentFormat::Toml).map(|_| ()));
+}
@@ -0,0 +1,116 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letters over types that don't implement `Fingerprint`.
+//!
+//! Types from other crates can't implement `Fingerprint` because of the orphan rule. `Encoded`
+//! pairs such a value with the bytes an encoder made from it, and those bytes are what gets
+//! signed.
+
+use std::ops::Deref;
+
+use edcert::fingerprint::Fingerprint;
+
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// A value together with its encoding, which is used as its fingerprint.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Encoded<T> {
+    value: T,
+    bytes: Vec<u8>,
+}
+
+impl<T> Encoded<T> {
+    /// Encodes the value with the given encoder.
+    pub fn new<F: Fn(&T) -> Vec<u8>>(value: T, encoder: F) -> Encoded<T> {
+        Encoded {
+            bytes: encoder(&value),
+            value,
+        }
+    }
+
+    /// Returns the value.
+    pub fn value(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the encoding of the value.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    /// Returns the value, dropping its encoding.
+    pub fn into_value(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Fingerprint for Encoded<T> {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.bytes.clone()
+    }
+}
+
+impl<T> Deref for Encoded<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> Letter<Encoded<T>> {
+    /// This method creates a Letter over content that doesn't implement `Fingerprint`, by signing
+    /// the bytes the encoder makes from it. The encoder must be deterministic, so verifiers can
+    /// encode the content the same way.
+    pub fn new_with_encoder<F>(content: T, encoder: F, signer: &Signer) -> Result<Letter<Encoded<T>>, SignError>
+        where F: Fn(&T) -> Vec<u8>
+    {
+        Letter::sign(Encoded::new(content, encoder), Header::new(), signer)
+    }
+}
+
+#[test]
+fn test_encoder() {
+    use std::net::Ipv4Addr;
+
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let encoder = |addr: &Ipv4Addr| addr.octets().to_vec();
+    let letter = Letter::new_with_encoder(Ipv4Addr::new(10, 0, 0, 1), encoder, &Signer::PrivateKey(&msk)).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(&Ipv4Addr::new(10, 0, 0, 1), letter.value());
+
+    let forged = Letter::from_parts(Encoded::new(Ipv4Addr::new(10, 0, 0, 2), encoder),
+                                    letter.header().clone(),
+                                    letter.signature().clone());
+    assert_eq!(false, cv.is_valid(&forged).is_ok());
+}
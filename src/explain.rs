@@ -0,0 +1,267 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Step by step explanations of letter validation.
+//!
+//! `ValidationReport` is meant for machines and audit logs. An `Explanation` is meant for people:
+//! it lists every check in the order it matters, from the shape of the chain over each
+//! certificate from the master key down to the signature of the letter, with its outcome. It is
+//! what a `--verbose` flag prints, or what goes into a support ticket. The overall result is the
+//! one `Validator::is_valid` returns, except that a letter or certificate that has expired at the
+//! time of the check makes it fail too, so the result agrees with the expiry steps when the time
+//! comes from another clock. The validator itself checks certificates at its own time.
+
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::UTC;
+use rustc_serialize::hex::ToHex;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+use letter;
+use letter::Letter;
+use strict;
+
+/// The outcome of a step.
+#[derive(Clone, PartialEq, Debug)]
+pub enum Outcome {
+    /// The check passed.
+    Passed,
+    /// The check failed, for the given reason.
+    Failed(String),
+    /// Nothing was checked, the step only states a fact.
+    Info,
+}
+
+/// One step of an explanation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Step {
+    /// What was checked.
+    pub description: String,
+    /// How the check went.
+    pub outcome: Outcome,
+}
+
+/// An account of the validation of a letter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Explanation {
+    /// The steps, in order.
+    pub steps: Vec<Step>,
+    /// The result of the validation, as `Validator::is_valid` returns it, or
+    /// `ValidationError::Expired` and `ValidationError::ParentInvalid` if the letter or a
+    /// certificate has expired at the time of the check.
+    pub result: Result<(), ValidationError>,
+    /// The time of the validation.
+    pub checked_at: DateTime<UTC>,
+}
+
+impl Explanation {
+    /// Returns true, if the letter is valid.
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok()
+    }
+
+    /// Returns the steps that failed.
+    pub fn failures(&self) -> Vec<&Step> {
+        self.steps.iter().filter(|s| matches!(s.outcome, Outcome::Failed(_))).collect()
+    }
+
+    fn step<S: Into<String>>(&mut self, description: S, result: Result<(), String>) {
+        self.steps.push(Step {
+            description: description.into(),
+            outcome: match result {
+                Ok(()) => Outcome::Passed,
+                Err(reason) => Outcome::Failed(reason),
+            },
+        });
+    }
+
+    fn info<S: Into<String>>(&mut self, description: S) {
+        self.steps.push(Step {
+            description: description.into(),
+            outcome: Outcome::Info,
+        });
+    }
+}
+
+/// The explanation is written one step per line.
+impl fmt::Display for Explanation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "validation at {}", self.checked_at)?;
+
+        for (i, step) in self.steps.iter().enumerate() {
+            match step.outcome {
+                Outcome::Passed => writeln!(f, "{:>3}. [ok]   {}", i + 1, step.description)?,
+                Outcome::Failed(ref reason) => {
+                    writeln!(f, "{:>3}. [FAIL] {}: {}", i + 1, step.description, reason)?
+                }
+                Outcome::Info => writeln!(f, "{:>3}. [info] {}", i + 1, step.description)?,
+            }
+        }
+
+        match self.result {
+            Ok(()) => write!(f, "result: valid"),
+            Err(ref e) => write!(f, "result: invalid ({:?})", e),
+        }
+    }
+}
+
+/// Returns the first bytes of the public key in hex, enough to tell certificates apart.
+fn key_id(public_key: &[u8]) -> String {
+    public_key[..public_key.len().min(8)].to_hex()
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method validates the letter like `Validator::is_valid` and explains every step.
+    pub fn explain<V: Validator>(&self, cv: &V) -> Explanation {
+        self.explain_with_clock(cv, &SystemClock)
+    }
+
+    /// This method explains like `explain`, but takes the time of the check from the clock.
+    pub fn explain_with_clock<V: Validator, C: Clock>(&self, cv: &V, clock: &C) -> Explanation {
+        let now = clock.now();
+        let mut e = Explanation {
+            steps: Vec::new(),
+            result: cv.is_valid(self),
+            checked_at: now,
+        };
+        let mut expiry = Ok(());
+
+        e.info(format!("letter signed at {} with {:?}",
+                       self.signed_at(),
+                       self.header().hash_algorithm()));
+
+        if let Some(content_type) = self.header().content_type() {
+            e.info(format!("content type {}", content_type));
+        }
+
+        match self.header().expires() {
+            Ok(Some(expires)) if expires > now => e.step(format!("letter expires at {}", expires), Ok(())),
+            Ok(Some(expires)) => {
+                expiry = Err(ValidationError::Expired);
+                e.step(format!("letter expires at {}", expires), Err("expired".to_string()));
+            }
+            Ok(None) => {}
+            Err(_) => {
+                expiry = Err(ValidationError::Expired);
+                e.step("letter expiry", Err("malformed".to_string()));
+            }
+        }
+
+        let chain = self.signer_chain();
+        e.step(format!("chain of {} certificate(s) up to the master key", chain.len()),
+               letter::check_parent_chain(self.signer_certificate(), letter::MAX_CHAIN_DEPTH)
+                   .map_err(|_| "too deep or has a cycle".to_string()));
+        e.step("signatures are canonical",
+               strict::check_signature(self.signature()).map_err(|e| format!("{:?}", e)));
+
+        for cert in chain.iter().rev() {
+            let id = key_id(cert.public_key());
+            let expired = match DateTime::parse_from_rfc3339(cert.expires()) {
+                Ok(expires) => expires.with_timezone(&UTC) < now,
+                Err(_) => true,
+            };
+
+            if expired && expiry.is_ok() {
+                expiry = Err(ValidationError::ParentInvalid);
+            }
+
+            e.step(format!("certificate {} expires at {}", id, cert.expires()),
+                   if expired { Err("expired".to_string()) } else { Ok(()) });
+            e.step(format!("certificate {} is not revoked", id),
+                   cv.is_revoked(*cert).map_err(|e| format!("{:?}", e)));
+            e.step(format!("certificate {} is valid", id),
+                   cv.is_valid(*cert).map_err(|e| format!("{:?}", e)));
+        }
+
+        let signer = match self.signer_certificate() {
+            Some(cert) => format!("certificate {}", key_id(cert.public_key())),
+            None => "the master key".to_string(),
+        };
+
+        let signature = self.signature();
+        let bytes = self.signed_bytes();
+        let signature_valid = match signature.parent() {
+            Some(parent) => parent.verify(&bytes, signature.hash()),
+            None => cv.is_signature_valid(&bytes, signature.hash()),
+        };
+
+        e.step(format!("letter is signed by {}", signer),
+               if signature_valid { Ok(()) } else { Err("signature doesn't match".to_string()) });
+        if e.result.is_ok() {
+            e.result = expiry;
+        }
+        e
+    }
+}
+
+#[test]
+fn test_explain() {
+    use chrono::Duration;
+    use clock::ManualClock;
+    use header::Header;
+    use signer::Signer;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let explanation = Letter::with_private_key("hello", &msk).explain(&cv);
+    assert_eq!(true, explanation.is_valid());
+    assert_eq!(true, explanation.failures().is_empty());
+    assert_eq!(true, explanation.to_string().contains("signed by the master key"));
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() - Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let explanation = Letter::with_certificate("hello", &cert).unwrap().explain(&cv);
+    assert_eq!(Err(ValidationError::ParentInvalid), explanation.result);
+    assert_eq!(Outcome::Failed("expired".to_string()), explanation.failures()[0].outcome);
+    assert_eq!(true, explanation.to_string().contains("[FAIL] certificate"));
+
+    // The result is checked at the time of the clock, like the steps.
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(30));
+    cert.sign_with_master(&msk);
+    let mut header = Header::new();
+    header.set_expires(UTC::now() + Duration::days(1));
+    let letter = Letter::sign("hello", header, &Signer::Certificate(&cert)).unwrap();
+
+    assert_eq!(true, letter.explain(&cv).is_valid());
+    let clock = ManualClock::new(UTC::now() + Duration::days(2));
+    let explanation = letter.explain_with_clock(&cv, &clock);
+    assert_eq!(Err(ValidationError::Expired), explanation.result);
+    assert_eq!(Outcome::Failed("expired".to_string()), explanation.failures()[0].outcome);
+
+    clock.advance(Duration::days(30));
+    let letter = Letter::with_certificate("hello", &cert).unwrap();
+    assert_eq!(Err(ValidationError::ParentInvalid), letter.explain_with_clock(&cv, &clock).result);
+}
@@ -0,0 +1,133 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Saving letters to files.
+//!
+//! `Letter::save` writes the letter to a temporary file next to the target, flushes it to disk and
+//! renames it over the target, so readers see either the old or the new letter but never a
+//! partially written one, even if the process crashes. `Letter::load` reads the file back and
+//! checks its magic and format version like `Letter::from_bytes`.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::fs::File;
+use std::fs::OpenOptions;
+use std::io;
+use std::io::Write;
+use std::path::Path;
+use std::process;
+
+use format::DecodeError;
+use format::FromFingerprint;
+use letter::Letter;
+
+/// This error is returned, if a letter can't be saved or loaded.
+#[derive(Debug)]
+pub enum FileError {
+    /// The file can't be read or written.
+    Io(io::Error),
+    /// The file doesn't contain a letter.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for FileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FileError::Io(ref e) => write!(f, "can't access letter file: {}", e),
+            FileError::Decode(ref e) => write!(f, "malformed letter file: {}", e),
+        }
+    }
+}
+
+impl Error for FileError {}
+
+/// Writes the bytes to the path atomically: to a temporary file in the same directory, which is
+/// synced and then renamed over the path.
+pub fn write_atomic<P: AsRef<Path>>(path: P, bytes: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let name = path.file_name()
+                   .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "path has no file name"))?;
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+
+    let mut tmp_name = name.to_os_string();
+    tmp_name.push(format!(".{}.tmp", process::id()));
+    let tmp = dir.join(tmp_name);
+
+    let result = OpenOptions::new()
+                     .write(true)
+                     .create_new(true)
+                     .open(&tmp)
+                     .and_then(|mut f| f.write_all(bytes).and_then(|_| f.sync_all()))
+                     .and_then(|_| fs::rename(&tmp, path));
+
+    if result.is_err() {
+        let _ = fs::remove_file(&tmp);
+        return result;
+    }
+
+    // Persist the rename itself. Directories can't be opened like this on every platform.
+    if let Ok(d) = File::open(dir) {
+        let _ = d.sync_all();
+    }
+
+    Ok(())
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// Writes the letter to a file atomically.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), FileError> {
+        write_atomic(path, &self.to_bytes()).map_err(FileError::Io)
+    }
+
+    /// Reads a letter from a file. The letter isn't validated.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Letter<T>, FileError> {
+        let bytes = fs::read(path).map_err(FileError::Io)?;
+        Letter::from_bytes(&bytes).map_err(FileError::Decode)
+    }
+}
+
+#[test]
+fn test_save_and_load() {
+    use std::env;
+
+    use edcert::ed25519;
+    use canonical::Fingerprintable;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let path = env::temp_dir().join(format!("edcert-letter-file-{}.edl", process::id()));
+
+    let letter = Letter::with_private_key(Fingerprintable("hello".to_string()), &msk);
+    letter.save(&path).unwrap();
+    Letter::with_private_key(Fingerprintable("world".to_string()), &msk).save(&path).unwrap();
+    assert_eq!("world", Letter::<Fingerprintable<String>>::load(&path).unwrap().as_str());
+
+    fs::write(&path, b"not a letter").unwrap();
+    let loaded: Result<Letter<Fingerprintable<String>>, FileError> = Letter::load(&path);
+    assert_eq!(true, loaded.is_err());
+
+    fs::remove_file(&path).unwrap();
+}
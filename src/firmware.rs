@@ -0,0 +1,375 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Firmware update letters with rollback protection.
+//!
+//! A `FirmwareUpdate` names a product, the digest and length of an image and a security counter.
+//! The vendor raises the counter whenever an update fixes a vulnerability. A device keeps the
+//! highest counter it has installed in a `CounterStore` and `FirmwareVerifier` rejects letters
+//! with a lower counter, so an attacker can't replay an old, validly signed but vulnerable image.
+//! The counter is only raised by `FirmwareVerifier::commit`, after the image has been installed
+//! successfully, so a failed update doesn't lock the device out of its current firmware. `commit`
+//! takes the `VerifiedUpdate` that `verify` returns, so only a validly signed letter can raise the
+//! counter.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use codec::Reader;
+use codec::Writer;
+use file;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of firmware update letters.
+pub const FIRMWARE_CONTENT_TYPE: &str = "edcert-letter/firmware-update";
+
+/// This error is returned, if a firmware update is not accepted.
+#[derive(Debug)]
+pub enum FirmwareError {
+    /// The letter isn't validly signed or isn't a firmware update letter.
+    Invalid(ValidationError),
+    /// The update is for another product.
+    WrongProduct,
+    /// The update has a lower counter than the installed firmware.
+    Rollback {
+        /// The counter of the installed firmware.
+        installed: u64,
+        /// The counter of the update.
+        offered: u64,
+    },
+    /// The image doesn't have the signed length and digest.
+    ImageModified,
+    /// The counter store couldn't be read or written.
+    Store(io::Error),
+}
+
+impl fmt::Display for FirmwareError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            FirmwareError::Invalid(ref e) => write!(f, "invalid firmware update: {:?}", e),
+            FirmwareError::WrongProduct => write!(f, "the update is for another product"),
+            FirmwareError::Rollback { installed, offered } => {
+                write!(f, "update counter {} is lower than installed counter {}", offered, installed)
+            }
+            FirmwareError::ImageModified => write!(f, "the firmware image has been modified"),
+            FirmwareError::Store(ref e) => write!(f, "can't access the update counter: {}", e),
+        }
+    }
+}
+
+impl Error for FirmwareError {}
+
+/// A signed firmware image.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct FirmwareUpdate {
+    /// The product the image is for.
+    pub product: String,
+    /// The security counter. It never decreases between releases.
+    pub counter: u64,
+    /// The length of the image.
+    pub image_length: u64,
+    /// The SHA-512 digest of the image.
+    pub image_sha512: Vec<u8>,
+}
+
+/// A signed firmware update.
+pub type FirmwareLetter = Letter<FirmwareUpdate>;
+
+impl FirmwareUpdate {
+    /// Describes the image.
+    pub fn for_image(product: &str, counter: u64, image: &[u8]) -> FirmwareUpdate {
+        FirmwareUpdate {
+            product: product.to_string(),
+            counter,
+            image_length: image.len() as u64,
+            image_sha512: sha512::hash(image).0.to_vec(),
+        }
+    }
+
+    /// Signs the update as a firmware update letter.
+    pub fn sign(self, mut header: Header, signer: &Signer) -> Result<FirmwareLetter, SignError> {
+        header.set_content_type(FIRMWARE_CONTENT_TYPE);
+        Letter::sign(self, header, signer)
+    }
+
+    /// Returns true, if the image has the signed length and digest.
+    pub fn matches(&self, image: &[u8]) -> bool {
+        image.len() as u64 == self.image_length && sha512::hash(image).0.to_vec() == self.image_sha512
+    }
+}
+
+impl Fingerprint for FirmwareUpdate {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(self.product.as_bytes());
+        w.u64(self.counter);
+        w.u64(self.image_length);
+        w.bytes(&self.image_sha512);
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for FirmwareUpdate {
+    fn from_fingerprint(bytes: &[u8]) -> Result<FirmwareUpdate, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let update = FirmwareUpdate {
+            product: String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?,
+            counter: r.u64()?,
+            image_length: r.u64()?,
+            image_sha512: r.bytes()?.to_vec(),
+        };
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(update)
+    }
+}
+
+/// Where a device keeps the counter of its installed firmware.
+pub trait CounterStore {
+    /// Returns the counter of the product, 0 if none was stored.
+    fn get(&self, product: &str) -> Result<u64, io::Error>;
+
+    /// Raises the counter of the product to `counter`, unless it is already higher, and returns
+    /// the stored counter. The counter must never decrease.
+    fn raise(&self, product: &str, counter: u64) -> Result<u64, io::Error>;
+}
+
+/// A `CounterStore` in memory, for tests and for devices that persist it themselves.
+#[derive(Debug, Default)]
+pub struct MemoryCounterStore {
+    counters: Mutex<BTreeMap<String, u64>>,
+}
+
+impl MemoryCounterStore {
+    /// Creates an empty store.
+    pub fn new() -> MemoryCounterStore {
+        MemoryCounterStore::default()
+    }
+}
+
+impl CounterStore for MemoryCounterStore {
+    fn get(&self, product: &str) -> Result<u64, io::Error> {
+        Ok(self.counters.lock().unwrap().get(product).cloned().unwrap_or(0))
+    }
+
+    fn raise(&self, product: &str, counter: u64) -> Result<u64, io::Error> {
+        let mut counters = self.counters.lock().unwrap();
+        let stored = counters.entry(product.to_string()).or_insert(0);
+        if counter > *stored {
+            *stored = counter;
+        }
+        Ok(*stored)
+    }
+}
+
+/// A `CounterStore` that keeps one file per product in a directory, written atomically. Only one
+/// process should update the firmware at a time.
+#[derive(Debug)]
+pub struct FileCounterStore {
+    dir: PathBuf,
+    lock: Mutex<()>,
+}
+
+impl FileCounterStore {
+    /// Uses the directory, creating it if it doesn't exist.
+    pub fn open<P: Into<PathBuf>>(dir: P) -> Result<FileCounterStore, io::Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+
+        Ok(FileCounterStore {
+            dir,
+            lock: Mutex::new(()),
+        })
+    }
+
+    fn path(&self, product: &str) -> PathBuf {
+        // Hex, so any product name is a safe file name.
+        self.dir.join(format!("{}.counter", product.as_bytes().to_hex()))
+    }
+}
+
+impl CounterStore for FileCounterStore {
+    fn get(&self, product: &str) -> Result<u64, io::Error> {
+        match fs::read_to_string(self.path(product)) {
+            Ok(text) => text.trim().parse().map_err(|_| io::Error::new(io::ErrorKind::InvalidData, "malformed counter")),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(0),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn raise(&self, product: &str, counter: u64) -> Result<u64, io::Error> {
+        let _guard = self.lock.lock().unwrap();
+        let stored = self.get(product)?;
+
+        if counter <= stored {
+            return Ok(stored);
+        }
+
+        file::write_atomic(self.path(product), counter.to_string().as_bytes())?;
+        Ok(counter)
+    }
+}
+
+/// An update that passed `FirmwareVerifier::verify`. It can only be created by the verifier and is
+/// consumed by `FirmwareVerifier::commit`.
+#[derive(Debug)]
+pub struct VerifiedUpdate {
+    product: String,
+    counter: u64,
+}
+
+impl VerifiedUpdate {
+    /// Returns the security counter of the update.
+    pub fn counter(&self) -> u64 {
+        self.counter
+    }
+}
+
+/// Checks firmware updates for one product against a counter store.
+pub struct FirmwareVerifier<V: Validator, S: CounterStore> {
+    cv: V,
+    store: S,
+    product: String,
+}
+
+impl<V: Validator, S: CounterStore> FirmwareVerifier<V, S> {
+    /// Creates a verifier for the product.
+    pub fn new(product: &str, cv: V, store: S) -> FirmwareVerifier<V, S> {
+        FirmwareVerifier {
+            cv,
+            store,
+            product: product.to_string(),
+        }
+    }
+
+    /// Returns the counter store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Checks that the letter is a valid update for the product, that its counter isn't lower
+    /// than the installed one and that the image matches. Reinstalling the same counter is
+    /// allowed. The returned update is passed to `commit` once the image is installed.
+    pub fn verify(&self, letter: &FirmwareLetter, image: &[u8]) -> Result<VerifiedUpdate, FirmwareError> {
+        letter.validate_as(&self.cv, FIRMWARE_CONTENT_TYPE).map_err(FirmwareError::Invalid)?;
+
+        let update = letter.get();
+        if update.product != self.product {
+            return Err(FirmwareError::WrongProduct);
+        }
+
+        let installed = self.store.get(&self.product).map_err(FirmwareError::Store)?;
+        if update.counter < installed {
+            return Err(FirmwareError::Rollback {
+                installed,
+                offered: update.counter,
+            });
+        }
+
+        if !update.matches(image) {
+            return Err(FirmwareError::ImageModified);
+        }
+
+        Ok(VerifiedUpdate {
+            product: update.product.clone(),
+            counter: update.counter,
+        })
+    }
+
+    /// Records that the verified update was installed, so older updates are rejected from now on.
+    /// Call this after the image was installed.
+    pub fn commit(&self, update: VerifiedUpdate) -> Result<(), FirmwareError> {
+        if update.product != self.product {
+            return Err(FirmwareError::WrongProduct);
+        }
+
+        self.store.raise(&self.product, update.counter).map_err(FirmwareError::Store)?;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_firmware_rollback() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let signer = Signer::PrivateKey(&msk);
+    let verifier = FirmwareVerifier::new("sensor", RootValidator::new(&mpk, NoRevoker), MemoryCounterStore::new());
+
+    let old_image = b"firmware 1.0".to_vec();
+    let new_image = b"firmware 1.1".to_vec();
+    let old = FirmwareUpdate::for_image("sensor", 1, &old_image).sign(Header::new(), &signer).unwrap();
+    let new = FirmwareUpdate::for_image("sensor", 2, &new_image).sign(Header::new(), &signer).unwrap();
+
+    let verified = verifier.verify(&old, &old_image).unwrap();
+    verifier.commit(verified).unwrap();
+    let verified = verifier.verify(&new, &new_image).unwrap();
+    assert_eq!(2, verified.counter());
+    verifier.commit(verified).unwrap();
+
+    match verifier.verify(&old, &old_image) {
+        Err(FirmwareError::Rollback { installed: 2, offered: 1 }) => {}
+        other => panic!("expected a rollback error, got {:?}", other),
+    }
+
+    assert_eq!(true, verifier.verify(&new, &old_image).is_err());
+    assert_eq!(true, verifier.verify(&new, &new_image).is_ok());
+}
+
+#[test]
+fn test_file_counter_store() {
+    use std::env;
+    use std::process;
+
+    let dir = env::temp_dir().join(format!("edcert-letter-counters-{}", process::id()));
+    let store = FileCounterStore::open(&dir).unwrap();
+
+    assert_eq!(0, store.get("sensor/v1").unwrap());
+    assert_eq!(5, store.raise("sensor/v1", 5).unwrap());
+    assert_eq!(5, store.raise("sensor/v1", 3).unwrap());
+    assert_eq!(5, FileCounterStore::open(&dir).unwrap().get("sensor/v1").unwrap());
+
+    fs::remove_dir_all(&dir).unwrap();
+}
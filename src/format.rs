@@ -0,0 +1,366 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The binary wire format of a letter.
+//!
+//! Every serialized letter starts with the magic bytes `EDL` followed by a single version byte.
+//! Readers check the version before touching anything else, so a letter written by a newer
+//! version of this crate is rejected with `DecodeError::UnsupportedVersion` instead of being
+//! misparsed.
+//!
+//! The parent certificate of a letter is written with its whole chain up to the master key, so a
+//! letter can be validated with nothing but the master public key. A detached letter only names
+//! the public key of its signer and needs the certificate from elsewhere, see `decode_attached`.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::json;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+
+use codec::Reader;
+use codec::Writer;
+use header::Header;
+use resolver::CertificateResolver;
+use resolver::NoResolver;
+
+/// The bytes every serialized letter starts with.
+pub const MAGIC: &[u8] = b"EDL";
+
+/// The versions of the binary letter format.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum LetterFormatVersion {
+    /// The first version: header, content bytes, signature hash and an optional parent
+    /// certificate.
+    V1 = 1,
+}
+
+impl LetterFormatVersion {
+    /// Returns the version this crate writes.
+    pub fn current() -> LetterFormatVersion {
+        LetterFormatVersion::V1
+    }
+
+    /// Parses a version byte. Unknown versions yield `DecodeError::UnsupportedVersion`.
+    pub fn from_byte(byte: u8) -> Result<LetterFormatVersion, DecodeError> {
+        match byte {
+            1 => Ok(LetterFormatVersion::V1),
+            v => Err(DecodeError::UnsupportedVersion(v)),
+        }
+    }
+
+    /// Returns the byte that represents this version on the wire.
+    pub fn as_byte(&self) -> u8 {
+        *self as u8
+    }
+}
+
+/// This error is returned, if a serialized letter can't be read.
+#[derive(Clone, PartialEq, Debug)]
+pub enum DecodeError {
+    /// The input doesn't start with the letter magic bytes.
+    InvalidMagic,
+    /// The letter was written in a format version this crate doesn't know.
+    UnsupportedVersion(u8),
+    /// The input ended before the letter was complete.
+    UnexpectedEnd,
+    /// The header is malformed.
+    InvalidHeader,
+    /// The content was digested with a hash algorithm this crate doesn't know or wasn't compiled
+    /// with.
+    UnsupportedHashAlgorithm(u8),
+    /// The content was compressed with an algorithm this crate doesn't know or wasn't compiled
+    /// with.
+    UnsupportedCompression(u8),
+    /// The content bytes couldn't be converted back into the content type.
+    InvalidContent,
+    /// The parent certificate couldn't be parsed.
+    InvalidCertificate,
+    /// The content is larger than the limit.
+    ContentTooLarge,
+    /// The parent certificate chain is deeper than the limit, or its encoding is larger than the
+    /// limit.
+    ChainTooDeep,
+    /// There are bytes after the end of the letter.
+    TrailingBytes,
+    /// The letter was written without its certificate chain and the certificate of the signer
+    /// isn't known.
+    DetachedChain,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            DecodeError::InvalidMagic => write!(f, "not a serialized letter"),
+            DecodeError::UnsupportedVersion(v) => {
+                write!(f, "unsupported letter format version {}", v)
+            }
+            DecodeError::UnexpectedEnd => write!(f, "unexpected end of letter"),
+            DecodeError::InvalidHeader => write!(f, "invalid letter header"),
+            DecodeError::UnsupportedHashAlgorithm(id) => {
+                write!(f, "unsupported hash algorithm {}", id)
+            }
+            DecodeError::UnsupportedCompression(id) => {
+                write!(f, "unsupported compression {}", id)
+            }
+            DecodeError::InvalidContent => write!(f, "invalid letter content"),
+            DecodeError::InvalidCertificate => write!(f, "invalid parent certificate"),
+            DecodeError::ContentTooLarge => write!(f, "letter content is too large"),
+            DecodeError::ChainTooDeep => write!(f, "parent certificate chain is too deep"),
+            DecodeError::TrailingBytes => write!(f, "trailing bytes after letter"),
+            DecodeError::DetachedChain => write!(f, "letter without its certificate chain"),
+        }
+    }
+}
+
+impl Error for DecodeError {}
+
+/// Limits that are enforced while reading a letter, so input from the network can't make the
+/// reader spend unbounded memory or time.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct DecodeLimits {
+    /// The maximum size of the content, after decompression.
+    pub max_content_size: u64,
+    /// The maximum size of the encoded parent certificate chain.
+    pub max_certificate_size: usize,
+    /// The maximum number of certificates between the letter and the master key.
+    pub max_chain_depth: usize,
+}
+
+impl Default for DecodeLimits {
+    fn default() -> DecodeLimits {
+        DecodeLimits {
+            max_content_size: ::compression::MAX_DECOMPRESSED_SIZE,
+            max_certificate_size: 64 * 1024,
+            max_chain_depth: ::letter::MAX_CHAIN_DEPTH,
+        }
+    }
+}
+
+/// Content types that can be restored from their fingerprint. Only letters with such content
+/// can be serialized, because the fingerprint is what gets written on the wire.
+pub trait FromFingerprint: Fingerprint + Sized {
+    /// Restores the content from the bytes returned by `fingerprint()`.
+    fn from_fingerprint(bytes: &[u8]) -> Result<Self, DecodeError>;
+}
+
+/// Writes the parts of a letter in the current format version. The content is compressed as the
+/// header says.
+pub fn encode(header: &Header, content: &[u8], signature: &Signature) -> Vec<u8> {
+    encode_parts(header, content, signature, false)
+}
+
+/// Writes the parts of a letter like `encode`, but only the public key of the signing
+/// certificate instead of the whole chain.
+pub fn encode_detached(header: &Header, content: &[u8], signature: &Signature) -> Vec<u8> {
+    encode_parts(header, content, signature, true)
+}
+
+fn encode_parts(header: &Header, content: &[u8], signature: &Signature, detached: bool) -> Vec<u8> {
+    let mut w = Writer::new();
+
+    w.raw(MAGIC);
+    w.u8(LetterFormatVersion::current().as_byte());
+    w.bytes(&header.to_bytes());
+    w.bytes(&header.compression().compress(content));
+    w.bytes(signature.hash());
+
+    match signature.parent() {
+        Some(parent) if detached => {
+            w.u8(2);
+            w.bytes(parent.public_key());
+        }
+        Some(parent) => {
+            w.u8(1);
+            w.bytes(&encode_certificate(parent));
+        }
+        None => w.u8(0),
+    }
+
+    w.into_bytes()
+}
+
+/// Reads the parts of a letter, returning the header, the content bytes and the signature. The
+/// default `DecodeLimits` apply.
+pub fn decode(bytes: &[u8]) -> Result<(Header, Vec<u8>, Signature), DecodeError> {
+    decode_with_limits(bytes, &DecodeLimits::default())
+}
+
+/// Reads the parts of a letter like `decode`, with the given limits. Detached letters yield
+/// `DecodeError::DetachedChain`.
+pub fn decode_with_limits(bytes: &[u8],
+                          limits: &DecodeLimits)
+                          -> Result<(Header, Vec<u8>, Signature), DecodeError> {
+    decode_attached(bytes, limits, &NoResolver)
+}
+
+/// Reads the parts of a letter like `decode_with_limits`. The certificate of a detached letter is
+/// looked up by its public key with the resolver. It must carry its own chain.
+pub fn decode_attached<R>(bytes: &[u8],
+                          limits: &DecodeLimits,
+                          resolver: &R)
+                          -> Result<(Header, Vec<u8>, Signature), DecodeError>
+    where R: CertificateResolver + ?Sized
+{
+    let mut r = Reader::new(bytes);
+
+    if r.raw(MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != MAGIC {
+        return Err(DecodeError::InvalidMagic);
+    }
+
+    match LetterFormatVersion::from_byte(r.u8()?)? {
+        LetterFormatVersion::V1 => {
+            let header = Header::from_bytes(r.bytes()?)?;
+            let content = header.compression().decompress_limited(r.bytes()?, limits.max_content_size)?;
+            let hash = r.bytes()?.to_vec();
+
+            let signature = match r.u8()? {
+                0 => Signature::new(hash),
+                1 => {
+                    let parent = decode_parent(r.bytes()?, limits)?;
+                    Signature::with_parent(Box::new(parent), hash)
+                }
+                2 => {
+                    let public_key = r.bytes()?;
+                    let mut parent = resolver.resolve(public_key).ok_or(DecodeError::DetachedChain)?;
+                    if parent.public_key().as_slice() != public_key {
+                        return Err(DecodeError::InvalidCertificate);
+                    }
+
+                    if chain_depth(&parent) > limits.max_chain_depth {
+                        return Err(DecodeError::ChainTooDeep);
+                    }
+
+                    parent.remove_private_key();
+                    Signature::with_parent(Box::new(parent), hash)
+                }
+                _ => return Err(DecodeError::InvalidCertificate),
+            };
+
+            if !r.is_empty() {
+                return Err(DecodeError::TrailingBytes);
+            }
+
+            Ok((header, content, signature))
+        }
+    }
+}
+
+/// Parses the encoded parent certificate of a letter and checks its size and the depth of its
+/// chain against the limits.
+pub fn decode_parent(bytes: &[u8], limits: &DecodeLimits) -> Result<Certificate, DecodeError> {
+    if bytes.len() > limits.max_certificate_size {
+        return Err(DecodeError::ChainTooDeep);
+    }
+
+    let parent = decode_certificate(bytes)?;
+    if chain_depth(&parent) > limits.max_chain_depth {
+        return Err(DecodeError::ChainTooDeep);
+    }
+
+    Ok(parent)
+}
+
+/// Returns the number of certificates from this one up to the master key.
+fn chain_depth(cert: &Certificate) -> usize {
+    let mut depth = 1;
+    let mut parent = cert.signature().and_then(|sig| sig.parent());
+
+    while let Some(cert) = parent {
+        depth += 1;
+        parent = cert.signature().and_then(|sig| sig.parent());
+    }
+
+    depth
+}
+
+/// Encodes a certificate the same way edcert does: as JSON.
+pub fn encode_certificate(cert: &Certificate) -> Vec<u8> {
+    json::encode(cert).expect("Certificates are always encodable.").into_bytes()
+}
+
+/// Encodes a certificate like `encode_certificate`, but without its private key. This is what
+/// goes into anything that is handed out.
+pub fn encode_public_certificate(cert: &Certificate) -> Vec<u8> {
+    if !cert.has_private_key() {
+        return encode_certificate(cert);
+    }
+
+    let mut public = cert.clone();
+    public.remove_private_key();
+    encode_certificate(&public)
+}
+
+/// Parses a JSON encoded certificate.
+pub fn decode_certificate(bytes: &[u8]) -> Result<Certificate, DecodeError> {
+    let s = ::std::str::from_utf8(bytes).map_err(|_| DecodeError::InvalidCertificate)?;
+    json::decode(s).map_err(|_| DecodeError::InvalidCertificate)
+}
+
+#[test]
+fn test_decode_errors() {
+    let header = Header::new();
+    let bytes = encode(&header, b"hello", &Signature::new(vec![7; 64]));
+
+    let (decoded, content, signature) = decode(&bytes).unwrap();
+    assert_eq!(header.to_bytes(), decoded.to_bytes());
+    assert_eq!(b"hello".to_vec(), content);
+    assert_eq!(&vec![7; 64], signature.hash());
+
+    // Every prefix of a letter is truncated.
+    for len in MAGIC.len() + 1..bytes.len() {
+        assert_eq!(Err(DecodeError::UnexpectedEnd), decode(&bytes[..len]).map(|_| ()));
+    }
+    assert_eq!(Err(DecodeError::InvalidMagic), decode(&bytes[..2]).map(|_| ()));
+    assert_eq!(Err(DecodeError::UnexpectedEnd), decode(MAGIC).map(|_| ()));
+
+    let mut bad = bytes.clone();
+    bad[0] = b'X';
+    assert_eq!(Err(DecodeError::InvalidMagic), decode(&bad).map(|_| ()));
+
+    let mut bad = bytes.clone();
+    bad[MAGIC.len()] = 9;
+    assert_eq!(Err(DecodeError::UnsupportedVersion(9)), decode(&bad).map(|_| ()));
+
+    // A length prefix beyond the end of the input.
+    let mut bad = bytes.clone();
+    for b in &mut bad[MAGIC.len() + 1..MAGIC.len() + 5] {
+        *b = 0xff;
+    }
+    assert_eq!(Err(DecodeError::UnexpectedEnd), decode(&bad).map(|_| ()));
+
+    let limits = DecodeLimits { max_content_size: 4, ..DecodeLimits::default() };
+    assert_eq!(Err(DecodeError::ContentTooLarge), decode_with_limits(&bytes, &limits).map(|_| ()));
+
+    let mut w = Writer::new();
+    w.raw(&bytes[..bytes.len() - 1]);
+    w.u8(1);
+    w.bytes(&vec![b' '; DecodeLimits::default().max_certificate_size + 1]);
+    assert_eq!(Err(DecodeError::ChainTooDeep), decode(&w.into_bytes()).map(|_| ()));
+
+    let mut bad = bytes.clone();
+    bad.push(0);
+    assert_eq!(Err(DecodeError::TrailingBytes), decode(&bad).map(|_| ()));
+}
@@ -0,0 +1,286 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The `Header` type, which holds the authenticated attributes of a letter.
+//!
+//! A letter doesn't sign its content directly. It signs the letter magic, the format version, the
+//! encoded header and a digest of the content's fingerprint. Everything in the header is
+//! therefore covered by the signature.
+
+use std::collections::BTreeMap;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use codec::Reader;
+use codec::Writer;
+use compression::Compression;
+use digest::HashAlgorithm;
+use format::DecodeError;
+use format::LetterFormatVersion;
+use format::MAGIC;
+
+/// The metadata key of the content type.
+pub const CONTENT_TYPE_KEY: &str = "content-type";
+
+/// The metadata key of the expiry time, in RFC 3339 format.
+pub const EXPIRES_KEY: &str = "expires";
+
+/// The metadata key of the audience.
+pub const AUDIENCE_KEY: &str = "audience";
+
+/// The authenticated attributes of a letter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Header {
+    hash_algorithm: HashAlgorithm,
+    meta: BTreeMap<String, String>,
+    signed_at: DateTime<UTC>,
+    compression: Compression,
+}
+
+impl Default for Header {
+    fn default() -> Header {
+        Header {
+            hash_algorithm: HashAlgorithm::default(),
+            meta: BTreeMap::new(),
+            signed_at: UTC::now(),
+            compression: Compression::default(),
+        }
+    }
+}
+
+impl Header {
+    /// Creates a header with default values.
+    pub fn new() -> Header {
+        Header::default()
+    }
+
+    /// Returns the time the letter was signed at.
+    pub fn signed_at(&self) -> &DateTime<UTC> {
+        &self.signed_at
+    }
+
+    /// Sets the signing time. `Letter::sign` sets it to the current time.
+    pub fn set_signed_at(&mut self, signed_at: DateTime<UTC>) {
+        self.signed_at = signed_at;
+    }
+
+    /// Returns the algorithm used to digest the content.
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        self.hash_algorithm
+    }
+
+    /// Sets the algorithm used to digest the content.
+    pub fn set_hash_algorithm(&mut self, hash_algorithm: HashAlgorithm) {
+        self.hash_algorithm = hash_algorithm;
+    }
+
+    /// Returns the compression of the content bytes in the serialized letter.
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Sets the compression of the content bytes in the serialized letter.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+
+    /// Returns the metadata of the letter, for example its content type or purpose.
+    pub fn meta(&self) -> &BTreeMap<String, String> {
+        &self.meta
+    }
+
+    /// Returns a single metadata value.
+    pub fn get_meta(&self, key: &str) -> Option<&str> {
+        self.meta.get(key).map(|v| v.as_str())
+    }
+
+    /// Sets a metadata value, replacing the old one.
+    pub fn set_meta(&mut self, key: &str, value: &str) {
+        self.meta.insert(key.to_string(), value.to_string());
+    }
+
+    /// Removes a metadata value.
+    pub fn remove_meta(&mut self, key: &str) -> Option<String> {
+        self.meta.remove(key)
+    }
+
+    /// Returns the content type, for example `app.example/handshake-v2`.
+    pub fn content_type(&self) -> Option<&str> {
+        self.get_meta(CONTENT_TYPE_KEY)
+    }
+
+    /// Sets the content type. Verifiers that require it with `Letter::validate_as` won't accept
+    /// the letter as another kind of message.
+    pub fn set_content_type(&mut self, content_type: &str) {
+        self.set_meta(CONTENT_TYPE_KEY, content_type);
+    }
+
+    /// Returns the time after which the letter should no longer be used or retained, or None if
+    /// the letter doesn't expire. It fails, if the expiry time is malformed, so that such letters
+    /// can't pass as letters that don't expire.
+    pub fn expires(&self) -> Result<Option<DateTime<UTC>>, DecodeError> {
+        match self.get_meta(EXPIRES_KEY) {
+            Some(expires) => {
+                let expires = DateTime::parse_from_rfc3339(expires).map_err(|_| DecodeError::InvalidHeader)?;
+                Ok(Some(expires.with_timezone(&UTC)))
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Sets the expiry time.
+    pub fn set_expires(&mut self, expires: DateTime<UTC>) {
+        self.set_meta(EXPIRES_KEY, &expires.to_rfc3339());
+    }
+
+    /// Returns the audience, the service the letter is meant for.
+    pub fn audience(&self) -> Option<&str> {
+        self.get_meta(AUDIENCE_KEY)
+    }
+
+    /// Sets the audience. Verifiers that expect another one, see `AudienceValidator`, won't
+    /// accept the letter, so it can't be replayed to another service.
+    pub fn set_audience(&mut self, audience: &str) {
+        self.set_meta(AUDIENCE_KEY, audience);
+    }
+
+    /// Returns the bytes that are signed for a content with the given fingerprint.
+    pub fn signed_bytes(&self, fingerprint: &[u8]) -> Vec<u8> {
+        self.signed_bytes_for_digest(&self.content_digest(fingerprint))
+    }
+
+    /// Returns the digest of a content fingerprint with the hash algorithm of this header. It is
+    /// what the signed bytes contain instead of the content.
+    pub fn content_digest(&self, fingerprint: &[u8]) -> Vec<u8> {
+        self.hash_algorithm.digest(fingerprint)
+    }
+
+    /// Returns the bytes that are signed for a content with the given digest, see
+    /// `content_digest`.
+    pub fn signed_bytes_for_digest(&self, digest: &[u8]) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(MAGIC);
+        w.u8(LetterFormatVersion::current().as_byte());
+        w.bytes(&self.to_bytes());
+        w.bytes(digest);
+        w.into_bytes()
+    }
+
+    /// Encodes the header.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u8(self.hash_algorithm.id());
+
+        // The map is sorted, so the encoding is canonical.
+        w.u32(self.meta.len() as u32);
+        for (key, value) in &self.meta {
+            w.bytes(key.as_bytes());
+            w.bytes(value.as_bytes());
+        }
+
+        w.u64(self.signed_at.timestamp() as u64);
+        w.u32(self.signed_at.timestamp_subsec_nanos());
+
+        // Uncompressed letters leave the field out, so their headers stay as they were.
+        if self.compression != Compression::None {
+            w.u8(self.compression.id());
+        }
+
+        w.into_bytes()
+    }
+
+    /// Decodes a header.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Header, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let hash_algorithm = HashAlgorithm::from_id(r.u8()?)?;
+
+        let mut meta = BTreeMap::new();
+        for _ in 0..r.u32()? {
+            let key = read_string(&mut r)?;
+            let value = read_string(&mut r)?;
+
+            // Keys must be unique and in order, otherwise the encoding wouldn't be canonical.
+            if meta.keys().next_back().is_some_and(|last: &String| *last >= key) {
+                return Err(DecodeError::InvalidHeader);
+            }
+
+            meta.insert(key, value);
+        }
+
+        let secs = r.u64()? as i64;
+        let nanos = r.u32()?;
+        let signed_at = UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidHeader)?;
+
+        let compression = if r.is_empty() {
+            Compression::None
+        } else {
+            match r.u8()? {
+                0 => return Err(DecodeError::InvalidHeader),
+                id => Compression::from_id(id)?,
+            }
+        };
+
+        if !r.is_empty() {
+            return Err(DecodeError::InvalidHeader);
+        }
+
+        Ok(Header {
+            hash_algorithm,
+            meta,
+            signed_at,
+            compression,
+        })
+    }
+}
+
+fn read_string(r: &mut Reader) -> Result<String, DecodeError> {
+    String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidHeader)
+}
+
+#[test]
+fn test_meta_roundtrip() {
+    let mut header = Header::new();
+    header.set_meta("purpose", "handshake");
+    header.set_meta("content-type", "application/x-pubkey");
+
+    let decoded = Header::from_bytes(&header.to_bytes()).unwrap();
+    assert_eq!(header, decoded);
+    assert_eq!(Some("handshake"), decoded.get_meta("purpose"));
+}
+
+#[test]
+fn test_expires() {
+    use chrono::Duration;
+
+    let mut header = Header::new();
+    assert_eq!(Ok(None), header.expires());
+
+    let expires = UTC.timestamp(UTC::now().timestamp(), 0) + Duration::days(1);
+    header.set_expires(expires);
+    assert_eq!(Ok(Some(expires)), header.expires());
+
+    // A malformed expiry doesn't mean that the letter never expires.
+    header.set_meta(EXPIRES_KEY, "next week");
+    assert_eq!(Err(DecodeError::InvalidHeader), header.expires());
+}
@@ -0,0 +1,280 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Compact JWS (RFC 7515) with the `EdDSA` algorithm (RFC 8037).
+//!
+//! A JWS signs `BASE64URL(protected header) || '.' || BASE64URL(payload)`, which is not what a
+//! letter signs, so a letter can't be converted without signing it again. `Letter::to_jws` does
+//! that with the same content and header. The payload is the content fingerprint. The protected
+//! header carries the letter header as `edh` and, if a certificate signed, the certificate as
+//! `edc`, an array like `x5c` holding the standard base64 of the edcert JSON encoding. Every
+//! certificate carries its own parents, so the array has one element.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::STANDARD;
+use rustc_serialize::base64::URL_SAFE;
+use rustc_serialize::json::Json;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use header::Header;
+use letter;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+use strict;
+
+/// This error is returned, if a JWS can't be verified.
+#[derive(Clone, PartialEq, Debug)]
+pub enum JwsError {
+    /// The input isn't a compact JWS.
+    Malformed,
+    /// The JWS uses another algorithm than `EdDSA`.
+    UnsupportedAlgorithm(String),
+    /// The letter header, the certificate or the payload can't be decoded.
+    Decode(DecodeError),
+    /// The signature or the certificate isn't valid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for JwsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            JwsError::Malformed => write!(f, "malformed JWS"),
+            JwsError::UnsupportedAlgorithm(ref alg) => write!(f, "unsupported JWS algorithm {}", alg),
+            JwsError::Decode(ref e) => write!(f, "can't decode JWS: {}", e),
+            JwsError::Invalid(ref e) => write!(f, "JWS is not valid: {:?}", e),
+        }
+    }
+}
+
+impl Error for JwsError {}
+
+impl From<DecodeError> for JwsError {
+    fn from(e: DecodeError) -> JwsError {
+        JwsError::Decode(e)
+    }
+}
+
+/// Signs the content and header as a compact JWS. It fails, if the signer is a certificate
+/// without a private key.
+pub fn sign<T: Fingerprint>(content: &T, header: &Header, signer: &Signer) -> Result<String, SignError> {
+    let mut protected = BTreeMap::new();
+    protected.insert("alg".to_string(), Json::String("EdDSA".to_string()));
+    protected.insert("edh".to_string(), Json::String(header.to_bytes().to_base64(URL_SAFE)));
+
+    if let Signer::Certificate(cert) = *signer {
+        let cert = format::encode_public_certificate(cert).to_base64(STANDARD);
+        protected.insert("edc".to_string(), Json::Array(vec![Json::String(cert)]));
+    }
+
+    let input = format!("{}.{}",
+                        Json::Object(protected).to_string().as_bytes().to_base64(URL_SAFE),
+                        content.fingerprint().to_base64(URL_SAFE));
+
+    let signature = signer.sign(input.as_bytes())?;
+    Ok(format!("{}.{}", input, signature.hash().to_base64(URL_SAFE)))
+}
+
+/// Verifies a compact JWS and returns its content and letter header. The certificate and the
+/// payload are decoded within the default `DecodeLimits`, like letters.
+pub fn verify<T: FromFingerprint, V: Validator>(token: &str, cv: &V) -> Result<(T, Header), JwsError> {
+    let limits = DecodeLimits::default();
+    let parts: Vec<&str> = token.split('.').collect();
+    if parts.len() != 3 {
+        return Err(JwsError::Malformed);
+    }
+
+    let protected = from_base64(parts[0])?;
+    let protected = String::from_utf8(protected).map_err(|_| JwsError::Malformed)?;
+    let protected = Json::from_str(&protected).map_err(|_| JwsError::Malformed)?;
+
+    match protected.find("alg").and_then(|alg| alg.as_string()) {
+        Some("EdDSA") => {}
+        Some(alg) => return Err(JwsError::UnsupportedAlgorithm(alg.to_string())),
+        None => return Err(JwsError::Malformed),
+    }
+
+    let input = &token[..parts[0].len() + 1 + parts[1].len()];
+    let signature = from_base64(parts[2])?;
+
+    match protected.find("edc") {
+        Some(chain) => {
+            let cert = chain.as_array()
+                            .and_then(|chain| chain.first())
+                            .and_then(|cert| cert.as_string())
+                            .ok_or(JwsError::Malformed)?;
+            let cert = cert.from_base64().map_err(|_| JwsError::Malformed)?;
+            let cert = format::decode_parent(&cert, &limits)?;
+            letter::check_parent_chain(Some(&cert), limits.max_chain_depth).map_err(JwsError::Invalid)?;
+
+            strict::check_signature_bytes(&signature, Some(&cert)).map_err(JwsError::Invalid)?;
+            cv.is_valid(&cert).map_err(|_| JwsError::Invalid(ValidationError::ParentInvalid))?;
+            if !cert.verify(input.as_bytes(), &signature) {
+                return Err(JwsError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+        None => {
+            strict::check_signature_bytes(&signature, None).map_err(JwsError::Invalid)?;
+            if !cv.is_signature_valid(input.as_bytes(), &signature) {
+                return Err(JwsError::Invalid(ValidationError::SignatureInvalid));
+            }
+        }
+    }
+
+    let header = protected.find("edh").and_then(|h| h.as_string()).ok_or(JwsError::Malformed)?;
+    let header = Header::from_bytes(&from_base64(header)?)?;
+    let payload = from_base64(parts[1])?;
+    if payload.len() as u64 > limits.max_content_size {
+        return Err(JwsError::Decode(DecodeError::ContentTooLarge));
+    }
+    let content = T::from_fingerprint(&payload)?;
+
+    Ok((content, header))
+}
+
+fn from_base64(s: &str) -> Result<Vec<u8>, JwsError> {
+    // base64url without padding, as RFC 7515 requires.
+    if s.contains(&['=', '+', '/'][..]) {
+        return Err(JwsError::Malformed);
+    }
+
+    s.from_base64().map_err(|_| JwsError::Malformed)
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method signs the content and header of the letter again as a compact JWS with the
+    /// `EdDSA` algorithm. The signature of the letter itself can't be reused.
+    pub fn to_jws(&self, signer: &Signer) -> Result<String, SignError> {
+        sign(self.get(), self.header(), signer)
+    }
+}
+
+#[test]
+fn test_jws_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut header = Header::new();
+    header.set_meta("purpose", "interop");
+    let letter = Letter::sign(Fingerprintable("hello".to_string()), header, &Signer::Certificate(&cert)).unwrap();
+
+    for signer in &[Signer::PrivateKey(&msk), Signer::Certificate(&cert)] {
+        let token = letter.to_jws(signer).unwrap();
+        let (content, header): (Fingerprintable<String>, Header) = verify(&token, &cv).unwrap();
+        assert_eq!(letter.get(), &content);
+        assert_eq!(letter.header(), &header);
+
+        let mut tampered = token.clone();
+        tampered.insert(token.find('.').unwrap() + 1, 'A');
+        assert_eq!(true, verify::<Fingerprintable<String>, _>(&tampered, &cv).is_err());
+
+        let (input, signature) = token.split_at(token.rfind('.').unwrap() + 1);
+        let malleated = ::strict::malleate(&from_base64(signature).unwrap());
+        let malleated = format!("{}{}", input, malleated.to_base64(URL_SAFE));
+        assert_eq!(Err(JwsError::Invalid(ValidationError::SignatureInvalid)),
+                   verify::<Fingerprintable<String>, _>(&malleated, &cv));
+    }
+}
+
+#[test]
+fn test_jws_without_private_key() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let token = sign(&"hello", &Header::new(), &Signer::Certificate(&cert)).unwrap();
+    let protected = from_base64(token.split('.').next().unwrap()).unwrap();
+    let protected = Json::from_str(&String::from_utf8(protected).unwrap()).unwrap();
+    let embedded = protected.find("edc").unwrap().as_array().unwrap()[0].as_string().unwrap();
+    let embedded = format::decode_certificate(&embedded.from_base64().unwrap()).unwrap();
+
+    assert_eq!(cert.public_key(), embedded.public_key());
+    assert_eq!(false, embedded.has_private_key());
+}
+
+#[test]
+fn test_jws_chain_limits() {
+    use canonical::Fingerprintable;
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(1);
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+
+    // A key that signs its own parent is a cycle.
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).unwrap();
+    let mut again = root.clone();
+    again.sign_with_parent(&leaf).unwrap();
+    let token = sign(&Fingerprintable("hello".to_string()), &Header::new(), &Signer::Certificate(&again)).unwrap();
+    assert_eq!(Err(JwsError::Invalid(ValidationError::ParentInvalid)),
+               verify::<Fingerprintable<String>, _>(&token, &cv).map(|_| ()));
+
+    let mut cert = root;
+    for _ in 0..letter::MAX_CHAIN_DEPTH {
+        let mut child = Certificate::generate_random(Meta::new_empty(), expires);
+        child.sign_with_parent(&cert).unwrap();
+        cert = child;
+    }
+    let token = sign(&Fingerprintable("hello".to_string()), &Header::new(), &Signer::Certificate(&cert)).unwrap();
+    assert_eq!(Err(JwsError::Decode(DecodeError::ChainTooDeep)),
+               verify::<Fingerprintable<String>, _>(&token, &cv).map(|_| ()));
+}
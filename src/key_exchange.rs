@@ -0,0 +1,335 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed ephemeral keys for key exchange.
+//!
+//! A `KeyExchangeLetter` carries an ephemeral X25519 public key together with the ids of both
+//! peers, a session id and an expiry time. Each peer signs its own key and sends the letter to the
+//! other one, which checks with `verify_key_exchange` that the key comes from the expected peer
+//! and was signed by that peer's certificate, that it is for this peer and this session, and that
+//! it hasn't expired. Both then compute the same secret with `shared_secret`, which hashes the
+//! X25519 output together with both public keys and the session id, like libsodium's
+//! `crypto_kx`.
+
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::hash::sha512;
+use sodiumoxide::crypto::scalarmult::curve25519;
+
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of key exchange letters.
+pub const KEY_EXCHANGE_CONTENT_TYPE: &str = "edcert-letter/key-exchange";
+
+/// This error is returned, if a key exchange letter is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum KeyExchangeError {
+    /// The letter isn't validly signed or isn't a key exchange letter.
+    Invalid(ValidationError),
+    /// The key claims to come from another peer than the expected one.
+    WrongSender,
+    /// The key was not signed by the certificate of the expected peer.
+    WrongSigner,
+    /// The key is meant for another peer.
+    WrongRecipient,
+    /// The key is meant for another session.
+    WrongSession,
+    /// The key has expired.
+    Expired,
+    /// The key is not a usable X25519 public key.
+    WeakKey,
+}
+
+impl fmt::Display for KeyExchangeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyExchangeError::Invalid(ref e) => write!(f, "the key exchange letter is not valid: {:?}", e),
+            KeyExchangeError::WrongSender => write!(f, "the key comes from another peer"),
+            KeyExchangeError::WrongSigner => write!(f, "the key was signed by another certificate"),
+            KeyExchangeError::WrongRecipient => write!(f, "the key is meant for another peer"),
+            KeyExchangeError::WrongSession => write!(f, "the key is meant for another session"),
+            KeyExchangeError::Expired => write!(f, "the key has expired"),
+            KeyExchangeError::WeakKey => write!(f, "the key is not a usable public key"),
+        }
+    }
+}
+
+impl Error for KeyExchangeError {}
+
+/// An ephemeral public key and the context it may be used in.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct KeyExchange {
+    public_key: box_::PublicKey,
+    sender: String,
+    recipient: String,
+    session_id: Vec<u8>,
+    expires: DateTime<UTC>,
+}
+
+/// A signed ephemeral public key.
+pub type KeyExchangeLetter = Letter<KeyExchange>;
+
+impl KeyExchange {
+    /// Creates the payload for an existing public key.
+    pub fn new(public_key: box_::PublicKey,
+               sender: &str,
+               recipient: &str,
+               session_id: &[u8],
+               expires: DateTime<UTC>)
+               -> KeyExchange {
+        KeyExchange {
+            public_key,
+            sender: sender.to_string(),
+            recipient: recipient.to_string(),
+            session_id: session_id.to_vec(),
+            expires,
+        }
+    }
+
+    /// Generates a fresh key pair, valid for the given time. The secret key is returned next to
+    /// the payload and should be dropped once the shared secret has been computed.
+    pub fn generate(sender: &str,
+                    recipient: &str,
+                    session_id: &[u8],
+                    lifetime: Duration)
+                    -> (KeyExchange, box_::SecretKey) {
+        let (pk, sk) = box_::gen_keypair();
+        (KeyExchange::new(pk, sender, recipient, session_id, UTC::now() + lifetime), sk)
+    }
+
+    /// Signs the payload as a key exchange letter.
+    pub fn sign(self, signer: &Signer) -> Result<KeyExchangeLetter, SignError> {
+        let mut header = Header::new();
+        header.set_content_type(KEY_EXCHANGE_CONTENT_TYPE);
+        Letter::sign(self, header, signer)
+    }
+
+    /// Returns the ephemeral public key.
+    pub fn public_key(&self) -> &box_::PublicKey {
+        &self.public_key
+    }
+
+    /// Returns the id of the peer that created the key.
+    pub fn sender(&self) -> &str {
+        &self.sender
+    }
+
+    /// Returns the id of the peer the key is meant for.
+    pub fn recipient(&self) -> &str {
+        &self.recipient
+    }
+
+    /// Returns the id of the session.
+    pub fn session_id(&self) -> &[u8] {
+        &self.session_id
+    }
+
+    /// Returns when the key expires.
+    pub fn expires(&self) -> &DateTime<UTC> {
+        &self.expires
+    }
+}
+
+impl Fingerprint for KeyExchange {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(&self.public_key.0);
+        w.bytes(self.sender.as_bytes());
+        w.bytes(self.recipient.as_bytes());
+        w.bytes(&self.session_id);
+        w.u64(self.expires.timestamp() as u64);
+        w.u32(self.expires.timestamp_subsec_nanos());
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for KeyExchange {
+    fn from_fingerprint(bytes: &[u8]) -> Result<KeyExchange, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let public_key = box_::PublicKey::from_slice(r.raw(box_::PUBLICKEYBYTES)?)
+                             .ok_or(DecodeError::InvalidContent)?;
+        let sender = String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?;
+        let recipient = String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?;
+        let session_id = r.bytes()?.to_vec();
+        let secs = r.u64()? as i64;
+        let nanos = r.u32()?;
+        let expires = UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidContent)?;
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(KeyExchange::new(public_key, &sender, &recipient, &session_id, expires))
+    }
+}
+
+/// Checks that the letter is a valid key exchange letter from `sender` for `recipient` and
+/// `session_id`, that it hasn't expired and wasn't signed after it expired, and returns the public
+/// key of the peer. `signer_id` is the id of the certificate the sender is known to sign with, as
+/// returned by `Letter::signer_id`. Without it, any certificate trusted by the validator could
+/// publish a key in the name of any peer.
+pub fn verify_key_exchange<V: Validator>(letter: &KeyExchangeLetter,
+                                         cv: &V,
+                                         sender: &str,
+                                         signer_id: &str,
+                                         recipient: &str,
+                                         session_id: &[u8])
+                                         -> Result<box_::PublicKey, KeyExchangeError> {
+    verify_key_exchange_with_clock(letter, cv, sender, signer_id, recipient, session_id, &SystemClock)
+}
+
+/// Like `verify_key_exchange`, but takes the current time from the clock.
+pub fn verify_key_exchange_with_clock<V: Validator, C: Clock>(letter: &KeyExchangeLetter,
+                                                              cv: &V,
+                                                              sender: &str,
+                                                              signer_id: &str,
+                                                              recipient: &str,
+                                                              session_id: &[u8],
+                                                              clock: &C)
+                                                              -> Result<box_::PublicKey, KeyExchangeError> {
+    letter.validate_as(cv, KEY_EXCHANGE_CONTENT_TYPE).map_err(KeyExchangeError::Invalid)?;
+
+    let exchange = letter.get();
+
+    if exchange.sender() != sender {
+        return Err(KeyExchangeError::WrongSender);
+    }
+
+    if letter.signer_id() != signer_id {
+        return Err(KeyExchangeError::WrongSigner);
+    }
+
+    if exchange.recipient() != recipient {
+        return Err(KeyExchangeError::WrongRecipient);
+    }
+
+    if exchange.session_id() != session_id {
+        return Err(KeyExchangeError::WrongSession);
+    }
+
+    if clock.now() >= *exchange.expires() || letter.signed_at() >= exchange.expires() {
+        return Err(KeyExchangeError::Expired);
+    }
+
+    if exchange.public_key().0 == [0u8; box_::PUBLICKEYBYTES] {
+        return Err(KeyExchangeError::WeakKey);
+    }
+
+    Ok(*exchange.public_key())
+}
+
+/// Computes the secret shared with the peer: SHA-512 over the X25519 output, both public keys and
+/// the session id, truncated to 32 bytes. The public keys are hashed in byte order, so both peers
+/// get the same secret. Fails if the peer key is of low order, which would make the secret
+/// predictable.
+pub fn shared_secret(secret_key: &box_::SecretKey,
+                     own_key: &box_::PublicKey,
+                     peer_key: &box_::PublicKey,
+                     session_id: &[u8])
+                     -> Result<[u8; 32], KeyExchangeError> {
+    let shared = curve25519::scalarmult(&curve25519::Scalar(secret_key.0),
+                                        &curve25519::GroupElement(peer_key.0));
+
+    if shared.0 == [0u8; 32] {
+        return Err(KeyExchangeError::WeakKey);
+    }
+
+    let (first, second) = if own_key.0 <= peer_key.0 { (own_key, peer_key) } else { (peer_key, own_key) };
+    let mut w = Writer::new();
+    w.raw(&shared.0);
+    w.raw(&first.0);
+    w.raw(&second.0);
+    w.bytes(session_id);
+
+    let mut secret = [0u8; 32];
+    secret.copy_from_slice(&sha512::hash(&w.into_bytes()).0[..32]);
+    Ok(secret)
+}
+
+#[test]
+fn test_key_exchange() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use clock::ManualClock;
+
+    use edcert::certificate::Certificate;
+    use edcert::meta::Meta;
+    use rustc_serialize::hex::ToHex;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut alice_cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    alice_cert.sign_with_master(&msk);
+    let mut bob_cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    bob_cert.sign_with_master(&msk);
+    let alice_id = alice_cert.public_key().to_hex();
+    let bob_id = bob_cert.public_key().to_hex();
+
+    let (alice, alice_sk) = KeyExchange::generate("alice", "bob", b"session", Duration::minutes(5));
+    let (bob, bob_sk) = KeyExchange::generate("bob", "alice", b"session", Duration::minutes(5));
+    let alice: KeyExchangeLetter =
+        Letter::from_bytes(&alice.sign(&Signer::Certificate(&alice_cert)).unwrap().to_bytes()).unwrap();
+    let bob = bob.sign(&Signer::Certificate(&bob_cert)).unwrap();
+
+    let alice_pk = verify_key_exchange(&alice, &cv, "alice", &alice_id, "bob", b"session").unwrap();
+    let bob_pk = verify_key_exchange(&bob, &cv, "bob", &bob_id, "alice", b"session").unwrap();
+    let secret = shared_secret(&bob_sk, &bob_pk, &alice_pk, b"session").unwrap();
+    assert_eq!(Ok(secret), shared_secret(&alice_sk, &alice_pk, &bob_pk, b"session"));
+    assert_eq!(false, secret == curve25519::scalarmult(&curve25519::Scalar(bob_sk.0),
+                                                        &curve25519::GroupElement(alice_pk.0)).0);
+    assert_eq!(false, Ok(secret) == shared_secret(&bob_sk, &bob_pk, &alice_pk, b"other"));
+
+    assert_eq!(Err(KeyExchangeError::WrongSender), verify_key_exchange(&alice, &cv, "carol", &alice_id, "bob", b"session"));
+    assert_eq!(Err(KeyExchangeError::WrongRecipient), verify_key_exchange(&alice, &cv, "alice", &alice_id, "carol", b"session"));
+    assert_eq!(Err(KeyExchangeError::WrongSession), verify_key_exchange(&alice, &cv, "alice", &alice_id, "bob", b"other"));
+
+    // Bob's certificate is trusted too, but it can't publish a key in Alice's name.
+    let (mallory, _) = KeyExchange::generate("alice", "bob", b"session", Duration::minutes(5));
+    let mallory = mallory.sign(&Signer::Certificate(&bob_cert)).unwrap();
+    assert_eq!(Err(KeyExchangeError::WrongSigner), verify_key_exchange(&mallory, &cv, "alice", &alice_id, "bob", b"session"));
+
+    let clock = ManualClock::new(UTC::now() + Duration::minutes(10));
+    assert_eq!(Err(KeyExchangeError::Expired),
+               verify_key_exchange_with_clock(&alice, &cv, "alice", &alice_id, "bob", b"session", &clock));
+}
@@ -0,0 +1,161 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Signing keys and certificates in the keychain of the operating system.
+//!
+//! Entries are stored through the `keyring` crate: in the Keychain on macOS, in the Credential
+//! Manager on Windows and through the Secret Service on Linux. Applications can then load their
+//! signing key by name instead of keeping a key file next to the binary.
+
+use std::error::Error;
+use std::fmt;
+
+use keyring::Entry;
+
+use edcert::certificate::Certificate;
+
+use format;
+
+/// The service name entries are stored under, unless another one is given.
+pub const DEFAULT_SERVICE: &str = "edcert-letter";
+
+/// The length of an ed25519 private key.
+const PRIVATE_KEY_LEN: usize = 64;
+
+/// This error is returned, if the keychain can't store or load an entry.
+#[derive(Debug)]
+pub enum KeychainError {
+    /// There is no entry with this name.
+    NotFound,
+    /// The entry exists, but it isn't a private key or a certificate.
+    Invalid,
+    /// The keychain failed or couldn't be reached.
+    Platform(String),
+}
+
+impl fmt::Display for KeychainError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeychainError::NotFound => write!(f, "no such keychain entry"),
+            KeychainError::Invalid => write!(f, "invalid keychain entry"),
+            KeychainError::Platform(ref e) => write!(f, "keychain failure: {}", e),
+        }
+    }
+}
+
+impl Error for KeychainError {}
+
+impl From<keyring::Error> for KeychainError {
+    fn from(e: keyring::Error) -> KeychainError {
+        match e {
+            keyring::Error::NoEntry => KeychainError::NotFound,
+            e => KeychainError::Platform(e.to_string()),
+        }
+    }
+}
+
+/// Named private keys and certificates in the keychain of the operating system.
+#[derive(Clone, Debug)]
+pub struct Keychain {
+    service: String,
+}
+
+impl Default for Keychain {
+    fn default() -> Keychain {
+        Keychain::with_service(DEFAULT_SERVICE)
+    }
+}
+
+impl Keychain {
+    /// Uses the default service name.
+    pub fn new() -> Keychain {
+        Keychain::default()
+    }
+
+    /// Uses another service name, for example to keep the keys of several applications apart.
+    pub fn with_service(service: &str) -> Keychain {
+        Keychain { service: service.to_string() }
+    }
+
+    fn entry(&self, kind: &str, name: &str) -> Result<Entry, KeychainError> {
+        Ok(Entry::new(&self.service, &format!("{}:{}", kind, name))?)
+    }
+
+    /// Stores a private key under the given name, replacing the old one.
+    pub fn store_key(&self, name: &str, private_key: &[u8]) -> Result<(), KeychainError> {
+        if private_key.len() != PRIVATE_KEY_LEN {
+            return Err(KeychainError::Invalid);
+        }
+
+        Ok(self.entry("key", name)?.set_secret(private_key)?)
+    }
+
+    /// Loads the private key with the given name. Use it with `Signer::PrivateKey`.
+    pub fn load_key(&self, name: &str) -> Result<Vec<u8>, KeychainError> {
+        let key = self.entry("key", name)?.get_secret()?;
+
+        if key.len() != PRIVATE_KEY_LEN {
+            return Err(KeychainError::Invalid);
+        }
+
+        Ok(key)
+    }
+
+    /// Removes the private key with the given name.
+    pub fn delete_key(&self, name: &str) -> Result<(), KeychainError> {
+        Ok(self.entry("key", name)?.delete_credential()?)
+    }
+
+    /// Stores a certificate, including its private key, under the given name.
+    pub fn store_certificate(&self, name: &str, cert: &Certificate) -> Result<(), KeychainError> {
+        Ok(self.entry("certificate", name)?.set_secret(&format::encode_certificate(cert))?)
+    }
+
+    /// Loads the certificate with the given name. Use it with `Signer::Certificate` or
+    /// `LetterSigner`.
+    pub fn load_certificate(&self, name: &str) -> Result<Certificate, KeychainError> {
+        let bytes = self.entry("certificate", name)?.get_secret()?;
+        format::decode_certificate(&bytes).map_err(|_| KeychainError::Invalid)
+    }
+
+    /// Removes the certificate with the given name.
+    pub fn delete_certificate(&self, name: &str) -> Result<(), KeychainError> {
+        Ok(self.entry("certificate", name)?.delete_credential()?)
+    }
+}
+
+#[test]
+fn test_keychain_errors() {
+    // The mock store keeps nothing, so this only checks how results are mapped.
+    keyring::set_default_credential_builder(keyring::mock::default_credential_builder());
+    let keychain = Keychain::with_service("edcert-letter-test");
+
+    match keychain.load_key("missing") {
+        Err(KeychainError::NotFound) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    match keychain.store_key("short", &[0; 32]) {
+        Err(KeychainError::Invalid) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
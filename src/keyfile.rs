@@ -0,0 +1,341 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! An encrypted file holding named private keys and certificates.
+//!
+//! The file starts with the magic bytes `EDK` and a version byte, followed by the parameters of
+//! the passphrase hash, a salt, a nonce and the encrypted entries. The key is derived from the
+//! passphrase with scrypt and the entries are encrypted with XSalsa20-Poly1305, so a wrong
+//! passphrase and a modified file are both detected.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path::Path;
+
+use sodiumoxide::crypto::pwhash;
+use sodiumoxide::crypto::secretbox;
+
+use edcert::certificate::Certificate;
+
+use codec::Reader;
+use codec::Writer;
+use format;
+use signer::Signer;
+
+/// The bytes every key file starts with.
+pub const KEYFILE_MAGIC: &[u8] = b"EDK";
+
+const VERSION: u8 = 1;
+const PRIVATE_KEY_LEN: usize = 64;
+
+/// This error is returned, if a key file can't be read or an entry can't be added.
+#[derive(Debug)]
+pub enum KeyFileError {
+    /// The file couldn't be read or written.
+    Io(io::Error),
+    /// The file isn't a key file, or its entries are malformed.
+    Decode,
+    /// The passphrase is wrong, or the file was modified.
+    Decrypt,
+    /// The private key doesn't have the length of an ed25519 private key.
+    InvalidKey,
+}
+
+impl fmt::Display for KeyFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            KeyFileError::Io(ref e) => write!(f, "can't access key file: {}", e),
+            KeyFileError::Decode => write!(f, "malformed key file"),
+            KeyFileError::Decrypt => write!(f, "wrong passphrase or modified key file"),
+            KeyFileError::InvalidKey => write!(f, "invalid private key"),
+        }
+    }
+}
+
+impl Error for KeyFileError {}
+
+/// An entry of a key file.
+#[derive(Clone, Debug)]
+pub enum KeyEntry {
+    /// An ed25519 private key, for example the master key.
+    PrivateKey(Vec<u8>),
+    /// A certificate with its private key.
+    Certificate(Certificate),
+}
+
+impl KeyEntry {
+    /// Returns a signer for this entry.
+    pub fn signer<'a>(&'a self) -> Signer<'a> {
+        match *self {
+            KeyEntry::PrivateKey(ref key) => Signer::PrivateKey(key),
+            KeyEntry::Certificate(ref cert) => Signer::Certificate(cert),
+        }
+    }
+}
+
+/// Named private keys and certificates that are stored encrypted with a passphrase.
+#[derive(Clone, Debug, Default)]
+pub struct KeyFile {
+    entries: BTreeMap<String, KeyEntry>,
+}
+
+impl KeyFile {
+    /// Creates an empty key file.
+    pub fn new() -> KeyFile {
+        KeyFile::default()
+    }
+
+    /// Returns the names of all entries, in order.
+    pub fn names(&self) -> Vec<&str> {
+        self.entries.keys().map(|name| name.as_str()).collect()
+    }
+
+    /// Returns the entry with the given name.
+    pub fn get(&self, name: &str) -> Option<&KeyEntry> {
+        self.entries.get(name)
+    }
+
+    /// Returns a signer for the entry with the given name.
+    pub fn signer<'a>(&'a self, name: &str) -> Option<Signer<'a>> {
+        self.get(name).map(|entry| entry.signer())
+    }
+
+    /// Adds a private key, replacing an entry with the same name.
+    pub fn add_key(&mut self, name: &str, private_key: &[u8]) -> Result<(), KeyFileError> {
+        if private_key.len() != PRIVATE_KEY_LEN {
+            return Err(KeyFileError::InvalidKey);
+        }
+
+        self.entries.insert(name.to_string(), KeyEntry::PrivateKey(private_key.to_vec()));
+        Ok(())
+    }
+
+    /// Adds a certificate, replacing an entry with the same name. It must have a private key,
+    /// otherwise it can't sign.
+    pub fn add_certificate(&mut self, name: &str, cert: Certificate) -> Result<(), KeyFileError> {
+        if !cert.has_private_key() {
+            return Err(KeyFileError::InvalidKey);
+        }
+
+        self.entries.insert(name.to_string(), KeyEntry::Certificate(cert));
+        Ok(())
+    }
+
+    /// Removes the entry with the given name.
+    pub fn remove(&mut self, name: &str) -> Option<KeyEntry> {
+        self.entries.remove(name)
+    }
+
+    /// Encrypts the entries with the passphrase.
+    pub fn seal(&self, passphrase: &str) -> Vec<u8> {
+        let opslimit = pwhash::OPSLIMIT_INTERACTIVE;
+        let memlimit = pwhash::MEMLIMIT_INTERACTIVE;
+        let salt = pwhash::gen_salt();
+        let nonce = secretbox::gen_nonce();
+        let key = derive_key(passphrase, &salt, opslimit, memlimit)
+            .expect("Deriving a key with the interactive limits only fails if libsodium runs out of memory.");
+
+        let mut w = Writer::new();
+        w.raw(KEYFILE_MAGIC);
+        w.u8(VERSION);
+        w.u64(opslimit.0 as u64);
+        w.u64(memlimit.0 as u64);
+        w.raw(&salt.0);
+        w.raw(&nonce.0);
+        w.bytes(&secretbox::seal(&self.encode_entries(), &nonce, &key));
+        w.into_bytes()
+    }
+
+    /// Decrypts a key file with the passphrase.
+    pub fn open(bytes: &[u8], passphrase: &str) -> Result<KeyFile, KeyFileError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(KEYFILE_MAGIC.len()).map_err(|_| KeyFileError::Decode)? != KEYFILE_MAGIC {
+            return Err(KeyFileError::Decode);
+        }
+
+        if r.u8().map_err(|_| KeyFileError::Decode)? != VERSION {
+            return Err(KeyFileError::Decode);
+        }
+
+        let (opslimit, memlimit, salt, nonce, ciphertext) = read_envelope(&mut r)
+            .map_err(|_| KeyFileError::Decode)?;
+
+        if !r.is_empty() {
+            return Err(KeyFileError::Decode);
+        }
+
+        let key = derive_key(passphrase, &salt, opslimit, memlimit)?;
+        let plaintext = secretbox::open(ciphertext, &nonce, &key).map_err(|_| KeyFileError::Decrypt)?;
+        KeyFile::decode_entries(&plaintext)
+    }
+
+    /// Encrypts the entries and writes them to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P, passphrase: &str) -> Result<(), KeyFileError> {
+        File::create(path)
+            .and_then(|mut f| f.write_all(&self.seal(passphrase)))
+            .map_err(KeyFileError::Io)
+    }
+
+    /// Reads a file and decrypts it.
+    pub fn load<P: AsRef<Path>>(path: P, passphrase: &str) -> Result<KeyFile, KeyFileError> {
+        let mut bytes = Vec::new();
+        File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)).map_err(KeyFileError::Io)?;
+        KeyFile::open(&bytes, passphrase)
+    }
+
+    fn encode_entries(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u32(self.entries.len() as u32);
+
+        for (name, entry) in &self.entries {
+            w.bytes(name.as_bytes());
+            match *entry {
+                KeyEntry::PrivateKey(ref key) => {
+                    w.u8(0);
+                    w.bytes(key);
+                }
+                KeyEntry::Certificate(ref cert) => {
+                    w.u8(1);
+                    w.bytes(&format::encode_certificate(cert));
+                }
+            }
+        }
+
+        w.into_bytes()
+    }
+
+    fn decode_entries(bytes: &[u8]) -> Result<KeyFile, KeyFileError> {
+        let mut r = Reader::new(bytes);
+        let mut keys = KeyFile::new();
+
+        for _ in 0..r.u32().map_err(|_| KeyFileError::Decode)? {
+            let name = r.bytes().map_err(|_| KeyFileError::Decode)?;
+            let name = ::std::str::from_utf8(name).map_err(|_| KeyFileError::Decode)?;
+            let kind = r.u8().map_err(|_| KeyFileError::Decode)?;
+            let data = r.bytes().map_err(|_| KeyFileError::Decode)?;
+
+            match kind {
+                0 => keys.add_key(name, data)?,
+                1 => {
+                    let cert = format::decode_certificate(data).map_err(|_| KeyFileError::Decode)?;
+                    keys.add_certificate(name, cert)?;
+                }
+                _ => return Err(KeyFileError::Decode),
+            }
+        }
+
+        if !r.is_empty() {
+            return Err(KeyFileError::Decode);
+        }
+
+        Ok(keys)
+    }
+}
+
+/// Reads the envelope of a key file. The parameters of the passphrase hash must lie between the
+/// interactive and the sensitive limits of libsodium, so a hostile file can't make us allocate
+/// arbitrary amounts of memory.
+fn read_envelope<'a>(r: &mut Reader<'a>)
+                     -> Result<(pwhash::OpsLimit, pwhash::MemLimit, pwhash::Salt, secretbox::Nonce, &'a [u8]),
+                               format::DecodeError> {
+    let opslimit = r.u64()?;
+    let memlimit = r.u64()?;
+
+    if opslimit < pwhash::OPSLIMIT_INTERACTIVE.0 as u64 || opslimit > pwhash::OPSLIMIT_SENSITIVE.0 as u64 ||
+       memlimit < pwhash::MEMLIMIT_INTERACTIVE.0 as u64 || memlimit > pwhash::MEMLIMIT_SENSITIVE.0 as u64 {
+        return Err(format::DecodeError::InvalidContent);
+    }
+
+    let opslimit = pwhash::OpsLimit(opslimit as usize);
+    let memlimit = pwhash::MemLimit(memlimit as usize);
+    let salt = pwhash::Salt::from_slice(r.raw(pwhash::SALTBYTES)?).unwrap();
+    let nonce = secretbox::Nonce::from_slice(r.raw(secretbox::NONCEBYTES)?).unwrap();
+    let ciphertext = r.bytes()?;
+    Ok((opslimit, memlimit, salt, nonce, ciphertext))
+}
+
+fn derive_key(passphrase: &str,
+              salt: &pwhash::Salt,
+              opslimit: pwhash::OpsLimit,
+              memlimit: pwhash::MemLimit)
+              -> Result<secretbox::Key, KeyFileError> {
+    let mut key = [0; secretbox::KEYBYTES];
+    pwhash::derive_key(&mut key, passphrase.as_bytes(), salt, opslimit, memlimit)
+        .map_err(|_| KeyFileError::Decode)?;
+    Ok(secretbox::Key(key))
+}
+
+#[test]
+fn test_keyfile() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    use header::Header;
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut keys = KeyFile::new();
+    keys.add_key("master", &msk).unwrap();
+    keys.add_certificate("server", cert).unwrap();
+    assert_eq!(true, keys.add_key("short", &msk[..32]).is_err());
+
+    let bytes = keys.seal("correct horse");
+    let keys = KeyFile::open(&bytes, "correct horse").unwrap();
+    assert_eq!(vec!["master", "server"], keys.names());
+
+    for name in keys.names() {
+        let letter = Letter::sign("hello", Header::new(), &keys.signer(name).unwrap()).unwrap();
+        assert_eq!(true, cv.is_valid(&letter).is_ok());
+    }
+
+    match KeyFile::open(&bytes, "wrong") {
+        Err(KeyFileError::Decrypt) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // The memory limit of the passphrase hash follows the magic bytes, the version and the
+    // operations limit.
+    let mut hostile = bytes.clone();
+    for b in &mut hostile[KEYFILE_MAGIC.len() + 9..KEYFILE_MAGIC.len() + 17] {
+        *b = 0xff;
+    }
+    match KeyFile::open(&hostile, "correct horse") {
+        Err(KeyFileError::Decode) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+}
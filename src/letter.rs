@@ -20,7 +20,18 @@
 // OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
 // SOFTWARE.
 
+use std::collections::BTreeMap;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::hash::Hash;
+use std::hash::Hasher;
 use std::ops::Deref;
+use std::sync::Arc;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::UTC;
+use rustc_serialize::hex::ToHex;
 
 use edcert::certificate::Certificate;
 use edcert::fingerprint::Fingerprint;
@@ -32,57 +43,345 @@ use edcert::revoker::RevokeError;
 use edcert::revoker::Revoker;
 use edcert::revoker::Revokable;
 
+use clock::Clock;
+use clock::SystemClock;
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use header::Header;
+use header::EXPIRES_KEY;
+use resolver::CertificateResolver;
+use signer::SignError;
+use signer::Signer;
+use strict;
+use strict::VerificationMode;
+
+/// The maximum number of certificates between a letter and the master key. Validation rejects
+/// longer chains before checking a single signature.
+pub const MAX_CHAIN_DEPTH: usize = 8;
+
+/// The signer id of letters signed by the master key, see `Letter::signer_id`.
+pub const MASTER_SIGNER: &str = "master";
+
 /// Use this type to sign content.
-#[derive(PartialEq, Debug)]
+///
+/// The digest of the content is computed once, when the letter is created, and reused for
+/// validation and hashing. The content can't be changed afterwards, so the cached digest never
+/// goes stale. Only the digest is kept, not the fingerprint, so the letter doesn't hold a second
+/// copy of its content. Serialization computes the fingerprint again.
+///
+/// The signature and the certificate chain in it are behind an `Arc`, so cloning a letter doesn't
+/// copy the chain. A `Letter<T>` is `Send` and `Sync` whenever `T` is, and can be shared between
+/// the threads of a server.
+#[derive(Clone, PartialEq)]
 pub struct Letter<T: Fingerprint> {
     content: T,
-    signature: Signature,
+    digest: Vec<u8>,
+    header: Header,
+    signature: Arc<Signature>,
 }
 
 impl<T: Fingerprint> Letter<T> {
     /// This method creates a Letter from its parts: A piece of content (which must be
-    /// convertable to a &[u8] (must implement AsRef<[u8]>)) and a Signature.
+    /// convertable to a &[u8] (must implement AsRef<[u8]>)) and a Signature. The letter gets a
+    /// default header.
     pub fn new(content: T, signature: Signature) -> Letter<T> {
+        Letter::from_parts(content, Header::new(), signature)
+    }
+
+    /// This method creates a Letter from its content, its header and the signature over both. The
+    /// signature can be shared with other letters over the same content by passing an `Arc`.
+    pub fn from_parts<S: Into<Arc<Signature>>>(content: T, header: Header, signature: S) -> Letter<T> {
+        let digest = header.content_digest(&content.fingerprint());
+        Letter::with_digest(content, digest, header, signature.into())
+    }
+
+    /// Creates a letter whose content digest, see `Header::content_digest`, is already known.
+    pub(crate) fn with_digest(content: T, digest: Vec<u8>, header: Header, signature: Arc<Signature>) -> Letter<T> {
         Letter {
-            content: content,
-            signature: signature,
+            content,
+            digest,
+            header,
+            signature,
+        }
+    }
+
+    /// This method creates a Letter by signing the content and the header with the given signer.
+    /// It fails, if the signer is a certificate without a private key.
+    pub fn sign(content: T, header: Header, signer: &Signer) -> Result<Letter<T>, SignError> {
+        Letter::sign_with_clock(content, header, signer, &SystemClock)
+    }
+
+    /// This method signs like `sign`, but takes the signing time from the given clock.
+    pub fn sign_with_clock<C: Clock>(content: T,
+                                     header: Header,
+                                     signer: &Signer,
+                                     clock: &C)
+                                     -> Result<Letter<T>, SignError> {
+        let digest = header.content_digest(&content.fingerprint());
+        Letter::sign_digest(content, digest, header, signer, clock)
+    }
+
+    /// Signs like `sign_with_clock`, for content whose digest, see `Header::content_digest`, is
+    /// already known.
+    pub(crate) fn sign_digest<C: Clock>(content: T,
+                                        digest: Vec<u8>,
+                                        mut header: Header,
+                                        signer: &Signer,
+                                        clock: &C)
+                                        -> Result<Letter<T>, SignError> {
+        let _span = trace_span!("sign", certificate = signer.certificate().is_some());
+
+        header.set_signed_at(clock.now());
+        let bytes = header.signed_bytes_for_digest(&digest);
+
+        match signer.sign(&bytes) {
+            Ok(signature) => {
+                trace_event!(debug, "letter signed");
+                Ok(Letter::with_digest(content, digest, header, Arc::new(signature)))
+            }
+            Err(e) => {
+                trace_event!(warn, "signing failed: {}", e);
+                Err(e)
+            }
         }
     }
 
     /// This method creates a Letter by signing itself with the given private key
     pub fn with_private_key(content: T, private_key: &[u8]) -> Letter<T> {
-        use edcert::ed25519;
-        let signature = Signature::new(ed25519::sign(&content.fingerprint(), private_key));
-        Letter::new(content, signature)
+        Letter::sign(content, Header::new(), &Signer::PrivateKey(private_key))
+            .expect("Signing with a private key can't fail.")
     }
 
     /// This method creates a Letter by signing itself with the given certificate. The certificate
     /// must have a private key.
     pub fn with_certificate(content: T, cert: &Certificate) -> Result<Letter<T>, ()> {
-        // This next call can fail, if the given certificate has no private key.
-        let res = cert.sign(&content.fingerprint());
-
-        match res {
-            Some(hash) => {
-                let signature = Signature::with_parent(Box::new(cert.clone()), hash);
-                Ok(Letter::new(content, signature))
-            },
+        Letter::sign(content, Header::new(), &Signer::Certificate(cert)).map_err(|_| ())
+    }
+
+    /// This method signs new content with the header of this letter, so the metadata and the
+    /// other header fields carry over. The signing time is updated.
+    pub fn resign(self, new_content: T, signer: &Signer) -> Result<Letter<T>, SignError> {
+        Letter::sign(new_content, self.header, signer)
+    }
+
+    /// This method signs the same content and header again, with the current time and the new
+    /// expiry time, or without one. Use it to renew letters before verifiers consider them too
+    /// old or they expire.
+    pub fn refresh(self, expires: Option<DateTime<UTC>>, signer: &Signer) -> Result<Letter<T>, SignError> {
+        let mut header = self.header;
+        match expires {
+            Some(expires) => header.set_expires(expires),
             None => {
-                Err(())
+                header.remove_meta(EXPIRES_KEY);
             }
         }
+
+        Letter::sign(self.content, header, signer)
+    }
+
+    /// This method returns the header of the letter.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// This method returns the time the letter was signed at. Verifiers can use it to reject
+    /// letters that are too old.
+    pub fn signed_at(&self) -> &DateTime<UTC> {
+        self.header.signed_at()
+    }
+
+    /// This method returns how long ago the letter was signed.
+    pub fn age(&self) -> Duration {
+        self.age_with_clock(&SystemClock)
+    }
+
+    /// This method returns how long ago the letter was signed, by the time of the given clock.
+    pub fn age_with_clock<C: Clock>(&self, clock: &C) -> Duration {
+        clock.now() - *self.signed_at()
+    }
+
+    /// This method returns true, if the letter is at most `max_age` old and wasn't signed in the
+    /// future. Both checks allow for the given clock skew, so letters from machines with a
+    /// slightly wrong clock aren't rejected.
+    pub fn is_fresh<C: Clock>(&self, max_age: Duration, skew: Duration, clock: &C) -> bool {
+        let age = self.age_with_clock(clock);
+        age <= max_age + skew && age >= -skew
+    }
+
+    /// This method returns the signed metadata of the letter.
+    pub fn meta(&self) -> &BTreeMap<String, String> {
+        self.header.meta()
+    }
+
+    /// This method validates the letter and checks that its content type is the expected one.
+    /// Letters of another or without a content type yield `ValidationError::Other`, so a letter
+    /// meant for one kind of message can't be accepted as another.
+    pub fn validate_as<V: Validator>(&self, cv: &V, content_type: &str) -> Result<(), ValidationError> {
+        cv.is_valid(self)?;
+
+        if self.header.content_type() == Some(content_type) {
+            Ok(())
+        } else {
+            Err(ValidationError::Other)
+        }
+    }
+
+    /// This method returns the signature of the letter.
+    pub fn signature(&self) -> &Signature {
+        &self.signature
+    }
+
+    /// This method returns the shared signature of the letter, to build other letters with it
+    /// without copying the certificate chain.
+    pub fn shared_signature(&self) -> &Arc<Signature> {
+        &self.signature
+    }
+
+    /// This method returns the certificate that signed the letter, or None if it was signed with
+    /// the master key directly.
+    pub fn signer_certificate(&self) -> Option<&Certificate> {
+        self.signature.parent()
+    }
+
+    /// This method returns an id of whoever signed the letter: the public key of the signing
+    /// certificate in hex, or `MASTER_SIGNER` for letters signed with the master key. The id
+    /// stays the same for every letter of a signer, so it can be put on allow lists. It only
+    /// means something after the letter was validated.
+    pub fn signer_id(&self) -> String {
+        match self.signer_certificate() {
+            Some(cert) => cert.public_key().to_hex(),
+            None => MASTER_SIGNER.to_string(),
+        }
+    }
+
+    /// This method returns the certificates between the letter and the master key, starting with
+    /// the one that signed the letter. It is empty for letters signed with the master key.
+    pub fn signer_chain(&self) -> Vec<&Certificate> {
+        let mut chain = Vec::new();
+        let mut parent = self.signer_certificate();
+
+        while let Some(cert) = parent {
+            chain.push(cert);
+            parent = cert.signature().and_then(|sig| sig.parent());
+        }
+
+        chain
+    }
+
+    /// This method validates the letter like `Validator::is_valid`, but rejects it with
+    /// `ValidationError::ParentInvalid` if more than `max_depth` certificates are between the
+    /// letter and the master key. The limit can only be lower than `MAX_CHAIN_DEPTH`, which
+    /// applies to every validation.
+    pub fn validate_with_max_depth<V: Validator>(&self, cv: &V, max_depth: usize) -> Result<(), ValidationError> {
+        check_parent_chain(self.signer_certificate(), max_depth)?;
+        cv.is_valid(self)
+    }
+
+    /// This method validates the letter like `Validator::is_valid`, in the given mode. Validation
+    /// is strict by default, see the `strict` module. Use `VerificationMode::Lenient` only for
+    /// letters from signers that produce non-canonical signatures.
+    pub fn validate_with_mode<V: Validator>(&self, cv: &V, mode: VerificationMode) -> Result<(), ValidationError> {
+        self.check_signature(cv, mode)?;
+        cv.is_revoked(self).map_err(|_| ValidationError::Revoked)
+    }
+
+    /// This method returns true, if the letter carries every certificate between it and the master
+    /// key, so it can be validated with nothing but the master public key.
+    pub fn is_self_contained(&self) -> bool {
+        match self.signer_chain().last() {
+            Some(cert) => cert.signature().is_some_and(|sig| sig.is_signed_by_master()),
+            None => true,
+        }
+    }
+
+    /// This method returns the bytes the signature of this letter is made over.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        self.header.signed_bytes_for_digest(&self.digest)
+    }
+
+    /// This method returns the digest of the content, which the signature covers instead of the
+    /// content itself.
+    pub fn content_digest(&self) -> &[u8] {
+        &self.digest
     }
 
     /// This method returns a reference to the contained object.
     pub fn get(&self) -> &T {
         &self.content
     }
+
+    /// This method returns the contained object.
+    pub fn into_inner(self) -> T {
+        self.content
+    }
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// This method serializes the letter into the binary letter format. The content is written
+    /// as its fingerprint, so it must be restorable from it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        format::encode(&self.header, &self.content.fingerprint(), &self.signature)
+    }
+
+    /// This method serializes the letter without its certificate chain. Only the public key of
+    /// the signing certificate is written, so the reader must already have the certificate, see
+    /// `from_bytes_attached`. Letters signed by the master key are written as usual.
+    pub fn to_bytes_detached(&self) -> Vec<u8> {
+        format::encode_detached(&self.header, &self.content.fingerprint(), &self.signature)
+    }
+
+    /// This method reads a letter like `from_bytes`. If it was written by `to_bytes_detached`,
+    /// the certificate of the signer is looked up by its public key with the resolver, for
+    /// example in the intermediates of a `TrustBundle`.
+    pub fn from_bytes_attached<R>(bytes: &[u8], resolver: &R) -> Result<Letter<T>, DecodeError>
+        where R: CertificateResolver + ?Sized
+    {
+        let (header, content, signature) = format::decode_attached(bytes, &DecodeLimits::default(), resolver)?;
+        let content = T::from_fingerprint(&content)?;
+        Ok(Letter::from_parts(content, header, signature))
+    }
+
+    /// This method reads a letter from the binary letter format. Letters written in an unknown
+    /// format version are rejected with `DecodeError::UnsupportedVersion`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Letter<T>, DecodeError> {
+        Letter::from_bytes_with_limits(bytes, &DecodeLimits::default())
+    }
+
+    /// This method reads a letter like `from_bytes`, but with the given limits on the content size
+    /// and the parent chain. Use it for input from untrusted sources.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &DecodeLimits) -> Result<Letter<T>, DecodeError> {
+        let (header, content, signature) = format::decode_with_limits(bytes, limits)?;
+        let content = T::from_fingerprint(&content)?;
+        Ok(Letter::from_parts(content, header, signature))
+    }
 }
 
 impl<T: Fingerprint> Validatable for Letter<T> {
     fn self_validate<V: Validator>(&self, cv: &V) -> Result<(), ValidationError> {
+        let _span = trace_span!("validate", by_master = self.signature.is_signed_by_master());
+
+        let result = self.check_signature(cv, VerificationMode::default());
+        match result {
+            Ok(()) => trace_event!(debug, "letter is valid"),
+            Err(ref _e) => trace_event!(info, error = ?_e, "letter rejected"),
+        }
+
+        result
+    }
+}
+
+impl<T: Fingerprint> Letter<T> {
+    fn check_signature<V: Validator>(&self, cv: &V, mode: VerificationMode) -> Result<(), ValidationError> {
+        check_parent_chain(self.signer_certificate(), MAX_CHAIN_DEPTH)?;
+
+        if mode == VerificationMode::Strict {
+            strict::check_signature(&self.signature)?;
+        }
+
         let sig = &self.signature;
-        let bytes = self.content.fingerprint();
+        let bytes = self.signed_bytes();
 
         if sig.is_signed_by_master() {
             if cv.is_signature_valid(&bytes, sig.hash()) {
@@ -106,6 +405,31 @@ impl<T: Fingerprint> Validatable for Letter<T> {
     }
 }
 
+/// Walks the parent chain starting at the given certificate and returns
+/// `ValidationError::ParentInvalid`, if it has more than `max_depth` certificates or a key appears
+/// in it twice. It stops at the first certificate past the limit, so a pathologically deep chain
+/// costs no more than a valid one.
+pub fn check_parent_chain(first: Option<&Certificate>, max_depth: usize) -> Result<(), ValidationError> {
+    let mut seen = BTreeSet::new();
+    let mut parent = first;
+
+    while let Some(cert) = parent {
+        if seen.len() == max_depth {
+            trace_event!(info, max_depth = max_depth, "parent chain too deep");
+            return Err(ValidationError::ParentInvalid);
+        }
+
+        if !seen.insert(cert.public_key()) {
+            trace_event!(info, key = %::trace::key_id(cert.public_key()), "parent chain has a cycle");
+            return Err(ValidationError::ParentInvalid);
+        }
+
+        parent = cert.signature().and_then(|sig| sig.parent());
+    }
+
+    Ok(())
+}
+
 impl<T: Fingerprint> Fingerprint for Letter<T> {
     fn fingerprint(&self) -> Vec<u8> {
         self.content.fingerprint()
@@ -120,8 +444,61 @@ impl<T: Fingerprint> Deref for Letter<T> {
     }
 }
 
+impl<T: Fingerprint + Eq> Eq for Letter<T> {}
+
+impl<T: Fingerprint> Hash for Letter<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.digest.hash(state);
+        self.signature.hash().hash(state);
+    }
+}
+
+/// The cached digest is left out, so a letter whose content hides its own `Debug` output, like
+/// `SecretContent`, doesn't leak a hash of it either.
+impl<T: Fingerprint + fmt::Debug> fmt::Debug for Letter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Letter")
+         .field("content", &self.content)
+         .field("header", &self.header)
+         .field("signature", &self.signature)
+         .finish()
+    }
+}
+
+/// Letters are displayed without their content, so they can be logged without leaking it.
+impl<T: Fingerprint> fmt::Display for Letter<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Letter signed at {} by ", self.signed_at())?;
+
+        match self.signer_certificate() {
+            Some(cert) => {
+                write!(f, "certificate ")?;
+                for byte in cert.public_key().iter().take(8) {
+                    write!(f, "{:02x}", byte)?;
+                }
+            }
+            None => write!(f, "the master key")?,
+        }
+
+        write!(f, " (content redacted)")
+    }
+}
+
 impl<T: Fingerprint> Revokable for Letter<T> {
-    fn self_check_revoked<R: Revoker>(&self, _: &R) -> Result<(), RevokeError> {
+    fn self_check_revoked<R: Revoker>(&self, revoker: &R) -> Result<(), RevokeError> {
+        // A letter can't be revoked itself, but every certificate between the letter and the
+        // master key can.
+        let _span = trace_span!("check_revoked");
+
+        for cert in self.signer_chain() {
+            trace_event!(debug, key = %::trace::key_id(cert.public_key()), "checking certificate");
+
+            if let Err(e) = revoker.is_revoked(cert) {
+                trace_event!(info, key = %::trace::key_id(cert.public_key()), error = ?e, "certificate rejected by revoker");
+                return Err(e);
+            }
+        }
+
         Ok(())
     }
 }
@@ -136,13 +513,13 @@ fn test_simple() {
 
     let test_str = "hello world";
 
-    let mut letter = Letter::with_private_key(test_str, &msk);
+    let letter = Letter::with_private_key(test_str, &msk);
 
     let cv = RootValidator::new(&mpk, NoRevoker);
 
     assert_eq!(true, cv.is_valid(&letter).is_ok());
 
-    letter.content = "world hello";
+    let letter = Letter::from_parts("world hello", letter.header, letter.signature);
 
     assert_eq!(false, cv.is_valid(&letter).is_ok());
 }
@@ -155,8 +532,6 @@ fn test_certificate() {
     use edcert::revoker::NoRevoker;
 
     use chrono::Timelike;
-    use chrono::UTC;
-    use chrono::Duration;
 
     let (mpk, msk) = ed25519::generate_keypair();
 
@@ -175,11 +550,11 @@ fn test_certificate() {
 
     let cv = RootValidator::new(&mpk, NoRevoker);
 
-    let mut letter = Letter::with_certificate(test_str, &cert).expect("This fails only if the Certificate has no private key.");
+    let letter = Letter::with_certificate(test_str, &cert).expect("This fails only if the Certificate has no private key.");
 
     assert_eq!(true, cv.is_valid(&letter).is_ok());
 
-    letter.content = "world hello";
+    let letter = Letter::from_parts("world hello", letter.header, letter.signature);
 
     assert_eq!(false, cv.is_valid(&letter).is_ok());
 }
@@ -195,3 +570,433 @@ fn test_deref() {
     let deref_str: &str = *letter;
     assert_eq!(deref_str, test_str);
 }
+
+#[test]
+fn test_std_traits() {
+    use std::collections::HashSet;
+    use edcert::ed25519;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let letter = Letter::with_private_key("top secret", &msk);
+
+    let mut set = HashSet::new();
+    set.insert(letter.clone());
+    set.insert(letter.clone());
+    assert_eq!(1, set.len());
+
+    let text = letter.to_string();
+    assert_eq!(true, text.contains("master key"));
+    assert_eq!(false, text.contains("top secret"));
+}
+
+#[cfg(test)]
+#[derive(PartialEq, Debug)]
+struct TestContent(Vec<u8>);
+
+#[cfg(test)]
+impl Fingerprint for TestContent {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+#[cfg(test)]
+impl FromFingerprint for TestContent {
+    fn from_fingerprint(bytes: &[u8]) -> Result<TestContent, DecodeError> {
+        Ok(TestContent(bytes.to_vec()))
+    }
+}
+
+#[test]
+fn test_serialization() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let letter = Letter::with_private_key(TestContent(b"hello world".to_vec()), &msk);
+
+    let bytes = letter.to_bytes();
+    let decoded: Letter<TestContent> = Letter::from_bytes(&bytes).unwrap();
+
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    assert_eq!(true, cv.is_valid(&decoded).is_ok());
+    assert_eq!(letter, decoded);
+
+    let mut future = bytes.clone();
+    future[3] = 200;
+    assert_eq!(Some(DecodeError::UnsupportedVersion(200)),
+               Letter::<TestContent>::from_bytes(&future).err());
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Some(DecodeError::TrailingBytes), Letter::<TestContent>::from_bytes(&trailing).err());
+
+    let limits = DecodeLimits { max_content_size: 4, ..DecodeLimits::default() };
+    assert_eq!(Some(DecodeError::ContentTooLarge),
+               Letter::<TestContent>::from_bytes_with_limits(&bytes, &limits).err());
+
+    // The version is part of the signed bytes, too.
+    let mut future = letter.signed_bytes();
+    future[3] = 200;
+    assert_eq!(false, cv.is_signature_valid(&future, letter.signature.hash()));
+}
+
+#[cfg(feature = "blake3")]
+#[test]
+fn test_hash_algorithm_is_authenticated() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+    use digest::HashAlgorithm;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut header = Header::new();
+    header.set_hash_algorithm(HashAlgorithm::Blake3);
+    let mut letter = Letter::sign("hello world", header, &Signer::PrivateKey(&msk)).unwrap();
+
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+
+    letter.header.set_hash_algorithm(HashAlgorithm::Sha512);
+
+    assert_eq!(false, cv.is_valid(&letter).is_ok());
+}
+
+#[cfg(feature = "deflate")]
+#[test]
+fn test_compression() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+    use compression::Compression;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut header = Header::new();
+    header.set_compression(Compression::Deflate);
+    let letter = Letter::sign(TestContent(vec![7; 4096]), header, &Signer::PrivateKey(&msk)).unwrap();
+
+    let bytes = letter.to_bytes();
+    assert_eq!(true, bytes.len() < 4096);
+
+    let mut decoded: Letter<TestContent> = Letter::from_bytes(&bytes).unwrap();
+    assert_eq!(letter, decoded);
+    assert_eq!(true, cv.is_valid(&decoded).is_ok());
+
+    decoded.header.set_compression(Compression::None);
+    assert_eq!(false, cv.is_valid(&decoded).is_ok());
+}
+
+#[test]
+fn test_meta_is_signed() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut header = Header::new();
+    header.set_meta("creator", "alice");
+    let mut letter = Letter::sign("hello world", header, &Signer::PrivateKey(&msk)).unwrap();
+
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!("alice", letter.meta()["creator"]);
+
+    letter.header.set_meta("creator", "mallory");
+
+    assert_eq!(false, cv.is_valid(&letter).is_ok());
+}
+
+#[test]
+fn test_content_type() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut header = Header::new();
+    header.set_content_type("app.example/handshake-v2");
+    let mut letter = Letter::sign("hello world", header, &Signer::PrivateKey(&msk)).unwrap();
+
+    assert_eq!(Ok(()), letter.validate_as(&cv, "app.example/handshake-v2"));
+    assert_eq!(Err(ValidationError::Other), letter.validate_as(&cv, "app.example/config-v1"));
+    assert_eq!(Err(ValidationError::Other),
+               Letter::with_private_key("hello world", &msk).validate_as(&cv, "app.example/handshake-v2"));
+
+    letter.header.set_content_type("app.example/config-v1");
+    assert_eq!(true, letter.validate_as(&cv, "app.example/config-v1").is_err());
+}
+
+#[test]
+fn test_signed_at() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let before = UTC::now();
+    let mut letter = Letter::with_private_key("hello world", &msk);
+
+    assert!(*letter.signed_at() >= before);
+    assert!(letter.age() < Duration::minutes(10));
+
+    let clock = ::clock::ManualClock::new(*letter.signed_at() - Duration::minutes(2));
+    assert_eq!(false, letter.is_fresh(Duration::hours(1), Duration::zero(), &clock));
+    assert_eq!(true, letter.is_fresh(Duration::hours(1), Duration::minutes(5), &clock));
+    clock.advance(Duration::minutes(64));
+    assert_eq!(true, letter.is_fresh(Duration::hours(1), Duration::minutes(5), &clock));
+    clock.advance(Duration::minutes(4));
+    assert_eq!(false, letter.is_fresh(Duration::hours(1), Duration::minutes(5), &clock));
+
+    letter.header.set_signed_at(before - Duration::days(1));
+
+    assert_eq!(false, cv.is_valid(&letter).is_ok());
+}
+
+#[test]
+fn test_resign_and_refresh() {
+    use chrono::TimeZone;
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let signer = Signer::PrivateKey(&msk);
+
+    let mut header = Header::new();
+    header.set_meta("purpose", "config");
+    header.set_expires(UTC::now() + Duration::days(1));
+    let mut letter = Letter::sign("v1", header, &signer).unwrap();
+    letter.header.set_signed_at(UTC::now() - Duration::days(7));
+    letter.signature = Arc::new(Signature::new(ed25519::sign(&letter.signed_bytes(), &msk)));
+
+    let expires = UTC.timestamp(UTC::now().timestamp(), 0) + Duration::days(30);
+    let letter = letter.refresh(Some(expires), &signer).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(true, letter.age() < Duration::minutes(1));
+    assert_eq!(Ok(Some(expires)), letter.header().expires());
+
+    let letter = letter.refresh(None, &signer).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(Ok(None), letter.header().expires());
+
+    let letter = letter.resign("v2", &signer).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!("v2", *letter.get());
+    assert_eq!(Some("config"), letter.header().get_meta("purpose"));
+}
+
+#[cfg(test)]
+struct CountingContent(::std::cell::Cell<usize>);
+
+#[cfg(test)]
+impl Fingerprint for CountingContent {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.0.set(self.0.get() + 1);
+        b"counted".to_vec()
+    }
+}
+
+#[test]
+fn test_digest_is_cached() {
+    use edcert::ed25519;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let letter = Letter::with_private_key(CountingContent(::std::cell::Cell::new(0)), &msk);
+    for _ in 0..3 {
+        assert_eq!(true, cv.is_valid(&letter).is_ok());
+    }
+
+    assert_eq!(1, letter.get().0.get());
+}
+
+#[cfg(test)]
+struct CountingRevoker {
+    checked: ::std::cell::Cell<usize>,
+    revoke: bool,
+}
+
+#[cfg(test)]
+impl Revoker for CountingRevoker {
+    fn is_revoked(&self, _: &Certificate) -> Result<(), RevokeError> {
+        self.checked.set(self.checked.get() + 1);
+
+        if self.revoke {
+            Err(RevokeError::Revoked)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_revocation_walks_chain() {
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let expires = UTC::now() + Duration::days(90);
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).expect("The root certificate has a private key.");
+
+    let letter = Letter::with_certificate("hello world", &leaf).unwrap();
+
+    let chain = letter.signer_chain();
+    assert_eq!(2, chain.len());
+    assert_eq!(leaf.public_key(), chain[0].public_key());
+    assert_eq!(root.public_key(), chain[1].public_key());
+    assert_eq!(Some(chain[0]), letter.signer_certificate());
+
+    let revoker = CountingRevoker { checked: ::std::cell::Cell::new(0), revoke: false };
+    assert_eq!(true, letter.self_check_revoked(&revoker).is_ok());
+    assert_eq!(2, revoker.checked.get());
+
+    let revoker = CountingRevoker { checked: ::std::cell::Cell::new(0), revoke: true };
+    assert_eq!(Err(RevokeError::Revoked), letter.self_check_revoked(&revoker));
+
+    // A letter signed by the master key has no certificate that could be revoked.
+    let letter = Letter::with_private_key("hello world", &msk);
+    assert_eq!(true, letter.self_check_revoked(&revoker).is_ok());
+    assert_eq!(true, letter.signer_chain().is_empty());
+}
+
+#[test]
+fn test_max_chain_depth() {
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let expires = UTC::now() + Duration::days(90);
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).expect("The root certificate has a private key.");
+
+    let letter = Letter::with_certificate("hello world", &leaf).unwrap();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    assert_eq!(Ok(()), letter.validate_with_max_depth(&cv, 2));
+    assert_eq!(Err(ValidationError::ParentInvalid), letter.validate_with_max_depth(&cv, 1));
+
+    // A key that signs its own parent is a cycle.
+    let mut looped = Certificate::generate_random(Meta::new_empty(), expires);
+    looped.sign_with_parent(&leaf).unwrap();
+    let mut again = root.clone();
+    again.sign_with_parent(&looped).unwrap();
+    assert_eq!(Err(ValidationError::ParentInvalid), check_parent_chain(Some(&again), MAX_CHAIN_DEPTH));
+}
+
+#[test]
+fn test_self_contained() {
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::root_validator::RootValidator;
+    use edcert::revoker::NoRevoker;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(90);
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).unwrap();
+
+    // The whole chain is written, so the master key is enough to validate the letter.
+    let letter = Letter::with_certificate(TestContent(b"hello".to_vec()), &leaf).unwrap();
+    let letter: Letter<TestContent> = Letter::from_bytes(&letter.to_bytes()).unwrap();
+    assert_eq!(true, letter.is_self_contained());
+    assert_eq!(2, letter.signer_chain().len());
+    assert_eq!(Ok(()), cv.is_valid(&letter));
+
+    let detached = letter.to_bytes_detached();
+    assert_eq!(true, detached.len() < letter.to_bytes().len());
+    assert_eq!(Some(DecodeError::DetachedChain), Letter::<TestContent>::from_bytes(&detached).err());
+
+    let resolve = |key: &[u8]| if key == leaf.public_key().as_slice() { Some(leaf.clone()) } else { None };
+    let attached: Letter<TestContent> = Letter::from_bytes_attached(&detached, &resolve).unwrap();
+    assert_eq!(letter, attached);
+    assert_eq!(Ok(()), cv.is_valid(&attached));
+
+    let unsigned = Certificate::generate_random(Meta::new_empty(), expires);
+    let letter = Letter::with_certificate(TestContent(b"hello".to_vec()), &unsigned).unwrap();
+    assert_eq!(false, letter.is_self_contained());
+}
+
+#[test]
+fn test_send_sync() {
+    use std::thread;
+
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Letter<&'static str>>();
+    assert_send_sync::<Letter<::canonical::Fingerprintable<Vec<u8>>>>();
+    assert_send_sync::<Header>();
+    assert_send_sync::<Signer>();
+    assert_send_sync::<::signer::LetterSigner>();
+    assert_send_sync::<::session::SigningSession>();
+    assert_send_sync::<::trust_bundle::TrustBundle>();
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let letter = Letter::with_certificate("shared", &cert).unwrap();
+    let copy = letter.clone();
+    assert_eq!(true, Arc::ptr_eq(letter.shared_signature(), copy.shared_signature()));
+
+    let letter = Arc::new(letter);
+    let cv = Arc::new(RootValidator::new(&mpk, NoRevoker));
+    let handles: Vec<_> = (0..4)
+                              .map(|_| {
+                                  let (letter, cv) = (letter.clone(), cv.clone());
+                                  thread::spawn(move || cv.is_valid(&*letter).is_ok())
+                              })
+                              .collect();
+
+    for handle in handles {
+        assert_eq!(true, handle.join().unwrap());
+    }
+}
+
+#[test]
+fn test_signer_id() {
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    assert_eq!(MASTER_SIGNER, Letter::with_private_key("hello", &msk).signer_id());
+
+    let a = Letter::with_certificate("a", &cert).unwrap();
+    let b = Letter::with_certificate("b", &cert).unwrap();
+    assert_eq!(a.signer_id(), b.signer_id());
+    assert_eq!(cert.public_key().to_hex(), a.signer_id());
+}
@@ -33,7 +33,331 @@
 
 extern crate edcert;
 extern crate chrono;
+extern crate rustc_serialize;
+extern crate sodiumoxide;
+
+#[cfg(feature = "sha3")]
+extern crate sha3;
+#[cfg(feature = "blake3")]
+extern crate blake3;
+
+#[cfg(feature = "canonical-json")]
+extern crate serde;
+#[cfg(feature = "canonical-json")]
+extern crate serde_json;
+#[cfg(feature = "http")]
+extern crate ureq;
+#[cfg(feature = "deflate")]
+extern crate flate2;
+#[cfg(feature = "zstd")]
+extern crate zstd;
+#[cfg(feature = "blake2b")]
+extern crate blake2b_simd;
+#[cfg(feature = "rayon")]
+extern crate rayon;
+#[cfg(feature = "keychain")]
+extern crate keyring;
+#[cfg(feature = "tracing")]
+extern crate tracing;
+#[cfg(feature = "age")]
+extern crate age;
+#[cfg(feature = "prost")]
+extern crate prost;
+#[cfg(feature = "rmp")]
+extern crate rmp;
+#[cfg(feature = "secure-memory")]
+extern crate zeroize;
+#[cfg(feature = "secure-memory")]
+extern crate region;
+#[cfg(feature = "futures")]
+extern crate futures;
+#[cfg(feature = "derive")]
+extern crate edcert_letter_derive;
+#[cfg(feature = "derive")]
+pub use edcert_letter_derive::Fingerprint;
+#[cfg(feature = "derive")]
+pub use edcert_letter_derive::Claims;
+
+#[macro_use]
+mod trace;
+mod codec;
+
+/// This module re-exports what is needed to sign and verify letters.
+pub mod prelude;
 
 /// This module contains the Letter<T> type.
 pub mod letter;
 pub use letter::Letter;
+
+/// This module contains the authenticated header of a letter.
+pub mod header;
+pub use header::Header;
+
+/// This module contains the hash algorithms for the content digest.
+pub mod digest;
+pub use digest::HashAlgorithm;
+
+/// This module contains the Signer type.
+pub mod signer;
+pub use signer::Signer;
+pub use signer::LetterSigner;
+
+/// This module contains the binary letter format and its version.
+pub mod format;
+pub use format::LetterFormatVersion;
+
+/// This module contains the canonical encoding used to build fingerprints.
+pub mod canonical;
+pub use canonical::Fingerprintable;
+
+/// This module contains the `CanonicalContent` type, which signs serde types as canonical JSON.
+#[cfg(feature = "canonical-json")]
+pub mod canonical_json;
+#[cfg(feature = "canonical-json")]
+pub use canonical_json::CanonicalContent;
+
+/// This module contains Merkle tree hashing as used by transparency logs.
+pub mod merkle;
+
+/// This module contains the transparency log client interface.
+pub mod transparency;
+
+/// This module contains revokers for use with validators.
+pub mod revocation;
+
+/// This module contains validation against several trusted master keys.
+pub mod trust;
+pub use trust::TrustStore;
+
+/// This module contains policies about who may sign a letter.
+pub mod policy;
+pub use policy::Policy;
+
+/// This module contains the validation of the role of a signer.
+pub mod role;
+pub use role::RoleValidator;
+
+/// This module contains append-only chains of letters.
+pub mod chain;
+pub use chain::LetterChain;
+
+/// This module contains letters that are signed and then encrypted to a recipient.
+pub mod sealed;
+pub use sealed::SealedLetter;
+
+/// This module contains the compression of content bytes on the wire.
+pub mod compression;
+pub use compression::Compression;
+
+/// This module contains the conversion of letters to JWS.
+pub mod jws;
+
+/// This module contains the conversion of letters to COSE_Sign1.
+pub mod cose;
+
+/// This module contains detached signatures in the minisign format.
+pub mod minisign;
+
+/// This module contains OpenSSH signatures and keys.
+pub mod sshsig;
+
+/// This module contains the conversion of letters to PASETO tokens.
+pub mod paseto;
+
+/// This module contains W3C Verifiable Credentials with `eddsa-jcs-2022` proofs.
+#[cfg(feature = "canonical-json")]
+pub mod credential;
+
+/// This module contains the rotation of master keys.
+pub mod rotation;
+
+/// This module contains letters that need the signatures of several keys.
+pub mod threshold;
+pub use threshold::ThresholdLetter;
+
+/// This module contains letters that sign collections of items.
+pub mod manifest;
+pub use manifest::ManifestLetter;
+
+/// This module contains letters over large data that is verified while it streams.
+pub mod chunked;
+pub use chunked::ChunkedLetter;
+
+/// This module contains the validation of many letters at once.
+pub mod batch;
+
+/// This module contains letters over borrowed content.
+pub mod borrowed;
+pub use borrowed::LetterRef;
+
+/// This module contains zero-copy views of serialized letters.
+pub mod view;
+pub use view::LetterView;
+
+/// This module contains signing keys in the keychain of the operating system.
+#[cfg(feature = "keychain")]
+pub mod keychain;
+
+/// This module contains the encrypted key file.
+pub mod keyfile;
+pub use keyfile::KeyFile;
+
+/// This module contains structured validation reports.
+pub mod report;
+pub use report::ValidationReport;
+
+/// This module contains the clock time-dependent checks use.
+pub mod clock;
+pub use clock::Clock;
+
+/// This module contains capability tokens with attenuating caveats.
+pub mod capability;
+pub use capability::Capability;
+
+/// This module contains letters that delegate a role to another key.
+pub mod delegation;
+pub use delegation::DelegationLetter;
+
+/// This module contains letters over content that is encoded by the caller.
+pub mod encoded;
+pub use encoded::Encoded;
+
+/// This module contains keys, validators and revokers for tests.
+#[cfg(feature = "test-utils")]
+pub mod testing;
+
+/// This module contains metrics about validation.
+pub mod metrics;
+pub use metrics::MetricsSink;
+
+/// This module contains bundles of several letters.
+pub mod bundle;
+pub use bundle::LetterBundle;
+
+/// This module contains signed manifests of directory trees.
+pub mod directory;
+pub use directory::DirectoryLetter;
+
+/// This module contains the ASCII armor of serialized letters.
+pub mod armor;
+
+/// This module contains signed configuration files.
+#[cfg(feature = "canonical-json")]
+pub mod config;
+
+/// This module contains adapters to sign and validate streams of letters.
+#[cfg(feature = "stream")]
+pub mod stream;
+
+/// This module contains trust bundles for client provisioning.
+pub mod trust_bundle;
+pub use trust_bundle::TrustBundle;
+
+/// This module contains the lookup of certificates for detached letters.
+pub mod resolver;
+pub use resolver::CertificateResolver;
+
+/// This module contains trust on first use for peers without a master key.
+pub mod tofu;
+pub use tofu::TofuValidator;
+
+/// This module contains certificate pinning.
+pub mod pinning;
+pub use pinning::PinnedValidator;
+
+/// This module contains countersignatures of notaries.
+pub mod notary;
+pub use notary::NotarizedLetter;
+
+/// This module contains signers identified by `did:key` and `did:web`.
+pub mod did;
+pub use did::Did;
+
+/// This module contains signed letters encrypted to age recipients.
+#[cfg(feature = "age")]
+pub mod age_sealed;
+
+/// This module contains challenge-response authentication helpers.
+pub mod challenge;
+
+/// This module contains signed ephemeral keys for key exchange.
+pub mod key_exchange;
+
+/// This module contains binding of letters to a TLS session.
+pub mod channel;
+
+/// This module contains a wrapper for secret letter content.
+#[cfg(feature = "secure-memory")]
+pub mod secret;
+
+/// This module contains saving letters to files.
+pub mod file;
+
+/// This module contains a persistent store of letters.
+pub mod store;
+
+/// This module contains a cache of validated letters.
+pub mod cache;
+pub use cache::ValidationCache;
+
+/// This module contains a watcher that distributes revocations to caches and sessions.
+pub mod watcher;
+
+/// This module contains letters as protobuf messages.
+#[cfg(feature = "protobuf")]
+pub mod protobuf;
+
+/// This module contains letters as MessagePack.
+#[cfg(feature = "msgpack")]
+pub mod msgpack;
+
+/// This module contains human-readable TOML and YAML letter documents.
+pub mod document;
+
+/// This module contains signed metadata for software updates in the style of TUF.
+pub mod tuf;
+
+/// This module contains firmware update letters with rollback protection.
+pub mod firmware;
+
+/// This module contains verification of plugins before they are loaded.
+pub mod artifact;
+
+/// This module contains license keys.
+pub mod license;
+
+/// This module contains scoped API tokens.
+pub mod token;
+
+/// This module contains the compact encoding for QR codes.
+pub mod qr;
+
+/// This module contains signed links.
+pub mod link;
+
+/// This module contains selective disclosure of fields.
+pub mod disclosure;
+
+/// This module contains letters with redactable sections.
+pub mod redaction;
+
+/// This module contains the strict checks of signatures and keys.
+pub mod strict;
+
+/// This module contains signing sessions for many letters.
+pub mod session;
+
+/// This module contains step by step explanations of validation.
+pub mod explain;
+
+/// This module contains allow and deny lists of signers.
+pub mod signers;
+
+/// This module contains audience restriction.
+pub mod audience;
+
+/// This module contains standard claims of token-like letters.
+pub mod claims;
+
+/// This module contains summaries of letters without validation.
+pub mod peek;
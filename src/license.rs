@@ -0,0 +1,245 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! License keys.
+//!
+//! A license key is a letter with a `License` as content, signed by the vendor and written as a
+//! single line of URL-safe base64, so customers can paste it into a configuration file or a
+//! dialog. Shipped binaries embed the public master key and check license keys offline with a
+//! `LicenseValidator`. Keep the signing key off the build machines; only the public key belongs
+//! into the binary.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::URL_SAFE;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::revoker::NoRevoker;
+use edcert::root_validator::RootValidator;
+use edcert::validator::ValidationError;
+
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of license letters.
+pub const LICENSE_CONTENT_TYPE: &str = "edcert-letter/license";
+
+/// This error is returned, if a license key is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LicenseError {
+    /// The text isn't a license key.
+    Malformed,
+    /// The license isn't signed by the vendor.
+    Invalid(ValidationError),
+    /// The license has expired.
+    Expired,
+}
+
+impl fmt::Display for LicenseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LicenseError::Malformed => write!(f, "malformed license key"),
+            LicenseError::Invalid(ref e) => write!(f, "invalid license key: {:?}", e),
+            LicenseError::Expired => write!(f, "the license has expired"),
+        }
+    }
+}
+
+impl Error for LicenseError {}
+
+/// The terms of a license.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct License {
+    /// The customer the license was issued to.
+    pub customer_id: String,
+    /// The features the customer may use.
+    pub features: BTreeSet<String>,
+    /// When the license expires. Perpetual licenses don't.
+    pub expires: Option<DateTime<UTC>>,
+}
+
+impl License {
+    /// Creates a perpetual license without features.
+    pub fn new(customer_id: &str) -> License {
+        License {
+            customer_id: customer_id.to_string(),
+            features: BTreeSet::new(),
+            expires: None,
+        }
+    }
+
+    /// Adds a feature.
+    pub fn with_feature(mut self, feature: &str) -> License {
+        self.features.insert(feature.to_string());
+        self
+    }
+
+    /// Sets the expiry time.
+    pub fn expiring(mut self, expires: DateTime<UTC>) -> License {
+        self.expires = Some(expires);
+        self
+    }
+
+    /// Returns true, if the license includes the feature.
+    pub fn has_feature(&self, feature: &str) -> bool {
+        self.features.contains(feature)
+    }
+
+    /// Signs the license and returns the license key.
+    pub fn issue(self, signer: &Signer) -> Result<String, SignError> {
+        let mut header = Header::new();
+        header.set_content_type(LICENSE_CONTENT_TYPE);
+
+        if let Some(expires) = self.expires {
+            header.set_expires(expires);
+        }
+
+        Ok(Letter::sign(self, header, signer)?.to_bytes().to_base64(URL_SAFE))
+    }
+}
+
+impl Fingerprint for License {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(self.customer_id.as_bytes());
+        w.u32(self.features.len() as u32);
+
+        for feature in &self.features {
+            w.bytes(feature.as_bytes());
+        }
+
+        match self.expires {
+            Some(expires) => {
+                w.u8(1);
+                w.u64(expires.timestamp() as u64);
+            }
+            None => w.u8(0),
+        }
+
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for License {
+    fn from_fingerprint(bytes: &[u8]) -> Result<License, DecodeError> {
+        let utf8 = |b: &[u8]| String::from_utf8(b.to_vec()).map_err(|_| DecodeError::InvalidContent);
+        let mut r = Reader::new(bytes);
+        let customer_id = utf8(r.bytes()?)?;
+        let mut features = BTreeSet::new();
+
+        for _ in 0..r.u32()? {
+            features.insert(utf8(r.bytes()?)?);
+        }
+
+        let expires = match r.u8()? {
+            0 => None,
+            1 => Some(UTC.timestamp_opt(r.u64()? as i64, 0).single().ok_or(DecodeError::InvalidContent)?),
+            _ => return Err(DecodeError::InvalidContent),
+        };
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(License {
+            customer_id,
+            features,
+            expires,
+        })
+    }
+}
+
+/// Checks license keys against the public master key of the vendor.
+pub struct LicenseValidator {
+    cv: RootValidator<NoRevoker>,
+}
+
+impl LicenseValidator {
+    /// Creates a validator for the master key embedded in the binary.
+    pub fn new(master_public_key: &[u8]) -> LicenseValidator {
+        LicenseValidator { cv: RootValidator::new(master_public_key, NoRevoker) }
+    }
+
+    /// Checks the license key and returns the license.
+    pub fn validate(&self, key: &str) -> Result<License, LicenseError> {
+        self.validate_with_clock(key, &SystemClock)
+    }
+
+    /// Like `validate`, but takes the current time from the clock.
+    pub fn validate_with_clock<C: Clock>(&self, key: &str, clock: &C) -> Result<License, LicenseError> {
+        let bytes = key.trim().from_base64().map_err(|_| LicenseError::Malformed)?;
+        let letter: Letter<License> = Letter::from_bytes(&bytes).map_err(|_| LicenseError::Malformed)?;
+        letter.validate_as(&self.cv, LICENSE_CONTENT_TYPE).map_err(LicenseError::Invalid)?;
+
+        let license = letter.into_inner();
+        if license.expires.is_some_and(|e| e <= clock.now()) {
+            return Err(LicenseError::Expired);
+        }
+
+        Ok(license)
+    }
+}
+
+#[test]
+fn test_license() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use clock::ManualClock;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (other_mpk, _) = ed25519::generate_keypair();
+    let validator = LicenseValidator::new(&mpk);
+
+    let key = License::new("ACME-0042")
+                  .with_feature("export")
+                  .with_feature("sso")
+                  .expiring(UTC::now() + Duration::days(365))
+                  .issue(&Signer::PrivateKey(&msk))
+                  .unwrap();
+    assert_eq!(false, key.contains('\n'));
+
+    let license = validator.validate(&key).unwrap();
+    assert_eq!("ACME-0042", license.customer_id);
+    assert_eq!(true, license.has_feature("sso"));
+    assert_eq!(false, license.has_feature("audit"));
+
+    let later = ManualClock::new(UTC::now() + Duration::days(400));
+    assert_eq!(Err(LicenseError::Expired), validator.validate_with_clock(&key, &later));
+    assert_eq!(true, LicenseValidator::new(&other_mpk).validate(&key).is_err());
+    assert_eq!(Err(LicenseError::Malformed), validator.validate("not a license"));
+}
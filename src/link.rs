@@ -0,0 +1,211 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed links.
+//!
+//! `sign_link` appends a letter to a URL as the query parameter `letter`, for download links or
+//! the link in an email verification mail. The letter is written in URL-safe base64, so it needs
+//! no percent-encoding. Its header carries the expiry and the URL without the parameter, so a
+//! letter can't be moved to another link. `verify_link` takes the link as it was requested and
+//! checks all of that. Keep the content small, links longer than about 2000 characters are
+//! truncated by some clients. Letters signed by a certificate carry its chain, so consider
+//! signing links with a key the receiving service knows directly.
+
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::UTC;
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::URL_SAFE;
+
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The query parameter that holds the letter.
+pub const LINK_PARAM: &str = "letter";
+
+/// The meta key in the header of a link letter, whose value is the URL the letter belongs to.
+pub const LINK_URL_KEY: &str = "link-url";
+
+/// This error is returned, if a signed link is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum LinkError {
+    /// The link has no letter.
+    Missing,
+    /// The letter in the link can't be read.
+    Malformed,
+    /// The letter isn't valid.
+    Invalid(ValidationError),
+    /// The letter belongs to another link.
+    WrongUrl,
+    /// The link has expired, or the letter has no expiry.
+    Expired,
+}
+
+impl fmt::Display for LinkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            LinkError::Missing => write!(f, "the link isn't signed"),
+            LinkError::Malformed => write!(f, "malformed letter in the link"),
+            LinkError::Invalid(ref e) => write!(f, "invalid letter in the link: {:?}", e),
+            LinkError::WrongUrl => write!(f, "the letter belongs to another link"),
+            LinkError::Expired => write!(f, "the link has expired"),
+        }
+    }
+}
+
+impl Error for LinkError {}
+
+/// Signs the content for the URL and returns the signed link. The URL may have a query, but
+/// mustn't have a `letter` parameter already.
+pub fn sign_link<T: FromFingerprint>(url: &str,
+                                     content: T,
+                                     expires: DateTime<UTC>,
+                                     signer: &Signer)
+                                     -> Result<String, SignError> {
+    let (url, fragment) = split_fragment(url);
+
+    let mut header = Header::new();
+    header.set_expires(expires);
+    header.set_meta(LINK_URL_KEY, url);
+
+    let letter = Letter::sign(content, header, signer)?;
+    let separator = if url.contains('?') { '&' } else { '?' };
+
+    Ok(format!("{}{}{}={}{}",
+               url,
+               separator,
+               LINK_PARAM,
+               letter.to_bytes().to_base64(URL_SAFE),
+               fragment))
+}
+
+/// Checks a signed link and returns its letter.
+pub fn verify_link<T, V>(link: &str, cv: &V) -> Result<Letter<T>, LinkError>
+    where T: FromFingerprint,
+          V: Validator
+{
+    verify_link_with_clock(link, cv, &SystemClock)
+}
+
+/// Like `verify_link`, but takes the current time from the clock.
+pub fn verify_link_with_clock<T, V, C>(link: &str, cv: &V, clock: &C) -> Result<Letter<T>, LinkError>
+    where T: FromFingerprint,
+          V: Validator,
+          C: Clock
+{
+    let (url, value) = split_letter(split_fragment(link).0).ok_or(LinkError::Missing)?;
+    let bytes = value.from_base64().map_err(|_| LinkError::Malformed)?;
+    let letter: Letter<T> = Letter::from_bytes(&bytes).map_err(|_| LinkError::Malformed)?;
+
+    cv.is_valid(&letter).map_err(LinkError::Invalid)?;
+
+    if letter.header().get_meta(LINK_URL_KEY) != Some(url.as_str()) {
+        return Err(LinkError::WrongUrl);
+    }
+
+    match letter.header().expires() {
+        Ok(Some(expires)) if expires > clock.now() => Ok(letter),
+        Err(_) => Err(LinkError::Malformed),
+        _ => Err(LinkError::Expired),
+    }
+}
+
+/// Splits the URL into the part before the fragment and the fragment with its `#`.
+fn split_fragment(url: &str) -> (&str, &str) {
+    match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    }
+}
+
+/// Removes the letter parameter from the URL and returns the rest of the URL and the value.
+fn split_letter(url: &str) -> Option<(String, &str)> {
+    let (path, query) = match url.find('?') {
+        Some(i) => (&url[..i], &url[i + 1..]),
+        None => return None,
+    };
+
+    let prefix = format!("{}=", LINK_PARAM);
+    let mut value = None;
+    let mut rest = Vec::new();
+
+    for pair in query.split('&') {
+        match pair.strip_prefix(prefix.as_str()) {
+            Some(v) if value.is_none() => value = Some(v),
+            _ => rest.push(pair),
+        }
+    }
+
+    let url = if rest.is_empty() {
+        path.to_string()
+    } else {
+        format!("{}?{}", path, rest.join("&"))
+    };
+
+    value.map(|v| (url, v))
+}
+
+#[test]
+fn test_link() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use canonical::Fingerprintable;
+    use clock::ManualClock;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let link = sign_link("https://example.com/verify?lang=en",
+                         Fingerprintable("alice@example.com".to_string()),
+                         UTC::now() + Duration::hours(1),
+                         &Signer::PrivateKey(&msk))
+                   .unwrap();
+    assert_eq!(true, link.starts_with("https://example.com/verify?lang=en&letter="));
+
+    let letter: Letter<Fingerprintable<String>> = verify_link(&link, &cv).unwrap();
+    assert_eq!("alice@example.com", letter.as_str());
+
+    let moved = link.replace("/verify?", "/delete?");
+    assert_eq!(Err(LinkError::WrongUrl),
+               verify_link::<Fingerprintable<String>, _>(&moved, &cv).map(|_| ()));
+
+    let later = ManualClock::new(UTC::now() + Duration::hours(2));
+    assert_eq!(Err(LinkError::Expired),
+               verify_link_with_clock::<Fingerprintable<String>, _, _>(&link, &cv, &later).map(|_| ()));
+    assert_eq!(Err(LinkError::Missing),
+               verify_link::<Fingerprintable<String>, _>("https://example.com/verify", &cv).map(|_| ()));
+}
@@ -0,0 +1,185 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letters that sign a whole collection of items at once.
+//!
+//! A `ManifestLetter` signs the Merkle tree root over all items (see the `merkle` module) and
+//! the number of items. A verifier only needs the small signed `Letter<Manifest>`, an item and
+//! its `ItemProof` to check that the item is part of the collection, without seeing the others.
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use canonical;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use merkle;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of manifest letters.
+pub const MANIFEST_CONTENT_TYPE: &str = "edcert-letter/manifest";
+
+/// The signed part of a manifest: the Merkle root and the number of items.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Manifest {
+    root: Vec<u8>,
+    size: u64,
+}
+
+impl Manifest {
+    /// Returns the Merkle root over the items.
+    pub fn root(&self) -> &[u8] {
+        &self.root
+    }
+
+    /// Returns the number of items.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+}
+
+impl Fingerprint for Manifest {
+    fn fingerprint(&self) -> Vec<u8> {
+        canonical::to_bytes(&(&self.root, self.size))
+    }
+}
+
+impl FromFingerprint for Manifest {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Manifest, DecodeError> {
+        let (root, size) = canonical::from_bytes(bytes)?;
+        Ok(Manifest {
+            root,
+            size,
+        })
+    }
+}
+
+/// The proof that an item is part of a manifest.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct ItemProof {
+    index: u64,
+    path: Vec<Vec<u8>>,
+}
+
+impl ItemProof {
+    /// Returns the position of the item in the collection.
+    pub fn index(&self) -> u64 {
+        self.index
+    }
+
+    /// Encodes the proof.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        canonical::to_bytes(&(self.index, &self.path))
+    }
+
+    /// Decodes a proof.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ItemProof, DecodeError> {
+        let (index, path) = canonical::from_bytes(bytes)?;
+        Ok(ItemProof {
+            index,
+            path,
+        })
+    }
+}
+
+/// A signed manifest together with the leaf hashes of all items, so it can prove every item.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ManifestLetter {
+    letter: Letter<Manifest>,
+    leaves: Vec<Vec<u8>>,
+}
+
+impl ManifestLetter {
+    /// Builds the Merkle tree over the items and signs its root. The header gets the manifest
+    /// content type. It fails, if the signer is a certificate without a private key.
+    pub fn sign<I: AsRef<[u8]>>(items: &[I], mut header: Header, signer: &Signer) -> Result<ManifestLetter, SignError> {
+        header.set_content_type(MANIFEST_CONTENT_TYPE);
+        let leaves: Vec<Vec<u8>> = items.iter().map(|item| merkle::leaf_hash(item.as_ref())).collect();
+        let manifest = Manifest {
+            root: merkle::root(&leaves),
+            size: leaves.len() as u64,
+        };
+
+        Ok(ManifestLetter {
+            letter: Letter::sign(manifest, header, signer)?,
+            leaves,
+        })
+    }
+
+    /// Returns the signed manifest, which is what verifiers need besides the proofs.
+    pub fn letter(&self) -> &Letter<Manifest> {
+        &self.letter
+    }
+
+    /// Returns the proof for the item at `index`, or None if the index is out of range.
+    pub fn prove(&self, index: usize) -> Option<ItemProof> {
+        merkle::prove(index, &self.leaves).map(|path| {
+            ItemProof {
+                index: index as u64,
+                path,
+            }
+        })
+    }
+}
+
+impl Letter<Manifest> {
+    /// This method validates the letter and checks that it has the manifest content type.
+    pub fn validate<V: Validator>(&self, cv: &V) -> Result<(), ValidationError> {
+        self.validate_as(cv, MANIFEST_CONTENT_TYPE)
+    }
+
+    /// This method checks that the item is part of the manifest. It doesn't check the signature
+    /// of the letter, so `validate` the letter first.
+    pub fn verify_item(&self, item: &[u8], proof: &ItemProof) -> bool {
+        merkle::verify(proof.index,
+                       self.size,
+                       &merkle::leaf_hash(item),
+                       &proof.path,
+                       &self.root)
+    }
+}
+
+#[test]
+fn test_manifest() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let items: Vec<String> = (0..10).map(|i| format!("file-{}", i)).collect();
+    let manifest = ManifestLetter::sign(&items, Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+
+    let letter: Letter<Manifest> = Letter::from_bytes(&manifest.letter().to_bytes()).unwrap();
+    assert_eq!(Ok(()), letter.validate(&cv));
+    assert_eq!(Err(ValidationError::Other), Letter::with_private_key(letter.get().clone(), &msk).validate(&cv));
+
+    let proof = ItemProof::from_bytes(&manifest.prove(7).unwrap().to_bytes()).unwrap();
+    assert_eq!(true, letter.verify_item(b"file-7", &proof));
+    assert_eq!(false, letter.verify_item(b"file-6", &proof));
+    assert_eq!(None, manifest.prove(10));
+}
@@ -0,0 +1,152 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Merkle trees as defined in RFC 6962 (Certificate Transparency).
+//!
+//! Leaves are hashed as `SHA-256(0x00 || data)` and inner nodes as
+//! `SHA-256(0x01 || left || right)`, so a leaf can never be confused with an inner node. The tree
+//! doesn't need to be complete: a tree of n leaves splits at the largest power of two below n.
+
+use sodiumoxide::crypto::hash::sha256;
+
+/// Hashes a leaf.
+pub fn leaf_hash(data: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(data.len() + 1);
+    buf.push(0);
+    buf.extend_from_slice(data);
+    sha256::hash(&buf).0.to_vec()
+}
+
+/// Hashes an inner node.
+pub fn node_hash(left: &[u8], right: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(left.len() + right.len() + 1);
+    buf.push(1);
+    buf.extend_from_slice(left);
+    buf.extend_from_slice(right);
+    sha256::hash(&buf).0.to_vec()
+}
+
+fn split(n: usize) -> usize {
+    let mut k = 1;
+    while k << 1 < n {
+        k <<= 1;
+    }
+    k
+}
+
+/// Computes the root hash of a tree over the given leaf hashes. The root of an empty tree is the
+/// hash of the empty string.
+pub fn root(leaves: &[Vec<u8>]) -> Vec<u8> {
+    match leaves.len() {
+        0 => sha256::hash(&[]).0.to_vec(),
+        1 => leaves[0].clone(),
+        n => {
+            let k = split(n);
+            node_hash(&root(&leaves[..k]), &root(&leaves[k..]))
+        }
+    }
+}
+
+/// Computes the audit path for the leaf at `index`. Returns None, if the index is out of range.
+pub fn prove(index: usize, leaves: &[Vec<u8>]) -> Option<Vec<Vec<u8>>> {
+    if index >= leaves.len() {
+        return None;
+    }
+
+    let mut path = Vec::new();
+    audit_path(index, leaves, &mut path);
+    Some(path)
+}
+
+fn audit_path(index: usize, leaves: &[Vec<u8>], path: &mut Vec<Vec<u8>>) {
+    let n = leaves.len();
+    if n <= 1 {
+        return;
+    }
+
+    let k = split(n);
+    if index < k {
+        audit_path(index, &leaves[..k], path);
+        path.push(root(&leaves[k..]));
+    } else {
+        audit_path(index - k, &leaves[k..], path);
+        path.push(root(&leaves[..k]));
+    }
+}
+
+/// Computes the root hash from a leaf hash and its audit path. Returns None, if the path doesn't
+/// fit the index and tree size.
+pub fn root_from_path(index: u64, tree_size: u64, leaf: &[u8], path: &[Vec<u8>]) -> Option<Vec<u8>> {
+    if index >= tree_size {
+        return None;
+    }
+
+    // This is the verification algorithm of RFC 9162, section 2.1.3.2.
+    let mut fnode = index;
+    let mut snode = tree_size - 1;
+    let mut hash = leaf.to_vec();
+
+    for sibling in path {
+        if snode == 0 {
+            return None;
+        }
+
+        if fnode & 1 == 1 || fnode == snode {
+            hash = node_hash(sibling, &hash);
+            while fnode & 1 == 0 && fnode != 0 {
+                fnode >>= 1;
+                snode >>= 1;
+            }
+        } else {
+            hash = node_hash(&hash, sibling);
+        }
+
+        fnode >>= 1;
+        snode >>= 1;
+    }
+
+    if snode == 0 {
+        Some(hash)
+    } else {
+        None
+    }
+}
+
+/// Checks that the leaf at `index` is part of the tree with the given root.
+pub fn verify(index: u64, tree_size: u64, leaf: &[u8], path: &[Vec<u8>], root: &[u8]) -> bool {
+    root_from_path(index, tree_size, leaf, path).is_some_and(|r| r == root)
+}
+
+#[test]
+fn test_every_leaf_verifies() {
+    for n in 1..12 {
+        let leaves: Vec<Vec<u8>> = (0..n).map(|i| leaf_hash(&[i as u8])).collect();
+        let r = root(&leaves);
+
+        for i in 0..n {
+            let path = prove(i, &leaves).unwrap();
+            assert_eq!(true, verify(i as u64, n as u64, &leaves[i], &path, &r));
+            assert_eq!(false, verify(n as u64, n as u64, &leaves[i], &path, &r));
+            assert_eq!(false, verify(i as u64, n as u64, &leaf_hash(b"x"), &path, &r));
+        }
+    }
+}
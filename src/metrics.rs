@@ -0,0 +1,181 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Metrics about letter validation.
+//!
+//! `MeteredValidator` wraps a validator and reports every call of `is_valid` to a `MetricsSink`:
+//! whether it succeeded, why it failed and how long it took. Parent certificates are checked by
+//! the wrapped validator, so each validated letter is reported once. `CountingSink` keeps simple
+//! counters in memory; other sinks can forward to a metrics library.
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::Duration;
+use std::time::Instant;
+
+use edcert::revoker::Revokable;
+use edcert::revoker::RevokeError;
+use edcert::validator::Validatable;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+/// Receives the outcome of validations.
+pub trait MetricsSink {
+    /// Called after every validation with its result and how long it took.
+    fn record(&self, result: &Result<(), ValidationError>, latency: Duration);
+}
+
+impl<M: MetricsSink + ?Sized> MetricsSink for &M {
+    fn record(&self, result: &Result<(), ValidationError>, latency: Duration) {
+        (**self).record(result, latency)
+    }
+}
+
+/// Returns a short label for the reason of a failed validation, for use in metric names.
+pub fn reason(error: &ValidationError) -> &'static str {
+    match *error {
+        ValidationError::SignatureInvalid => "signature_invalid",
+        ValidationError::ParentInvalid => "parent_invalid",
+        ValidationError::Expired => "expired",
+        ValidationError::Revoked => "revoked",
+        _ => "other",
+    }
+}
+
+/// The counters of a `CountingSink`.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct Counts {
+    /// The number of validations.
+    pub verifications: u64,
+    /// The number of failed validations, by reason.
+    pub failures: BTreeMap<&'static str, u64>,
+    /// The time all validations took together.
+    pub total_latency: Duration,
+    /// The time the slowest validation took.
+    pub max_latency: Duration,
+}
+
+/// A sink that counts in memory.
+#[derive(Debug, Default)]
+pub struct CountingSink {
+    counts: Mutex<Counts>,
+}
+
+impl CountingSink {
+    /// Creates a sink with all counters at zero.
+    pub fn new() -> CountingSink {
+        CountingSink::default()
+    }
+
+    /// Returns the current counters.
+    pub fn snapshot(&self) -> Counts {
+        self.counts.lock().unwrap().clone()
+    }
+}
+
+impl MetricsSink for CountingSink {
+    fn record(&self, result: &Result<(), ValidationError>, latency: Duration) {
+        let mut counts = self.counts.lock().unwrap();
+        counts.verifications += 1;
+        counts.total_latency += latency;
+
+        if latency > counts.max_latency {
+            counts.max_latency = latency;
+        }
+
+        if let Err(ref e) = *result {
+            *counts.failures.entry(reason(e)).or_insert(0) += 1;
+        }
+    }
+}
+
+/// A validator that reports every validation to a metrics sink.
+pub struct MeteredValidator<V: Validator, M: MetricsSink> {
+    inner: V,
+    sink: M,
+}
+
+impl<V: Validator, M: MetricsSink> MeteredValidator<V, M> {
+    /// Wraps the validator.
+    pub fn new(inner: V, sink: M) -> MeteredValidator<V, M> {
+        MeteredValidator {
+            inner,
+            sink,
+        }
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Returns the sink.
+    pub fn sink(&self) -> &M {
+        &self.sink
+    }
+}
+
+impl<V: Validator, M: MetricsSink> Validator for MeteredValidator<V, M> {
+    fn is_signature_valid(&self, data: &[u8], signature: &[u8]) -> bool {
+        self.inner.is_signature_valid(data, signature)
+    }
+
+    fn is_revoked<T: Revokable>(&self, item: &T) -> Result<(), RevokeError> {
+        self.inner.is_revoked(item)
+    }
+
+    fn is_valid<T: Validatable + Revokable>(&self, item: &T) -> Result<(), ValidationError> {
+        let start = Instant::now();
+        let result = self.inner.is_valid(item);
+        self.sink.record(&result, start.elapsed());
+        result
+    }
+}
+
+#[test]
+fn test_metrics() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (_, other) = ed25519::generate_keypair();
+    let sink = CountingSink::new();
+    let cv = MeteredValidator::new(RootValidator::new(&mpk, NoRevoker), &sink);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    assert_eq!(true, cv.is_valid(&Letter::with_certificate("hello", &cert).unwrap()).is_ok());
+    assert_eq!(false, cv.is_valid(&Letter::with_private_key("hello", &other)).is_ok());
+
+    let counts = sink.snapshot();
+    assert_eq!(2, counts.verifications);
+    assert_eq!(Some(&1), counts.failures.get("signature_invalid"));
+    assert_eq!(1, counts.failures.len());
+}
@@ -0,0 +1,196 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Detached signatures in the minisign format, which `minisign` and OpenBSD's `signify`
+//! verify.
+//!
+//! A minisign signature file has four lines: an untrusted comment, the base64 of the algorithm,
+//! the key id and the Ed25519 signature, a trusted comment and the base64 of a global signature
+//! over the signature and the trusted comment. This crate writes the legacy `Ed` algorithm,
+//! which signs the data itself. Signatures with the prehashed `ED` algorithm, the default of
+//! newer minisign versions, can be verified with the `blake2b` feature.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::STANDARD;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::Validator;
+
+use letter::Letter;
+use signer::public_key_of;
+use signer::SignError;
+use signer::Signer;
+
+const UNTRUSTED_PREFIX: &str = "untrusted comment: ";
+const TRUSTED_PREFIX: &str = "trusted comment: ";
+
+/// This error is returned, if a minisign signature can't be verified.
+#[derive(Clone, PartialEq, Debug)]
+pub enum MinisignError {
+    /// The input isn't a minisign signature.
+    Malformed,
+    /// The signature uses an algorithm this crate doesn't know or wasn't compiled with.
+    UnsupportedAlgorithm,
+    /// The signature over the data is wrong.
+    SignatureInvalid,
+    /// The signature over the trusted comment is wrong.
+    CommentInvalid,
+}
+
+impl fmt::Display for MinisignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            MinisignError::Malformed => write!(f, "malformed minisign signature"),
+            MinisignError::UnsupportedAlgorithm => write!(f, "unsupported minisign algorithm"),
+            MinisignError::SignatureInvalid => write!(f, "invalid minisign signature"),
+            MinisignError::CommentInvalid => write!(f, "invalid minisign trusted comment signature"),
+        }
+    }
+}
+
+impl Error for MinisignError {}
+
+/// Returns the minisign key id of a public key: the first 8 bytes of its SHA-512.
+pub fn key_id(public_key: &[u8]) -> Vec<u8> {
+    sha512::hash(public_key).0[..8].to_vec()
+}
+
+/// Returns a minisign public key file for the given Ed25519 public key.
+pub fn public_key(public_key: &[u8]) -> String {
+    let mut bytes = b"Ed".to_vec();
+    bytes.extend_from_slice(&key_id(public_key));
+    bytes.extend_from_slice(public_key);
+
+    format!("{}minisign public key {}\n{}\n",
+            UNTRUSTED_PREFIX,
+            key_id(public_key).iter().rev().map(|b| format!("{:02X}", b)).collect::<String>(),
+            bytes.to_base64(STANDARD))
+}
+
+fn signer_public_key(signer: &Signer) -> Result<Vec<u8>, SignError> {
+    match *signer {
+        Signer::PrivateKey(private_key) => Ok(public_key_of(private_key)?.to_vec()),
+        Signer::Certificate(cert) => Ok(cert.public_key().clone()),
+    }
+}
+
+/// Signs the data and returns a minisign signature file. The trusted comment must not contain a
+/// line break. It fails, if the signer is a certificate without a private key or a private key of
+/// the wrong length.
+pub fn sign(data: &[u8], signer: &Signer, trusted_comment: &str) -> Result<String, SignError> {
+    if trusted_comment.contains('\n') {
+        return Err(SignError::InvalidContent);
+    }
+
+    let public_key = signer_public_key(signer)?;
+
+    let signature = signer.sign(data)?.hash().clone();
+
+    let mut global = signature.clone();
+    global.extend_from_slice(trusted_comment.as_bytes());
+    let global = signer.sign(&global)?.hash().clone();
+
+    let mut bytes = b"Ed".to_vec();
+    bytes.extend_from_slice(&key_id(&public_key));
+    bytes.extend_from_slice(&signature);
+
+    Ok(format!("{}signature from edcert-letter\n{}\n{}{}\n{}\n",
+               UNTRUSTED_PREFIX,
+               bytes.to_base64(STANDARD),
+               TRUSTED_PREFIX,
+               trusted_comment,
+               global.to_base64(STANDARD)))
+}
+
+/// Verifies a minisign signature of the data against the master key of the validator and returns
+/// the trusted comment.
+pub fn verify<V: Validator>(data: &[u8], signature: &str, cv: &V) -> Result<String, MinisignError> {
+    let lines: Vec<&str> = signature.lines().collect();
+    if lines.len() < 4 || !lines[0].starts_with(UNTRUSTED_PREFIX) ||
+       !lines[2].starts_with(TRUSTED_PREFIX) {
+        return Err(MinisignError::Malformed);
+    }
+
+    let bytes = lines[1].from_base64().map_err(|_| MinisignError::Malformed)?;
+    let global = lines[3].from_base64().map_err(|_| MinisignError::Malformed)?;
+    if bytes.len() != 74 {
+        return Err(MinisignError::Malformed);
+    }
+
+    let sig = &bytes[10..];
+    let signed = match &bytes[..2] {
+        b"Ed" => data.to_vec(),
+        #[cfg(feature = "blake2b")]
+        b"ED" => ::blake2b_simd::blake2b(data).as_bytes().to_vec(),
+        _ => return Err(MinisignError::UnsupportedAlgorithm),
+    };
+
+    if !cv.is_signature_valid(&signed, sig) {
+        return Err(MinisignError::SignatureInvalid);
+    }
+
+    let trusted_comment = &lines[2][TRUSTED_PREFIX.len()..];
+    let mut signed_comment = sig.to_vec();
+    signed_comment.extend_from_slice(trusted_comment.as_bytes());
+    if !cv.is_signature_valid(&signed_comment, &global) {
+        return Err(MinisignError::CommentInvalid);
+    }
+
+    Ok(trusted_comment.to_string())
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method signs the content fingerprint, which for byte contents is the artifact itself,
+    /// as a detached minisign signature. The trusted comment holds the signing time of the letter.
+    pub fn to_minisign(&self, signer: &Signer) -> Result<String, SignError> {
+        let comment = format!("timestamp:{}", self.signed_at().timestamp());
+        sign(&self.get().fingerprint(), signer, &comment)
+    }
+}
+
+#[test]
+fn test_minisign_roundtrip() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let letter = Letter::with_private_key("release-1.0.tar.gz contents", &msk);
+    let signature = letter.to_minisign(&Signer::PrivateKey(&msk)).unwrap();
+
+    let comment = verify(b"release-1.0.tar.gz contents", &signature, &cv).unwrap();
+    assert_eq!(format!("timestamp:{}", letter.signed_at().timestamp()), comment);
+    assert_eq!(Err(MinisignError::SignatureInvalid), verify(b"other", &signature, &cv));
+
+    let forged = signature.replace("trusted comment: timestamp", "trusted comment: forged");
+    assert_eq!(Err(MinisignError::CommentInvalid), verify(b"release-1.0.tar.gz contents", &forged, &cv));
+
+    assert_eq!(true, public_key(&mpk).lines().nth(1).unwrap().from_base64().unwrap().ends_with(&mpk));
+    assert_eq!(Err(SignError::InvalidKey), sign(b"data", &Signer::PrivateKey(&msk[..16]), "comment"));
+}
@@ -0,0 +1,202 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Letters as MessagePack.
+//!
+//! `Letter::to_msgpack` writes a letter as a MessagePack map with the same parts as the binary
+//! format, so clients that standardize on MessagePack can pass letters around and inspect them:
+//!
+//! ```text
+//! {
+//!     "version": 1,               // the letter format version
+//!     "header": <bin>,            // the encoded letter header
+//!     "content": <bin>,           // the content fingerprint, uncompressed
+//!     "signature": <bin>,         // the signature
+//!     "certificate": <bin> | nil, // the edcert JSON encoding of the signing certificate
+//! }
+//! ```
+//!
+//! Parsing is strict: every key must be present exactly once and unknown keys are rejected.
+
+use rmp::decode;
+use rmp::encode;
+
+use edcert::signature::Signature;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use format::LetterFormatVersion;
+use header::Header;
+use letter::Letter;
+
+/// The MessagePack marker of nil.
+const NIL: u8 = 0xc0;
+
+fn write_bin(out: &mut Vec<u8>, key: &str, value: &[u8]) {
+    encode::write_str(out, key).expect("writing to a Vec can't fail");
+    encode::write_bin(out, value).expect("writing to a Vec can't fail");
+}
+
+fn take<'a>(rd: &mut &'a [u8], len: u32) -> Result<&'a [u8], DecodeError> {
+    let len = len as usize;
+    if rd.len() < len {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+
+    let (value, rest) = rd.split_at(len);
+    *rd = rest;
+    Ok(value)
+}
+
+fn read_bin<'a>(rd: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = decode::read_bin_len(rd).map_err(|_| DecodeError::InvalidHeader)?;
+    take(rd, len)
+}
+
+fn read_str<'a>(rd: &mut &'a [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = decode::read_str_len(rd).map_err(|_| DecodeError::InvalidHeader)?;
+    take(rd, len)
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// Serializes the letter as a MessagePack map.
+    pub fn to_msgpack(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        encode::write_map_len(&mut out, 5).expect("writing to a Vec can't fail");
+
+        encode::write_str(&mut out, "version").expect("writing to a Vec can't fail");
+        encode::write_uint(&mut out, LetterFormatVersion::current().as_byte() as u64)
+            .expect("writing to a Vec can't fail");
+        write_bin(&mut out, "header", &self.header().to_bytes());
+        write_bin(&mut out, "content", &self.get().fingerprint());
+        write_bin(&mut out, "signature", self.signature().hash());
+
+        match self.signer_certificate() {
+            Some(cert) => write_bin(&mut out, "certificate", &format::encode_certificate(cert)),
+            None => {
+                encode::write_str(&mut out, "certificate").expect("writing to a Vec can't fail");
+                encode::write_nil(&mut out).expect("writing to a Vec can't fail");
+            }
+        }
+
+        out
+    }
+
+    /// Parses a letter from a MessagePack map. The letter isn't validated. The default
+    /// `DecodeLimits` apply.
+    pub fn from_msgpack(bytes: &[u8]) -> Result<Letter<T>, DecodeError> {
+        let limits = DecodeLimits::default();
+        let mut rd = bytes;
+
+        let mut version = None;
+        let mut header = None;
+        let mut content = None;
+        let mut signature = None;
+        let mut certificate = None;
+
+        let len = decode::read_map_len(&mut rd).map_err(|_| DecodeError::InvalidHeader)?;
+        for _ in 0..len {
+            let duplicate = match read_str(&mut rd)? {
+                b"version" => {
+                    let v: u8 = decode::read_int(&mut rd).map_err(|_| DecodeError::InvalidHeader)?;
+                    version.replace(LetterFormatVersion::from_byte(v)?).is_some()
+                }
+                b"header" => header.replace(Header::from_bytes(read_bin(&mut rd)?)?).is_some(),
+                b"content" => {
+                    let value = read_bin(&mut rd)?;
+                    if value.len() as u64 > limits.max_content_size {
+                        return Err(DecodeError::ContentTooLarge);
+                    }
+                    content.replace(T::from_fingerprint(value)?).is_some()
+                }
+                b"signature" => signature.replace(read_bin(&mut rd)?.to_vec()).is_some(),
+                b"certificate" => {
+                    let parent = if rd.first() == Some(&NIL) {
+                        rd = &rd[1..];
+                        None
+                    } else {
+                        Some(format::decode_parent(read_bin(&mut rd)?, &limits)?)
+                    };
+                    certificate.replace(parent).is_some()
+                }
+                _ => return Err(DecodeError::InvalidHeader),
+            };
+
+            if duplicate {
+                return Err(DecodeError::InvalidHeader);
+            }
+        }
+
+        if !rd.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        match (version, header, content, signature, certificate) {
+            (Some(_), Some(header), Some(content), Some(hash), Some(parent)) => {
+                let signature = match parent {
+                    Some(parent) => Signature::with_parent(Box::new(parent), hash),
+                    None => Signature::new(hash),
+                };
+                Ok(Letter::from_parts(content, header, signature))
+            }
+            _ => Err(DecodeError::UnexpectedEnd),
+        }
+    }
+}
+
+#[test]
+fn test_msgpack_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    for letter in &[Letter::with_certificate(Fingerprintable("hello".to_string()), &cert).unwrap(),
+                    Letter::with_private_key(Fingerprintable("hello".to_string()), &msk)] {
+        let bytes = letter.to_msgpack();
+        let parsed: Letter<Fingerprintable<String>> = Letter::from_msgpack(&bytes).unwrap();
+        assert_eq!("hello", parsed.as_str());
+        assert_eq!(true, cv.is_valid(&parsed).is_ok());
+
+        let mut trailing = bytes.clone();
+        trailing.push(0);
+        assert_eq!(Err(DecodeError::TrailingBytes), Letter::<Fingerprintable<String>>::from_msgpack(&trailing).map(|_| ()));
+    }
+
+    // A map without the certificate key.
+    let mut truncated = Vec::new();
+    encode::write_map_len(&mut truncated, 0).unwrap();
+    assert_eq!(true, Letter::<Fingerprintable<String>>::from_msgpack(&truncated).is_err());
+}
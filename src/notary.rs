@@ -0,0 +1,288 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Countersignatures of notaries.
+//!
+//! After the author signed a letter, it can be sent to notaries. Each notary signs the letter
+//! together with the author's signature and the time it saw the letter, and returns a
+//! `Countersignature`. A `NotarizedLetter` carries the letter and its countersignatures;
+//! `verify` requires a valid author signature and valid countersignatures of at least a given
+//! number of distinct notaries. The notary keys are kept in a `TrustStore`, and a key must have
+//! been valid at the time of its countersignature.
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use letter::Letter;
+use signer::public_key_of;
+use signer::SignError;
+use trust::TrustStore;
+
+/// The bytes every serialized notarized letter and every countersigned message starts with.
+pub const NOTARY_MAGIC: &[u8] = b"EDN";
+
+/// The signature of a notary over a letter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Countersignature {
+    public_key: Vec<u8>,
+    timestamp: DateTime<UTC>,
+    signature: Vec<u8>,
+}
+
+impl Countersignature {
+    /// Countersigns the letter with the private key of the notary at the current time. It fails,
+    /// if the private key has the wrong length.
+    pub fn sign<T: Fingerprint>(letter: &Letter<T>, private_key: &[u8]) -> Result<Countersignature, SignError> {
+        Countersignature::sign_with_clock(letter, private_key, &SystemClock)
+    }
+
+    /// Countersigns the letter at the time of the clock.
+    pub fn sign_with_clock<T: Fingerprint, C: Clock>(letter: &Letter<T>,
+                                                     private_key: &[u8],
+                                                     clock: &C)
+                                                     -> Result<Countersignature, SignError> {
+        let public_key = public_key_of(private_key)?;
+        let timestamp = clock.now();
+
+        Ok(Countersignature {
+            public_key: public_key.to_vec(),
+            timestamp,
+            signature: ed25519::sign(&countersigned_bytes(letter, &timestamp), private_key),
+        })
+    }
+
+    /// Returns the public key of the notary.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the time the notary saw the letter.
+    pub fn timestamp(&self) -> &DateTime<UTC> {
+        &self.timestamp
+    }
+
+    /// Returns true, if this countersignature was made over the letter.
+    pub fn verify<T: Fingerprint>(&self, letter: &Letter<T>) -> bool {
+        ed25519::verify(&countersigned_bytes(letter, &self.timestamp), &self.signature, &self.public_key)
+    }
+}
+
+/// Returns the bytes a notary signs: the signed bytes and the signature of the letter and the
+/// time.
+fn countersigned_bytes<T: Fingerprint>(letter: &Letter<T>, timestamp: &DateTime<UTC>) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.raw(NOTARY_MAGIC);
+    w.bytes(&letter.signed_bytes());
+    w.bytes(letter.signature().hash());
+    w.u64(timestamp.timestamp() as u64);
+    w.u32(timestamp.timestamp_subsec_nanos());
+    w.into_bytes()
+}
+
+/// A letter with the countersignatures of notaries.
+#[derive(Clone, PartialEq, Debug)]
+pub struct NotarizedLetter<T: Fingerprint> {
+    letter: Letter<T>,
+    countersignatures: Vec<Countersignature>,
+}
+
+impl<T: Fingerprint> NotarizedLetter<T> {
+    /// Wraps a letter that isn't countersigned yet.
+    pub fn new(letter: Letter<T>) -> NotarizedLetter<T> {
+        NotarizedLetter {
+            letter,
+            countersignatures: Vec::new(),
+        }
+    }
+
+    /// Adds a countersignature. An earlier countersignature of the same notary is replaced.
+    pub fn add(&mut self, countersignature: Countersignature) {
+        self.countersignatures.retain(|c| c.public_key != countersignature.public_key);
+        self.countersignatures.push(countersignature);
+    }
+
+    /// Returns the letter.
+    pub fn letter(&self) -> &Letter<T> {
+        &self.letter
+    }
+
+    /// Returns the countersignatures.
+    pub fn countersignatures(&self) -> &[Countersignature] {
+        &self.countersignatures
+    }
+
+    /// Returns the countersignatures that are valid and made by a notary of the store that was
+    /// valid at the time of the countersignature, at most one per notary. Countersignatures dated
+    /// before the letter was signed are ignored.
+    pub fn trusted_countersignatures(&self, notaries: &TrustStore) -> Vec<&Countersignature> {
+        let mut trusted: Vec<&Countersignature> = Vec::new();
+
+        for c in &self.countersignatures {
+            if trusted.iter().any(|t| t.public_key == c.public_key) {
+                continue;
+            }
+
+            let by_notary = notaries.anchors()
+                                    .iter()
+                                    .any(|a| a.public_key() == c.public_key.as_slice() && a.is_valid_at(&c.timestamp));
+
+            if by_notary && c.timestamp >= *self.letter.signed_at() && c.verify(&self.letter) {
+                trusted.push(c);
+            }
+        }
+
+        trusted
+    }
+
+    /// Validates the letter of the author with the validator and checks that at least `min`
+    /// notaries of the store countersigned it. Too few countersignatures yield
+    /// `ValidationError::SignatureInvalid`.
+    pub fn verify<V: Validator>(&self, cv: &V, notaries: &TrustStore, min: usize) -> Result<(), ValidationError> {
+        cv.is_valid(&self.letter)?;
+
+        if self.trusted_countersignatures(notaries).len() >= min {
+            Ok(())
+        } else {
+            Err(ValidationError::SignatureInvalid)
+        }
+    }
+}
+
+impl<T: FromFingerprint> NotarizedLetter<T> {
+    /// Serializes the letter and its countersignatures.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(NOTARY_MAGIC);
+        w.u8(1);
+        w.bytes(&self.letter.to_bytes());
+        w.u32(self.countersignatures.len() as u32);
+        for c in &self.countersignatures {
+            w.bytes(&c.public_key);
+            w.u64(c.timestamp.timestamp() as u64);
+            w.u32(c.timestamp.timestamp_subsec_nanos());
+            w.bytes(&c.signature);
+        }
+        w.into_bytes()
+    }
+
+    /// Parses a serialized notarized letter. The signatures are only checked by `verify`. Two
+    /// countersignatures of the same notary are rejected.
+    pub fn from_bytes(bytes: &[u8]) -> Result<NotarizedLetter<T>, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(NOTARY_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != NOTARY_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let mut notarized = NotarizedLetter::new(Letter::from_bytes(r.bytes()?)?);
+
+        for _ in 0..r.u32()? {
+            let public_key = r.bytes()?.to_vec();
+            let secs = r.u64()? as i64;
+            let nanos = r.u32()?;
+            let timestamp = UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidContent)?;
+            let signature = r.bytes()?.to_vec();
+
+            if notarized.countersignatures.iter().any(|c| c.public_key == public_key) {
+                return Err(DecodeError::InvalidContent);
+            }
+
+            notarized.countersignatures.push(Countersignature {
+                public_key,
+                timestamp,
+                signature,
+            });
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(notarized)
+    }
+}
+
+#[test]
+fn test_notary() {
+    use chrono::Duration;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use canonical::Fingerprintable;
+    use trust::TrustAnchor;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (n1_pk, n1_sk) = ed25519::generate_keypair();
+    let (n2_pk, n2_sk) = ed25519::generate_keypair();
+    let (_, outsider) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut notaries = TrustStore::new();
+    notaries.add(TrustAnchor::new("n1", &n1_pk));
+    notaries.add(TrustAnchor::new("n2", &n2_pk).with_validity(None, Some(UTC::now() - Duration::days(1))));
+
+    let letter = Letter::with_private_key(Fingerprintable("contract".to_string()), &msk);
+    let mut notarized = NotarizedLetter::new(letter.clone());
+    assert_eq!(Err(ValidationError::SignatureInvalid), notarized.verify(&cv, &notaries, 1));
+
+    // Notaries outside the store and notaries whose key had expired don't count.
+    notarized.add(Countersignature::sign(&letter, &outsider).unwrap());
+    notarized.add(Countersignature::sign(&letter, &n2_sk).unwrap());
+    assert_eq!(Err(ValidationError::SignatureInvalid), notarized.verify(&cv, &notaries, 1));
+
+    notarized.add(Countersignature::sign(&letter, &n1_sk).unwrap());
+    let notarized: NotarizedLetter<Fingerprintable<String>> = NotarizedLetter::from_bytes(&notarized.to_bytes()).unwrap();
+    assert_eq!(Ok(()), notarized.verify(&cv, &notaries, 1));
+    assert_eq!(Err(ValidationError::SignatureInvalid), notarized.verify(&cv, &notaries, 2));
+
+    // One notary counts once, even if its countersignature appears twice.
+    let mut doubled = notarized.clone();
+    doubled.countersignatures.push(doubled.countersignatures[2].clone());
+    assert_eq!(1, doubled.trusted_countersignatures(&notaries).len());
+    assert_eq!(Err(ValidationError::SignatureInvalid), doubled.verify(&cv, &notaries, 2));
+    assert_eq!(Err(DecodeError::InvalidContent),
+               NotarizedLetter::<Fingerprintable<String>>::from_bytes(&doubled.to_bytes()).map(|_| ()));
+
+    assert_eq!(Err(SignError::InvalidKey), Countersignature::sign(&letter, &n1_sk[..32]).map(|_| ()));
+
+    // A countersignature is bound to the letter.
+    let other = Letter::with_private_key(Fingerprintable("other".to_string()), &msk);
+    assert_eq!(false, Countersignature::sign(&letter, &n1_sk).unwrap().verify(&other));
+}
@@ -0,0 +1,317 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! PASETO `v4.public` tokens.
+//!
+//! A token is `v4.public.` followed by the base64url of the message and its Ed25519 signature
+//! and, optionally, a dot and the base64url of a footer. The signature covers the
+//! pre-authentication encoding (PAE) of the header, the message, the footer and an empty implicit
+//! assertion. The message is a JSON object with the claims `iat` (the signing time), `meta` (the
+//! letter metadata), `edh` (the encoded letter header) and `data` (the base64url of the content
+//! fingerprint). If a certificate signed, the footer is `{"edc":"<base64 of the certificate>"}`.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::URL_SAFE;
+use rustc_serialize::json::Json;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use header::Header;
+use letter;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+use strict;
+
+/// The header every `v4.public` token starts with.
+pub const HEADER: &str = "v4.public.";
+
+const SIGNATURE_BYTES: usize = 64;
+
+/// This error is returned, if a PASETO token can't be verified.
+#[derive(Clone, PartialEq, Debug)]
+pub enum PasetoError {
+    /// The input isn't a `v4.public` token or its claims are missing.
+    Malformed,
+    /// The letter header, the certificate or the content can't be decoded.
+    Decode(DecodeError),
+    /// The signature or the certificate isn't valid.
+    Invalid(ValidationError),
+}
+
+impl fmt::Display for PasetoError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PasetoError::Malformed => write!(f, "malformed PASETO token"),
+            PasetoError::Decode(ref e) => write!(f, "can't decode PASETO token: {}", e),
+            PasetoError::Invalid(ref e) => write!(f, "PASETO token is not valid: {:?}", e),
+        }
+    }
+}
+
+impl Error for PasetoError {}
+
+impl From<DecodeError> for PasetoError {
+    fn from(e: DecodeError) -> PasetoError {
+        PasetoError::Decode(e)
+    }
+}
+
+/// The pre-authentication encoding: the number of pieces and the length of every piece as
+/// little-endian u64 with the top bit cleared, each followed by the piece.
+fn pae(pieces: &[&[u8]]) -> Vec<u8> {
+    fn le64(n: usize, out: &mut Vec<u8>) {
+        let n = n as u64 & 0x7fff_ffff_ffff_ffff;
+        for i in 0..8 {
+            out.push((n >> (i * 8)) as u8);
+        }
+    }
+
+    let mut out = Vec::new();
+    le64(pieces.len(), &mut out);
+    for piece in pieces {
+        le64(piece.len(), &mut out);
+        out.extend_from_slice(piece);
+    }
+    out
+}
+
+fn from_base64(s: &str) -> Result<Vec<u8>, PasetoError> {
+    if s.contains(&['=', '+', '/'][..]) {
+        return Err(PasetoError::Malformed);
+    }
+
+    s.from_base64().map_err(|_| PasetoError::Malformed)
+}
+
+/// Signs the content and header as a `v4.public` token. It fails, if the signer is a
+/// certificate without a private key.
+pub fn sign<T: Fingerprint>(content: &T, header: &Header, signer: &Signer) -> Result<String, SignError> {
+    let meta = header.meta()
+                     .iter()
+                     .map(|(k, v)| (k.clone(), Json::String(v.clone())))
+                     .collect();
+
+    let mut claims = BTreeMap::new();
+    claims.insert("iat".to_string(), Json::String(header.signed_at().to_rfc3339()));
+    claims.insert("meta".to_string(), Json::Object(meta));
+    claims.insert("edh".to_string(), Json::String(header.to_bytes().to_base64(URL_SAFE)));
+    claims.insert("data".to_string(), Json::String(content.fingerprint().to_base64(URL_SAFE)));
+    let message = Json::Object(claims).to_string().into_bytes();
+
+    let footer = match *signer {
+        Signer::Certificate(cert) => {
+            let mut footer = BTreeMap::new();
+            footer.insert("edc".to_string(),
+                          Json::String(format::encode_public_certificate(cert).to_base64(URL_SAFE)));
+            Json::Object(footer).to_string().into_bytes()
+        }
+        Signer::PrivateKey(_) => Vec::new(),
+    };
+
+    let signature = signer.sign(&pae(&[HEADER.as_bytes(), &message, &footer, b""]))?;
+
+    let mut body = message;
+    body.extend_from_slice(signature.hash());
+
+    let mut token = format!("{}{}", HEADER, body.to_base64(URL_SAFE));
+    if !footer.is_empty() {
+        token.push('.');
+        token.push_str(&footer.to_base64(URL_SAFE));
+    }
+    Ok(token)
+}
+
+/// Verifies a `v4.public` token and returns its content and letter header. The certificate and
+/// the content are decoded within the default `DecodeLimits`, like letters.
+pub fn verify<T: FromFingerprint, V: Validator>(token: &str, cv: &V) -> Result<(T, Header), PasetoError> {
+    let limits = DecodeLimits::default();
+    if !token.starts_with(HEADER) {
+        return Err(PasetoError::Malformed);
+    }
+
+    let mut parts = token[HEADER.len()..].split('.');
+    let body = from_base64(parts.next().unwrap_or(""))?;
+    let footer = match parts.next() {
+        Some(footer) => from_base64(footer)?,
+        None => Vec::new(),
+    };
+    if parts.next().is_some() || body.len() < SIGNATURE_BYTES {
+        return Err(PasetoError::Malformed);
+    }
+
+    let (message, signature) = body.split_at(body.len() - SIGNATURE_BYTES);
+    let signed = pae(&[HEADER.as_bytes(), message, &footer, b""]);
+
+    if footer.is_empty() {
+        strict::check_signature_bytes(signature, None).map_err(PasetoError::Invalid)?;
+        if !cv.is_signature_valid(&signed, signature) {
+            return Err(PasetoError::Invalid(ValidationError::SignatureInvalid));
+        }
+    } else {
+        let footer = String::from_utf8(footer).map_err(|_| PasetoError::Malformed)?;
+        let footer = Json::from_str(&footer).map_err(|_| PasetoError::Malformed)?;
+        let cert = footer.find("edc").and_then(|c| c.as_string()).ok_or(PasetoError::Malformed)?;
+        let cert = format::decode_parent(&from_base64(cert)?, &limits)?;
+        letter::check_parent_chain(Some(&cert), limits.max_chain_depth).map_err(PasetoError::Invalid)?;
+
+        strict::check_signature_bytes(signature, Some(&cert)).map_err(PasetoError::Invalid)?;
+        cv.is_valid(&cert).map_err(|_| PasetoError::Invalid(ValidationError::ParentInvalid))?;
+        if !cert.verify(&signed, signature) {
+            return Err(PasetoError::Invalid(ValidationError::SignatureInvalid));
+        }
+    }
+
+    let message = ::std::str::from_utf8(message).map_err(|_| PasetoError::Malformed)?;
+    let claims = Json::from_str(message).map_err(|_| PasetoError::Malformed)?;
+    let claim = |name| claims.find(name).and_then(|c| c.as_string()).ok_or(PasetoError::Malformed);
+
+    let header = Header::from_bytes(&from_base64(claim("edh")?)?)?;
+    let data = from_base64(claim("data")?)?;
+    if data.len() as u64 > limits.max_content_size {
+        return Err(PasetoError::Decode(DecodeError::ContentTooLarge));
+    }
+    let content = T::from_fingerprint(&data)?;
+
+    Ok((content, header))
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method signs the content and header of the letter again as a PASETO `v4.public`
+    /// token. The signature of the letter itself can't be reused.
+    pub fn to_paseto(&self, signer: &Signer) -> Result<String, SignError> {
+        sign(self.get(), self.header(), signer)
+    }
+}
+
+#[test]
+fn test_pae() {
+    // The test vectors from the PASETO specification.
+    assert_eq!(vec![0; 8], pae(&[]));
+    assert_eq!(vec![1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], pae(&[b""]));
+    assert_eq!(b"\x01\0\0\0\0\0\0\0\x04\0\0\0\0\0\0\0test".to_vec(), pae(&[b"test"]));
+}
+
+#[test]
+fn test_paseto_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut header = Header::new();
+    header.set_meta("sub", "alice");
+    let letter = Letter::sign(Fingerprintable("token".to_string()), header, &Signer::PrivateKey(&msk)).unwrap();
+
+    for signer in &[Signer::PrivateKey(&msk), Signer::Certificate(&cert)] {
+        let token = letter.to_paseto(signer).unwrap();
+        assert_eq!(true, token.starts_with("v4.public."));
+
+        let (content, header): (Fingerprintable<String>, Header) = verify(&token, &cv).unwrap();
+        assert_eq!(letter.get(), &content);
+        assert_eq!(Some("alice"), header.get_meta("sub"));
+
+        let tampered = token.replacen("v4.public.e", "v4.public.f", 1);
+        assert_eq!(true, verify::<Fingerprintable<String>, _>(&tampered, &cv).is_err());
+    }
+}
+
+#[test]
+fn test_paseto_without_private_key() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let token = sign(&"hello", &Header::new(), &Signer::Certificate(&cert)).unwrap();
+    let footer = from_base64(token.rsplit('.').next().unwrap()).unwrap();
+    let footer = Json::from_str(&String::from_utf8(footer).unwrap()).unwrap();
+    let embedded = from_base64(footer.find("edc").unwrap().as_string().unwrap()).unwrap();
+    let embedded = format::decode_certificate(&embedded).unwrap();
+
+    assert_eq!(cert.public_key(), embedded.public_key());
+    assert_eq!(false, embedded.has_private_key());
+}
+
+#[test]
+fn test_paseto_chain_limits() {
+    use canonical::Fingerprintable;
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(1);
+
+    let mut root = Certificate::generate_random(Meta::new_empty(), expires);
+    root.sign_with_master(&msk);
+
+    // A key that signs its own parent is a cycle.
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&root).unwrap();
+    let mut again = root.clone();
+    again.sign_with_parent(&leaf).unwrap();
+    let token = sign(&Fingerprintable("hello".to_string()), &Header::new(), &Signer::Certificate(&again)).unwrap();
+    assert_eq!(Err(PasetoError::Invalid(ValidationError::ParentInvalid)),
+               verify::<Fingerprintable<String>, _>(&token, &cv).map(|_| ()));
+
+    let mut cert = root;
+    for _ in 0..letter::MAX_CHAIN_DEPTH {
+        let mut child = Certificate::generate_random(Meta::new_empty(), expires);
+        child.sign_with_parent(&cert).unwrap();
+        cert = child;
+    }
+    let token = sign(&Fingerprintable("hello".to_string()), &Header::new(), &Signer::Certificate(&cert)).unwrap();
+    assert_eq!(Err(PasetoError::Decode(DecodeError::ChainTooDeep)),
+               verify::<Fingerprintable<String>, _>(&token, &cv).map(|_| ()));
+}
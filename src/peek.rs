@@ -0,0 +1,131 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Looking at letters before validating them.
+//!
+//! Routers and triage queues often need to know what a letter is before they spend time on its
+//! signature chain: its content type, who claims to have signed it, how deep its chain is. An
+//! `UntrustedSummary` has exactly that, read straight from the letter. **Nothing in it is
+//! verified.** Anybody can write any content type, signer or time into a letter; only after
+//! validation do these values mean anything. Use the summary to decide where a letter goes or
+//! which validator to use, never to decide whether to accept it.
+
+use chrono::DateTime;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+
+use format;
+use format::DecodeError;
+use format::FromFingerprint;
+use letter::Letter;
+
+/// What a letter claims about itself, without any validation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct UntrustedSummary {
+    /// The length of the content fingerprint, in bytes.
+    pub content_length: usize,
+    /// The content type in the header, unverified.
+    pub content_type: Option<String>,
+    /// The id of the signer, see `Letter::signer_id`, unverified.
+    pub signer_id: String,
+    /// The number of certificates between the letter and the master key, unverified.
+    pub chain_depth: usize,
+    /// The time the letter claims to be signed at, unverified.
+    pub signed_at: DateTime<UTC>,
+    /// The expiry time in the header, unverified. It is an error, if the expiry is malformed.
+    pub expires: Result<Option<DateTime<UTC>>, DecodeError>,
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method returns what the letter claims about itself. It doesn't validate anything,
+    /// see the `peek` module.
+    pub fn peek(&self) -> UntrustedSummary {
+        UntrustedSummary {
+            content_length: Fingerprint::fingerprint(self).len(),
+            content_type: self.header().content_type().map(|t| t.to_string()),
+            signer_id: self.signer_id(),
+            chain_depth: self.signer_chain().len(),
+            signed_at: *self.signed_at(),
+            expires: self.header().expires(),
+        }
+    }
+}
+
+/// Reads the summary of a serialized letter, without building its content. Like
+/// `Letter::from_bytes`, this rejects malformed letters, but it doesn't validate anything.
+pub fn peek_bytes(bytes: &[u8]) -> Result<UntrustedSummary, DecodeError> {
+    let (header, content, signature) = format::decode(bytes)?;
+    Ok(Letter::from_parts(Raw(content), header, signature).peek())
+}
+
+/// The content bytes of a letter as they are.
+struct Raw(Vec<u8>);
+
+impl Fingerprint for Raw {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.0.clone()
+    }
+}
+
+impl FromFingerprint for Raw {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Raw, DecodeError> {
+        Ok(Raw(bytes.to_vec()))
+    }
+}
+
+#[test]
+fn test_peek() {
+    use chrono::Duration;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    use header::Header;
+    use signer::Signer;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let mut header = Header::new();
+    header.set_content_type("invoice");
+    let letter = Letter::sign("hello", header, &Signer::Certificate(&cert)).unwrap();
+
+    let summary = letter.peek();
+    assert_eq!(5, summary.content_length);
+    assert_eq!(Some("invoice".to_string()), summary.content_type);
+    assert_eq!(letter.signer_id(), summary.signer_id);
+    assert_eq!(1, summary.chain_depth);
+    assert_eq!(Ok(None), summary.expires);
+
+    // A letter signed by somebody else entirely is summarized just the same.
+    let (_, other) = ed25519::generate_keypair();
+    let forged = Letter::with_private_key("hello", &other);
+    assert_eq!(0, forged.peek().chain_depth);
+
+    let raw = Letter::from_parts(Raw(b"hello".to_vec()),
+                                 letter.header().clone(),
+                                 letter.shared_signature().clone());
+    assert_eq!(Ok(summary), peek_bytes(&raw.to_bytes()));
+}
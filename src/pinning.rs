@@ -0,0 +1,127 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Certificate pinning.
+//!
+//! A `PinnedValidator` validates letters with another validator and additionally requires the
+//! certificate that signed the letter to be one of a fixed set. Pins are SHA-512 digests of the
+//! public key of a certificate, so a certificate that is renewed with the same key still matches,
+//! while a certificate that was issued to somebody else by the same master key doesn't.
+
+use std::collections::BTreeSet;
+
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// Returns the pin of a certificate.
+pub fn certificate_pin(cert: &Certificate) -> Vec<u8> {
+    sha512::hash(cert.public_key()).0.to_vec()
+}
+
+/// A validator that only accepts letters signed by pinned certificates.
+pub struct PinnedValidator<V: Validator> {
+    inner: V,
+    pins: BTreeSet<Vec<u8>>,
+}
+
+impl<V: Validator> PinnedValidator<V> {
+    /// Wraps the validator. Without pins, no letter is accepted.
+    pub fn new(inner: V) -> PinnedValidator<V> {
+        PinnedValidator {
+            inner,
+            pins: BTreeSet::new(),
+        }
+    }
+
+    /// Adds a pin, as returned by `certificate_pin`.
+    pub fn pin(mut self, pin: &[u8]) -> PinnedValidator<V> {
+        self.pins.insert(pin.to_vec());
+        self
+    }
+
+    /// Adds the pin of the certificate.
+    pub fn pin_certificate(self, cert: &Certificate) -> PinnedValidator<V> {
+        self.pin(&certificate_pin(cert))
+    }
+
+    /// Returns the pins.
+    pub fn pins(&self) -> &BTreeSet<Vec<u8>> {
+        &self.pins
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Validates the letter with the wrapped validator and checks that its signing certificate is
+    /// pinned. Letters signed by the master key directly or by a certificate that isn't pinned
+    /// yield `ValidationError::Other`.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.inner.is_valid(letter)?;
+
+        match letter.signer_certificate() {
+            Some(cert) if self.pins.contains(&certificate_pin(cert)) => Ok(()),
+            _ => {
+                trace_event!(info, "signer isn't pinned");
+                Err(ValidationError::Other)
+            }
+        }
+    }
+}
+
+#[test]
+fn test_pinning() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let expires = UTC::now() + Duration::days(90);
+
+    let mut pinned = Certificate::generate_random(Meta::new_empty(), expires);
+    pinned.sign_with_master(&msk);
+    let mut other = Certificate::generate_random(Meta::new_empty(), expires);
+    other.sign_with_master(&msk);
+
+    let cv = PinnedValidator::new(RootValidator::new(&mpk, NoRevoker)).pin_certificate(&pinned);
+
+    assert_eq!(Ok(()), cv.validate(&Letter::with_certificate("hello", &pinned).unwrap()));
+    assert_eq!(Err(ValidationError::Other), cv.validate(&Letter::with_certificate("hello", &other).unwrap()));
+    assert_eq!(Err(ValidationError::Other), cv.validate(&Letter::with_private_key("hello", &msk)));
+
+    // The pin doesn't replace the chain validation.
+    let (_, evil) = ed25519::generate_keypair();
+    let mut forged = pinned.clone();
+    forged.sign_with_master(&evil);
+    assert_eq!(true, cv.validate(&Letter::with_certificate("hello", &forged).unwrap()).is_err());
+}
@@ -0,0 +1,166 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Declarative rules about who may sign a letter.
+//!
+//! A `Policy` looks at the chain of certificates between a letter and the master key. The
+//! depth of a letter is the number of certificates in that chain, so a letter signed with the
+//! master key directly has depth 0. Policies are combined with `all_of`, `any_of` and `!`:
+//!
+//! ```ignore
+//! // Must be signed by the master key or by a certificate with role=release.
+//! let policy = Policy::any_of(vec![Policy::max_depth(0), Policy::require_meta("role", "release")]);
+//! policy.validate(&cv, &letter)?;
+//! ```
+//!
+//! The `role` module has the common case, requiring meta values of the signing certificate.
+
+use std::ops::Not;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// A rule about the signer chain of a letter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Policy {
+    /// Every policy must hold.
+    AllOf(Vec<Policy>),
+    /// At least one policy must hold.
+    AnyOf(Vec<Policy>),
+    /// The policy must not hold.
+    Not(Box<Policy>),
+    /// The depth must be at least this.
+    MinDepth(usize),
+    /// The depth must be at most this.
+    MaxDepth(usize),
+    /// The certificate that signed the letter must have this meta value.
+    RequireMeta(String, String),
+}
+
+impl Policy {
+    /// Creates a policy that holds, if all given policies hold. An empty list always holds.
+    pub fn all_of(policies: Vec<Policy>) -> Policy {
+        Policy::AllOf(policies)
+    }
+
+    /// Creates a policy that holds, if any given policy holds. An empty list never holds.
+    pub fn any_of(policies: Vec<Policy>) -> Policy {
+        Policy::AnyOf(policies)
+    }
+
+    /// Creates a policy that holds, if the letter is signed at depth `n` or deeper, that is, by a
+    /// certificate and not directly by a key closer to the master key.
+    pub fn not_before_depth(n: usize) -> Policy {
+        Policy::MinDepth(n)
+    }
+
+    /// Creates a policy that holds, if the letter is signed at depth `n` or closer to the master
+    /// key. `max_depth(0)` requires the master key itself.
+    pub fn max_depth(n: usize) -> Policy {
+        Policy::MaxDepth(n)
+    }
+
+    /// Creates a policy that holds, if the letter is signed by a certificate whose meta data maps
+    /// `key` to `value`. Letters signed with the master key directly never match.
+    pub fn require_meta(key: &str, value: &str) -> Policy {
+        Policy::RequireMeta(key.to_string(), value.to_string())
+    }
+
+    /// Returns true, if the policy holds for the letter. This doesn't check any signatures.
+    pub fn check<T: Fingerprint>(&self, letter: &Letter<T>) -> bool {
+        self.check_chain(&letter.signer_chain())
+    }
+
+    /// Validates the letter and then checks the policy. A violated policy yields
+    /// `ValidationError::Other`.
+    pub fn validate<V: Validator, T: Fingerprint>(&self,
+                                                   cv: &V,
+                                                   letter: &Letter<T>)
+                                                   -> Result<(), ValidationError> {
+        cv.is_valid(letter)?;
+
+        if self.check(letter) {
+            Ok(())
+        } else {
+            Err(ValidationError::Other)
+        }
+    }
+
+    fn check_chain(&self, chain: &[&Certificate]) -> bool {
+        match *self {
+            Policy::AllOf(ref policies) => policies.iter().all(|p| p.check_chain(chain)),
+            Policy::AnyOf(ref policies) => policies.iter().any(|p| p.check_chain(chain)),
+            Policy::Not(ref policy) => !policy.check_chain(chain),
+            Policy::MinDepth(n) => chain.len() >= n,
+            Policy::MaxDepth(n) => chain.len() <= n,
+            Policy::RequireMeta(ref key, ref value) => {
+                chain.first().and_then(|cert| cert.meta().get(key)) == Some(value)
+            }
+        }
+    }
+}
+
+impl Not for Policy {
+    type Output = Policy;
+
+    fn not(self) -> Policy {
+        Policy::Not(Box::new(self))
+    }
+}
+
+#[test]
+fn test_master_or_release_role() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let policy = Policy::any_of(vec![Policy::max_depth(0), Policy::require_meta("role", "release")]);
+
+    let mut meta = Meta::new_empty();
+    meta.set("role", "release");
+    let mut release = Certificate::generate_random(meta, UTC::now() + Duration::days(1));
+    release.sign_with_master(&msk);
+
+    let mut other = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    other.sign_with_master(&msk);
+
+    let by_master = Letter::with_private_key("a", &msk);
+    let by_release = Letter::with_certificate("b", &release).unwrap();
+    let by_other = Letter::with_certificate("c", &other).unwrap();
+
+    assert_eq!(true, policy.validate(&cv, &by_master).is_ok());
+    assert_eq!(true, policy.validate(&cv, &by_release).is_ok());
+    assert_eq!(Err(ValidationError::Other), policy.validate(&cv, &by_other));
+
+    assert_eq!(true, Policy::not_before_depth(1).check(&by_other));
+    assert_eq!(false, !Policy::not_before_depth(1).check(&by_other));
+    assert_eq!(false, Policy::all_of(vec![Policy::max_depth(0), Policy::not_before_depth(1)]).check(&by_master));
+}
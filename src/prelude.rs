@@ -0,0 +1,66 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The types and traits needed to sign and verify letters, in one import.
+//!
+//! ```ignore
+//! use edcert_letter::prelude::*;
+//! ```
+//!
+//! This brings the letter types and the edcert traits, keys and validators into scope, so code
+//! that only signs or verifies letters doesn't need to depend on edcert itself.
+
+pub use edcert::certificate::Certificate;
+pub use edcert::ed25519;
+pub use edcert::fingerprint::Fingerprint;
+pub use edcert::meta::Meta;
+pub use edcert::revoker::NoRevoker;
+pub use edcert::revoker::Revokable;
+pub use edcert::revoker::Revoker;
+pub use edcert::root_validator::RootValidator;
+pub use edcert::validator::Validatable;
+pub use edcert::validator::ValidationError;
+pub use edcert::validator::Validator;
+
+#[cfg(feature = "derive")]
+pub use edcert_letter_derive::Fingerprint;
+#[cfg(feature = "derive")]
+pub use edcert_letter_derive::Claims;
+
+pub use canonical::Fingerprintable;
+pub use claims::Claims;
+pub use format::FromFingerprint;
+pub use header::Header;
+pub use letter::Letter;
+pub use signer::LetterSigner;
+pub use signer::SignError;
+pub use signer::Signer;
+
+#[test]
+fn test_prelude() {
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let letter = Letter::sign(Fingerprintable(42u32), Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(true, letter.self_validate(&cv).is_ok());
+}
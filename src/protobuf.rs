@@ -0,0 +1,138 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Letters as protobuf messages.
+//!
+//! `LetterEnvelope` is the `prost` message of `proto/letter.proto`, so gRPC services can carry
+//! letters as message fields. It holds the same parts as the binary format, so converting a
+//! letter to an envelope and back keeps the signature valid.
+
+use prost::Message;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use format::FromFingerprint;
+use format::LetterFormatVersion;
+use header::Header;
+use letter::Letter;
+
+/// The envelope of a letter, `edcert.letter.v1.LetterEnvelope`.
+#[derive(Clone, PartialEq, Message)]
+pub struct LetterEnvelope {
+    /// The letter format version the header was encoded with.
+    #[prost(uint32, tag = "1")]
+    pub version: u32,
+    /// The encoded letter header.
+    #[prost(bytes = "vec", tag = "2")]
+    pub header: Vec<u8>,
+    /// The content fingerprint, uncompressed.
+    #[prost(bytes = "vec", tag = "3")]
+    pub content: Vec<u8>,
+    /// The signature.
+    #[prost(bytes = "vec", tag = "4")]
+    pub signature: Vec<u8>,
+    /// The signing certificate in the edcert JSON encoding, empty for the master key.
+    #[prost(bytes = "vec", tag = "5")]
+    pub certificate: Vec<u8>,
+}
+
+impl LetterEnvelope {
+    /// Returns the envelope of the letter.
+    pub fn from_letter<T: Fingerprint>(letter: &Letter<T>) -> LetterEnvelope {
+        LetterEnvelope {
+            version: LetterFormatVersion::current().as_byte() as u32,
+            header: letter.header().to_bytes(),
+            content: letter.get().fingerprint(),
+            signature: letter.signature().hash().clone(),
+            certificate: letter.signer_certificate().map(format::encode_certificate).unwrap_or_default(),
+        }
+    }
+
+    /// Returns the letter in the envelope. The letter isn't validated. The default
+    /// `DecodeLimits` apply.
+    pub fn to_letter<T: FromFingerprint>(&self) -> Result<Letter<T>, DecodeError> {
+        let limits = DecodeLimits::default();
+
+        // Versions that don't fit into a byte are reported as 255, which is unsupported as well.
+        LetterFormatVersion::from_byte(if self.version < 0xff { self.version as u8 } else { 0xff })?;
+
+        if self.content.len() as u64 > limits.max_content_size {
+            return Err(DecodeError::ContentTooLarge);
+        }
+
+        let header = Header::from_bytes(&self.header)?;
+        let signature = if self.certificate.is_empty() {
+            Signature::new(self.signature.clone())
+        } else {
+            let parent = format::decode_parent(&self.certificate, &limits)?;
+            Signature::with_parent(Box::new(parent), self.signature.clone())
+        };
+
+        Ok(Letter::from_parts(T::from_fingerprint(&self.content)?, header, signature))
+    }
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// Serializes the letter as a protobuf `LetterEnvelope`.
+    pub fn to_protobuf(&self) -> Vec<u8> {
+        LetterEnvelope::from_letter(self).encode_to_vec()
+    }
+
+    /// Parses a letter from a protobuf `LetterEnvelope`. The letter isn't validated.
+    pub fn from_protobuf(bytes: &[u8]) -> Result<Letter<T>, DecodeError> {
+        let envelope = LetterEnvelope::decode(bytes).map_err(|_| DecodeError::InvalidHeader)?;
+        envelope.to_letter()
+    }
+}
+
+#[test]
+fn test_protobuf_roundtrip() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let letter = Letter::with_certificate(Fingerprintable("hello".to_string()), &cert).unwrap();
+    let parsed: Letter<Fingerprintable<String>> = Letter::from_protobuf(&letter.to_protobuf()).unwrap();
+    assert_eq!("hello", parsed.as_str());
+    assert_eq!(true, cv.is_valid(&parsed).is_ok());
+
+    let mut envelope = LetterEnvelope::from_letter(&letter);
+    envelope.content = Fingerprintable("bye".to_string()).fingerprint();
+    let tampered: Letter<Fingerprintable<String>> = envelope.to_letter().unwrap();
+    assert_eq!(false, cv.is_valid(&tampered).is_ok());
+}
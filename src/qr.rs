@@ -0,0 +1,177 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! A compact text encoding of letters for QR codes.
+//!
+//! Tickets and badges are scanned by devices that are often offline, and a QR code holds only a
+//! few hundred bytes comfortably. This profile writes the letter detached, so only the 32 byte
+//! public key of the signer goes into the code, and the scanner looks up its certificate in a
+//! resolver it carries, like a `TrustBundle`. A small letter then stays under 400 bytes. Letters
+//! signed with the master key carry no chain at all. With the `deflate` feature, signing with
+//! `Compression::Deflate` in the header shrinks larger contents further.
+//!
+//! The bytes are written in base45 (RFC 9285), which uses only the characters of the
+//! alphanumeric QR mode and thus makes smaller codes than base64, or in URL-safe base64 for
+//! codes that contain a link. A short prefix tells the two apart.
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::URL_SAFE;
+
+use format::DecodeError;
+use format::FromFingerprint;
+use letter::Letter;
+use resolver::CertificateResolver;
+
+/// The prefix of base45 encoded letters.
+pub const BASE45_PREFIX: &str = "EDL1:";
+
+/// The prefix of base64 encoded letters.
+pub const BASE64_PREFIX: &str = "edl1.";
+
+const BASE45_ALPHABET: &[u8] = b"0123456789ABCDEFGHIJKLMNOPQRSTUVWXYZ $%*+-./:";
+
+/// The text encodings of the QR profile.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QrEncoding {
+    /// Base45, for the alphanumeric mode of QR codes.
+    Base45,
+    /// URL-safe base64 without padding.
+    Base64,
+}
+
+/// Encodes the bytes in base45.
+pub fn base45_encode(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() / 2 * 3 + 2);
+
+    for chunk in bytes.chunks(2) {
+        let mut n = chunk.iter().fold(0usize, |n, &b| n * 256 + b as usize);
+        let digits = if chunk.len() == 2 { 3 } else { 2 };
+
+        for _ in 0..digits {
+            out.push(BASE45_ALPHABET[n % 45] as char);
+            n /= 45;
+        }
+    }
+
+    out
+}
+
+/// Decodes base45 text.
+pub fn base45_decode(text: &str) -> Result<Vec<u8>, DecodeError> {
+    let digits = text.bytes()
+                     .map(|c| BASE45_ALPHABET.iter().position(|&a| a == c))
+                     .collect::<Option<Vec<usize>>>()
+                     .ok_or(DecodeError::InvalidContent)?;
+    let mut out = Vec::with_capacity(digits.len() / 3 * 2 + 1);
+
+    for chunk in digits.chunks(3) {
+        let n = chunk.iter().rev().fold(0, |n, &d| n * 45 + d);
+
+        match chunk.len() {
+            3 if n <= 0xffff => {
+                out.push((n >> 8) as u8);
+                out.push(n as u8);
+            }
+            2 if n <= 0xff => out.push(n as u8),
+            _ => return Err(DecodeError::InvalidContent),
+        }
+    }
+
+    Ok(out)
+}
+
+impl<T: FromFingerprint> Letter<T> {
+    /// This method writes the letter detached, in the given text encoding, for a QR code.
+    pub fn to_qr(&self, encoding: QrEncoding) -> String {
+        let bytes = self.to_bytes_detached();
+
+        match encoding {
+            QrEncoding::Base45 => format!("{}{}", BASE45_PREFIX, base45_encode(&bytes)),
+            QrEncoding::Base64 => format!("{}{}", BASE64_PREFIX, bytes.to_base64(URL_SAFE)),
+        }
+    }
+
+    /// This method reads a letter written by `to_qr`, in either encoding. The certificate of the
+    /// signer is looked up with the resolver.
+    pub fn from_qr<R>(text: &str, resolver: &R) -> Result<Letter<T>, DecodeError>
+        where R: CertificateResolver + ?Sized
+    {
+        let text = text.trim_end_matches(&['\r', '\n'][..]);
+
+        let bytes = if let Some(text) = text.strip_prefix(BASE45_PREFIX) {
+            base45_decode(text)?
+        } else if let Some(text) = text.strip_prefix(BASE64_PREFIX) {
+            text.from_base64().map_err(|_| DecodeError::InvalidContent)?
+        } else {
+            return Err(DecodeError::InvalidMagic);
+        };
+
+        Letter::from_bytes_attached(&bytes, resolver)
+    }
+}
+
+#[test]
+fn test_base45() {
+    assert_eq!("BB8", base45_encode(b"AB"));
+    assert_eq!("%69 VD92EX0", base45_encode(b"Hello!!"));
+    assert_eq!(b"ietf!".to_vec(), base45_decode("QED8WEX0").unwrap());
+    assert_eq!(Err(DecodeError::InvalidContent), base45_decode("GGW"));
+    assert_eq!(Err(DecodeError::InvalidContent), base45_decode("ab"));
+}
+
+#[test]
+fn test_qr() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    use canonical::Fingerprintable;
+    use resolver::CertificateStore;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let letter = Letter::with_certificate(Fingerprintable("seat 14C".to_string()), &cert).unwrap();
+    let text = letter.to_qr(QrEncoding::Base45);
+    assert_eq!(true, letter.to_bytes_detached().len() < 400);
+
+    let store = CertificateStore::new();
+    store.add(&cert);
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let read: Letter<Fingerprintable<String>> = Letter::from_qr(&text, &store).unwrap();
+    assert_eq!(true, cv.is_valid(&read).is_ok());
+    assert_eq!("seat 14C", read.as_str());
+
+    let read: Letter<Fingerprintable<String>> = Letter::from_qr(&letter.to_qr(QrEncoding::Base64), &store).unwrap();
+    assert_eq!(true, cv.is_valid(&read).is_ok());
+    assert_eq!(Err(DecodeError::DetachedChain),
+               Letter::<Fingerprintable<String>>::from_qr(&text, &CertificateStore::new()).map(|_| ()));
+}
@@ -0,0 +1,282 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Letters with sections that can be redacted after signing.
+//!
+//! `RedactableContent` is a list of sections. Fixed sections are signed as they are. For a
+//! redactable section, only the SHA-512 digest of a random salt and its data goes into the
+//! fingerprint. Redacting the section replaces it by that digest, so the fingerprint and thus the
+//! signature stay the same, and the letter still validates. The salt keeps short redacted
+//! sections from being guessed. Whether a section is redactable is signed as well, so fixed
+//! sections can't be removed.
+//!
+//! The fingerprint doesn't contain the redactable data, so these letters have their own wire
+//! encoding, `Letter::to_redactable_bytes`, which writes the sections with their salts.
+
+use std::error::Error;
+use std::fmt;
+
+use sodiumoxide::randombytes::randombytes;
+
+use edcert::fingerprint::Fingerprint;
+
+use codec::Reader;
+use codec::Writer;
+use digest::HashAlgorithm;
+use format;
+use format::DecodeError;
+use format::DecodeLimits;
+use letter::Letter;
+
+/// The number of random bytes in the salt of a redactable section.
+pub const SALT_SIZE: usize = 16;
+
+/// The reasons a section can't be redacted.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RedactionError {
+    /// There is no section with this index.
+    NoSuchSection,
+    /// The section is fixed.
+    Fixed,
+}
+
+impl fmt::Display for RedactionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            RedactionError::NoSuchSection => write!(f, "no such section"),
+            RedactionError::Fixed => write!(f, "the section can't be redacted"),
+        }
+    }
+}
+
+impl Error for RedactionError {}
+
+/// A section of a redactable letter.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub enum Section {
+    /// A section that can't be redacted.
+    Fixed(Vec<u8>),
+    /// A section that can be redacted.
+    Redactable {
+        /// The random salt.
+        salt: Vec<u8>,
+        /// The data of the section.
+        data: Vec<u8>,
+    },
+    /// A redacted section, of which only the digest is left.
+    Redacted(Vec<u8>),
+}
+
+impl Section {
+    /// Returns the data, unless the section was redacted.
+    pub fn data(&self) -> Option<&[u8]> {
+        match *self {
+            Section::Fixed(ref data) |
+            Section::Redactable { ref data, .. } => Some(data),
+            Section::Redacted(_) => None,
+        }
+    }
+
+    /// Returns true, if the section was redacted.
+    pub fn is_redacted(&self) -> bool {
+        matches!(*self, Section::Redacted(_))
+    }
+
+    fn digest(salt: &[u8], data: &[u8]) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(salt);
+        w.bytes(data);
+        HashAlgorithm::Sha512.digest(&w.into_bytes())
+    }
+}
+
+/// Content made of fixed and redactable sections.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct RedactableContent {
+    sections: Vec<Section>,
+}
+
+impl RedactableContent {
+    /// Creates content without sections.
+    pub fn new() -> RedactableContent {
+        RedactableContent::default()
+    }
+
+    /// Appends a section that can't be redacted.
+    pub fn push_fixed(&mut self, data: &[u8]) {
+        self.sections.push(Section::Fixed(data.to_vec()));
+    }
+
+    /// Appends a section that can be redacted.
+    pub fn push_redactable(&mut self, data: &[u8]) {
+        self.sections.push(Section::Redactable {
+            salt: randombytes(SALT_SIZE),
+            data: data.to_vec(),
+        });
+    }
+
+    /// Returns the sections.
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// Redacts the section. It fails, if there is no such section or it is fixed.
+    pub fn redact(&mut self, index: usize) -> Result<(), RedactionError> {
+        let digest = match self.sections.get(index) {
+            Some(Section::Redactable { salt, data }) => Section::digest(salt, data),
+            Some(&Section::Redacted(_)) => return Ok(()),
+            Some(&Section::Fixed(_)) => return Err(RedactionError::Fixed),
+            None => return Err(RedactionError::NoSuchSection),
+        };
+
+        self.sections[index] = Section::Redacted(digest);
+        Ok(())
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u32(self.sections.len() as u32);
+
+        for section in &self.sections {
+            match *section {
+                Section::Fixed(ref data) => {
+                    w.u8(0);
+                    w.bytes(data);
+                }
+                Section::Redactable { ref salt, ref data } => {
+                    w.u8(1);
+                    w.bytes(salt);
+                    w.bytes(data);
+                }
+                Section::Redacted(ref digest) => {
+                    w.u8(2);
+                    w.bytes(digest);
+                }
+            }
+        }
+
+        w.into_bytes()
+    }
+
+    fn decode(bytes: &[u8]) -> Result<RedactableContent, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let mut sections = Vec::new();
+
+        for _ in 0..r.u32()? {
+            sections.push(match r.u8()? {
+                0 => Section::Fixed(r.bytes()?.to_vec()),
+                1 => {
+                    Section::Redactable {
+                        salt: r.bytes()?.to_vec(),
+                        data: r.bytes()?.to_vec(),
+                    }
+                }
+                2 => Section::Redacted(r.bytes()?.to_vec()),
+                _ => return Err(DecodeError::InvalidContent),
+            });
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(RedactableContent { sections })
+    }
+}
+
+impl Fingerprint for RedactableContent {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u32(self.sections.len() as u32);
+
+        for section in &self.sections {
+            match *section {
+                Section::Fixed(ref data) => {
+                    w.u8(0);
+                    w.bytes(data);
+                }
+                Section::Redactable { ref salt, ref data } => {
+                    w.u8(1);
+                    w.bytes(&Section::digest(salt, data));
+                }
+                Section::Redacted(ref digest) => {
+                    w.u8(1);
+                    w.bytes(digest);
+                }
+            }
+        }
+
+        w.into_bytes()
+    }
+}
+
+impl Letter<RedactableContent> {
+    /// This method returns a copy of the letter with the section redacted. The signature stays
+    /// valid. It fails, if there is no such section or it is fixed.
+    pub fn redact(&self, index: usize) -> Result<Letter<RedactableContent>, RedactionError> {
+        let mut content = self.get().clone();
+        content.redact(index)?;
+        Ok(Letter::from_parts(content, self.header().clone(), self.shared_signature().clone()))
+    }
+
+    /// This method serializes the letter with the data and salts of its sections.
+    pub fn to_redactable_bytes(&self) -> Vec<u8> {
+        format::encode(self.header(), &self.get().encode(), self.signature())
+    }
+
+    /// This method reads a letter written by `to_redactable_bytes`.
+    pub fn from_redactable_bytes(bytes: &[u8]) -> Result<Letter<RedactableContent>, DecodeError> {
+        let (header, content, signature) = format::decode_with_limits(bytes, &DecodeLimits::default())?;
+        Ok(Letter::from_parts(RedactableContent::decode(&content)?, header, signature))
+    }
+}
+
+#[test]
+fn test_redaction() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut content = RedactableContent::new();
+    content.push_fixed(b"Minutes of the board meeting");
+    content.push_redactable(b"Salary of the CEO: ...");
+    content.push_redactable(b"Next year's budget: ...");
+
+    let letter = Letter::with_private_key(content, &msk);
+    let published = letter.redact(1).unwrap();
+    let published = Letter::from_redactable_bytes(&published.to_redactable_bytes()).unwrap();
+
+    assert_eq!(true, cv.is_valid(&published).is_ok());
+    assert_eq!(true, published.get().sections()[1].is_redacted());
+    assert_eq!(Some(&b"Next year's budget: ..."[..]), published.get().sections()[2].data());
+    assert_eq!(Err(RedactionError::Fixed), letter.redact(0).map(|_| ()));
+
+    let mut forged = published.get().clone();
+    forged.sections[0] = Section::Fixed(b"Minutes of another meeting".to_vec());
+    let forged = Letter::from_parts(forged, published.header().clone(), published.signature().clone());
+    assert_eq!(false, cv.is_valid(&forged).is_ok());
+}
@@ -0,0 +1,186 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Structured results of letter validation, for audit logs and error messages.
+
+use std::fmt;
+use std::time::Duration;
+use std::time::Instant;
+
+use chrono::DateTime;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::revoker::RevokeError;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+use letter::Letter;
+
+/// What was found out about one certificate of the signer chain.
+#[derive(Clone, PartialEq, Debug)]
+pub struct CertificateReport {
+    /// The public key of the certificate.
+    pub public_key: Vec<u8>,
+    /// The expiration date, as written in the certificate.
+    pub expires: String,
+    /// True, if the certificate has expired.
+    pub expired: bool,
+    /// The answer of the revoker for this certificate.
+    pub revocation: Result<(), RevokeError>,
+}
+
+/// What was checked while validating a letter, and with which outcome.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ValidationReport {
+    /// True, if the letter was signed with the master key directly.
+    pub signed_by_master: bool,
+    /// The signature that was checked.
+    pub signature: Vec<u8>,
+    /// The time the letter claims to be signed at.
+    pub signed_at: DateTime<UTC>,
+    /// The certificates between the letter and the master key, starting with the one that signed
+    /// the letter.
+    pub chain: Vec<CertificateReport>,
+    /// The result of the validation, as `Validator::is_valid` returns it.
+    pub result: Result<(), ValidationError>,
+    /// The time the validation started.
+    pub checked_at: DateTime<UTC>,
+    /// How long the validation took.
+    pub duration: Duration,
+}
+
+impl ValidationReport {
+    /// Returns true, if the letter is valid.
+    pub fn is_valid(&self) -> bool {
+        self.result.is_ok()
+    }
+}
+
+/// The report is written as a single line, so it can go into an audit log.
+impl fmt::Display for ValidationReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.result {
+            Ok(()) => write!(f, "valid")?,
+            Err(ref e) => write!(f, "invalid ({:?})", e)?,
+        }
+
+        write!(f, ", signed at {} by ", self.signed_at)?;
+        if self.signed_by_master {
+            write!(f, "the master key")?;
+        }
+
+        for (i, cert) in self.chain.iter().enumerate() {
+            if i > 0 {
+                write!(f, " <- ")?;
+            }
+
+            for byte in cert.public_key.iter().take(8) {
+                write!(f, "{:02x}", byte)?;
+            }
+
+            if cert.expired {
+                write!(f, " (expired)")?;
+            }
+
+            if let Err(ref e) = cert.revocation {
+                write!(f, " ({:?})", e)?;
+            }
+        }
+
+        write!(f, ", checked at {} in {:?}", self.checked_at, self.duration)
+    }
+}
+
+impl<T: Fingerprint> Letter<T> {
+    /// This method validates the letter like `Validator::is_valid` and reports what was checked.
+    pub fn validate_report<V: Validator>(&self, cv: &V) -> ValidationReport {
+        self.validate_report_with_clock(cv, &SystemClock)
+    }
+
+    /// This method reports like `validate_report`, but takes the time of the check and of the
+    /// expiry of the certificates from the given clock.
+    pub fn validate_report_with_clock<V: Validator, C: Clock>(&self, cv: &V, clock: &C) -> ValidationReport {
+        let checked_at = clock.now();
+        let start = Instant::now();
+
+        let result = cv.is_valid(self);
+
+        let chain = self.signer_chain()
+            .into_iter()
+            .map(|cert| {
+                CertificateReport {
+                    public_key: cert.public_key().clone(),
+                    expires: cert.expires().to_string(),
+                    expired: is_expired_at(cert.expires(), &checked_at),
+                    revocation: cv.is_revoked(cert),
+                }
+            })
+            .collect();
+
+        ValidationReport {
+            signed_by_master: self.signature().is_signed_by_master(),
+            signature: self.signature().hash().clone(),
+            signed_at: *self.signed_at(),
+            chain,
+            result,
+            checked_at,
+            duration: start.elapsed(),
+        }
+    }
+}
+
+/// Certificates with an unreadable expiration date count as expired, like in edcert.
+fn is_expired_at(expires: &str, now: &DateTime<UTC>) -> bool {
+    match DateTime::parse_from_rfc3339(expires) {
+        Ok(expires) => expires.with_timezone(&UTC) < *now,
+        Err(_) => true,
+    }
+}
+
+#[test]
+fn test_validation_report() {
+    use chrono::Duration;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let report = Letter::with_private_key("hello", &msk).validate_report(&cv);
+    assert_eq!(true, report.is_valid());
+    assert_eq!(true, report.signed_by_master);
+    assert_eq!(true, report.chain.is_empty());
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() - Duration::days(1));
+    cert.sign_with_master(&msk);
+    let report = Letter::with_certificate("hello", &cert).unwrap().validate_report(&cv);
+    assert_eq!(Err(ValidationError::ParentInvalid), report.result);
+    assert_eq!(1, report.chain.len());
+    assert_eq!(true, report.chain[0].expired);
+    assert_eq!(true, report.to_string().contains("(expired)"));
+}
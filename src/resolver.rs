@@ -0,0 +1,142 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Lookup of certificates that aren't shipped with a letter.
+//!
+//! A detached letter (see `Letter::to_bytes_detached`) names its signer only by the public key
+//! of the certificate. While it is read, a `CertificateResolver` supplies the certificate, for
+//! example from a `CertificateStore`, the intermediates of a `TrustBundle` or a network service.
+//! The certificate must carry its own chain up to the master key; the letter is validated as
+//! usual afterwards, so a resolver can't make an invalid letter valid.
+
+use std::collections::BTreeMap;
+use std::sync::RwLock;
+
+use edcert::certificate::Certificate;
+
+/// Something that finds a certificate by its public key.
+pub trait CertificateResolver {
+    /// Returns the certificate with the given public key, or None if it is unknown.
+    fn resolve(&self, public_key: &[u8]) -> Option<Certificate>;
+}
+
+impl<F: Fn(&[u8]) -> Option<Certificate>> CertificateResolver for F {
+    fn resolve(&self, public_key: &[u8]) -> Option<Certificate> {
+        self(public_key)
+    }
+}
+
+/// A resolver that never finds a certificate.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub struct NoResolver;
+
+impl CertificateResolver for NoResolver {
+    fn resolve(&self, _: &[u8]) -> Option<Certificate> {
+        None
+    }
+}
+
+/// An in-memory store of certificates. Certificates can be added while it is shared, so it can
+/// also be used as a cache in front of a slower resolver.
+#[derive(Debug, Default)]
+pub struct CertificateStore {
+    certs: RwLock<BTreeMap<Vec<u8>, Certificate>>,
+}
+
+impl CertificateStore {
+    /// Creates an empty store.
+    pub fn new() -> CertificateStore {
+        CertificateStore::default()
+    }
+
+    /// Adds a certificate. Its private key isn't stored.
+    pub fn add(&self, cert: &Certificate) {
+        let mut cert = cert.clone();
+        cert.remove_private_key();
+        self.certs.write().unwrap().insert(cert.public_key().clone(), cert);
+    }
+
+    /// Removes the certificate with the given public key.
+    pub fn remove(&self, public_key: &[u8]) -> Option<Certificate> {
+        self.certs.write().unwrap().remove(public_key)
+    }
+
+    /// Returns the number of certificates.
+    pub fn len(&self) -> usize {
+        self.certs.read().unwrap().len()
+    }
+
+    /// Returns true, if the store is empty.
+    pub fn is_empty(&self) -> bool {
+        self.certs.read().unwrap().is_empty()
+    }
+}
+
+impl CertificateResolver for CertificateStore {
+    fn resolve(&self, public_key: &[u8]) -> Option<Certificate> {
+        self.certs.read().unwrap().get(public_key).cloned()
+    }
+}
+
+/// Asks the first resolver and, if it doesn't know the certificate, the second one. Certificates
+/// found by the second one are not remembered; put a `CertificateStore` first and fill it to
+/// cache them.
+pub struct Chained<A, B>(pub A, pub B);
+
+impl<A: CertificateResolver, B: CertificateResolver> CertificateResolver for Chained<A, B> {
+    fn resolve(&self, public_key: &[u8]) -> Option<Certificate> {
+        self.0.resolve(public_key).or_else(|| self.1.resolve(public_key))
+    }
+}
+
+#[test]
+fn test_resolver() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    use letter::Letter;
+    use format::DecodeError;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(90));
+    cert.sign_with_master(&msk);
+
+    let letter = Letter::with_certificate(::canonical::Fingerprintable(7u32), &cert).unwrap();
+    let detached = letter.to_bytes_detached();
+
+    let store = CertificateStore::new();
+    assert_eq!(Some(DecodeError::DetachedChain),
+               Letter::<::canonical::Fingerprintable<u32>>::from_bytes_attached(&detached, &store).err());
+
+    store.add(&cert);
+    assert_eq!(false, store.resolve(cert.public_key()).unwrap().has_private_key());
+
+    let resolver = Chained(NoResolver, store);
+    let letter: Letter<::canonical::Fingerprintable<u32>> = Letter::from_bytes_attached(&detached, &resolver).unwrap();
+    assert_eq!(Ok(()), RootValidator::new(&mpk, NoRevoker).is_valid(&letter));
+}
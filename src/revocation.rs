@@ -0,0 +1,469 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Revokers that can be plugged into a validator.
+//!
+//! `CachingRevoker` asks a `StatusSource` whether a certificate has been revoked and remembers
+//! the answer for a while. If the source can't be reached, the `FailurePolicy` decides: with
+//! `SoftFail` the certificate is accepted, with `HardFail` it is rejected. With the `http`
+//! feature, `HttpRevoker` asks a web service.
+//!
+//! `FileRevoker` reads a signed `RevocationList` from disk instead, for deployments without
+//! network access.
+
+use std::collections::BTreeSet;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+use std::path::PathBuf;
+use std::sync::Mutex;
+use std::sync::RwLock;
+use std::time::Duration;
+use std::time::Instant;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::revoker::NoRevoker;
+use edcert::revoker::RevokeError;
+use edcert::revoker::Revoker;
+use edcert::root_validator::RootValidator;
+use edcert::validator::ValidationError;
+
+use canonical;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// What to do, if the revocation status of a certificate can't be determined.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum FailurePolicy {
+    /// Accept the certificate. Use this, if availability matters more than revocation.
+    SoftFail,
+    /// Reject the certificate.
+    HardFail,
+}
+
+/// A source of revocation information for single certificates.
+pub trait StatusSource {
+    /// Returns true, if the certificate with the given public key has been revoked. Returns an
+    /// error, if the status can't be determined.
+    fn is_key_revoked(&self, public_key: &[u8]) -> Result<bool, String>;
+}
+
+/// A revoker that caches the answers of a `StatusSource`.
+pub struct CachingRevoker<S: StatusSource> {
+    source: S,
+    ttl: Duration,
+    policy: FailurePolicy,
+    cache: Mutex<HashMap<Vec<u8>, (bool, Instant)>>,
+}
+
+impl<S: StatusSource> CachingRevoker<S> {
+    /// Creates a revoker that keeps answers for `ttl` and handles failures with `policy`.
+    pub fn new(source: S, ttl: Duration, policy: FailurePolicy) -> CachingRevoker<S> {
+        CachingRevoker {
+            source,
+            ttl,
+            policy,
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Forgets all cached answers.
+    pub fn clear_cache(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    fn cached(&self, public_key: &[u8]) -> Option<bool> {
+        let cache = self.cache.lock().unwrap();
+        match cache.get(public_key) {
+            Some(&(revoked, at)) if at.elapsed() < self.ttl => Some(revoked),
+            _ => None,
+        }
+    }
+}
+
+impl<S: StatusSource> Revoker for CachingRevoker<S> {
+    fn is_revoked(&self, cert: &Certificate) -> Result<(), RevokeError> {
+        let public_key = cert.public_key();
+
+        let revoked = match self.cached(public_key) {
+            Some(revoked) => revoked,
+            None => {
+                match self.source.is_key_revoked(public_key) {
+                    Ok(revoked) => {
+                        let mut cache = self.cache.lock().unwrap();
+                        cache.insert(public_key.clone(), (revoked, Instant::now()));
+                        revoked
+                    }
+                    Err(_e) => {
+                        trace_event!(warn, error = %_e, policy = ?self.policy, "revocation status unavailable");
+                        return match self.policy {
+                            FailurePolicy::SoftFail => Ok(()),
+                            FailurePolicy::HardFail => Err(RevokeError::ServerUnreachable),
+                        }
+                    }
+                }
+            }
+        };
+
+        if revoked {
+            Err(RevokeError::Revoked)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+/// Asks a web service for the revocation status. For a certificate with the public key `k`, it
+/// requests `GET <url>/<hex(k)>` and expects a JSON object like `{"revoked": false}`.
+#[cfg(feature = "http")]
+pub struct HttpStatusSource {
+    url: String,
+    agent: ::ureq::Agent,
+}
+
+#[cfg(feature = "http")]
+impl HttpStatusSource {
+    /// Creates a source for the given base URL. Requests that take longer than `timeout` fail.
+    pub fn new(url: &str, timeout: Duration) -> HttpStatusSource {
+        HttpStatusSource {
+            url: url.trim_end_matches('/').to_string(),
+            agent: ::ureq::AgentBuilder::new().timeout(timeout).build(),
+        }
+    }
+}
+
+#[cfg(feature = "http")]
+impl StatusSource for HttpStatusSource {
+    fn is_key_revoked(&self, public_key: &[u8]) -> Result<bool, String> {
+        use rustc_serialize::hex::ToHex;
+        use rustc_serialize::json::Json;
+
+        let url = format!("{}/{}", self.url, public_key.to_hex());
+        let body = self.agent
+                       .get(&url)
+                       .call()
+                       .map_err(|e| e.to_string())?
+                       .into_string()
+                       .map_err(|e| e.to_string())?;
+
+        let json = Json::from_str(&body).map_err(|e| e.to_string())?;
+        json.find("revoked")
+            .and_then(|r| r.as_boolean())
+            .ok_or_else(|| "response has no \"revoked\" field".to_string())
+    }
+}
+
+/// A revoker that asks a web service and caches the answers.
+#[cfg(feature = "http")]
+pub type HttpRevoker = CachingRevoker<HttpStatusSource>;
+
+#[cfg(feature = "http")]
+impl CachingRevoker<HttpStatusSource> {
+    /// Creates a revoker that asks the service at `url`, waits at most `timeout` for an answer
+    /// and keeps answers for `ttl`.
+    pub fn http(url: &str, timeout: Duration, ttl: Duration, policy: FailurePolicy) -> HttpRevoker {
+        CachingRevoker::new(HttpStatusSource::new(url, timeout), ttl, policy)
+    }
+}
+
+/// The content type of revocation list letters.
+pub const REVOCATION_LIST_CONTENT_TYPE: &str = "edcert-letter/revocation-list";
+
+/// A list of revoked public keys. Sign it with the master key and write `letter.to_bytes()` to a
+/// file to distribute it to a `FileRevoker`.
+#[derive(Clone, PartialEq, Eq, Debug, Default)]
+pub struct RevocationList {
+    keys: BTreeSet<Vec<u8>>,
+}
+
+impl RevocationList {
+    /// Creates an empty list.
+    pub fn new() -> RevocationList {
+        RevocationList { keys: BTreeSet::new() }
+    }
+
+    /// Adds a public key to the list.
+    pub fn revoke(&mut self, public_key: &[u8]) {
+        self.keys.insert(public_key.to_vec());
+    }
+
+    /// Returns true, if the public key is on the list.
+    pub fn contains(&self, public_key: &[u8]) -> bool {
+        self.keys.contains(public_key)
+    }
+
+    /// Returns the revoked public keys.
+    pub fn keys(&self) -> &BTreeSet<Vec<u8>> {
+        &self.keys
+    }
+
+    /// Signs the list. The header gets the revocation list content type.
+    pub fn sign(self, mut header: Header, signer: &Signer) -> Result<Letter<RevocationList>, SignError> {
+        header.set_content_type(REVOCATION_LIST_CONTENT_TYPE);
+        Letter::sign(self, header, signer)
+    }
+}
+
+/// The list can be used as a revoker directly, for example when it comes with a `TrustBundle`.
+impl Revoker for RevocationList {
+    fn is_revoked(&self, cert: &Certificate) -> Result<(), RevokeError> {
+        if self.contains(cert.public_key()) {
+            Err(RevokeError::Revoked)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+impl Fingerprint for RevocationList {
+    fn fingerprint(&self) -> Vec<u8> {
+        let keys: Vec<&Vec<u8>> = self.keys.iter().collect();
+        canonical::to_bytes(&keys)
+    }
+}
+
+impl FromFingerprint for RevocationList {
+    fn from_fingerprint(bytes: &[u8]) -> Result<RevocationList, DecodeError> {
+        let keys: Vec<Vec<u8>> = canonical::from_bytes(bytes)?;
+
+        // The encoding is sorted, so there is only one encoding for every list.
+        let list = RevocationList { keys: keys.iter().cloned().collect() };
+        if list.keys.len() != keys.len() || list.keys.iter().zip(keys.iter()).any(|(a, b)| a != b) {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(list)
+    }
+}
+
+/// This error is returned, if a revocation list file can't be loaded.
+#[derive(Debug)]
+pub enum CrlError {
+    /// The file couldn't be read.
+    Io(io::Error),
+    /// The file doesn't contain a revocation list letter.
+    Decode(DecodeError),
+    /// The list isn't signed by the master key.
+    Invalid(ValidationError),
+    /// The list was signed before the one that is already loaded.
+    Outdated,
+}
+
+impl fmt::Display for CrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CrlError::Io(ref e) => write!(f, "can't read revocation list: {}", e),
+            CrlError::Decode(ref e) => write!(f, "can't decode revocation list: {}", e),
+            CrlError::Invalid(ref e) => write!(f, "revocation list is not valid: {:?}", e),
+            CrlError::Outdated => write!(f, "revocation list is older than the loaded one"),
+        }
+    }
+}
+
+impl Error for CrlError {}
+
+/// A revoker that reads a signed `RevocationList` from a file. It works without network access,
+/// so it can be used in air-gapped deployments. The list must be signed with the master key.
+///
+/// If a reload interval is set, the file is read again when the loaded list gets older than the
+/// interval. If that fails, for example because the file is being replaced, the old list stays in
+/// use. A list signed before the loaded one is never accepted, so an old file can't undo a
+/// revocation.
+pub struct FileRevoker {
+    path: PathBuf,
+    master_public_key: Vec<u8>,
+    reload_interval: Option<Duration>,
+    state: RwLock<(Letter<RevocationList>, Instant)>,
+}
+
+impl FileRevoker {
+    /// Loads the revocation list at `path` and checks its signature against the master key.
+    pub fn open<P: AsRef<Path>>(path: P, master_public_key: &[u8]) -> Result<FileRevoker, CrlError> {
+        let path = path.as_ref().to_path_buf();
+        let list = load_revocation_list(&path, master_public_key)?;
+
+        Ok(FileRevoker {
+            path,
+            master_public_key: master_public_key.to_vec(),
+            reload_interval: None,
+            state: RwLock::new((list, Instant::now())),
+        })
+    }
+
+    /// Sets the interval after which the file is read again.
+    pub fn with_reload_interval(mut self, interval: Duration) -> FileRevoker {
+        self.reload_interval = Some(interval);
+        self
+    }
+
+    /// Reads the file again. On error, the old list stays in use.
+    pub fn reload(&self) -> Result<(), CrlError> {
+        let list = load_revocation_list(&self.path, &self.master_public_key)?;
+
+        let mut state = self.state.write().unwrap();
+        if list.signed_at() < state.0.signed_at() {
+            return Err(CrlError::Outdated);
+        }
+
+        *state = (list, Instant::now());
+        Ok(())
+    }
+
+    fn reload_if_due(&self) {
+        let due = match self.reload_interval {
+            Some(interval) => self.state.read().unwrap().1.elapsed() >= interval,
+            None => false,
+        };
+
+        if !due {
+            return;
+        }
+
+        if let Err(_e) = self.reload() {
+            trace_event!(warn, error = %_e, "keeping the old revocation list");
+            // Keep the old list, but don't try again before the next interval.
+            self.state.write().unwrap().1 = Instant::now();
+        }
+    }
+}
+
+/// Reads a revocation list letter from a file and checks that it is signed by the master key and
+/// has the revocation list content type.
+pub fn load_revocation_list(path: &Path, master_public_key: &[u8]) -> Result<Letter<RevocationList>, CrlError> {
+    let mut bytes = Vec::new();
+    File::open(path).and_then(|mut f| f.read_to_end(&mut bytes)).map_err(CrlError::Io)?;
+
+    let letter: Letter<RevocationList> = Letter::from_bytes(&bytes).map_err(CrlError::Decode)?;
+    letter.validate_as(&RootValidator::new(master_public_key, NoRevoker), REVOCATION_LIST_CONTENT_TYPE)
+          .map_err(CrlError::Invalid)?;
+
+    Ok(letter)
+}
+
+impl Revoker for FileRevoker {
+    fn is_revoked(&self, cert: &Certificate) -> Result<(), RevokeError> {
+        self.reload_if_due();
+
+        if self.state.read().unwrap().0.contains(cert.public_key()) {
+            Err(RevokeError::Revoked)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+struct ScriptedSource {
+    answer: Result<bool, String>,
+    calls: ::std::cell::Cell<usize>,
+}
+
+#[cfg(test)]
+impl StatusSource for ScriptedSource {
+    fn is_key_revoked(&self, _: &[u8]) -> Result<bool, String> {
+        self.calls.set(self.calls.get() + 1);
+        self.answer.clone()
+    }
+}
+
+#[cfg(test)]
+fn test_certificate() -> Certificate {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::meta::Meta;
+
+    Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1))
+}
+
+#[test]
+fn test_answers_are_cached() {
+    let source = ScriptedSource { answer: Ok(true), calls: ::std::cell::Cell::new(0) };
+    let revoker = CachingRevoker::new(source, Duration::from_secs(60), FailurePolicy::HardFail);
+    let cert = test_certificate();
+
+    assert_eq!(Err(RevokeError::Revoked), revoker.is_revoked(&cert));
+    assert_eq!(Err(RevokeError::Revoked), revoker.is_revoked(&cert));
+    assert_eq!(1, revoker.source.calls.get());
+
+    revoker.clear_cache();
+    assert_eq!(Err(RevokeError::Revoked), revoker.is_revoked(&cert));
+    assert_eq!(2, revoker.source.calls.get());
+}
+
+#[test]
+fn test_failure_policy() {
+    let cert = test_certificate();
+
+    let source = ScriptedSource { answer: Err("timeout".to_string()), calls: ::std::cell::Cell::new(0) };
+    let revoker = CachingRevoker::new(source, Duration::from_secs(60), FailurePolicy::SoftFail);
+    assert_eq!(Ok(()), revoker.is_revoked(&cert));
+
+    let source = ScriptedSource { answer: Err("timeout".to_string()), calls: ::std::cell::Cell::new(0) };
+    let revoker = CachingRevoker::new(source, Duration::from_secs(60), FailurePolicy::HardFail);
+    assert_eq!(Err(RevokeError::ServerUnreachable), revoker.is_revoked(&cert));
+}
+
+#[test]
+fn test_file_revoker() {
+    use std::io::Write;
+    use edcert::ed25519;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cert = test_certificate();
+    let path = ::std::env::temp_dir().join(format!("edcert-letter-crl-{}.edl", ::std::process::id()));
+    let write = |list: &RevocationList| {
+        let letter = list.clone().sign(Header::new(), &Signer::PrivateKey(&msk)).unwrap();
+        File::create(&path).unwrap().write_all(&letter.to_bytes()).unwrap();
+    };
+
+    let mut list = RevocationList::new();
+    write(&list);
+    let revoker = FileRevoker::open(&path, &mpk).unwrap();
+    assert_eq!(Ok(()), revoker.is_revoked(&cert));
+
+    list.revoke(cert.public_key());
+    write(&list);
+    assert_eq!(Ok(()), revoker.is_revoked(&cert));
+    revoker.reload().unwrap();
+    assert_eq!(Err(RevokeError::Revoked), revoker.is_revoked(&cert));
+
+    let (_, other_sk) = ed25519::generate_keypair();
+    let forged = RevocationList::new().sign(Header::new(), &Signer::PrivateKey(&other_sk)).unwrap();
+    File::create(&path).unwrap().write_all(&forged.to_bytes()).unwrap();
+    assert_eq!(true, revoker.reload().is_err());
+
+    let untagged = Letter::with_private_key(RevocationList::new(), &msk);
+    File::create(&path).unwrap().write_all(&untagged.to_bytes()).unwrap();
+    assert_eq!(true, revoker.reload().is_err());
+    assert_eq!(Err(RevokeError::Revoked), revoker.is_revoked(&cert));
+
+    ::std::fs::remove_file(&path).unwrap();
+}
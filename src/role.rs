@@ -0,0 +1,118 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Validation of the role of a signer.
+//!
+//! A `RoleValidator` requires meta values like `role=release` and `env=prod` of the certificate
+//! that signed a letter, so a certificate for staging can't sign production letters. It is a
+//! `Policy` of `require_meta` rules with a validator attached.
+
+use std::collections::BTreeMap;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+use policy::Policy;
+
+/// A validator that requires meta values of the certificate that signed a letter.
+///
+/// It doesn't implement `Validator`: `Validator::is_valid` is generic over everything that can be
+/// validated, so it can't look at the signing certificate of a letter. Call `validate` instead.
+/// `cv.is_valid(&letter)` with the wrapped validator checks the signature but skips the roles.
+pub struct RoleValidator<V: Validator> {
+    inner: V,
+    required: BTreeMap<String, String>,
+}
+
+impl<V: Validator> RoleValidator<V> {
+    /// Wraps the validator. Without requirements, it accepts what the wrapped validator accepts.
+    pub fn new(inner: V) -> RoleValidator<V> {
+        RoleValidator {
+            inner,
+            required: BTreeMap::new(),
+        }
+    }
+
+    /// Requires the signing certificate to map `key` to `value`. A second requirement for the
+    /// same key replaces the first.
+    pub fn require(mut self, key: &str, value: &str) -> RoleValidator<V> {
+        self.required.insert(key.to_string(), value.to_string());
+        self
+    }
+
+    /// Returns the required meta values.
+    pub fn required(&self) -> &BTreeMap<String, String> {
+        &self.required
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Returns the requirements as a policy.
+    pub fn policy(&self) -> Policy {
+        Policy::all_of(self.required.iter().map(|(k, v)| Policy::require_meta(k, v)).collect())
+    }
+
+    /// Validates the letter with the wrapped validator and checks the meta values of its signing
+    /// certificate. A missing or different value yields `ValidationError::Other`, and so do
+    /// letters signed with the master key directly, if anything is required.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.policy().validate(&self.inner, letter)
+    }
+}
+
+#[test]
+fn test_role_validator() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RoleValidator::new(RootValidator::new(&mpk, NoRevoker))
+                 .require("role", "release")
+                 .require("env", "prod");
+
+    let certificate = |env: &str| {
+        let mut meta = Meta::new_empty();
+        meta.set("role", "release");
+        meta.set("env", env);
+        let mut cert = Certificate::generate_random(meta, UTC::now() + Duration::days(1));
+        cert.sign_with_master(&msk);
+        cert
+    };
+
+    let prod = certificate("prod");
+    let staging = certificate("staging");
+
+    assert_eq!(Ok(()), cv.validate(&Letter::with_certificate("v1", &prod).unwrap()));
+    assert_eq!(Err(ValidationError::Other), cv.validate(&Letter::with_certificate("v1", &staging).unwrap()));
+    assert_eq!(Err(ValidationError::Other), cv.validate(&Letter::with_private_key("v1", &msk)));
+}
@@ -0,0 +1,185 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Rotation of master keys.
+//!
+//! To retire a master key, sign a `Rotation` naming the new key with the old one. A
+//! `TrustStoreValidator` that applies the resulting `RotationLetter` trusts the new key from
+//! then on and keeps trusting the old key until the end of the grace window, so letters signed
+//! shortly before the rotation stay valid while they are replaced.
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::revoker::Revoker;
+use edcert::validator::ValidationError;
+
+use canonical;
+use clock::Clock;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+use trust::TrustAnchor;
+use trust::TrustStoreValidator;
+
+/// The content type of rotation letters.
+pub const ROTATION_CONTENT_TYPE: &str = "edcert-letter/rotation";
+
+/// The endorsement of a new master key by the old one.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Rotation {
+    name: String,
+    new_key: Vec<u8>,
+    grace_until: DateTime<UTC>,
+}
+
+/// A `Rotation` signed with the old master key.
+pub type RotationLetter = Letter<Rotation>;
+
+impl Rotation {
+    /// Creates a rotation to the new key, which will be trusted under the given name. The old key
+    /// stays trusted until `grace_until`.
+    pub fn new(name: &str, new_key: &[u8], grace_until: DateTime<UTC>) -> Rotation {
+        Rotation {
+            name: name.to_string(),
+            new_key: new_key.to_vec(),
+            grace_until,
+        }
+    }
+
+    /// Signs the rotation with the old master key. The header gets the rotation content type. It
+    /// fails, if the key isn't an Ed25519 private key.
+    pub fn sign(self, old_private_key: &[u8]) -> Result<RotationLetter, SignError> {
+        let mut header = Header::new();
+        header.set_content_type(ROTATION_CONTENT_TYPE);
+        Letter::sign(self, header, &Signer::PrivateKey(old_private_key))
+    }
+
+    /// Returns the name of the new key.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the new master public key.
+    pub fn new_key(&self) -> &[u8] {
+        &self.new_key
+    }
+
+    /// Returns the end of the grace window of the old key.
+    pub fn grace_until(&self) -> &DateTime<UTC> {
+        &self.grace_until
+    }
+}
+
+impl Fingerprint for Rotation {
+    fn fingerprint(&self) -> Vec<u8> {
+        canonical::to_bytes(&(&self.name, &self.new_key, self.grace_until.timestamp()))
+    }
+}
+
+impl FromFingerprint for Rotation {
+    fn from_fingerprint(bytes: &[u8]) -> Result<Rotation, DecodeError> {
+        let (name, new_key, secs): (String, Vec<u8>, i64) = canonical::from_bytes(bytes)?;
+        let grace_until = UTC.timestamp_opt(secs, 0).single().ok_or(DecodeError::InvalidContent)?;
+        Ok(Rotation::new(&name, &new_key, grace_until))
+    }
+}
+
+impl<R: Revoker, C: Clock> TrustStoreValidator<R, C> {
+    /// Checks that the rotation letter is signed directly by a trusted master key and has the
+    /// rotation content type, adds the new key and ends the trust in the old key at the end of the
+    /// grace window.
+    pub fn apply_rotation(&mut self, letter: &RotationLetter) -> Result<(), ValidationError> {
+        // Certificates can't endorse master keys.
+        if !letter.signature().is_signed_by_master() {
+            return Err(ValidationError::SignatureInvalid);
+        }
+
+        letter.validate_as(&*self, ROTATION_CONTENT_TYPE)?;
+
+        let old = self.store()
+                      .find_signer_within(&letter.signed_bytes(), letter.signature().hash(), &self.clock().now(), self.skew())
+                      .cloned()
+                      .ok_or(ValidationError::SignatureInvalid)?;
+
+        let grace_until = match old.not_after() {
+            Some(t) if t < *letter.grace_until() => t,
+            _ => *letter.grace_until(),
+        };
+
+        let store = self.store_mut();
+        store.add(old.clone().with_validity(old.not_before(), Some(grace_until)));
+        store.add(TrustAnchor::new(letter.name(), letter.new_key()));
+
+        Ok(())
+    }
+}
+
+#[test]
+fn test_rotation() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::validator::Validator;
+    use delegation::Delegation;
+    use trust::TrustStore;
+
+    let (old_pk, old_sk) = ed25519::generate_keypair();
+    let (new_pk, new_sk) = ed25519::generate_keypair();
+
+    let mut store = TrustStore::new();
+    store.add(TrustAnchor::new("2016", &old_pk));
+    let mut cv = TrustStoreValidator::new(store, NoRevoker);
+
+    let rotation = Rotation::new("2017", &new_pk, UTC::now() + Duration::days(30)).sign(&old_sk).unwrap();
+    let rotation: RotationLetter = Letter::from_bytes(&rotation.to_bytes()).unwrap();
+    cv.apply_rotation(&rotation).unwrap();
+
+    assert_eq!(true, cv.is_valid(&Letter::with_private_key("new", &new_sk)).is_ok());
+    assert_eq!(true, cv.is_valid(&Letter::with_private_key("old", &old_sk)).is_ok());
+
+    // After the grace window the old key is no longer trusted.
+    let mut store = TrustStore::new();
+    store.add(TrustAnchor::new("2016", &old_pk));
+    let mut expired = TrustStoreValidator::new(store, NoRevoker);
+    let rotation = Rotation::new("2017", &new_pk, UTC::now() - Duration::days(1)).sign(&old_sk).unwrap();
+    expired.apply_rotation(&rotation).unwrap();
+    assert_eq!(true, expired.is_valid(&Letter::with_private_key("new", &new_sk)).is_ok());
+    assert_eq!(false, expired.is_valid(&Letter::with_private_key("old", &old_sk)).is_ok());
+
+    // A key that isn't trusted can't rotate.
+    let (_, other_sk) = ed25519::generate_keypair();
+    let rotation = Rotation::new("evil", &new_pk, UTC::now()).sign(&other_sk).unwrap();
+    assert_eq!(true, cv.apply_rotation(&rotation).is_err());
+
+    // A delegation decodes as a rotation, but isn't one.
+    let delegation = Delegation::new(b"evil", "not a key", UTC::now() + Duration::days(30));
+    let delegation = delegation.sign(Header::new(), &Signer::PrivateKey(&old_sk)).unwrap();
+    let rotation: RotationLetter = Letter::from_bytes(&delegation.to_bytes()).unwrap();
+    assert_eq!("evil", rotation.name());
+    assert_eq!(Err(ValidationError::Other), cv.apply_rotation(&rotation));
+}
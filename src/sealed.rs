@@ -0,0 +1,206 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Signed and then encrypted letters.
+//!
+//! A `SealedLetter` is a serialized letter encrypted with NaCl `crypto_box` to the X25519
+//! public key of a recipient, using a fresh ephemeral key pair for every letter. The recipient key
+//! is written into the header before signing, so a recipient can't decrypt a letter and pass it on
+//! as if it had been sealed for someone else.
+
+use std::error::Error;
+use std::fmt;
+use std::marker::PhantomData;
+
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::box_;
+use sodiumoxide::crypto::scalarmult::curve25519;
+
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The bytes every serialized sealed letter starts with.
+pub const SEALED_MAGIC: &[u8] = b"EDS";
+
+/// The metadata key holding the recipient public key, hex encoded.
+pub const RECIPIENT_KEY: &str = "sealed.recipient";
+
+/// This error is returned, if a sealed letter can't be opened.
+#[derive(Clone, PartialEq, Debug)]
+pub enum SealError {
+    /// The letter isn't encrypted to this key or has been modified.
+    Decrypt,
+    /// The decrypted bytes aren't a letter.
+    Decode(DecodeError),
+    /// The letter isn't validly signed.
+    Invalid(ValidationError),
+    /// The letter was signed for a different recipient.
+    WrongRecipient,
+}
+
+impl fmt::Display for SealError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SealError::Decrypt => write!(f, "can't decrypt sealed letter"),
+            SealError::Decode(ref e) => write!(f, "can't decode sealed letter: {}", e),
+            SealError::Invalid(ref e) => write!(f, "sealed letter is not valid: {:?}", e),
+            SealError::WrongRecipient => write!(f, "sealed letter was signed for another recipient"),
+        }
+    }
+}
+
+impl Error for SealError {}
+
+/// A letter that only the recipient can read.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SealedLetter<T: FromFingerprint> {
+    ephemeral_key: box_::PublicKey,
+    nonce: box_::Nonce,
+    ciphertext: Vec<u8>,
+    content: PhantomData<T>,
+}
+
+impl<T: FromFingerprint> SealedLetter<T> {
+    /// Signs the content and encrypts the letter to the recipient. It fails, if the signer is a
+    /// certificate without a private key.
+    pub fn seal(content: T,
+                mut header: Header,
+                signer: &Signer,
+                recipient: &box_::PublicKey)
+                -> Result<SealedLetter<T>, SignError> {
+        header.set_meta(RECIPIENT_KEY, &recipient.0.to_hex());
+        let letter = Letter::sign(content, header, signer)?;
+
+        let (ephemeral_key, ephemeral_secret) = box_::gen_keypair();
+        let nonce = box_::gen_nonce();
+        let ciphertext = box_::seal(&letter.to_bytes(), &nonce, recipient, &ephemeral_secret);
+
+        Ok(SealedLetter {
+            ephemeral_key,
+            nonce,
+            ciphertext,
+            content: PhantomData,
+        })
+    }
+
+    /// Decrypts the letter, checks that it was sealed for this recipient and validates it.
+    pub fn open<V: Validator>(&self,
+                              recipient_secret: &box_::SecretKey,
+                              cv: &V)
+                              -> Result<Letter<T>, SealError> {
+        let bytes = box_::open(&self.ciphertext, &self.nonce, &self.ephemeral_key, recipient_secret)
+                        .map_err(|_| SealError::Decrypt)?;
+
+        let letter: Letter<T> = Letter::from_bytes(&bytes).map_err(SealError::Decode)?;
+        cv.is_valid(&letter).map_err(SealError::Invalid)?;
+
+        let recipient = curve25519::scalarmult_base(&curve25519::Scalar(recipient_secret.0));
+        if letter.header().get_meta(RECIPIENT_KEY) != Some(&recipient.0.to_hex()[..]) {
+            return Err(SealError::WrongRecipient);
+        }
+
+        Ok(letter)
+    }
+
+    /// Serializes the sealed letter.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(SEALED_MAGIC);
+        w.u8(1);
+        w.raw(&self.ephemeral_key.0);
+        w.raw(&self.nonce.0);
+        w.bytes(&self.ciphertext);
+        w.into_bytes()
+    }
+
+    /// Parses a sealed letter. The letter inside is only checked by `open`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<SealedLetter<T>, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(SEALED_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != SEALED_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let ephemeral_key = box_::PublicKey::from_slice(r.raw(box_::PUBLICKEYBYTES)?)
+                                .ok_or(DecodeError::UnexpectedEnd)?;
+        let nonce = box_::Nonce::from_slice(r.raw(box_::NONCEBYTES)?)
+                        .ok_or(DecodeError::UnexpectedEnd)?;
+        let ciphertext = r.bytes()?.to_vec();
+
+        if !r.is_empty() {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(SealedLetter {
+            ephemeral_key,
+            nonce,
+            ciphertext,
+            content: PhantomData,
+        })
+    }
+}
+
+#[test]
+fn test_seal_and_open() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let (alice_pk, alice_sk) = box_::gen_keypair();
+    let (bob_pk, bob_sk) = box_::gen_keypair();
+
+    let content = Fingerprintable("secret".to_string());
+    let sealed = SealedLetter::seal(content, Header::new(), &Signer::PrivateKey(&msk), &alice_pk).unwrap();
+    let sealed: SealedLetter<Fingerprintable<String>> = SealedLetter::from_bytes(&sealed.to_bytes()).unwrap();
+
+    assert_eq!("secret", sealed.open(&alice_sk, &cv).unwrap().as_str());
+    assert_eq!(Err(SealError::Decrypt), sealed.open(&bob_sk, &cv));
+
+    // Alice passes the letter on to Bob.
+    let letter = sealed.open(&alice_sk, &cv).unwrap();
+    let (ephemeral_key, ephemeral_secret) = box_::gen_keypair();
+    let nonce = box_::gen_nonce();
+    let forwarded: SealedLetter<Fingerprintable<String>> = SealedLetter {
+        ephemeral_key,
+        nonce,
+        ciphertext: box_::seal(&letter.to_bytes(), &nonce, &bob_pk, &ephemeral_secret),
+        content: PhantomData,
+    };
+    assert_eq!(Err(SealError::WrongRecipient), forwarded.open(&bob_sk, &cv));
+}
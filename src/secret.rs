@@ -0,0 +1,141 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Letters with secret content.
+//!
+//! `SecretContent` wraps a payload that is itself secret, like a session key or a password. Its
+//! buffer is locked into memory where the platform allows it, so it isn't written to swap, it is
+//! overwritten with zeros when dropped, and `Debug` doesn't show it, neither on its own nor inside
+//! a `Letter`. A letter only keeps a digest of its content, not a copy. Signing, validating,
+//! serializing and parsing a letter still copy the content into temporary buffers, the
+//! fingerprint and the encoded bytes, which are freed but not zeroed; the wrapper only protects
+//! the copy the application keeps around.
+
+use std::fmt;
+
+use region::LockGuard;
+use zeroize::Zeroize;
+
+use edcert::fingerprint::Fingerprint;
+
+use format::DecodeError;
+use format::FromFingerprint;
+
+/// A secret payload that is locked into memory and zeroed on drop.
+pub struct SecretContent<T: Zeroize + AsRef<[u8]>> {
+    value: T,
+    lock: Option<LockGuard>,
+}
+
+impl<T: Zeroize + AsRef<[u8]>> SecretContent<T> {
+    /// Wraps the value and locks its buffer into memory. If the platform doesn't allow locking
+    /// more memory, the value is still zeroed on drop; `is_locked` tells whether locking worked.
+    pub fn new(value: T) -> SecretContent<T> {
+        let lock = {
+            let bytes = value.as_ref();
+
+            if bytes.is_empty() {
+                None
+            } else {
+                region::lock(bytes.as_ptr(), bytes.len()).ok()
+            }
+        };
+
+        SecretContent {
+            value,
+            lock,
+        }
+    }
+
+    /// Returns the secret value.
+    pub fn expose(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns true, if the buffer of the value is locked into memory.
+    pub fn is_locked(&self) -> bool {
+        self.lock.is_some()
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> Drop for SecretContent<T> {
+    fn drop(&mut self) {
+        // The memory is unlocked afterwards, when the lock guard is dropped.
+        self.value.zeroize();
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> fmt::Debug for SecretContent<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "SecretContent([REDACTED])")
+    }
+}
+
+impl<T: Zeroize + AsRef<[u8]>> Fingerprint for SecretContent<T> {
+    fn fingerprint(&self) -> Vec<u8> {
+        self.value.as_ref().to_vec()
+    }
+}
+
+impl FromFingerprint for SecretContent<Vec<u8>> {
+    fn from_fingerprint(bytes: &[u8]) -> Result<SecretContent<Vec<u8>>, DecodeError> {
+        Ok(SecretContent::new(bytes.to_vec()))
+    }
+}
+
+impl FromFingerprint for SecretContent<String> {
+    fn from_fingerprint(bytes: &[u8]) -> Result<SecretContent<String>, DecodeError> {
+        let value = String::from_utf8(bytes.to_vec()).map_err(|e| {
+            let mut bytes = e.into_bytes();
+            bytes.zeroize();
+            DecodeError::InvalidContent
+        })?;
+
+        Ok(SecretContent::new(value))
+    }
+}
+
+#[test]
+fn test_secret_content() {
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let secret = SecretContent::new(b"session key".to_vec());
+    let letter = Letter::with_private_key(secret, &msk);
+    let debug = format!("{:?}", letter);
+    let plain = format!("{:?}", &b"session key"[..]);
+    let digest = format!("{:?}", letter.content_digest());
+    assert_eq!(false, debug.contains("session key"));
+    assert_eq!(false, debug.contains(&plain[1..plain.len() - 1]));
+    assert_eq!(false, debug.contains(&digest[1..digest.len() - 1]));
+
+    let letter: Letter<SecretContent<Vec<u8>>> = Letter::from_bytes(&letter.to_bytes()).unwrap();
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+    assert_eq!(b"session key", &letter.expose()[..]);
+}
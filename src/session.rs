@@ -0,0 +1,212 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signing many letters with the same signer and header.
+//!
+//! A `SigningSession` fixes the signer, a header template with the content type and meta data,
+//! and the expiry once. Checking the private key and preparing the certificate copy that goes
+//! into every letter happen when the session is created, so `sign` fingerprints and hashes the
+//! content once, signs, and clones the prepared copy. That clone is the one copy left: edcert's
+//! `Signature` owns its parent certificate in a `Box`, so every letter signed by a certificate
+//! carries its own copy of the public certificate and its parents. Letters signed with a private
+//! key don't copy anything. The session shares its certificates, so it is cheap to clone into
+//! other threads. With the `rayon` feature, `sign_all_parallel` signs on all cores.
+
+use std::fmt;
+use std::sync::Arc;
+
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+
+use chrono::DateTime;
+use chrono::UTC;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+
+use clock::Clock;
+use clock::SystemClock;
+use header::Header;
+use letter::Letter;
+use signer::public_key_of;
+use signer::RedactedKey;
+use signer::SignError;
+use signer::Signer;
+
+#[derive(Clone)]
+enum SessionKey {
+    PrivateKey(Vec<u8>),
+    Certificate {
+        cert: Arc<Certificate>,
+        public: Arc<Certificate>,
+    },
+}
+
+/// Keys are shown with their public key only, so sessions can be logged.
+impl fmt::Debug for SessionKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SessionKey::PrivateKey(ref private_key) => {
+                f.debug_tuple("PrivateKey").field(&RedactedKey(private_key)).finish()
+            }
+            SessionKey::Certificate { ref public, .. } => f.debug_tuple("Certificate").field(public).finish(),
+        }
+    }
+}
+
+/// Signs letters with a fixed signer, header template and expiry.
+#[derive(Clone, Debug)]
+pub struct SigningSession<C: Clock = SystemClock> {
+    key: SessionKey,
+    template: Header,
+    clock: C,
+}
+
+impl SigningSession {
+    /// Creates a session. Every letter gets a copy of the template with the signing time set. It
+    /// fails, if the signer is a certificate without a private key.
+    pub fn new(signer: &Signer, template: Header) -> Result<SigningSession, SignError> {
+        SigningSession::with_clock(signer, template, SystemClock)
+    }
+}
+
+impl<C: Clock> SigningSession<C> {
+    /// Creates a session like `new`, which takes the signing time from the clock.
+    pub fn with_clock(signer: &Signer, template: Header, clock: C) -> Result<SigningSession<C>, SignError> {
+        let key = match *signer {
+            Signer::PrivateKey(private_key) => {
+                public_key_of(private_key)?;
+                SessionKey::PrivateKey(private_key.to_vec())
+            }
+            Signer::Certificate(cert) => {
+                if !cert.has_private_key() {
+                    return Err(SignError::NoPrivateKey);
+                }
+
+                let mut public = cert.clone();
+                public.remove_private_key();
+
+                SessionKey::Certificate {
+                    cert: Arc::new(cert.clone()),
+                    public: Arc::new(public),
+                }
+            }
+        };
+
+        Ok(SigningSession {
+            key,
+            template,
+            clock,
+        })
+    }
+
+    /// Sets the expiry of every letter signed from now on. Validators don't check the expiry in
+    /// the header: verifiers have to, for example with a `ClaimsValidator` for content that
+    /// implements `Claims`.
+    pub fn expiring(mut self, expires: DateTime<UTC>) -> SigningSession<C> {
+        self.template.set_expires(expires);
+        self
+    }
+
+    /// Returns the header template.
+    pub fn template(&self) -> &Header {
+        &self.template
+    }
+
+    /// Signs the content. It fails, if the private key can't sign.
+    pub fn sign<T: Fingerprint>(&self, content: T) -> Result<Letter<T>, SignError> {
+        let mut header = self.template.clone();
+        header.set_signed_at(self.clock.now());
+        let digest = header.content_digest(&content.fingerprint());
+        let bytes = header.signed_bytes_for_digest(&digest);
+
+        let signature = match self.key {
+            SessionKey::PrivateKey(ref private_key) => Signer::PrivateKey(private_key).sign(&bytes)?,
+            SessionKey::Certificate { ref cert, ref public } => {
+                let hash = cert.sign(&bytes).ok_or(SignError::NoPrivateKey)?;
+                Signature::with_parent(Box::new((**public).clone()), hash)
+            }
+        };
+
+        Ok(Letter::with_digest(content, digest, header, Arc::new(signature)))
+    }
+
+    /// Signs every content, in order. It stops at the first content that can't be signed.
+    pub fn sign_all<T, I>(&self, contents: I) -> Result<Vec<Letter<T>>, SignError>
+        where T: Fingerprint,
+              I: IntoIterator<Item = T>
+    {
+        contents.into_iter().map(|content| self.sign(content)).collect()
+    }
+
+    /// Signs every content on the rayon thread pool. The letters are in the order of the input.
+    #[cfg(feature = "rayon")]
+    pub fn sign_all_parallel<T>(&self, contents: Vec<T>) -> Result<Vec<Letter<T>>, SignError>
+        where T: Fingerprint + Send,
+              C: Sync
+    {
+        contents.into_par_iter().map(|content| self.sign(content)).collect()
+    }
+}
+
+#[test]
+fn test_signing_session() {
+    use chrono::Duration;
+    use rustc_serialize::hex::ToHex;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let expires = UTC::now() + Duration::days(30);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), expires);
+    cert.sign_with_master(&msk);
+
+    let mut template = Header::new();
+    template.set_content_type("ticket");
+
+    let session = SigningSession::new(&Signer::Certificate(&cert), template).unwrap().expiring(expires);
+    let letters = session.sign_all(vec!["a", "b", "c"]).unwrap();
+    assert_eq!(3, letters.len());
+
+    for letter in &letters {
+        assert_eq!(true, letter.validate_as(&cv, "ticket").is_ok());
+        assert_eq!(Some(expires.timestamp()), letter.header().expires().unwrap().map(|e| e.timestamp()));
+    }
+
+    #[cfg(feature = "rayon")]
+    assert_eq!(true, session.sign_all_parallel(vec!["d"; 50]).unwrap().iter().all(|l| cv.is_valid(l).is_ok()));
+
+    let master = SigningSession::new(&Signer::PrivateKey(&msk), Header::new()).unwrap();
+    assert_eq!(true, cv.is_valid(&master.sign("e").unwrap()).is_ok());
+    assert_eq!(false, format!("{:?}", master).contains(&msk[..32].to_hex()));
+    assert_eq!(false, format!("{:?}", session).contains(&cert.private_key().unwrap().to_hex()));
+
+    cert.remove_private_key();
+    assert_eq!(true, SigningSession::new(&Signer::Certificate(&cert), Header::new()).is_err());
+}
@@ -0,0 +1,242 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! The `Signer` type, which is anything letters can be signed with, and `LetterSigner`, which
+//! signs many letters with the same certificate.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::hex::ToHex;
+
+use edcert::certificate::Certificate;
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::signature::Signature;
+
+use clock::Clock;
+use clock::SystemClock;
+use header::Header;
+use letter::Letter;
+
+/// Something that can sign a letter.
+#[derive(Clone, Copy)]
+pub enum Signer<'a> {
+    /// A private key. If it is the master private key, the letter is signed by the master.
+    PrivateKey(&'a [u8]),
+    /// A certificate. It must have a private key.
+    Certificate(&'a Certificate),
+}
+
+/// The length of an Ed25519 private key, which ends with the public key.
+pub const PRIVATE_KEY_BYTES: usize = 64;
+
+/// The reasons signing can fail.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum SignError {
+    /// The certificate has no private key.
+    NoPrivateKey,
+    /// The private key isn't an Ed25519 private key.
+    InvalidKey,
+    /// The content can't be signed in this form.
+    InvalidContent,
+}
+
+impl fmt::Display for SignError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            SignError::NoPrivateKey => write!(f, "the certificate has no private key"),
+            SignError::InvalidKey => write!(f, "the private key is not an Ed25519 private key"),
+            SignError::InvalidContent => write!(f, "the content can't be signed"),
+        }
+    }
+}
+
+impl Error for SignError {}
+
+/// Returns the public key that makes up the second half of an Ed25519 private key. It fails, if
+/// the key has the wrong length.
+pub fn public_key_of(private_key: &[u8]) -> Result<&[u8], SignError> {
+    if private_key.len() != PRIVATE_KEY_BYTES {
+        return Err(SignError::InvalidKey);
+    }
+
+    Ok(&private_key[32..])
+}
+
+impl<'a> Signer<'a> {
+    /// Signs the given bytes. It fails, if the certificate has no private key or the private key
+    /// has the wrong length.
+    pub fn sign(&self, bytes: &[u8]) -> Result<Signature, SignError> {
+        match *self {
+            Signer::PrivateKey(private_key) => {
+                public_key_of(private_key)?;
+                Ok(Signature::new(ed25519::sign(bytes, private_key)))
+            }
+            Signer::Certificate(cert) => {
+                // This next call can fail, if the given certificate has no private key.
+                cert.sign(bytes)
+                    .map(|hash| Signature::with_parent(Box::new(public_copy(cert)), hash))
+                    .ok_or(SignError::NoPrivateKey)
+            }
+        }
+    }
+
+    /// Returns the certificate, if this signer is one.
+    pub fn certificate(&self) -> Option<&'a Certificate> {
+        match *self {
+            Signer::PrivateKey(_) => None,
+            Signer::Certificate(cert) => Some(cert),
+        }
+    }
+}
+
+/// Signers are shown with their public key only, so they can be logged without leaking the
+/// private key.
+impl<'a> fmt::Debug for Signer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Signer::PrivateKey(private_key) => f.debug_tuple("PrivateKey").field(&RedactedKey(private_key)).finish(),
+            Signer::Certificate(cert) => f.debug_tuple("Certificate").field(&cert.public_key().to_hex()).finish(),
+        }
+    }
+}
+
+/// Shows a private key as the public key in its second half, for the `Debug` output of anything
+/// that holds one.
+pub(crate) struct RedactedKey<'a>(pub &'a [u8]);
+
+impl<'a> fmt::Debug for RedactedKey<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match public_key_of(self.0) {
+            Ok(public_key) => write!(f, "{:?}", public_key.to_hex()),
+            Err(_) => write!(f, "<invalid key>"),
+        }
+    }
+}
+
+/// Returns a copy of the certificate without its private key, which is what goes into a letter.
+fn public_copy(cert: &Certificate) -> Certificate {
+    let mut public = cert.clone();
+    public.remove_private_key();
+    public
+}
+
+/// Signs many letters with one certificate.
+///
+/// Every letter has to own the certificate that signed it, because edcert's `Signature` holds it
+/// in a `Box`. `Signer::Certificate` builds that copy from scratch on every call. A `LetterSigner`
+/// checks the private key and prepares the copy without the private key once, so signing a letter
+/// only clones the prepared copy.
+#[derive(Clone)]
+pub struct LetterSigner {
+    cert: Certificate,
+    public: Certificate,
+}
+
+impl LetterSigner {
+    /// Creates a signer. It fails, if the certificate has no private key.
+    pub fn new(cert: Certificate) -> Result<LetterSigner, SignError> {
+        if !cert.has_private_key() {
+            return Err(SignError::NoPrivateKey);
+        }
+
+        Ok(LetterSigner {
+            public: public_copy(&cert),
+            cert,
+        })
+    }
+
+    /// Returns the certificate as it is put into the letters, without the private key.
+    pub fn certificate(&self) -> &Certificate {
+        &self.public
+    }
+
+    /// Signs the content and the header, like `Letter::sign`.
+    pub fn sign<T: Fingerprint>(&self, content: T, header: Header) -> Result<Letter<T>, SignError> {
+        self.sign_with_clock(content, header, &SystemClock)
+    }
+
+    /// Signs like `sign`, but takes the signing time from the given clock.
+    pub fn sign_with_clock<T: Fingerprint, C: Clock>(&self,
+                                                     content: T,
+                                                     mut header: Header,
+                                                     clock: &C)
+                                                     -> Result<Letter<T>, SignError> {
+        header.set_signed_at(clock.now());
+        let bytes = header.signed_bytes(&content.fingerprint());
+        let hash = self.cert.sign(&bytes).ok_or(SignError::NoPrivateKey)?;
+        Ok(Letter::from_parts(content, header, Signature::with_parent(Box::new(self.public.clone()), hash)))
+    }
+}
+
+/// Only the certificate without the private key is shown.
+impl fmt::Debug for LetterSigner {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("LetterSigner").field("certificate", &self.public).finish()
+    }
+}
+
+#[test]
+fn test_letter_signer() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use clock::ManualClock;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use edcert::validator::Validator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let signer = LetterSigner::new(cert.clone()).unwrap();
+    for i in 0..3 {
+        let letter = signer.sign(if i % 2 == 0 { "even" } else { "odd" }, Header::new()).unwrap();
+        assert_eq!(true, cv.is_valid(&letter).is_ok());
+        assert_eq!(false, letter.signer_certificate().unwrap().has_private_key());
+    }
+
+    // Letters signed through `Signer` don't carry the private key either.
+    let letter = Letter::with_certificate("hello", &cert).unwrap();
+    assert_eq!(false, letter.signer_certificate().unwrap().has_private_key());
+
+    let clock = ManualClock::new(UTC::now() - Duration::hours(1));
+    let letter = signer.sign_with_clock("earlier", Header::new(), &clock).unwrap();
+    assert_eq!(clock.now(), *letter.signed_at());
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+
+    assert_eq!(false, format!("{:?}", signer).contains(&cert.private_key().unwrap().to_hex()));
+    assert_eq!(true, LetterSigner::new(signer.certificate().clone()).is_err());
+}
+
+#[test]
+fn test_signer_debug() {
+    let (pk, sk) = ed25519::generate_keypair();
+    let debug = format!("{:?}", Signer::PrivateKey(&sk));
+
+    assert_eq!(true, debug.contains(&pk.to_hex()));
+    assert_eq!(false, debug.contains(&sk[..32].to_hex()));
+}
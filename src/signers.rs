@@ -0,0 +1,189 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Allow and deny lists of signers.
+//!
+//! Many certificates can chain to the same master key, but an application may only want to
+//! accept letters from some of them. `AllowedSigners` validates letters with another validator
+//! and additionally requires the signer id, see `Letter::signer_id`, to be on its list.
+//! `DeniedSigners` rejects letters if the signer or any certificate above it is on its list, so
+//! denying an intermediate certificate denies everything it issued. Unlike revocation, the lists
+//! are local to the application.
+
+use std::collections::BTreeSet;
+
+use rustc_serialize::hex::ToHex;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use letter::Letter;
+
+/// Returns the id of the certificate, as `Letter::signer_id` returns it for letters it signed.
+pub fn certificate_id(cert: &Certificate) -> String {
+    cert.public_key().to_hex()
+}
+
+/// A validator that only accepts letters of the listed signers.
+pub struct AllowedSigners<V: Validator> {
+    inner: V,
+    signers: BTreeSet<String>,
+}
+
+impl<V: Validator> AllowedSigners<V> {
+    /// Wraps the validator. Without signers, no letter is accepted.
+    pub fn new(inner: V) -> AllowedSigners<V> {
+        AllowedSigners {
+            inner,
+            signers: BTreeSet::new(),
+        }
+    }
+
+    /// Allows the signer id. `MASTER_SIGNER` allows letters signed with the master key.
+    pub fn allow(mut self, signer_id: &str) -> AllowedSigners<V> {
+        self.signers.insert(signer_id.to_lowercase());
+        self
+    }
+
+    /// Allows the certificate.
+    pub fn allow_certificate(self, cert: &Certificate) -> AllowedSigners<V> {
+        let id = certificate_id(cert);
+        self.allow(&id)
+    }
+
+    /// Returns the allowed signer ids.
+    pub fn signers(&self) -> &BTreeSet<String> {
+        &self.signers
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Validates the letter with the wrapped validator and checks that its signer is allowed.
+    /// Letters of other signers yield `ValidationError::Other`.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.inner.is_valid(letter)?;
+
+        if self.signers.contains(&letter.signer_id()) {
+            Ok(())
+        } else {
+            trace_event!(info, "signer isn't allowed");
+            Err(ValidationError::Other)
+        }
+    }
+}
+
+/// A validator that rejects letters of the listed signers.
+pub struct DeniedSigners<V: Validator> {
+    inner: V,
+    signers: BTreeSet<String>,
+}
+
+impl<V: Validator> DeniedSigners<V> {
+    /// Wraps the validator. Without signers, it accepts what the wrapped validator accepts.
+    pub fn new(inner: V) -> DeniedSigners<V> {
+        DeniedSigners {
+            inner,
+            signers: BTreeSet::new(),
+        }
+    }
+
+    /// Denies the signer id.
+    pub fn deny(mut self, signer_id: &str) -> DeniedSigners<V> {
+        self.signers.insert(signer_id.to_lowercase());
+        self
+    }
+
+    /// Denies the certificate and every certificate it issued.
+    pub fn deny_certificate(self, cert: &Certificate) -> DeniedSigners<V> {
+        let id = certificate_id(cert);
+        self.deny(&id)
+    }
+
+    /// Returns the denied signer ids.
+    pub fn signers(&self) -> &BTreeSet<String> {
+        &self.signers
+    }
+
+    /// Returns the wrapped validator.
+    pub fn inner(&self) -> &V {
+        &self.inner
+    }
+
+    /// Validates the letter with the wrapped validator and checks that neither its signer nor a
+    /// certificate above it is denied. Denied letters yield `ValidationError::Other`.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.inner.is_valid(letter)?;
+
+        let denied = self.signers.contains(&letter.signer_id()) ||
+                     letter.signer_chain().iter().any(|cert| self.signers.contains(&certificate_id(cert)));
+
+        if denied {
+            trace_event!(info, "signer is denied");
+            Err(ValidationError::Other)
+        } else {
+            Ok(())
+        }
+    }
+}
+
+#[test]
+fn test_signer_lists() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use letter::MASTER_SIGNER;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let expires = UTC::now() + Duration::days(90);
+
+    let mut issuer = Certificate::generate_random(Meta::new_empty(), expires);
+    issuer.sign_with_master(&msk);
+    let mut leaf = Certificate::generate_random(Meta::new_empty(), expires);
+    leaf.sign_with_parent(&issuer).unwrap();
+    let mut other = Certificate::generate_random(Meta::new_empty(), expires);
+    other.sign_with_master(&msk);
+
+    let by_leaf = Letter::with_certificate("hello", &leaf).unwrap();
+    let by_other = Letter::with_certificate("hello", &other).unwrap();
+    let by_master = Letter::with_private_key("hello", &msk);
+
+    let allowed = AllowedSigners::new(RootValidator::new(&mpk, NoRevoker))
+                      .allow_certificate(&leaf)
+                      .allow(MASTER_SIGNER);
+    assert_eq!(Ok(()), allowed.validate(&by_leaf));
+    assert_eq!(Ok(()), allowed.validate(&by_master));
+    assert_eq!(Err(ValidationError::Other), allowed.validate(&by_other));
+
+    let denied = DeniedSigners::new(RootValidator::new(&mpk, NoRevoker)).deny_certificate(&issuer);
+    assert_eq!(Err(ValidationError::Other), denied.validate(&by_leaf));
+    assert_eq!(Ok(()), denied.validate(&by_other));
+}
@@ -0,0 +1,461 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! A persistent store of letters.
+//!
+//! `LetterStore` keeps letters in a directory, one file per letter, for services that must retain
+//! all letters they received. A letter is keyed by its signer, the hex encoded public key of the
+//! signing certificate or `master` for letters signed by the master key, and the SHA-512 digest
+//! of its content fingerprint. The files are laid out as `<signer>/<fingerprint>.edl` and are
+//! written atomically, so the store stays consistent if the process crashes.
+//!
+//! `LetterStore::gc` removes letters according to a `RetentionPolicy`: letters past the expiry
+//! time in their header and letters signed longer ago than a maximum age.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::marker::PhantomData;
+use std::path::Path;
+use std::path::PathBuf;
+use std::vec;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::UTC;
+use rustc_serialize::hex::ToHex;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+
+use clock::Clock;
+use clock::SystemClock;
+use file;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use view::LetterView;
+
+pub use letter::MASTER_SIGNER;
+
+/// The file extension of stored letters.
+const EXTENSION: &str = "edl";
+
+/// This error is returned, if the store can't be accessed.
+#[derive(Debug)]
+pub enum StoreError {
+    /// The store directory can't be read or written.
+    Io(io::Error),
+    /// A stored file doesn't contain a letter.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            StoreError::Io(ref e) => write!(f, "can't access letter store: {}", e),
+            StoreError::Decode(ref e) => write!(f, "malformed letter in store: {}", e),
+        }
+    }
+}
+
+impl Error for StoreError {}
+
+/// The key of a stored letter.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub struct StoreKey {
+    /// The signer of the letter.
+    pub signer: String,
+    /// The digest of the content fingerprint, hex encoded.
+    pub fingerprint: String,
+}
+
+impl StoreKey {
+    /// Returns the key of the letter.
+    pub fn of<T: Fingerprint>(letter: &Letter<T>) -> StoreKey {
+        StoreKey {
+            signer: signer_id(letter),
+            fingerprint: content_digest(letter.get()),
+        }
+    }
+}
+
+impl fmt::Display for StoreKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}/{}", self.signer, self.fingerprint)
+    }
+}
+
+/// Returns the signer id of the letter, see `Letter::signer_id`.
+pub fn signer_id<T: Fingerprint>(letter: &Letter<T>) -> String {
+    letter.signer_id()
+}
+
+/// Returns the digest of the content fingerprint, hex encoded.
+pub fn content_digest<T: Fingerprint>(content: &T) -> String {
+    sha512::hash(&content.fingerprint()).0.to_hex()
+}
+
+/// Returns true, if the name is a hex string or `master`, so it can't leave the store directory.
+fn is_valid_name(name: &str) -> bool {
+    name == MASTER_SIGNER || (!name.is_empty() && name.bytes().all(|b| b.is_ascii_hexdigit()))
+}
+
+/// Which letters `LetterStore::gc` removes.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RetentionPolicy {
+    /// Remove letters whose expiry time has passed, and letters with a malformed expiry time.
+    pub remove_expired: bool,
+    /// Remove letters signed longer ago than this.
+    pub max_age: Option<Duration>,
+}
+
+impl Default for RetentionPolicy {
+    fn default() -> RetentionPolicy {
+        RetentionPolicy {
+            remove_expired: true,
+            max_age: None,
+        }
+    }
+}
+
+impl RetentionPolicy {
+    /// Creates a policy that only removes expired letters.
+    pub fn new() -> RetentionPolicy {
+        RetentionPolicy::default()
+    }
+
+    /// Also removes letters signed longer ago than the given age.
+    pub fn with_max_age(mut self, max_age: Duration) -> RetentionPolicy {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Returns true, if a letter signed with the header should be removed at the given time.
+    pub fn should_remove(&self, header: &Header, now: &DateTime<UTC>) -> bool {
+        let expired = self.remove_expired &&
+                      match header.expires() {
+                          Ok(expires) => expires.is_some_and(|e| e <= *now),
+                          Err(_) => true,
+                      };
+        let too_old = self.max_age.is_some_and(|max| *now - *header.signed_at() > max);
+        expired || too_old
+    }
+}
+
+/// A directory of letters, keyed by signer and content fingerprint.
+#[derive(Clone, Debug)]
+pub struct LetterStore {
+    root: PathBuf,
+}
+
+impl LetterStore {
+    /// Opens the store in the directory, creating it if it doesn't exist.
+    pub fn open<P: AsRef<Path>>(root: P) -> Result<LetterStore, StoreError> {
+        fs::create_dir_all(&root).map_err(StoreError::Io)?;
+
+        Ok(LetterStore { root: root.as_ref().to_path_buf() })
+    }
+
+    /// Returns the directory of the store.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, key: &StoreKey) -> Option<PathBuf> {
+        if is_valid_name(&key.signer) && is_valid_name(&key.fingerprint) {
+            Some(self.root.join(&key.signer).join(format!("{}.{}", key.fingerprint, EXTENSION)))
+        } else {
+            None
+        }
+    }
+
+    /// Stores the letter and returns its key. A letter with the same key is replaced.
+    pub fn put<T: FromFingerprint>(&self, letter: &Letter<T>) -> Result<StoreKey, StoreError> {
+        let key = StoreKey::of(letter);
+        let path = self.path(&key).expect("generated keys are valid");
+
+        fs::create_dir_all(self.root.join(&key.signer)).map_err(StoreError::Io)?;
+        file::write_atomic(path, &letter.to_bytes()).map_err(StoreError::Io)?;
+
+        Ok(key)
+    }
+
+    fn read(&self, key: &StoreKey) -> Result<Option<Vec<u8>>, StoreError> {
+        let path = match self.path(key) {
+            Some(path) => path,
+            None => return Ok(None),
+        };
+
+        match fs::read(path) {
+            Ok(bytes) => Ok(Some(bytes)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(StoreError::Io(e)),
+        }
+    }
+
+    /// Returns the letter with the key, if it is stored.
+    pub fn get<T: FromFingerprint>(&self, key: &StoreKey) -> Result<Option<Letter<T>>, StoreError> {
+        match self.read(key)? {
+            Some(bytes) => Letter::from_bytes(&bytes).map(Some).map_err(StoreError::Decode),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns true, if a letter with the key is stored.
+    pub fn contains(&self, key: &StoreKey) -> bool {
+        self.path(key).is_some_and(|p| p.is_file())
+    }
+
+    /// Removes the letter with the key. Returns false, if it wasn't stored.
+    pub fn remove(&self, key: &StoreKey) -> Result<bool, StoreError> {
+        let path = match self.path(key) {
+            Some(path) => path,
+            None => return Ok(false),
+        };
+
+        match fs::remove_file(path) {
+            Ok(()) => Ok(true),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(StoreError::Io(e)),
+        }
+    }
+
+    /// Returns the signers of all stored letters.
+    pub fn signers(&self) -> Result<Vec<String>, StoreError> {
+        let mut signers = Vec::new();
+
+        for entry in fs::read_dir(&self.root).map_err(StoreError::Io)? {
+            let entry = entry.map_err(StoreError::Io)?;
+
+            if let Some(name) = entry.file_name().to_str() {
+                if is_valid_name(name) && entry.path().is_dir() {
+                    signers.push(name.to_string());
+                }
+            }
+        }
+
+        signers.sort();
+        Ok(signers)
+    }
+
+    /// Returns the keys of the letters by the signer, sorted.
+    pub fn keys_by_signer(&self, signer: &str) -> Result<Vec<StoreKey>, StoreError> {
+        if !is_valid_name(signer) {
+            return Ok(Vec::new());
+        }
+
+        let dir = match fs::read_dir(self.root.join(signer)) {
+            Ok(dir) => dir,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(StoreError::Io(e)),
+        };
+
+        let mut keys = Vec::new();
+
+        for entry in dir {
+            let path = entry.map_err(StoreError::Io)?.path();
+
+            // Skips temporary files of interrupted writes.
+            if path.extension().and_then(|e| e.to_str()) != Some(EXTENSION) {
+                continue;
+            }
+
+            if let Some(stem) = path.file_stem().and_then(|s| s.to_str()) {
+                if is_valid_name(stem) {
+                    keys.push(StoreKey {
+                        signer: signer.to_string(),
+                        fingerprint: stem.to_string(),
+                    });
+                }
+            }
+        }
+
+        keys.sort();
+        Ok(keys)
+    }
+
+    /// Returns the keys of all stored letters, sorted.
+    pub fn keys(&self) -> Result<Vec<StoreKey>, StoreError> {
+        let mut keys = Vec::new();
+
+        for signer in self.signers()? {
+            keys.extend(self.keys_by_signer(&signer)?);
+        }
+
+        Ok(keys)
+    }
+
+    /// Returns the keys of the letters with the content, by any signer.
+    pub fn find<T: Fingerprint>(&self, content: &T) -> Result<Vec<StoreKey>, StoreError> {
+        let fingerprint = content_digest(content);
+
+        Ok(self.keys()?.into_iter().filter(|k| k.fingerprint == fingerprint).collect())
+    }
+
+    /// Removes the letters the policy doesn't retain and returns their keys. Files that don't
+    /// contain a letter are left alone.
+    pub fn gc(&self, policy: &RetentionPolicy) -> Result<Vec<StoreKey>, StoreError> {
+        self.gc_with_clock(policy, &SystemClock)
+    }
+
+    /// Like `gc`, but takes the current time from the clock.
+    pub fn gc_with_clock<C: Clock>(&self, policy: &RetentionPolicy, clock: &C) -> Result<Vec<StoreKey>, StoreError> {
+        let now = clock.now();
+        let mut removed = Vec::new();
+
+        for key in self.keys()? {
+            let bytes = match self.read(&key)? {
+                Some(bytes) => bytes,
+                None => continue,
+            };
+
+            let header = match LetterView::parse(&bytes).and_then(|v| v.header()) {
+                Ok(header) => header,
+                Err(_) => continue,
+            };
+
+            if policy.should_remove(&header, &now) && self.remove(&key)? {
+                removed.push(key);
+            }
+        }
+
+        Ok(removed)
+    }
+
+    /// Returns an iterator over all stored letters. Letters are read lazily.
+    pub fn iter<'a, T: FromFingerprint>(&'a self) -> Result<Letters<'a, T>, StoreError> {
+        Ok(Letters {
+            store: self,
+            keys: self.keys()?.into_iter(),
+            content: PhantomData,
+        })
+    }
+}
+
+/// An iterator over the letters of a store.
+pub struct Letters<'a, T: FromFingerprint> {
+    store: &'a LetterStore,
+    keys: vec::IntoIter<StoreKey>,
+    content: PhantomData<T>,
+}
+
+impl<'a, T: FromFingerprint> Iterator for Letters<'a, T> {
+    type Item = Result<(StoreKey, Letter<T>), StoreError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        for key in &mut self.keys {
+            match self.store.get(&key) {
+                Ok(Some(letter)) => return Some(Ok((key, letter))),
+                // Removed since the keys were listed.
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+
+        None
+    }
+}
+
+#[test]
+fn test_letter_store() {
+    use std::env;
+    use std::process;
+
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use canonical::Fingerprintable;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+
+    let root = env::temp_dir().join(format!("edcert-letter-store-{}", process::id()));
+    let store = LetterStore::open(&root).unwrap();
+
+    let hello = Fingerprintable("hello".to_string());
+    let a = store.put(&Letter::with_private_key(hello.clone(), &msk)).unwrap();
+    let b = store.put(&Letter::with_certificate(hello.clone(), &cert).unwrap()).unwrap();
+    let c = store.put(&Letter::with_private_key(Fingerprintable("world".to_string()), &msk)).unwrap();
+
+    assert_eq!(MASTER_SIGNER, a.signer);
+    assert_eq!(3, store.keys().unwrap().len());
+    assert_eq!(2, store.find(&hello).unwrap().len());
+    assert_eq!(2, store.keys_by_signer(MASTER_SIGNER).unwrap().len());
+
+    let letter: Letter<Fingerprintable<String>> = store.get(&b).unwrap().unwrap();
+    assert_eq!("hello", letter.as_str());
+    assert_eq!(3, store.iter::<Fingerprintable<String>>().unwrap().count());
+
+    assert_eq!(true, store.remove(&c).unwrap());
+    assert_eq!(false, store.contains(&c));
+    assert_eq!(None, store.get::<Fingerprintable<String>>(&c).unwrap().map(|l| l.into_inner()));
+
+    let escape = StoreKey {
+        signer: "..".to_string(),
+        fingerprint: "x".to_string(),
+    };
+    assert_eq!(false, store.remove(&escape).unwrap());
+
+    fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_gc() {
+    use std::env;
+    use std::process;
+
+    use edcert::ed25519;
+    use canonical::Fingerprintable;
+    use clock::ManualClock;
+    use header::EXPIRES_KEY;
+    use signer::Signer;
+
+    let (_, msk) = ed25519::generate_keypair();
+    let root = env::temp_dir().join(format!("edcert-letter-gc-{}", process::id()));
+    let store = LetterStore::open(&root).unwrap();
+    let sign = |text: &str, header: Header| Letter::sign(Fingerprintable(text.to_string()), header, &Signer::PrivateKey(&msk)).unwrap();
+
+    let mut expiring = Header::new();
+    expiring.set_expires(UTC::now() + Duration::hours(1));
+    let expiring = store.put(&sign("expiring", expiring)).unwrap();
+    let kept = store.put(&sign("kept", Header::new())).unwrap();
+
+    let clock = ManualClock::new(UTC::now() + Duration::hours(2));
+    assert_eq!(vec![expiring], store.gc_with_clock(&RetentionPolicy::new(), &clock).unwrap());
+    assert_eq!(vec![kept.clone()], store.keys().unwrap());
+
+    let policy = RetentionPolicy::new().with_max_age(Duration::days(1));
+    assert_eq!(0, store.gc_with_clock(&policy, &clock).unwrap().len());
+    clock.advance(Duration::days(2));
+    assert_eq!(vec![kept], store.gc_with_clock(&policy, &clock).unwrap());
+
+    let mut malformed = Header::new();
+    malformed.set_meta(EXPIRES_KEY, "next week");
+    assert_eq!(true, RetentionPolicy::new().should_remove(&malformed, &UTC::now()));
+
+    fs::remove_dir_all(&root).unwrap();
+}
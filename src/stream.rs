@@ -0,0 +1,177 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Adapters for `futures::Stream`.
+//!
+//! `SignExt::sign_letters` signs every item of a stream into a letter. `VerifyExt::verify_letters`
+//! validates incoming letters and yields the content of the valid ones. Rejected letters are
+//! dropped, or sent to a channel together with the error, if one is given with
+//! `verify_letters_with_rejects`.
+
+use std::pin::Pin;
+use std::task::Context;
+use std::task::Poll;
+
+use futures::channel::mpsc::UnboundedSender;
+use futures::Stream;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// A stream that signs the items of another stream. See `SignExt::sign_letters`.
+pub struct SignLetters<'a, S> {
+    inner: S,
+    header: Header,
+    signer: Signer<'a>,
+}
+
+impl<'a, S> Stream for SignLetters<'a, S>
+    where S: Stream + Unpin,
+          S::Item: Fingerprint
+{
+    type Item = Result<Letter<S::Item>, SignError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<Self::Item>> {
+        match Pin::new(&mut self.inner).poll_next(cx) {
+            Poll::Ready(Some(item)) => {
+                let header = self.header.clone();
+                Poll::Ready(Some(Letter::sign(item, header, &self.signer)))
+            }
+            Poll::Ready(None) => Poll::Ready(None),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Signing of the items of a stream.
+pub trait SignExt: Stream + Sized {
+    /// Signs every item into a letter with a copy of the header. The items fail like
+    /// `Letter::sign` does, if the signer has no private key.
+    fn sign_letters<'a>(self, header: Header, signer: Signer<'a>) -> SignLetters<'a, Self> {
+        SignLetters {
+            inner: self,
+            header,
+            signer,
+        }
+    }
+}
+
+impl<S: Stream> SignExt for S where S::Item: Fingerprint {}
+
+/// A stream that yields the content of the valid letters of another stream. See
+/// `VerifyExt::verify_letters`.
+pub struct VerifyLetters<S, V, T: Fingerprint> {
+    inner: S,
+    cv: V,
+    rejects: Option<UnboundedSender<(Letter<T>, ValidationError)>>,
+}
+
+impl<S, V, T> Stream for VerifyLetters<S, V, T>
+    where S: Stream<Item = Letter<T>> + Unpin,
+          V: Validator + Unpin,
+          T: Fingerprint
+{
+    type Item = T;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Option<T>> {
+        loop {
+            let letter = match Pin::new(&mut self.inner).poll_next(cx) {
+                Poll::Ready(Some(letter)) => letter,
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+
+            match self.cv.is_valid(&letter) {
+                Ok(()) => return Poll::Ready(Some(letter.into_inner())),
+                Err(e) => {
+                    // A closed channel only means nobody is interested in the rejects anymore.
+                    if let Some(ref rejects) = self.rejects {
+                        let _ = rejects.unbounded_send((letter, e));
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Validation of the letters of a stream.
+pub trait VerifyExt<T: Fingerprint>: Stream<Item = Letter<T>> + Sized {
+    /// Validates every letter and yields the content of the valid ones. Invalid letters are
+    /// dropped.
+    fn verify_letters<V: Validator>(self, cv: V) -> VerifyLetters<Self, V, T> {
+        VerifyLetters {
+            inner: self,
+            cv,
+            rejects: None,
+        }
+    }
+
+    /// Validates every letter like `verify_letters`, but sends invalid letters to the channel
+    /// together with the reason.
+    fn verify_letters_with_rejects<V: Validator>(self,
+                                                 cv: V,
+                                                 rejects: UnboundedSender<(Letter<T>, ValidationError)>)
+                                                 -> VerifyLetters<Self, V, T> {
+        VerifyLetters {
+            inner: self,
+            cv,
+            rejects: Some(rejects),
+        }
+    }
+}
+
+impl<T: Fingerprint, S: Stream<Item = Letter<T>>> VerifyExt<T> for S {}
+
+#[test]
+fn test_stream() {
+    use futures::channel::mpsc;
+    use futures::executor::block_on;
+    use futures::stream;
+    use futures::StreamExt;
+
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let (_, other) = ed25519::generate_keypair();
+
+    let signed = stream::iter(vec!["a", "b"]).sign_letters(Header::new(), Signer::PrivateKey(&msk));
+    let mut letters: Vec<Letter<&str>> = block_on(signed.map(|r| r.unwrap()).collect());
+    letters.insert(1, Letter::with_private_key("evil", &other));
+
+    let (tx, rx) = mpsc::unbounded();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let verified: Vec<&str> = block_on(stream::iter(letters).verify_letters_with_rejects(cv, tx).collect());
+    assert_eq!(vec!["a", "b"], verified);
+
+    let rejects: Vec<(Letter<&str>, ValidationError)> = block_on(rx.collect());
+    assert_eq!(1, rejects.len());
+    assert_eq!("evil", *rejects[0].0.get());
+}
@@ -0,0 +1,189 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Strict checks of Ed25519 signatures and keys.
+//!
+//! Ed25519 signatures are malleable, if verifiers accept any scalar `S`: adding the group order
+//! to `S` gives different signature bytes that still verify. Systems that key caches or replay
+//! protection off signature bytes then see two letters where there is one. Public keys of small
+//! order are just as bad, because a signature made with them verifies for many messages.
+//!
+//! Letter validation is strict by default: it rejects signatures with a non-canonical `S` and
+//! certificates with a small order key anywhere in the chain, before any signature is checked.
+//! `VerificationMode::Lenient` skips these checks, for letters signed by old implementations.
+//! `LetterView`, the TOFU validator, threshold letters and the JWS, COSE, PASETO and credential
+//! verifiers are always strict.
+
+use edcert::certificate::Certificate;
+use edcert::signature::Signature;
+use edcert::validator::ValidationError;
+
+/// The order of the Ed25519 base point, little endian.
+const GROUP_ORDER: [u8; 32] = [0xed, 0xd3, 0xf5, 0x5c, 0x1a, 0x63, 0x12, 0x58, 0xd6, 0x9c, 0xf7,
+                               0xa2, 0xde, 0xf9, 0xde, 0x14, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+                               0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x10];
+
+/// The encodings of the points of small order, without the sign bit, as libsodium lists them.
+const SMALL_ORDER_POINTS: [[u8; 32]; 7] = [
+    // 0 (order 4)
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+     0x00, 0x00],
+    // 1 (order 1)
+    [0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+     0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+     0x00, 0x00],
+    // order 8
+    [0x26, 0xe8, 0x95, 0x8f, 0xc2, 0xb2, 0x27, 0xb0, 0x45, 0xc3, 0xf4, 0x89, 0xf2, 0xef, 0x98,
+     0xf0, 0xd5, 0xdf, 0xac, 0x05, 0xd3, 0xc6, 0x33, 0x39, 0xb1, 0x38, 0x02, 0x88, 0x6d, 0x53,
+     0xfc, 0x05],
+    // order 8
+    [0xc7, 0x17, 0x6a, 0x70, 0x3d, 0x4d, 0xd8, 0x4f, 0xba, 0x3c, 0x0b, 0x76, 0x0d, 0x10, 0x67,
+     0x0f, 0x2a, 0x20, 0x53, 0xfa, 0x2c, 0x39, 0xcc, 0xc6, 0x4e, 0xc7, 0xfd, 0x77, 0x92, 0xac,
+     0x03, 0x7a],
+    // p - 1 (order 2)
+    [0xec, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0x7f],
+    // p, a non-canonical 0 (order 4)
+    [0xed, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0x7f],
+    // p + 1, a non-canonical 1 (order 1)
+    [0xee, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+     0xff, 0x7f],
+];
+
+/// How strictly signatures and keys are checked.
+#[derive(Clone, Copy, PartialEq, Eq, Debug, Default)]
+pub enum VerificationMode {
+    /// Reject non-canonical signatures and small order keys, the default.
+    #[default]
+    Strict,
+    /// Accept whatever the Ed25519 implementation accepts.
+    Lenient,
+}
+
+
+/// Returns true, if the signature is 64 bytes long and its scalar `S` is smaller than the group
+/// order.
+pub fn is_canonical_signature(signature: &[u8]) -> bool {
+    if signature.len() != 64 {
+        return false;
+    }
+
+    // Compare S with the group order from the most significant byte down.
+    for (s, l) in signature[32..].iter().rev().zip(GROUP_ORDER.iter().rev()) {
+        if s != l {
+            return s < l;
+        }
+    }
+
+    false
+}
+
+/// Returns true, if the public key is a point of small order, or isn't 32 bytes long.
+pub fn has_small_order(public_key: &[u8]) -> bool {
+    if public_key.len() != 32 {
+        return true;
+    }
+
+    SMALL_ORDER_POINTS.iter().any(|point| {
+        public_key[..31] == point[..31] && public_key[31] & 0x7f == point[31]
+    })
+}
+
+/// Checks the signature of a letter and every certificate in its chain. The signer yields
+/// `ValidationError::SignatureInvalid`, the certificates above it `ValidationError::ParentInvalid`.
+pub fn check_signature(signature: &Signature) -> Result<(), ValidationError> {
+    check_signature_bytes(signature.hash(), signature.parent())
+}
+
+/// Like `check_signature`, for a signature that isn't wrapped in a `Signature`, like the ones of
+/// `LetterView` or a JWS. `parent` is the certificate that made it, or None for the master key.
+pub fn check_signature_bytes(signature: &[u8], parent: Option<&Certificate>) -> Result<(), ValidationError> {
+    if !is_canonical_signature(signature) {
+        return Err(ValidationError::SignatureInvalid);
+    }
+
+    let mut error = ValidationError::SignatureInvalid;
+    let mut parent = parent;
+
+    while let Some(cert) = parent {
+        if has_small_order(cert.public_key()) {
+            return Err(error);
+        }
+
+        error = ValidationError::ParentInvalid;
+
+        if let Some(sig) = cert.signature() {
+            if !is_canonical_signature(sig.hash()) {
+                return Err(error);
+            }
+        }
+
+        parent = cert.signature().and_then(|sig| sig.parent());
+    }
+
+    Ok(())
+}
+
+/// Returns the signature with the group order added to `S`, which lenient verifiers still accept.
+#[cfg(test)]
+pub fn malleate(signature: &[u8]) -> Vec<u8> {
+    let mut malleated = signature.to_vec();
+    let mut carry = 0u16;
+    for i in 0..32 {
+        let sum = malleated[32 + i] as u16 + GROUP_ORDER[i] as u16 + carry;
+        malleated[32 + i] = sum as u8;
+        carry = sum >> 8;
+    }
+
+    malleated
+}
+
+#[test]
+fn test_strict() {
+    use edcert::ed25519;
+
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    assert_eq!(false, has_small_order(&mpk));
+    assert_eq!(true, has_small_order(&SMALL_ORDER_POINTS[3]));
+
+    let mut negative = SMALL_ORDER_POINTS[1];
+    negative[31] |= 0x80;
+    assert_eq!(true, has_small_order(&negative));
+
+    let letter = Letter::with_private_key("hello", &msk);
+    assert_eq!(true, is_canonical_signature(letter.signature().hash()));
+
+    // S + L verifies with lenient implementations, but is the same signature.
+    let malleated = malleate(letter.signature().hash());
+
+    assert_eq!(false, is_canonical_signature(&malleated));
+    assert_eq!(Err(ValidationError::SignatureInvalid),
+               check_signature(&Signature::new(malleated)));
+}
@@ -0,0 +1,186 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Helpers for testing code that handles letters, behind the `test-utils` feature.
+//!
+//! `keypair` derives keys from a name, so tests get the same keys on every run. `AcceptAll` and
+//! `RejectAll` are validators that don't look at the letter at all, and `ScriptedRevoker`
+//! answers revocation checks the way a test tells it to.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::Mutex;
+
+use sodiumoxide::crypto::hash::sha256;
+use sodiumoxide::crypto::sign::ed25519;
+
+use edcert::certificate::Certificate;
+use edcert::revoker::Revokable;
+use edcert::revoker::RevokeError;
+use edcert::revoker::Revoker;
+use edcert::validator::Validatable;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+/// Derives an ed25519 keypair from a name. The same name always gives the same keys, so never use
+/// these keys outside of tests.
+pub fn keypair(name: &str) -> (Vec<u8>, Vec<u8>) {
+    let seed = ed25519::Seed(sha256::hash(name.as_bytes()).0);
+    let (pk, sk) = ed25519::keypair_from_seed(&seed);
+    (pk.0.to_vec(), sk.0.to_vec())
+}
+
+/// A validator that accepts everything.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct AcceptAll;
+
+impl Validator for AcceptAll {
+    fn is_signature_valid(&self, _: &[u8], _: &[u8]) -> bool {
+        true
+    }
+
+    fn is_revoked<T: Revokable>(&self, _: &T) -> Result<(), RevokeError> {
+        Ok(())
+    }
+
+    fn is_valid<T: Validatable + Revokable>(&self, _: &T) -> Result<(), ValidationError> {
+        Ok(())
+    }
+}
+
+/// A validator that rejects everything with the given error.
+#[derive(Clone, Debug)]
+pub struct RejectAll(pub ValidationError);
+
+impl Default for RejectAll {
+    fn default() -> RejectAll {
+        RejectAll(ValidationError::SignatureInvalid)
+    }
+}
+
+impl Validator for RejectAll {
+    fn is_signature_valid(&self, _: &[u8], _: &[u8]) -> bool {
+        false
+    }
+
+    fn is_revoked<T: Revokable>(&self, _: &T) -> Result<(), RevokeError> {
+        Err(RevokeError::Revoked)
+    }
+
+    fn is_valid<T: Validatable + Revokable>(&self, _: &T) -> Result<(), ValidationError> {
+        Err(self.0.clone())
+    }
+}
+
+/// A revoker that answers with what it was told for each public key, and records the keys it
+/// was asked about. Clones share their answers, so a test can keep a clone to script the revoker
+/// it handed to a validator.
+#[derive(Clone, Debug)]
+pub struct ScriptedRevoker {
+    answers: Arc<Mutex<HashMap<Vec<u8>, RevokeError>>>,
+    default: Arc<Mutex<Result<(), RevokeError>>>,
+    checked: Arc<Mutex<Vec<Vec<u8>>>>,
+}
+
+impl Default for ScriptedRevoker {
+    fn default() -> ScriptedRevoker {
+        ScriptedRevoker {
+            answers: Arc::new(Mutex::new(HashMap::new())),
+            default: Arc::new(Mutex::new(Ok(()))),
+            checked: Arc::new(Mutex::new(Vec::new())),
+        }
+    }
+}
+
+impl ScriptedRevoker {
+    /// Creates a revoker that considers nothing revoked.
+    pub fn new() -> ScriptedRevoker {
+        ScriptedRevoker::default()
+    }
+
+    /// Answers `RevokeError::Revoked` for the certificate with this public key.
+    pub fn revoke(&self, public_key: &[u8]) {
+        self.fail_with(public_key, RevokeError::Revoked);
+    }
+
+    /// Answers with the error for the certificate with this public key.
+    pub fn fail_with(&self, public_key: &[u8], error: RevokeError) {
+        self.answers.lock().unwrap().insert(public_key.to_vec(), error);
+    }
+
+    /// Removes the answer for the certificate with this public key.
+    pub fn reinstate(&self, public_key: &[u8]) {
+        self.answers.lock().unwrap().remove(public_key);
+    }
+
+    /// Sets the answer for certificates without an answer of their own, for example
+    /// `Err(RevokeError::ServerUnreachable)` to simulate an outage.
+    pub fn set_default(&self, answer: Result<(), RevokeError>) {
+        *self.default.lock().unwrap() = answer;
+    }
+
+    /// Returns the public keys of the certificates that were checked, in order.
+    pub fn checked(&self) -> Vec<Vec<u8>> {
+        self.checked.lock().unwrap().clone()
+    }
+}
+
+impl Revoker for ScriptedRevoker {
+    fn is_revoked(&self, cert: &Certificate) -> Result<(), RevokeError> {
+        self.checked.lock().unwrap().push(cert.public_key().clone());
+
+        match self.answers.lock().unwrap().get(cert.public_key()) {
+            Some(error) => Err(error.clone()),
+            None => self.default.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[test]
+fn test_utils() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::meta::Meta;
+    use edcert::root_validator::RootValidator;
+
+    use letter::Letter;
+
+    let (mpk, msk) = keypair("master");
+    assert_eq!((mpk.clone(), msk.clone()), keypair("master"));
+    assert_eq!(false, mpk == keypair("other").0);
+
+    let letter = Letter::with_private_key("hello", &keypair("other").1);
+    assert_eq!(true, AcceptAll.is_valid(&letter).is_ok());
+    assert_eq!(Err(ValidationError::Expired), RejectAll(ValidationError::Expired).is_valid(&letter));
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+    let letter = Letter::with_certificate("hello", &cert).unwrap();
+
+    let revoker = ScriptedRevoker::new();
+    let cv = RootValidator::new(&mpk, revoker.clone());
+    assert_eq!(true, cv.is_valid(&letter).is_ok());
+
+    revoker.revoke(cert.public_key());
+    assert_eq!(false, cv.is_valid(&letter).is_ok());
+    assert_eq!(true, revoker.checked().contains(cert.public_key()));
+}
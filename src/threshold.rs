@@ -0,0 +1,287 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Letters that need the signatures of k out of n keys.
+//!
+//! This is a multi-signature scheme, not a threshold signature scheme like FROST: every
+//! participant signs with their own Ed25519 key and the letter carries all signatures. A verifier
+//! holds a `Quorum` of the n public keys and accepts the letter, if at least k distinct keys of
+//! the quorum signed it. This needs no interactive protocol between the participants and no new
+//! cryptographic primitives, at the cost of a letter that grows with the number of signers.
+
+use std::error::Error;
+use std::fmt;
+
+use chrono::UTC;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use signer::public_key_of;
+use signer::SignError;
+use strict;
+
+/// The bytes every serialized threshold letter starts with.
+pub const THRESHOLD_MAGIC: &[u8] = b"EDT";
+
+/// The reasons a quorum can't be created.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum QuorumError {
+    /// The threshold is 0 or larger than the number of keys.
+    InvalidThreshold,
+    /// A key appears twice, so one signature would count twice.
+    DuplicateKey,
+}
+
+impl fmt::Display for QuorumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            QuorumError::InvalidThreshold => write!(f, "invalid quorum threshold"),
+            QuorumError::DuplicateKey => write!(f, "duplicate quorum key"),
+        }
+    }
+}
+
+impl Error for QuorumError {}
+
+/// The n public keys that may sign and the number k of signatures that are needed.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Quorum {
+    keys: Vec<Vec<u8>>,
+    threshold: usize,
+}
+
+impl Quorum {
+    /// Creates a quorum. It fails, if a key appears twice or the threshold is 0 or larger than
+    /// the number of keys.
+    pub fn new(keys: Vec<Vec<u8>>, threshold: usize) -> Result<Quorum, QuorumError> {
+        if threshold == 0 || threshold > keys.len() {
+            return Err(QuorumError::InvalidThreshold);
+        }
+
+        let mut sorted: Vec<&Vec<u8>> = keys.iter().collect();
+        sorted.sort();
+        sorted.dedup();
+        if sorted.len() != keys.len() {
+            return Err(QuorumError::DuplicateKey);
+        }
+
+        Ok(Quorum {
+            keys,
+            threshold,
+        })
+    }
+
+    /// Returns the public keys.
+    pub fn keys(&self) -> &[Vec<u8>] {
+        &self.keys
+    }
+
+    /// Returns the number of signatures that are needed.
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+}
+
+/// A letter that is signed by several keys.
+#[derive(Clone, PartialEq, Debug)]
+pub struct ThresholdLetter<T: Fingerprint> {
+    content: T,
+    header: Header,
+    signatures: Vec<(Vec<u8>, Vec<u8>)>,
+}
+
+impl<T: Fingerprint> ThresholdLetter<T> {
+    /// Creates an unsigned letter. The signing time is set now, because every participant signs
+    /// the same bytes.
+    pub fn new(content: T, mut header: Header) -> ThresholdLetter<T> {
+        header.set_signed_at(UTC::now());
+
+        ThresholdLetter {
+            content,
+            header,
+            signatures: Vec::new(),
+        }
+    }
+
+    /// Returns the bytes every participant signs. They start with `THRESHOLD_MAGIC`, so a
+    /// signature share can't be passed off as an ordinary letter of that participant.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut bytes = THRESHOLD_MAGIC.to_vec();
+        bytes.extend_from_slice(&self.header.signed_bytes(&self.content.fingerprint()));
+        bytes
+    }
+
+    /// Adds the signature of a participant. It fails, if the private key has the wrong length.
+    pub fn sign(&mut self, private_key: &[u8]) -> Result<(), SignError> {
+        let public_key = public_key_of(private_key)?;
+        let signature = ed25519::sign(&self.signed_bytes(), private_key);
+        self.add_signature(public_key, &signature);
+        Ok(())
+    }
+
+    /// Adds a signature that a participant made over `signed_bytes()` elsewhere. An earlier
+    /// signature of the same key is replaced.
+    pub fn add_signature(&mut self, public_key: &[u8], signature: &[u8]) {
+        self.signatures.retain(|s| s.0 != public_key);
+        self.signatures.push((public_key.to_vec(), signature.to_vec()));
+    }
+
+    /// Returns the public keys and signatures.
+    pub fn signatures(&self) -> &[(Vec<u8>, Vec<u8>)] {
+        &self.signatures
+    }
+
+    /// Returns the header.
+    pub fn header(&self) -> &Header {
+        &self.header
+    }
+
+    /// Returns the content.
+    pub fn get(&self) -> &T {
+        &self.content
+    }
+
+    /// Returns the content, consuming the letter.
+    pub fn into_inner(self) -> T {
+        self.content
+    }
+
+    /// Checks that at least `quorum.threshold()` distinct keys of the quorum signed the letter.
+    /// Signatures of keys outside the quorum are ignored, and so are non-canonical signatures and
+    /// keys of small order, see the `strict` module.
+    pub fn verify(&self, quorum: &Quorum) -> Result<(), ValidationError> {
+        let bytes = self.signed_bytes();
+
+        let valid = quorum.keys
+                          .iter()
+                          .filter(|key| {
+                              self.signatures
+                                  .iter()
+                                  .any(|s| {
+                                      s.0 == **key && strict::check_signature_bytes(&s.1, None).is_ok() &&
+                                      !strict::has_small_order(key) && ed25519::verify(&bytes, &s.1, key)
+                                  })
+                          })
+                          .count();
+
+        if valid >= quorum.threshold {
+            Ok(())
+        } else {
+            Err(ValidationError::SignatureInvalid)
+        }
+    }
+}
+
+impl<T: FromFingerprint> ThresholdLetter<T> {
+    /// Serializes the letter, so it can be passed between the participants.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(THRESHOLD_MAGIC);
+        w.u8(1);
+        w.bytes(&self.header.to_bytes());
+        w.bytes(&self.content.fingerprint());
+        w.u32(self.signatures.len() as u32);
+        for (key, signature) in &self.signatures {
+            w.bytes(key);
+            w.bytes(signature);
+        }
+        w.into_bytes()
+    }
+
+    /// Parses a serialized letter. The signatures are only checked by `verify`.
+    pub fn from_bytes(bytes: &[u8]) -> Result<ThresholdLetter<T>, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(THRESHOLD_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != THRESHOLD_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let header = Header::from_bytes(r.bytes()?)?;
+        let content = T::from_fingerprint(r.bytes()?)?;
+
+        let mut signatures = Vec::new();
+        for _ in 0..r.u32()? {
+            let key = r.bytes()?.to_vec();
+            let signature = r.bytes()?.to_vec();
+            signatures.push((key, signature));
+        }
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(ThresholdLetter {
+            content,
+            header,
+            signatures,
+        })
+    }
+}
+
+#[test]
+fn test_two_of_three() {
+    use canonical::Fingerprintable;
+
+    let keys: Vec<(Vec<u8>, Vec<u8>)> = (0..3).map(|_| ed25519::generate_keypair()).collect();
+    let quorum = Quorum::new(keys.iter().map(|k| k.0.clone()).collect(), 2).unwrap();
+
+    let mut letter = ThresholdLetter::new(Fingerprintable("rotate root".to_string()), Header::new());
+    letter.sign(&keys[0].1).unwrap();
+    letter.sign(&keys[0].1).unwrap();
+    assert_eq!(Err(ValidationError::SignatureInvalid), letter.verify(&quorum));
+
+    let (_, outsider) = ed25519::generate_keypair();
+    letter.sign(&outsider).unwrap();
+    assert_eq!(Err(ValidationError::SignatureInvalid), letter.verify(&quorum));
+
+    let mut letter: ThresholdLetter<Fingerprintable<String>> = ThresholdLetter::from_bytes(&letter.to_bytes()).unwrap();
+    letter.sign(&keys[2].1).unwrap();
+    assert_eq!(Ok(()), letter.verify(&quorum));
+
+    let mut bytes = letter.to_bytes();
+    bytes.push(0);
+    assert_eq!(Err(DecodeError::TrailingBytes),
+               ThresholdLetter::<Fingerprintable<String>>::from_bytes(&bytes).map(|_| ()));
+
+    // A malleated signature is the same signature, but isn't accepted.
+    let last = letter.signatures.len() - 1;
+    letter.signatures[last].1 = strict::malleate(&letter.signatures[last].1);
+    assert_eq!(Err(ValidationError::SignatureInvalid), letter.verify(&quorum));
+
+    assert_eq!(Err(QuorumError::InvalidThreshold), Quorum::new(vec![], 1));
+    assert_eq!(Err(SignError::InvalidKey), letter.sign(&keys[1].1[..32]));
+
+    // The same key twice would let one signature meet a threshold of two.
+    assert_eq!(Err(QuorumError::DuplicateKey), Quorum::new(vec![keys[0].0.clone(), keys[0].0.clone()], 2));
+}
@@ -0,0 +1,199 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Trust on first use.
+//!
+//! Without a master key, a peer can still be recognized again: `TofuValidator` remembers the
+//! public key of the certificate that signed the first letter of every peer and afterwards
+//! accepts letters of that peer only if they are signed by the same key. The certificate itself
+//! is not checked against a master key, only its expiry date is. Where the keys are kept is up to
+//! the `TofuStore`; `MemoryTofuStore` keeps them in memory and can be saved and restored by the
+//! application.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::sync::Mutex;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+
+use letter::Letter;
+use strict;
+
+/// This error is returned, if a letter of a peer isn't accepted.
+#[derive(Debug)]
+pub enum TofuError {
+    /// The letter isn't signed with a certificate, so there is no key to remember.
+    NoCertificate,
+    /// The signature or the certificate is invalid.
+    Invalid(ValidationError),
+    /// The letter is signed by another key than the first letter of the peer.
+    KeyChanged,
+    /// The store couldn't read or write the key.
+    Store(io::Error),
+}
+
+impl fmt::Display for TofuError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TofuError::NoCertificate => write!(f, "letter isn't signed with a certificate"),
+            TofuError::Invalid(ref e) => write!(f, "invalid letter: {:?}", e),
+            TofuError::KeyChanged => write!(f, "the key of the peer has changed"),
+            TofuError::Store(ref e) => write!(f, "can't access the known keys: {}", e),
+        }
+    }
+}
+
+impl Error for TofuError {}
+
+/// Where the first key of every peer is kept.
+pub trait TofuStore {
+    /// Returns the key of the peer, if one was stored.
+    fn get(&self, peer: &str) -> Result<Option<Vec<u8>>, io::Error>;
+
+    /// Stores the key of a peer, unless a key is already stored, and returns the stored key. This
+    /// must be atomic, so two first letters of a peer with different keys can't both be accepted.
+    fn put(&self, peer: &str, public_key: &[u8]) -> Result<Vec<u8>, io::Error>;
+
+    /// Forgets the key of a peer, so the next letter is trusted again.
+    fn remove(&self, peer: &str) -> Result<(), io::Error>;
+}
+
+/// A `TofuStore` in memory.
+#[derive(Debug, Default)]
+pub struct MemoryTofuStore {
+    keys: Mutex<BTreeMap<String, Vec<u8>>>,
+}
+
+impl MemoryTofuStore {
+    /// Creates an empty store.
+    pub fn new() -> MemoryTofuStore {
+        MemoryTofuStore::default()
+    }
+
+    /// Creates a store with the given peers and keys, for example loaded from disk.
+    pub fn from_entries(entries: BTreeMap<String, Vec<u8>>) -> MemoryTofuStore {
+        MemoryTofuStore { keys: Mutex::new(entries) }
+    }
+
+    /// Returns a copy of all peers and keys, for example to save them.
+    pub fn entries(&self) -> BTreeMap<String, Vec<u8>> {
+        self.keys.lock().unwrap().clone()
+    }
+}
+
+impl TofuStore for MemoryTofuStore {
+    fn get(&self, peer: &str) -> Result<Option<Vec<u8>>, io::Error> {
+        Ok(self.keys.lock().unwrap().get(peer).cloned())
+    }
+
+    fn put(&self, peer: &str, public_key: &[u8]) -> Result<Vec<u8>, io::Error> {
+        let mut keys = self.keys.lock().unwrap();
+        Ok(keys.entry(peer.to_string()).or_insert_with(|| public_key.to_vec()).clone())
+    }
+
+    fn remove(&self, peer: &str) -> Result<(), io::Error> {
+        self.keys.lock().unwrap().remove(peer);
+        Ok(())
+    }
+}
+
+/// Accepts letters of a peer, if they are signed by the same key as its first letter.
+pub struct TofuValidator<S: TofuStore> {
+    store: S,
+}
+
+impl<S: TofuStore> TofuValidator<S> {
+    /// Creates a validator over the store.
+    pub fn new(store: S) -> TofuValidator<S> {
+        TofuValidator { store }
+    }
+
+    /// Returns the store.
+    pub fn store(&self) -> &S {
+        &self.store
+    }
+
+    /// Checks the letter of the peer. The first valid letter of a peer decides its key.
+    pub fn validate<T: Fingerprint>(&self, peer: &str, letter: &Letter<T>) -> Result<(), TofuError> {
+        let cert = letter.signer_certificate().ok_or(TofuError::NoCertificate)?;
+
+        if cert.is_expired() {
+            return Err(TofuError::Invalid(ValidationError::Expired));
+        }
+
+        strict::check_signature(letter.signature()).map_err(TofuError::Invalid)?;
+
+        if !cert.verify(&letter.signed_bytes(), letter.signature().hash()) {
+            return Err(TofuError::Invalid(ValidationError::SignatureInvalid));
+        }
+
+        match self.store.get(peer).map_err(TofuError::Store)? {
+            Some(ref key) if key == cert.public_key() => Ok(()),
+            Some(_) => Err(TofuError::KeyChanged),
+            None => {
+                trace_event!(info, peer = peer, key = %::trace::key_id(cert.public_key()), "trusting new peer");
+
+                if self.store.put(peer, cert.public_key()).map_err(TofuError::Store)? == *cert.public_key() {
+                    Ok(())
+                } else {
+                    Err(TofuError::KeyChanged)
+                }
+            }
+        }
+    }
+
+    /// Forgets the key of the peer, for example after it rotated its key out of band.
+    pub fn forget(&self, peer: &str) -> Result<(), TofuError> {
+        self.store.remove(peer).map_err(TofuError::Store)
+    }
+}
+
+#[test]
+fn test_tofu() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::meta::Meta;
+
+    let expires = UTC::now() + Duration::days(90);
+    let alice = Certificate::generate_random(Meta::new_empty(), expires);
+    let mallory = Certificate::generate_random(Meta::new_empty(), expires);
+
+    let tofu = TofuValidator::new(MemoryTofuStore::new());
+    assert_eq!(true, tofu.validate("alice", &Letter::with_certificate("hi", &alice).unwrap()).is_ok());
+    assert_eq!(true, tofu.validate("alice", &Letter::with_certificate("again", &alice).unwrap()).is_ok());
+
+    match tofu.validate("alice", &Letter::with_certificate("hi", &mallory).unwrap()) {
+        Err(TofuError::KeyChanged) => {}
+        r => panic!("unexpected result {:?}", r),
+    }
+
+    // A restored store remembers the peers.
+    let tofu = TofuValidator::new(MemoryTofuStore::from_entries(tofu.store().entries()));
+    assert_eq!(true, tofu.validate("alice", &Letter::with_certificate("hi", &mallory).unwrap()).is_err());
+    tofu.forget("alice").unwrap();
+    assert_eq!(true, tofu.validate("alice", &Letter::with_certificate("hi", &mallory).unwrap()).is_ok());
+}
@@ -0,0 +1,289 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Scoped API tokens.
+//!
+//! A `TokenLetter` carries `TokenClaims` like a JWT: who the token is about, which service it is
+//! meant for, what it may do and until when. Unlike a JWT it is checked against the edcert trust
+//! chain, so services don't need a shared secret or a key set of their own. A `TokenValidator`
+//! checks the signature, the scopes the request needs, and, through a `ClaimsValidator`, the
+//! expiry and the audience of the receiving service.
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use claims;
+use claims::ClaimsError;
+use claims::ClaimsValidator;
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use letter::Letter;
+use signer::SignError;
+use signer::Signer;
+
+/// The content type of token letters.
+pub const TOKEN_CONTENT_TYPE: &str = "edcert-letter/token";
+
+/// The claims of an API token.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TokenClaims {
+    /// Who the token is about, like a user or a service account.
+    pub subject: String,
+    /// The service the token is meant for.
+    pub audience: String,
+    /// What the token may be used for.
+    pub scopes: BTreeSet<String>,
+    /// When the token expires.
+    pub expires: DateTime<UTC>,
+}
+
+impl TokenClaims {
+    /// Creates claims without scopes.
+    pub fn new(subject: &str, audience: &str, expires: DateTime<UTC>) -> TokenClaims {
+        TokenClaims {
+            subject: subject.to_string(),
+            audience: audience.to_string(),
+            scopes: BTreeSet::new(),
+            expires,
+        }
+    }
+
+    /// Adds a scope.
+    pub fn with_scope(mut self, scope: &str) -> TokenClaims {
+        self.scopes.insert(scope.to_string());
+        self
+    }
+
+    /// Returns true, if the token has the scope.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+
+    /// Signs the claims. The header gets the token content type and the expiry of the claims.
+    pub fn issue(self, header: Header, signer: &Signer) -> Result<TokenLetter, SignError> {
+        let mut header = header;
+        header.set_content_type(TOKEN_CONTENT_TYPE);
+        header.set_expires(self.expires);
+        Letter::sign(self, header, signer)
+    }
+}
+
+impl Fingerprint for TokenClaims {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.bytes(self.subject.as_bytes());
+        w.bytes(self.audience.as_bytes());
+        w.u32(self.scopes.len() as u32);
+
+        for scope in &self.scopes {
+            w.bytes(scope.as_bytes());
+        }
+
+        w.u64(self.expires.timestamp() as u64);
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for TokenClaims {
+    fn from_fingerprint(bytes: &[u8]) -> Result<TokenClaims, DecodeError> {
+        let utf8 = |b: &[u8]| String::from_utf8(b.to_vec()).map_err(|_| DecodeError::InvalidContent);
+        let mut r = Reader::new(bytes);
+        let subject = utf8(r.bytes()?)?;
+        let audience = utf8(r.bytes()?)?;
+        let mut scopes = BTreeSet::new();
+
+        for _ in 0..r.u32()? {
+            scopes.insert(utf8(r.bytes()?)?);
+        }
+
+        let expires = UTC.timestamp_opt(r.u64()? as i64, 0).single().ok_or(DecodeError::InvalidContent)?;
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(TokenClaims {
+            subject,
+            audience,
+            scopes,
+            expires,
+        })
+    }
+}
+
+impl claims::Claims for TokenClaims {
+    fn subject(&self) -> Option<&str> {
+        Some(&self.subject)
+    }
+
+    fn audience(&self) -> Option<&str> {
+        Some(&self.audience)
+    }
+
+    fn expires(&self) -> Option<claims::Timestamp> {
+        Some(self.expires)
+    }
+}
+
+/// A letter with API token claims.
+pub type TokenLetter = Letter<TokenClaims>;
+
+/// This error is returned, if a token is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TokenError {
+    /// The letter isn't a valid token.
+    Invalid(ValidationError),
+    /// The token has expired.
+    Expired,
+    /// The token is meant for another service.
+    WrongAudience(String),
+    /// The token lacks a required scope.
+    MissingScope(String),
+}
+
+impl fmt::Display for TokenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TokenError::Invalid(ref e) => write!(f, "invalid token: {:?}", e),
+            TokenError::Expired => write!(f, "the token has expired"),
+            TokenError::WrongAudience(ref a) => write!(f, "the token is meant for {}", a),
+            TokenError::MissingScope(ref s) => write!(f, "the token lacks the scope {}", s),
+        }
+    }
+}
+
+impl Error for TokenError {}
+
+/// Checks tokens for one service.
+pub struct TokenValidator<V: Validator> {
+    claims: ClaimsValidator<V>,
+    audience: String,
+    scopes: BTreeSet<String>,
+}
+
+impl<V: Validator> TokenValidator<V> {
+    /// Creates a validator that accepts tokens for the audience.
+    pub fn new(cv: V, audience: &str) -> TokenValidator<V> {
+        TokenValidator {
+            claims: ClaimsValidator::new(cv).expect_audience(audience).require_expiry(),
+            audience: audience.to_string(),
+            scopes: BTreeSet::new(),
+        }
+    }
+
+    /// Tolerates clocks that are off by up to `skew`, see `ClaimsValidator::with_skew`.
+    pub fn with_skew(mut self, skew: Duration) -> TokenValidator<V> {
+        self.claims = self.claims.with_skew(skew);
+        self
+    }
+
+    /// Requires the scope in every token.
+    pub fn require_scope(mut self, scope: &str) -> TokenValidator<V> {
+        self.scopes.insert(scope.to_string());
+        self
+    }
+
+    /// Checks the token and returns its claims.
+    pub fn validate<'a>(&self, token: &'a TokenLetter) -> Result<&'a TokenClaims, TokenError> {
+        self.validate_with_clock(token, &SystemClock)
+    }
+
+    /// Like `validate`, but takes the current time from the clock.
+    pub fn validate_with_clock<'a, C: Clock>(&self,
+                                             token: &'a TokenLetter,
+                                             clock: &C)
+                                             -> Result<&'a TokenClaims, TokenError> {
+        token.validate_as(self.claims.inner(), TOKEN_CONTENT_TYPE).map_err(TokenError::Invalid)?;
+
+        let claims = token.get();
+        match self.claims.check_claims(token, clock.now()) {
+            Ok(()) => {}
+            Err(ClaimsError::Invalid(e)) => return Err(TokenError::Invalid(e)),
+            Err(ClaimsError::Expired) => return Err(TokenError::Expired),
+            Err(ClaimsError::WrongAudience) => {
+                let audience = match token.header().audience() {
+                    Some(audience) if claims.audience == self.audience => audience,
+                    _ => &claims.audience,
+                };
+                return Err(TokenError::WrongAudience(audience.to_string()));
+            }
+            Err(ClaimsError::Missing(_)) | Err(ClaimsError::Malformed(_)) | Err(ClaimsError::WrongIssuer) => {
+                return Err(TokenError::Invalid(ValidationError::Other))
+            }
+        }
+
+        if let Some(scope) = self.scopes.iter().find(|s| !claims.has_scope(s)) {
+            return Err(TokenError::MissingScope(scope.clone()));
+        }
+
+        Ok(claims)
+    }
+}
+
+#[test]
+fn test_token() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+    use clock::ManualClock;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let token = TokenClaims::new("alice", "billing", UTC::now() + Duration::hours(1))
+                    .with_scope("invoices:read")
+                    .issue(Header::new(), &Signer::PrivateKey(&msk))
+                    .unwrap();
+    let token = TokenLetter::from_bytes(&token.to_bytes()).unwrap();
+
+    let billing = TokenValidator::new(RootValidator::new(&mpk, NoRevoker), "billing");
+    assert_eq!("alice", billing.validate(&token).unwrap().subject);
+
+    let writer = TokenValidator::new(RootValidator::new(&mpk, NoRevoker), "billing")
+                     .require_scope("invoices:write");
+    assert_eq!(Err(TokenError::MissingScope("invoices:write".to_string())),
+               writer.validate(&token));
+
+    let shipping = TokenValidator::new(RootValidator::new(&mpk, NoRevoker), "shipping");
+    assert_eq!(Err(TokenError::WrongAudience("billing".to_string())),
+               shipping.validate(&token));
+
+    let later = ManualClock::new(UTC::now() + Duration::hours(2));
+    assert_eq!(Err(TokenError::Expired), billing.validate_with_clock(&token, &later));
+    let billing = billing.with_skew(Duration::hours(2));
+    assert_eq!(true, billing.validate_with_clock(&token, &later).is_ok());
+}
@@ -0,0 +1,568 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Publishing letters to an append-only transparency log.
+//!
+//! A log collects the leaves of many letters in a Merkle tree (see the `merkle` module) and
+//! signs the tree head with its ed25519 key. For every submitted letter it returns a `LogEntry`:
+//! the signed tree head and a proof that the letter's leaf is included in that tree. Anyone who
+//! knows the log's public key can check the entry, so a signer can't hide letters it signed.
+//!
+//! This crate doesn't ship an HTTP client. Implement `TransparencyLog` for the log you use and
+//! convert its responses into a `LogEntry`. The tree head is signed in this crate's own format,
+//! see `TreeHead::signed_bytes`, so the log has to sign it with `TreeHead::sign`.
+//!
+//! Rekor logs are supported separately: `rekor_body` returns the `hashedrekord` entry to submit,
+//! `RekorEntry::from_json` reads the entry Rekor returns and `verify_rekor` checks it against the
+//! log's signed-note checkpoint. Only ed25519 note keys are supported; a log that signs its
+//! checkpoints with ECDSA, like the public Sigstore instance, can't be verified.
+
+use std::error::Error;
+use std::fmt;
+
+use rustc_serialize::base64::FromBase64;
+use rustc_serialize::base64::ToBase64;
+use rustc_serialize::base64::STANDARD;
+use rustc_serialize::hex::FromHex;
+use rustc_serialize::hex::ToHex;
+use rustc_serialize::json::Json;
+use sodiumoxide::crypto::hash::sha256;
+
+use edcert::ed25519;
+use edcert::fingerprint::Fingerprint;
+
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use letter::Letter;
+use merkle;
+
+/// The head of a log's Merkle tree, signed by the log.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TreeHead {
+    /// The number of leaves in the tree.
+    pub tree_size: u64,
+    /// The root hash of the tree.
+    pub root_hash: Vec<u8>,
+    /// The log's ed25519 signature over `signed_bytes()`.
+    pub signature: Vec<u8>,
+}
+
+impl TreeHead {
+    /// Creates a tree head and signs it with the log's private key.
+    pub fn sign(tree_size: u64, root_hash: Vec<u8>, log_private_key: &[u8]) -> TreeHead {
+        let mut head = TreeHead {
+            tree_size,
+            root_hash,
+            signature: Vec::new(),
+        };
+        head.signature = ed25519::sign(&head.signed_bytes(), log_private_key);
+        head
+    }
+
+    /// Returns the bytes the log signs.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(b"edcert-letter tree head");
+        w.u64(self.tree_size);
+        w.bytes(&self.root_hash);
+        w.into_bytes()
+    }
+
+    /// Checks the log's signature.
+    pub fn verify(&self, log_public_key: &[u8]) -> bool {
+        ed25519::verify(&self.signed_bytes(), &self.signature, log_public_key)
+    }
+}
+
+/// The proof that a leaf is part of a tree.
+#[derive(Clone, PartialEq, Debug)]
+pub struct InclusionProof {
+    /// The position of the leaf in the log.
+    pub log_index: u64,
+    /// The size of the tree the proof was made for.
+    pub tree_size: u64,
+    /// The audit path from the leaf to the root.
+    pub hashes: Vec<Vec<u8>>,
+}
+
+/// What a log returns for a submitted letter. Store it next to the letter.
+#[derive(Clone, PartialEq, Debug)]
+pub struct LogEntry {
+    /// The inclusion proof of the letter's leaf.
+    pub proof: InclusionProof,
+    /// The signed tree head the proof leads to.
+    pub tree_head: TreeHead,
+}
+
+impl LogEntry {
+    /// Serializes the entry.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u64(self.proof.log_index);
+        w.u64(self.proof.tree_size);
+        w.u32(self.proof.hashes.len() as u32);
+        for hash in &self.proof.hashes {
+            w.bytes(hash);
+        }
+        w.u64(self.tree_head.tree_size);
+        w.bytes(&self.tree_head.root_hash);
+        w.bytes(&self.tree_head.signature);
+        w.into_bytes()
+    }
+
+    /// Reads a serialized entry.
+    pub fn from_bytes(bytes: &[u8]) -> Result<LogEntry, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        let log_index = r.u64()?;
+        let tree_size = r.u64()?;
+        let mut hashes = Vec::new();
+        for _ in 0..r.u32()? {
+            hashes.push(r.bytes()?.to_vec());
+        }
+
+        let tree_head = TreeHead {
+            tree_size: r.u64()?,
+            root_hash: r.bytes()?.to_vec(),
+            signature: r.bytes()?.to_vec(),
+        };
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(LogEntry {
+            proof: InclusionProof {
+                log_index,
+                tree_size,
+                hashes,
+            },
+            tree_head,
+        })
+    }
+}
+
+/// This error is returned, if a letter can't be logged or its log entry is invalid.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TransparencyError {
+    /// The log didn't accept the submission.
+    Submission(String),
+    /// The tree head isn't signed by the log.
+    InvalidTreeHead,
+    /// The proof doesn't show that the letter is part of the tree.
+    NotIncluded,
+    /// A checkpoint or a Rekor entry can't be parsed.
+    Malformed,
+}
+
+impl fmt::Display for TransparencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TransparencyError::Submission(ref msg) => write!(f, "submission failed: {}", msg),
+            TransparencyError::InvalidTreeHead => write!(f, "invalid tree head signature"),
+            TransparencyError::NotIncluded => write!(f, "letter is not included in the log"),
+            TransparencyError::Malformed => write!(f, "malformed log checkpoint or entry"),
+        }
+    }
+}
+
+impl Error for TransparencyError {}
+
+/// An append-only log that letters can be submitted to.
+pub trait TransparencyLog {
+    /// Appends the leaf data to the log and returns the proof of its inclusion.
+    fn submit(&self, leaf: &[u8]) -> Result<LogEntry, TransparencyError>;
+}
+
+/// Returns the data that is logged for a letter: the signed bytes and the signature.
+pub fn leaf_data<T: Fingerprint>(letter: &Letter<T>) -> Vec<u8> {
+    let mut w = Writer::new();
+    w.bytes(&letter.signed_bytes());
+    w.bytes(letter.signature().hash());
+    w.into_bytes()
+}
+
+/// Submits a letter to a log and checks the returned entry against the log's public key.
+pub fn submit<T: Fingerprint, L: TransparencyLog>(letter: &Letter<T>,
+                                                  log: &L,
+                                                  log_public_key: &[u8])
+                                                  -> Result<LogEntry, TransparencyError> {
+    let entry = log.submit(&leaf_data(letter))?;
+    verify(letter, &entry, log_public_key)?;
+    Ok(entry)
+}
+
+/// Checks that the letter is included in the log.
+pub fn verify<T: Fingerprint>(letter: &Letter<T>,
+                              entry: &LogEntry,
+                              log_public_key: &[u8])
+                              -> Result<(), TransparencyError> {
+    if !entry.tree_head.verify(log_public_key) {
+        return Err(TransparencyError::InvalidTreeHead);
+    }
+
+    if entry.proof.tree_size != entry.tree_head.tree_size {
+        return Err(TransparencyError::NotIncluded);
+    }
+
+    let leaf = merkle::leaf_hash(&leaf_data(letter));
+    if merkle::verify(entry.proof.log_index,
+                      entry.proof.tree_size,
+                      &leaf,
+                      &entry.proof.hashes,
+                      &entry.tree_head.root_hash) {
+        Ok(())
+    } else {
+        Err(TransparencyError::NotIncluded)
+    }
+}
+
+/// The DER prefix of an ed25519 `SubjectPublicKeyInfo`, which Rekor expects in PEM.
+const ED25519_SPKI_PREFIX: &[u8] = &[0x30, 0x2a, 0x30, 0x05, 0x06, 0x03, 0x2b, 0x65, 0x70, 0x03, 0x21, 0x00];
+
+/// The signature type byte of ed25519 note keys.
+const NOTE_ED25519: u8 = 0x01;
+
+/// A tree head in the checkpoint format of Rekor and other logs. It is published as a signed
+/// note: the body lines below, a blank line and one signature line per key.
+#[derive(Clone, PartialEq, Debug)]
+pub struct Checkpoint {
+    /// The name of the log.
+    pub origin: String,
+    /// The number of leaves in the tree.
+    pub tree_size: u64,
+    /// The root hash of the tree.
+    pub root_hash: Vec<u8>,
+    /// The lines after the root hash, like Rekor's timestamp. They aren't interpreted.
+    pub extensions: Vec<String>,
+}
+
+impl Checkpoint {
+    /// Returns the note body: the origin, the tree size, the base64 root hash and the extension
+    /// lines, each ending with a newline.
+    pub fn body(&self) -> String {
+        let mut body = format!("{}\n{}\n{}\n", self.origin, self.tree_size, self.root_hash.to_base64(STANDARD));
+        for line in &self.extensions {
+            body.push_str(line);
+            body.push('\n');
+        }
+        body
+    }
+
+    /// Signs the checkpoint with the ed25519 note key of the given name and returns the note.
+    pub fn sign(&self, key_name: &str, private_key: &[u8]) -> String {
+        let body = self.body();
+        let mut signature = note_key_hash(key_name, &private_key[32..]);
+        signature.extend_from_slice(&ed25519::sign(body.as_bytes(), private_key));
+        format!("{}\n\u{2014} {} {}\n", body, key_name, signature.to_base64(STANDARD))
+    }
+
+    /// Reads a signed note and returns its checkpoint, if one of the signatures was made by the
+    /// ed25519 note key of the given name. Signatures of other keys are ignored.
+    pub fn from_note(note: &str, key_name: &str, public_key: &[u8]) -> Result<Checkpoint, TransparencyError> {
+        let split = note.find("\n\n").ok_or(TransparencyError::Malformed)?;
+        let (body, signatures) = (&note[..split + 1], &note[split + 2..]);
+        if !signatures.ends_with('\n') {
+            return Err(TransparencyError::Malformed);
+        }
+
+        let key_hash = note_key_hash(key_name, public_key);
+        let mut signed = false;
+        for line in signatures[..signatures.len() - 1].split('\n') {
+            let mut parts = line.splitn(3, ' ');
+            let (dash, name, signature) = match (parts.next(), parts.next(), parts.next()) {
+                (Some(dash), Some(name), Some(signature)) => (dash, name, signature),
+                _ => return Err(TransparencyError::Malformed),
+            };
+            let signature = signature.from_base64().map_err(|_| TransparencyError::Malformed)?;
+            if dash != "\u{2014}" || signature.len() < key_hash.len() {
+                return Err(TransparencyError::Malformed);
+            }
+
+            if name == key_name && signature[..4] == key_hash[..] &&
+               ed25519::verify(body.as_bytes(), &signature[4..], public_key) {
+                signed = true;
+            }
+        }
+        if !signed {
+            return Err(TransparencyError::InvalidTreeHead);
+        }
+
+        let mut lines = body[..body.len() - 1].split('\n');
+        let origin = lines.next().unwrap_or("");
+        let tree_size = lines.next().unwrap_or("");
+        let root_hash = lines.next().unwrap_or("").from_base64().map_err(|_| TransparencyError::Malformed)?;
+        if origin.is_empty() || tree_size.is_empty() || !tree_size.bytes().all(|b| b.is_ascii_digit()) ||
+           root_hash.len() != 32 {
+            return Err(TransparencyError::Malformed);
+        }
+
+        Ok(Checkpoint {
+            origin: origin.to_string(),
+            tree_size: tree_size.parse().map_err(|_| TransparencyError::Malformed)?,
+            root_hash,
+            extensions: lines.map(|line| line.to_string()).collect(),
+        })
+    }
+}
+
+/// Returns the key hash of an ed25519 note key: the first four bytes of
+/// `SHA-256(name || "\n" || 0x01 || public_key)`.
+fn note_key_hash(key_name: &str, public_key: &[u8]) -> Vec<u8> {
+    let mut data = key_name.as_bytes().to_vec();
+    data.push(b'\n');
+    data.push(NOTE_ED25519);
+    data.extend_from_slice(public_key);
+    sha256::hash(&data).0[..4].to_vec()
+}
+
+/// An entry of a Rekor log with its inclusion proof.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RekorEntry {
+    /// The logged entry, the leaf data of the tree.
+    pub body: Vec<u8>,
+    /// The inclusion proof of the body's leaf.
+    pub proof: InclusionProof,
+    /// The root hash the proof leads to.
+    pub root_hash: Vec<u8>,
+    /// The signed-note checkpoint of the tree.
+    pub checkpoint: String,
+}
+
+impl RekorEntry {
+    /// Reads an entry from the JSON Rekor returns for a submission or a lookup: an object that
+    /// maps the entry's UUID to the entry.
+    pub fn from_json(json: &str) -> Result<RekorEntry, TransparencyError> {
+        let json = Json::from_str(json).map_err(|_| TransparencyError::Malformed)?;
+        let entries = json.as_object().ok_or(TransparencyError::Malformed)?;
+        if entries.len() != 1 {
+            return Err(TransparencyError::Malformed);
+        }
+
+        let entry = entries.values().next().unwrap();
+        let proof = entry.find_path(&["verification", "inclusionProof"]).ok_or(TransparencyError::Malformed)?;
+        let number = |name| proof.find(name).and_then(|v| v.as_u64()).ok_or(TransparencyError::Malformed);
+        let hex = |s: &str| s.from_hex().map_err(|_| TransparencyError::Malformed);
+
+        let mut hashes = Vec::new();
+        for hash in proof.find("hashes").and_then(|h| h.as_array()).ok_or(TransparencyError::Malformed)? {
+            hashes.push(hex(hash.as_string().ok_or(TransparencyError::Malformed)?)?);
+        }
+
+        Ok(RekorEntry {
+            body: json_string(entry, "body")?.from_base64().map_err(|_| TransparencyError::Malformed)?,
+            proof: InclusionProof {
+                log_index: number("logIndex")?,
+                tree_size: number("treeSize")?,
+                hashes,
+            },
+            root_hash: hex(json_string(proof, "rootHash")?)?,
+            checkpoint: json_string(proof, "checkpoint")?.to_string(),
+        })
+    }
+}
+
+fn json_string<'a>(json: &'a Json, name: &str) -> Result<&'a str, TransparencyError> {
+    json.find(name).and_then(|v| v.as_string()).ok_or(TransparencyError::Malformed)
+}
+
+/// Returns the `hashedrekord` entry that logs the letter in Rekor: the SHA-256 of the signed
+/// bytes, the signature and the signing key in PEM. Submit it to `POST /api/v1/log/entries`.
+/// Letters signed by a certificate name its key; `master_public_key` is used for the others.
+pub fn rekor_body<T: Fingerprint>(letter: &Letter<T>, master_public_key: &[u8]) -> String {
+    let public_key = letter.signature().parent().map_or(master_public_key, |cert| cert.public_key());
+    let mut spki = ED25519_SPKI_PREFIX.to_vec();
+    spki.extend_from_slice(public_key);
+    let pem = format!("-----BEGIN PUBLIC KEY-----\n{}\n-----END PUBLIC KEY-----\n", spki.to_base64(STANDARD));
+
+    let object = |pairs: Vec<(&str, Json)>| {
+        Json::Object(pairs.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    };
+    let hash = object(vec![("algorithm", Json::String("sha256".to_string())),
+                           ("value", Json::String(sha256::hash(&letter.signed_bytes()).0.to_hex()))]);
+    let public_key = object(vec![("content", Json::String(pem.as_bytes().to_base64(STANDARD)))]);
+    let signature = object(vec![("content", Json::String(letter.signature().hash().to_base64(STANDARD))),
+                                ("publicKey", public_key)]);
+    let spec = object(vec![("data", object(vec![("hash", hash)])), ("signature", signature)]);
+
+    object(vec![("apiVersion", Json::String("0.0.1".to_string())),
+                ("kind", Json::String("hashedrekord".to_string())),
+                ("spec", spec)])
+        .to_string()
+}
+
+/// Checks that the letter is included in a Rekor log: the checkpoint is signed by the log's
+/// ed25519 note key, the proof leads from the entry to the checkpoint's root, and the entry is a
+/// `hashedrekord` of the letter's signed bytes and signature.
+pub fn verify_rekor<T: Fingerprint>(letter: &Letter<T>,
+                                    entry: &RekorEntry,
+                                    log_key_name: &str,
+                                    log_public_key: &[u8])
+                                    -> Result<(), TransparencyError> {
+    let checkpoint = Checkpoint::from_note(&entry.checkpoint, log_key_name, log_public_key)?;
+    if checkpoint.tree_size != entry.proof.tree_size || checkpoint.root_hash != entry.root_hash {
+        return Err(TransparencyError::NotIncluded);
+    }
+
+    if !merkle::verify(entry.proof.log_index,
+                       entry.proof.tree_size,
+                       &merkle::leaf_hash(&entry.body),
+                       &entry.proof.hashes,
+                       &checkpoint.root_hash) {
+        return Err(TransparencyError::NotIncluded);
+    }
+
+    let body = ::std::str::from_utf8(&entry.body).map_err(|_| TransparencyError::Malformed)?;
+    let body = Json::from_str(body).map_err(|_| TransparencyError::Malformed)?;
+    let string = |path: &[&str]| body.find_path(path).and_then(|v| v.as_string()).unwrap_or("");
+
+    let hash = sha256::hash(&letter.signed_bytes()).0.to_hex();
+    let signature = string(&["spec", "signature", "content"]).from_base64().unwrap_or_default();
+    if string(&["kind"]) == "hashedrekord" && string(&["spec", "data", "hash", "algorithm"]) == "sha256" &&
+       string(&["spec", "data", "hash", "value"]).to_lowercase() == hash &&
+       signature == *letter.signature().hash() {
+        Ok(())
+    } else {
+        Err(TransparencyError::NotIncluded)
+    }
+}
+
+#[cfg(test)]
+struct MemoryLog {
+    leaves: ::std::cell::RefCell<Vec<Vec<u8>>>,
+    private_key: Vec<u8>,
+}
+
+#[cfg(test)]
+impl TransparencyLog for MemoryLog {
+    fn submit(&self, leaf: &[u8]) -> Result<LogEntry, TransparencyError> {
+        let mut leaves = self.leaves.borrow_mut();
+        leaves.push(merkle::leaf_hash(leaf));
+
+        let index = leaves.len() - 1;
+        let root = merkle::root(&leaves);
+
+        Ok(LogEntry {
+            proof: InclusionProof {
+                log_index: index as u64,
+                tree_size: leaves.len() as u64,
+                hashes: merkle::prove(index, &leaves).unwrap(),
+            },
+            tree_head: TreeHead::sign(leaves.len() as u64, root, &self.private_key),
+        })
+    }
+}
+
+#[test]
+fn test_submit_and_verify() {
+    let (log_pk, log_sk) = ed25519::generate_keypair();
+    let (_, msk) = ed25519::generate_keypair();
+
+    let log = MemoryLog {
+        leaves: ::std::cell::RefCell::new(Vec::new()),
+        private_key: log_sk,
+    };
+
+    let first = Letter::with_private_key("first", &msk);
+    let second = Letter::with_private_key("second", &msk);
+
+    let entry = submit(&first, &log, &log_pk).unwrap();
+    submit(&second, &log, &log_pk).unwrap();
+
+    let mut bytes = entry.to_bytes();
+    let entry = LogEntry::from_bytes(&bytes).unwrap();
+    assert_eq!(Ok(()), verify(&first, &entry, &log_pk));
+
+    bytes.push(0);
+    assert_eq!(Err(DecodeError::TrailingBytes), LogEntry::from_bytes(&bytes));
+    assert_eq!(Err(TransparencyError::NotIncluded), verify(&second, &entry, &log_pk));
+
+    let (other_pk, _) = ed25519::generate_keypair();
+    assert_eq!(Err(TransparencyError::InvalidTreeHead), verify(&first, &entry, &other_pk));
+}
+
+#[test]
+fn test_rekor() {
+    use std::collections::BTreeMap;
+
+    const NAME: &str = "rekor.example.com";
+
+    let (log_pk, log_sk) = ed25519::generate_keypair();
+    let (mpk, msk) = ed25519::generate_keypair();
+
+    let first = Letter::with_private_key("first", &msk);
+    let second = Letter::with_private_key("second", &msk);
+    let bodies = [rekor_body(&first, &mpk), rekor_body(&second, &mpk), "{}".to_string()];
+    let leaves: Vec<Vec<u8>> = bodies.iter().map(|b| merkle::leaf_hash(b.as_bytes())).collect();
+
+    let checkpoint = Checkpoint {
+        origin: "rekor.example.com - 42".to_string(),
+        tree_size: leaves.len() as u64,
+        root_hash: merkle::root(&leaves),
+        extensions: vec!["Timestamp: 1700000000".to_string()],
+    };
+    let note = checkpoint.sign(NAME, &log_sk);
+    assert_eq!(Ok(checkpoint.clone()), Checkpoint::from_note(&note, NAME, &log_pk));
+
+    // The JSON Rekor returns for the first entry.
+    let json = |note: &str, tree_size: usize| {
+        let mut proof = BTreeMap::new();
+        proof.insert("checkpoint".to_string(), Json::String(note.to_string()));
+        let hashes = merkle::prove(0, &leaves[..tree_size]).unwrap();
+        let hashes = hashes.iter().map(|h| Json::String(h.to_hex())).collect();
+        proof.insert("hashes".to_string(), Json::Array(hashes));
+        proof.insert("logIndex".to_string(), Json::U64(0));
+        proof.insert("rootHash".to_string(), Json::String(merkle::root(&leaves[..tree_size]).to_hex()));
+        proof.insert("treeSize".to_string(), Json::U64(tree_size as u64));
+        let mut verification = BTreeMap::new();
+        verification.insert("inclusionProof".to_string(), Json::Object(proof));
+        let mut entry = BTreeMap::new();
+        entry.insert("body".to_string(), Json::String(bodies[0].as_bytes().to_base64(STANDARD)));
+        entry.insert("logIndex".to_string(), Json::U64(0));
+        entry.insert("verification".to_string(), Json::Object(verification));
+        let mut entries = BTreeMap::new();
+        entries.insert("24296fb24b8ad77a".to_string(), Json::Object(entry));
+        Json::Object(entries).to_string()
+    };
+
+    let entry = RekorEntry::from_json(&json(&note, leaves.len())).unwrap();
+    assert_eq!(bodies[0].as_bytes(), &entry.body[..]);
+    assert_eq!(Ok(()), verify_rekor(&first, &entry, NAME, &log_pk));
+    assert_eq!(Err(TransparencyError::NotIncluded), verify_rekor(&second, &entry, NAME, &log_pk));
+
+    let (other_pk, other_sk) = ed25519::generate_keypair();
+    assert_eq!(Err(TransparencyError::InvalidTreeHead), verify_rekor(&first, &entry, NAME, &other_pk));
+    assert_eq!(Err(TransparencyError::InvalidTreeHead), verify_rekor(&first, &entry, "other.example.com", &log_pk));
+
+    // A note signed by another key as well still verifies, the other signature is ignored.
+    let cosigned = format!("{}{}", note, &checkpoint.sign("witness", &other_sk)[checkpoint.body().len() + 1..]);
+    assert_eq!(Ok(checkpoint.clone()), Checkpoint::from_note(&cosigned, NAME, &log_pk));
+
+    // The proof has to lead to the checkpoint's tree.
+    let entry = RekorEntry::from_json(&json(&note, 2)).unwrap();
+    assert_eq!(Err(TransparencyError::NotIncluded), verify_rekor(&first, &entry, NAME, &log_pk));
+
+    assert_eq!(Err(TransparencyError::Malformed), Checkpoint::from_note(note.trim_end(), NAME, &log_pk));
+    assert_eq!(Err(TransparencyError::Malformed), Checkpoint::from_note(&checkpoint.body(), NAME, &log_pk));
+    assert_eq!(Err(TransparencyError::Malformed), RekorEntry::from_json("{}"));
+}
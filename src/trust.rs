@@ -0,0 +1,275 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Validation against several trusted master keys.
+//!
+//! A `TrustStore` holds named master public keys, each with an optional validity window. The
+//! `TrustStoreValidator` accepts a signature, if any key that is valid right now verifies it, so
+//! one service can check letters from several domains or tenants.
+
+use chrono::DateTime;
+use chrono::Duration;
+use chrono::UTC;
+
+use edcert::ed25519;
+use edcert::revoker::Revokable;
+use edcert::revoker::RevokeError;
+use edcert::revoker::Revoker;
+use edcert::validator::Validator;
+
+use clock::Clock;
+use clock::SystemClock;
+
+/// A trusted master public key.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TrustAnchor {
+    name: String,
+    public_key: Vec<u8>,
+    not_before: Option<DateTime<UTC>>,
+    not_after: Option<DateTime<UTC>>,
+}
+
+impl TrustAnchor {
+    /// Creates an anchor that is valid at any time.
+    pub fn new(name: &str, public_key: &[u8]) -> TrustAnchor {
+        TrustAnchor {
+            name: name.to_string(),
+            public_key: public_key.to_vec(),
+            not_before: None,
+            not_after: None,
+        }
+    }
+
+    /// Restricts the anchor to the given window. `None` leaves that side open.
+    pub fn with_validity(mut self,
+                         not_before: Option<DateTime<UTC>>,
+                         not_after: Option<DateTime<UTC>>)
+                         -> TrustAnchor {
+        self.not_before = not_before;
+        self.not_after = not_after;
+        self
+    }
+
+    /// Returns the name of this anchor.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the master public key.
+    pub fn public_key(&self) -> &[u8] {
+        &self.public_key
+    }
+
+    /// Returns the start of the validity window, if there is one.
+    pub fn not_before(&self) -> Option<DateTime<UTC>> {
+        self.not_before
+    }
+
+    /// Returns the end of the validity window, if there is one.
+    pub fn not_after(&self) -> Option<DateTime<UTC>> {
+        self.not_after
+    }
+
+    /// Returns true, if the anchor may be used at the given time.
+    pub fn is_valid_at(&self, time: &DateTime<UTC>) -> bool {
+        self.is_valid_within(time, Duration::zero())
+    }
+
+    /// Returns true, if the anchor may be used at a time no further than `skew` from the given
+    /// time, so a clock that is slightly off doesn't matter.
+    pub fn is_valid_within(&self, time: &DateTime<UTC>, skew: Duration) -> bool {
+        self.not_before.is_none_or(|t| t <= *time + skew) &&
+        self.not_after.is_none_or(|t| *time - skew <= t)
+    }
+}
+
+/// A set of trusted master keys, looked up by name.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TrustStore {
+    anchors: Vec<TrustAnchor>,
+}
+
+impl TrustStore {
+    /// Creates an empty store.
+    pub fn new() -> TrustStore {
+        TrustStore { anchors: Vec::new() }
+    }
+
+    /// Adds an anchor. An anchor with the same name is replaced.
+    pub fn add(&mut self, anchor: TrustAnchor) {
+        self.remove(&anchor.name);
+        self.anchors.push(anchor);
+    }
+
+    /// Removes the anchor with the given name and returns it.
+    pub fn remove(&mut self, name: &str) -> Option<TrustAnchor> {
+        let index = self.anchors.iter().position(|a| a.name == name)?;
+        Some(self.anchors.remove(index))
+    }
+
+    /// Returns the anchor with the given name.
+    pub fn get(&self, name: &str) -> Option<&TrustAnchor> {
+        self.anchors.iter().find(|a| a.name == name)
+    }
+
+    /// Returns all anchors.
+    pub fn anchors(&self) -> &[TrustAnchor] {
+        &self.anchors
+    }
+
+    /// Returns the anchor whose key made the signature, if it is valid at the given time.
+    pub fn find_signer(&self,
+                       data: &[u8],
+                       signature: &[u8],
+                       time: &DateTime<UTC>)
+                       -> Option<&TrustAnchor> {
+        self.find_signer_within(data, signature, time, Duration::zero())
+    }
+
+    /// Finds the anchor that made the signature, allowing for the given clock skew.
+    pub fn find_signer_within(&self,
+                              data: &[u8],
+                              signature: &[u8],
+                              time: &DateTime<UTC>,
+                              skew: Duration)
+                              -> Option<&TrustAnchor> {
+        self.anchors
+            .iter()
+            .filter(|a| a.is_valid_within(time, skew))
+            .find(|a| ed25519::verify(data, signature, &a.public_key))
+    }
+}
+
+/// A validator that trusts every master key in a `TrustStore`. The validity windows of the
+/// anchors are checked against the time of its clock, give or take the configured skew.
+pub struct TrustStoreValidator<R: Revoker, C: Clock = SystemClock> {
+    store: TrustStore,
+    revoker: R,
+    clock: C,
+    skew: Duration,
+}
+
+impl<R: Revoker> TrustStoreValidator<R> {
+    /// Creates a validator over the given store.
+    pub fn new(store: TrustStore, revoker: R) -> TrustStoreValidator<R> {
+        TrustStoreValidator {
+            store,
+            revoker,
+            clock: SystemClock,
+            skew: Duration::zero(),
+        }
+    }
+}
+
+impl<R: Revoker, C: Clock> TrustStoreValidator<R, C> {
+    /// Replaces the clock the validity windows are checked against.
+    pub fn with_clock<D: Clock>(self, clock: D) -> TrustStoreValidator<R, D> {
+        TrustStoreValidator {
+            store: self.store,
+            revoker: self.revoker,
+            clock,
+            skew: self.skew,
+        }
+    }
+
+    /// Sets how far the clocks of signers may be off, for example five minutes. It is zero by
+    /// default.
+    pub fn with_skew(mut self, skew: Duration) -> TrustStoreValidator<R, C> {
+        self.skew = skew;
+        self
+    }
+
+    /// Returns the tolerated clock skew.
+    pub fn skew(&self) -> Duration {
+        self.skew
+    }
+
+    /// Returns the clock.
+    pub fn clock(&self) -> &C {
+        &self.clock
+    }
+
+    /// Returns the trust store.
+    pub fn store(&self) -> &TrustStore {
+        &self.store
+    }
+
+    /// Returns the trust store, so anchors can be added or removed.
+    pub fn store_mut(&mut self) -> &mut TrustStore {
+        &mut self.store
+    }
+}
+
+impl<R: Revoker, C: Clock> Validator for TrustStoreValidator<R, C> {
+    fn is_signature_valid(&self, data: &[u8], signature: &[u8]) -> bool {
+        self.store.find_signer_within(data, signature, &self.clock.now(), self.skew).is_some()
+    }
+
+    fn is_revoked<T: Revokable>(&self, item: &T) -> Result<(), RevokeError> {
+        item.self_check_revoked(&self.revoker)
+    }
+}
+
+#[test]
+fn test_trust_store() {
+    use chrono::Duration;
+    use edcert::revoker::NoRevoker;
+    use letter::Letter;
+
+    let (a_pk, a_sk) = ed25519::generate_keypair();
+    let (b_pk, b_sk) = ed25519::generate_keypair();
+    let (_, c_sk) = ed25519::generate_keypair();
+
+    let mut store = TrustStore::new();
+    store.add(TrustAnchor::new("a", &a_pk));
+    store.add(TrustAnchor::new("b", &b_pk)
+                  .with_validity(None, Some(UTC::now() - Duration::days(1))));
+
+    let mut cv = TrustStoreValidator::new(store, NoRevoker);
+
+    let letter_a = Letter::with_private_key("a", &a_sk);
+    let letter_b = Letter::with_private_key("b", &b_sk);
+    let letter_c = Letter::with_private_key("c", &c_sk);
+
+    assert_eq!(true, cv.is_valid(&letter_a).is_ok());
+    assert_eq!(false, cv.is_valid(&letter_b).is_ok());
+    assert_eq!(false, cv.is_valid(&letter_c).is_ok());
+
+    cv.store_mut().add(TrustAnchor::new("b", &b_pk));
+    assert_eq!(true, cv.is_valid(&letter_b).is_ok());
+    assert_eq!(1, cv.store().anchors().iter().filter(|a| a.name() == "b").count());
+
+    // The validity windows are checked against the clock of the validator.
+    let mut store = TrustStore::new();
+    store.add(TrustAnchor::new("b", &b_pk)
+                  .with_validity(None, Some(UTC::now() - Duration::days(1))));
+    let clock = ::clock::ManualClock::new(UTC::now() - Duration::days(2));
+    let cv = TrustStoreValidator::new(store, NoRevoker).with_clock(&clock);
+    assert_eq!(true, cv.is_valid(&letter_b).is_ok());
+    clock.advance(Duration::days(2));
+    assert_eq!(false, cv.is_valid(&letter_b).is_ok());
+
+    clock.set(UTC::now() - Duration::days(1) + Duration::minutes(3));
+    assert_eq!(false, cv.is_valid(&letter_b).is_ok());
+    let cv = cv.with_skew(Duration::minutes(5));
+    assert_eq!(true, cv.is_valid(&letter_b).is_ok());
+}
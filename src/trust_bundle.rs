@@ -0,0 +1,286 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Everything a client needs to validate letters, in one file.
+//!
+//! A `TrustBundle` holds the trusted master keys, intermediate certificates and the revoked keys.
+//! It is written as an armored text file, so it can be shipped with an installer or pasted into a
+//! provisioning system. The bundle itself isn't signed: it contains the roots of trust, so it
+//! must come from a trusted source, like the binary that loads it.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+
+use edcert::certificate::Certificate;
+use edcert::fingerprint::Fingerprint;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use armor;
+use codec::Reader;
+use codec::Writer;
+use format;
+use format::DecodeError;
+use format::FromFingerprint;
+use letter::Letter;
+use revocation::RevocationList;
+use resolver::CertificateResolver;
+use trust::TrustAnchor;
+use trust::TrustStore;
+use trust::TrustStoreValidator;
+
+/// The bytes every serialized trust bundle starts with.
+pub const TRUST_BUNDLE_MAGIC: &[u8] = b"EDA";
+
+/// The label of armored trust bundles.
+pub const TRUST_BUNDLE_LABEL: &str = "EDCERT TRUST BUNDLE";
+
+/// This error is returned, if a trust bundle file can't be read or written.
+#[derive(Debug)]
+pub enum TrustBundleError {
+    /// The file couldn't be accessed.
+    Io(io::Error),
+    /// The file doesn't contain a trust bundle.
+    Decode(DecodeError),
+}
+
+impl fmt::Display for TrustBundleError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TrustBundleError::Io(ref e) => write!(f, "can't access trust bundle: {}", e),
+            TrustBundleError::Decode(ref e) => write!(f, "malformed trust bundle: {}", e),
+        }
+    }
+}
+
+impl Error for TrustBundleError {}
+
+/// Master keys, intermediate certificates and revocations, distributed together.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct TrustBundle {
+    anchors: TrustStore,
+    intermediates: Vec<Certificate>,
+    revocations: RevocationList,
+}
+
+impl TrustBundle {
+    /// Creates an empty bundle.
+    pub fn new() -> TrustBundle {
+        TrustBundle::default()
+    }
+
+    /// Adds a master key. An anchor with the same name is replaced.
+    pub fn add_anchor(&mut self, anchor: TrustAnchor) {
+        self.anchors.add(anchor);
+    }
+
+    /// Adds an intermediate certificate. Its private key is never written.
+    pub fn add_intermediate(&mut self, cert: &Certificate) {
+        let mut cert = cert.clone();
+        cert.remove_private_key();
+
+        self.intermediates.retain(|c| c.public_key() != cert.public_key());
+        self.intermediates.push(cert);
+    }
+
+    /// Marks a public key as revoked.
+    pub fn revoke(&mut self, public_key: &[u8]) {
+        self.revocations.revoke(public_key);
+    }
+
+    /// Returns the master keys.
+    pub fn anchors(&self) -> &TrustStore {
+        &self.anchors
+    }
+
+    /// Returns the intermediate certificates.
+    pub fn intermediates(&self) -> &[Certificate] {
+        &self.intermediates
+    }
+
+    /// Returns the intermediate certificate with the given public key.
+    pub fn intermediate(&self, public_key: &[u8]) -> Option<&Certificate> {
+        self.intermediates.iter().find(|c| c.public_key() == public_key)
+    }
+
+    /// Returns the revoked keys.
+    pub fn revocations(&self) -> &RevocationList {
+        &self.revocations
+    }
+
+    /// Returns a validator that trusts the master keys of the bundle and rejects its revoked
+    /// keys.
+    pub fn validator(&self) -> TrustStoreValidator<RevocationList> {
+        TrustStoreValidator::new(self.anchors.clone(), self.revocations.clone())
+    }
+
+    /// Validates a letter against the bundle.
+    pub fn validate<T: Fingerprint>(&self, letter: &Letter<T>) -> Result<(), ValidationError> {
+        self.validator().is_valid(letter)
+    }
+
+    /// Serializes the bundle.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.raw(TRUST_BUNDLE_MAGIC);
+        w.u8(1);
+
+        w.u32(self.anchors.anchors().len() as u32);
+        for anchor in self.anchors.anchors() {
+            w.bytes(anchor.name().as_bytes());
+            w.bytes(anchor.public_key());
+            write_time(&mut w, anchor.not_before());
+            write_time(&mut w, anchor.not_after());
+        }
+
+        w.u32(self.intermediates.len() as u32);
+        for cert in &self.intermediates {
+            w.bytes(&format::encode_certificate(cert));
+        }
+
+        w.bytes(&self.revocations.fingerprint());
+        w.into_bytes()
+    }
+
+    /// Parses a serialized bundle.
+    pub fn from_bytes(bytes: &[u8]) -> Result<TrustBundle, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(TRUST_BUNDLE_MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != TRUST_BUNDLE_MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match r.u8()? {
+            1 => {}
+            v => return Err(DecodeError::UnsupportedVersion(v)),
+        }
+
+        let mut bundle = TrustBundle::new();
+
+        for _ in 0..r.u32()? {
+            let name = String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?;
+            let public_key = r.bytes()?.to_vec();
+            let not_before = read_time(&mut r)?;
+            let not_after = read_time(&mut r)?;
+            bundle.add_anchor(TrustAnchor::new(&name, &public_key).with_validity(not_before, not_after));
+        }
+
+        for _ in 0..r.u32()? {
+            bundle.intermediates.push(format::decode_certificate(r.bytes()?)?);
+        }
+
+        bundle.revocations = RevocationList::from_fingerprint(r.bytes()?)?;
+
+        if !r.is_empty() {
+            return Err(DecodeError::TrailingBytes);
+        }
+
+        Ok(bundle)
+    }
+
+    /// Returns the bundle as armored text.
+    pub fn to_armored(&self) -> String {
+        armor::armor_with_label(TRUST_BUNDLE_LABEL, &self.to_bytes())
+    }
+
+    /// Parses an armored bundle.
+    pub fn from_armored(text: &str) -> Result<TrustBundle, DecodeError> {
+        TrustBundle::from_bytes(&armor::dearmor_with_label(TRUST_BUNDLE_LABEL, text)?)
+    }
+
+    /// Writes the armored bundle to a file.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), TrustBundleError> {
+        fs::write(path, self.to_armored()).map_err(TrustBundleError::Io)
+    }
+
+    /// Reads an armored bundle from a file.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<TrustBundle, TrustBundleError> {
+        let text = fs::read_to_string(path).map_err(TrustBundleError::Io)?;
+        TrustBundle::from_armored(&text).map_err(TrustBundleError::Decode)
+    }
+}
+
+impl CertificateResolver for TrustBundle {
+    fn resolve(&self, public_key: &[u8]) -> Option<Certificate> {
+        self.intermediate(public_key).cloned()
+    }
+}
+
+fn write_time(w: &mut Writer, time: Option<DateTime<UTC>>) {
+    match time {
+        Some(time) => {
+            w.u8(1);
+            w.u64(time.timestamp() as u64);
+        }
+        None => w.u8(0),
+    }
+}
+
+fn read_time(r: &mut Reader) -> Result<Option<DateTime<UTC>>, DecodeError> {
+    match r.u8()? {
+        0 => Ok(None),
+        1 => {
+            let secs = r.u64()? as i64;
+            UTC.timestamp_opt(secs, 0).single().map(Some).ok_or(DecodeError::InvalidContent)
+        }
+        _ => Err(DecodeError::InvalidContent),
+    }
+}
+
+#[test]
+fn test_trust_bundle() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(90));
+    cert.sign_with_master(&msk);
+    let mut revoked = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(90));
+    revoked.sign_with_master(&msk);
+
+    let mut bundle = TrustBundle::new();
+    bundle.add_anchor(TrustAnchor::new("2016", &mpk).with_validity(None, Some(UTC.timestamp(2000000000, 0))));
+    bundle.add_intermediate(&cert);
+    bundle.revoke(revoked.public_key());
+
+    let path = ::std::env::temp_dir().join(format!("edcert-letter-bundle-{}.txt", ::std::process::id()));
+    bundle.save(&path).unwrap();
+    let loaded = TrustBundle::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(bundle, loaded);
+    assert_eq!(false, loaded.intermediate(cert.public_key()).unwrap().has_private_key());
+
+    let letter = Letter::with_certificate("hello", &cert).unwrap();
+    assert_eq!(Ok(()), loaded.validate(&letter));
+    let letter = Letter::with_certificate("hello", &revoked).unwrap();
+    assert_eq!(true, loaded.validate(&letter).is_err());
+}
@@ -0,0 +1,686 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Signed metadata for software updates, in the style of The Update Framework (TUF).
+//!
+//! Every role signs its metadata as a `ThresholdLetter`, so a role can require several of its
+//! keys. The root metadata names the keys and thresholds of all roles, the targets metadata lists
+//! the files with their length and digest, the snapshot metadata pins the version of the targets
+//! metadata and the timestamp metadata pins the version and digest of the snapshot metadata.
+//! Every metadata has a version and an expiry time.
+//!
+//! A `TufClient` starts from a trusted root and accepts new metadata only if it is signed by the
+//! threshold of the role's keys, hasn't expired, isn't older than the metadata it already has
+//! (rollback) and agrees with the metadata of the role above it. A new root must be signed by the
+//! thresholds of both the old and the new root keys and has the next version number.
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+use chrono::DateTime;
+use chrono::TimeZone;
+use chrono::UTC;
+use sodiumoxide::crypto::hash::sha512;
+
+use edcert::fingerprint::Fingerprint;
+
+use clock::Clock;
+use clock::SystemClock;
+use codec::Reader;
+use codec::Writer;
+use format::DecodeError;
+use format::FromFingerprint;
+use header::Header;
+use threshold::Quorum;
+use threshold::ThresholdLetter;
+
+/// A role that signs metadata.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum Role {
+    /// Names the keys of all roles.
+    Root,
+    /// Lists the target files.
+    Targets,
+    /// Pins the version of the targets metadata.
+    Snapshot,
+    /// Pins the snapshot metadata, and is re-signed often to prove freshness.
+    Timestamp,
+}
+
+impl Role {
+    /// Returns the name of the role, which is also the content type of its letters.
+    pub fn content_type(&self) -> &'static str {
+        match *self {
+            Role::Root => "tuf/root",
+            Role::Targets => "tuf/targets",
+            Role::Snapshot => "tuf/snapshot",
+            Role::Timestamp => "tuf/timestamp",
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Role, DecodeError> {
+        match byte {
+            0 => Ok(Role::Root),
+            1 => Ok(Role::Targets),
+            2 => Ok(Role::Snapshot),
+            3 => Ok(Role::Timestamp),
+            _ => Err(DecodeError::InvalidContent),
+        }
+    }
+
+    fn as_byte(&self) -> u8 {
+        match *self {
+            Role::Root => 0,
+            Role::Targets => 1,
+            Role::Snapshot => 2,
+            Role::Timestamp => 3,
+        }
+    }
+}
+
+/// This error is returned, if metadata or a target is not accepted.
+#[derive(Clone, PartialEq, Debug)]
+pub enum TufError {
+    /// The metadata can't be decoded.
+    Decode(DecodeError),
+    /// The metadata isn't metadata of the expected role.
+    WrongRole(Role),
+    /// The metadata isn't signed by enough keys of the role.
+    Signature(Role),
+    /// The metadata has expired.
+    Expired(Role),
+    /// The metadata is older than the trusted one, or a new root skips a version.
+    Rollback(Role),
+    /// The metadata doesn't agree with the metadata of the role above it, or the metadata of the
+    /// role above it is missing.
+    Mismatch(Role),
+    /// The target isn't listed in the targets metadata.
+    UnknownTarget(String),
+    /// The target doesn't have the listed length and digest.
+    TargetModified(String),
+}
+
+impl fmt::Display for TufError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            TufError::Decode(ref e) => write!(f, "can't decode metadata: {}", e),
+            TufError::WrongRole(role) => write!(f, "expected {} metadata", role.content_type()),
+            TufError::Signature(role) => write!(f, "{} metadata isn't signed by enough keys", role.content_type()),
+            TufError::Expired(role) => write!(f, "{} metadata has expired", role.content_type()),
+            TufError::Rollback(role) => write!(f, "{} metadata would roll back the version", role.content_type()),
+            TufError::Mismatch(role) => write!(f, "{} metadata doesn't match the metadata above it", role.content_type()),
+            TufError::UnknownTarget(ref name) => write!(f, "unknown target {}", name),
+            TufError::TargetModified(ref name) => write!(f, "target {} has been modified", name),
+        }
+    }
+}
+
+impl Error for TufError {}
+
+impl From<DecodeError> for TufError {
+    fn from(e: DecodeError) -> TufError {
+        TufError::Decode(e)
+    }
+}
+
+fn write_time(w: &mut Writer, time: &DateTime<UTC>) {
+    w.u64(time.timestamp() as u64);
+    w.u32(time.timestamp_subsec_nanos());
+}
+
+fn read_time(r: &mut Reader) -> Result<DateTime<UTC>, DecodeError> {
+    let secs = r.u64()? as i64;
+    let nanos = r.u32()?;
+    UTC.timestamp_opt(secs, nanos).single().ok_or(DecodeError::InvalidContent)
+}
+
+fn finish(r: &Reader) -> Result<(), DecodeError> {
+    if r.is_empty() {
+        Ok(())
+    } else {
+        Err(DecodeError::TrailingBytes)
+    }
+}
+
+/// The metadata of a role.
+pub trait RoleMetadata: FromFingerprint {
+    /// Returns the role that signs this metadata.
+    fn role() -> Role;
+
+    /// Returns the version of the metadata.
+    fn version(&self) -> u64;
+
+    /// Returns when the metadata expires.
+    fn expires(&self) -> &DateTime<UTC>;
+
+    /// Wraps the metadata in an unsigned letter of its role. The role's keys then sign it with
+    /// `ThresholdLetter::sign`.
+    fn into_letter(self) -> ThresholdLetter<Self> {
+        let mut header = Header::new();
+        header.set_content_type(Self::role().content_type());
+        header.set_expires(*self.expires());
+        ThresholdLetter::new(self, header)
+    }
+}
+
+/// The keys and thresholds of all roles.
+#[derive(Clone, PartialEq, Debug)]
+pub struct RootMetadata {
+    /// The version, starting at 1.
+    pub version: u64,
+    /// When the metadata expires.
+    pub expires: DateTime<UTC>,
+    /// The keys and thresholds of the roles. Every role must be present.
+    pub roles: BTreeMap<Role, Quorum>,
+}
+
+impl Fingerprint for RootMetadata {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u64(self.version);
+        write_time(&mut w, &self.expires);
+        w.u32(self.roles.len() as u32);
+
+        for (role, quorum) in &self.roles {
+            w.u8(role.as_byte());
+            w.u32(quorum.threshold() as u32);
+            w.u32(quorum.keys().len() as u32);
+            for key in quorum.keys() {
+                w.bytes(key);
+            }
+        }
+
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for RootMetadata {
+    fn from_fingerprint(bytes: &[u8]) -> Result<RootMetadata, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let version = r.u64()?;
+        let expires = read_time(&mut r)?;
+        let mut roles = BTreeMap::new();
+
+        for _ in 0..r.u32()? {
+            let role = Role::from_byte(r.u8()?)?;
+            let threshold = r.u32()? as usize;
+            let count = r.u32()?;
+            let mut keys = Vec::new();
+            for _ in 0..count {
+                keys.push(r.bytes()?.to_vec());
+            }
+
+            let quorum = Quorum::new(keys, threshold).map_err(|_| DecodeError::InvalidContent)?;
+            if roles.insert(role, quorum).is_some() {
+                return Err(DecodeError::InvalidContent);
+            }
+        }
+
+        finish(&r)?;
+
+        if roles.len() != 4 {
+            return Err(DecodeError::InvalidContent);
+        }
+
+        Ok(RootMetadata {
+            version,
+            expires,
+            roles,
+        })
+    }
+}
+
+impl RoleMetadata for RootMetadata {
+    fn role() -> Role {
+        Role::Root
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn expires(&self) -> &DateTime<UTC> {
+        &self.expires
+    }
+}
+
+/// The length and digest of a target file.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct TargetInfo {
+    /// The length in bytes.
+    pub length: u64,
+    /// The SHA-512 digest.
+    pub sha512: Vec<u8>,
+}
+
+impl TargetInfo {
+    /// Returns the info of the file contents.
+    pub fn of(data: &[u8]) -> TargetInfo {
+        TargetInfo {
+            length: data.len() as u64,
+            sha512: sha512::hash(data).0.to_vec(),
+        }
+    }
+}
+
+/// The list of target files.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TargetsMetadata {
+    /// The version, starting at 1.
+    pub version: u64,
+    /// When the metadata expires.
+    pub expires: DateTime<UTC>,
+    /// The target files by name.
+    pub targets: BTreeMap<String, TargetInfo>,
+}
+
+impl Fingerprint for TargetsMetadata {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u64(self.version);
+        write_time(&mut w, &self.expires);
+        w.u32(self.targets.len() as u32);
+
+        for (name, info) in &self.targets {
+            w.bytes(name.as_bytes());
+            w.u64(info.length);
+            w.bytes(&info.sha512);
+        }
+
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for TargetsMetadata {
+    fn from_fingerprint(bytes: &[u8]) -> Result<TargetsMetadata, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let version = r.u64()?;
+        let expires = read_time(&mut r)?;
+        let mut targets = BTreeMap::new();
+
+        for _ in 0..r.u32()? {
+            let name = String::from_utf8(r.bytes()?.to_vec()).map_err(|_| DecodeError::InvalidContent)?;
+            let info = TargetInfo {
+                length: r.u64()?,
+                sha512: r.bytes()?.to_vec(),
+            };
+
+            if targets.insert(name, info).is_some() {
+                return Err(DecodeError::InvalidContent);
+            }
+        }
+
+        finish(&r)?;
+
+        Ok(TargetsMetadata {
+            version,
+            expires,
+            targets,
+        })
+    }
+}
+
+impl RoleMetadata for TargetsMetadata {
+    fn role() -> Role {
+        Role::Targets
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn expires(&self) -> &DateTime<UTC> {
+        &self.expires
+    }
+}
+
+/// Pins the version of the targets metadata.
+#[derive(Clone, PartialEq, Debug)]
+pub struct SnapshotMetadata {
+    /// The version, starting at 1.
+    pub version: u64,
+    /// When the metadata expires.
+    pub expires: DateTime<UTC>,
+    /// The version of the current targets metadata.
+    pub targets_version: u64,
+}
+
+impl Fingerprint for SnapshotMetadata {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u64(self.version);
+        write_time(&mut w, &self.expires);
+        w.u64(self.targets_version);
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for SnapshotMetadata {
+    fn from_fingerprint(bytes: &[u8]) -> Result<SnapshotMetadata, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let snapshot = SnapshotMetadata {
+            version: r.u64()?,
+            expires: read_time(&mut r)?,
+            targets_version: r.u64()?,
+        };
+        finish(&r)?;
+        Ok(snapshot)
+    }
+}
+
+impl RoleMetadata for SnapshotMetadata {
+    fn role() -> Role {
+        Role::Snapshot
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn expires(&self) -> &DateTime<UTC> {
+        &self.expires
+    }
+}
+
+/// Pins the version and digest of the snapshot metadata.
+#[derive(Clone, PartialEq, Debug)]
+pub struct TimestampMetadata {
+    /// The version, starting at 1.
+    pub version: u64,
+    /// When the metadata expires. This is usually short, so clients notice a frozen repository.
+    pub expires: DateTime<UTC>,
+    /// The version of the current snapshot metadata.
+    pub snapshot_version: u64,
+    /// The SHA-512 digest of the serialized snapshot letter.
+    pub snapshot_sha512: Vec<u8>,
+}
+
+impl Fingerprint for TimestampMetadata {
+    fn fingerprint(&self) -> Vec<u8> {
+        let mut w = Writer::new();
+        w.u64(self.version);
+        write_time(&mut w, &self.expires);
+        w.u64(self.snapshot_version);
+        w.bytes(&self.snapshot_sha512);
+        w.into_bytes()
+    }
+}
+
+impl FromFingerprint for TimestampMetadata {
+    fn from_fingerprint(bytes: &[u8]) -> Result<TimestampMetadata, DecodeError> {
+        let mut r = Reader::new(bytes);
+        let timestamp = TimestampMetadata {
+            version: r.u64()?,
+            expires: read_time(&mut r)?,
+            snapshot_version: r.u64()?,
+            snapshot_sha512: r.bytes()?.to_vec(),
+        };
+        finish(&r)?;
+        Ok(timestamp)
+    }
+}
+
+impl RoleMetadata for TimestampMetadata {
+    fn role() -> Role {
+        Role::Timestamp
+    }
+
+    fn version(&self) -> u64 {
+        self.version
+    }
+
+    fn expires(&self) -> &DateTime<UTC> {
+        &self.expires
+    }
+}
+
+/// Checks a role letter against the quorum and the time, and returns its metadata.
+fn verify_role<T: RoleMetadata>(bytes: &[u8], quorum: &Quorum, now: &DateTime<UTC>) -> Result<T, TufError> {
+    let letter: ThresholdLetter<T> = ThresholdLetter::from_bytes(bytes)?;
+
+    if letter.header().content_type() != Some(T::role().content_type()) {
+        return Err(TufError::WrongRole(T::role()));
+    }
+
+    letter.verify(quorum).map_err(|_| TufError::Signature(T::role()))?;
+
+    if letter.get().expires() <= now {
+        return Err(TufError::Expired(T::role()));
+    }
+
+    Ok(letter.into_inner())
+}
+
+/// The trusted metadata of a client.
+pub struct TufClient<C: Clock = SystemClock> {
+    root: RootMetadata,
+    timestamp: Option<TimestampMetadata>,
+    snapshot: Option<SnapshotMetadata>,
+    targets: Option<TargetsMetadata>,
+    clock: C,
+}
+
+impl TufClient<SystemClock> {
+    /// Starts from a root letter that is trusted out of band, for example because it was shipped
+    /// with the software. It must still be signed by its own root keys and must not have expired.
+    pub fn new(root: &[u8]) -> Result<TufClient<SystemClock>, TufError> {
+        TufClient::with_clock(root, SystemClock)
+    }
+}
+
+impl<C: Clock> TufClient<C> {
+    /// Like `new`, but takes the current time from the clock.
+    pub fn with_clock(root: &[u8], clock: C) -> Result<TufClient<C>, TufError> {
+        let letter: ThresholdLetter<RootMetadata> = ThresholdLetter::from_bytes(root)?;
+        let quorum = letter.get().roles[&Role::Root].clone();
+        let root = verify_role(root, &quorum, &clock.now())?;
+
+        Ok(TufClient {
+            root,
+            timestamp: None,
+            snapshot: None,
+            targets: None,
+            clock,
+        })
+    }
+
+    /// Returns the trusted root metadata.
+    pub fn root(&self) -> &RootMetadata {
+        &self.root
+    }
+
+    /// Returns the trusted targets metadata, if any.
+    pub fn targets(&self) -> Option<&TargetsMetadata> {
+        self.targets.as_ref()
+    }
+
+    /// Accepts the next root. It must be signed by the old and the new root keys and have the
+    /// next version. The metadata of roles whose keys changed is forgotten, so it can't pin the
+    /// client to versions signed with the old keys.
+    pub fn update_root(&mut self, bytes: &[u8]) -> Result<(), TufError> {
+        let now = self.clock.now();
+        let letter: ThresholdLetter<RootMetadata> = ThresholdLetter::from_bytes(bytes)?;
+        let new_quorum = letter.get().roles[&Role::Root].clone();
+
+        let root: RootMetadata = verify_role(bytes, &self.root.roles[&Role::Root], &now)?;
+        verify_role::<RootMetadata>(bytes, &new_quorum, &now)?;
+
+        if root.version != self.root.version + 1 {
+            return Err(TufError::Rollback(Role::Root));
+        }
+
+        if root.roles[&Role::Timestamp] != self.root.roles[&Role::Timestamp] {
+            self.timestamp = None;
+        }
+        if root.roles[&Role::Snapshot] != self.root.roles[&Role::Snapshot] {
+            self.snapshot = None;
+        }
+        if root.roles[&Role::Targets] != self.root.roles[&Role::Targets] {
+            self.targets = None;
+        }
+
+        self.root = root;
+        Ok(())
+    }
+
+    /// Accepts a new timestamp.
+    pub fn update_timestamp(&mut self, bytes: &[u8]) -> Result<(), TufError> {
+        let timestamp: TimestampMetadata = verify_role(bytes, &self.root.roles[&Role::Timestamp], &self.clock.now())?;
+
+        if let Some(ref old) = self.timestamp {
+            if timestamp.version < old.version || timestamp.snapshot_version < old.snapshot_version {
+                return Err(TufError::Rollback(Role::Timestamp));
+            }
+        }
+
+        self.timestamp = Some(timestamp);
+        Ok(())
+    }
+
+    /// Accepts a new snapshot. It must be the one pinned by the trusted timestamp.
+    pub fn update_snapshot(&mut self, bytes: &[u8]) -> Result<(), TufError> {
+        let snapshot: SnapshotMetadata = verify_role(bytes, &self.root.roles[&Role::Snapshot], &self.clock.now())?;
+
+        match self.timestamp {
+            Some(ref ts) if ts.snapshot_version == snapshot.version &&
+                            ts.snapshot_sha512 == sha512::hash(bytes).0.to_vec() => {}
+            _ => return Err(TufError::Mismatch(Role::Snapshot)),
+        }
+
+        if let Some(ref old) = self.snapshot {
+            if snapshot.version < old.version || snapshot.targets_version < old.targets_version {
+                return Err(TufError::Rollback(Role::Snapshot));
+            }
+        }
+
+        self.snapshot = Some(snapshot);
+        Ok(())
+    }
+
+    /// Accepts new targets. They must have the version pinned by the trusted snapshot.
+    pub fn update_targets(&mut self, bytes: &[u8]) -> Result<(), TufError> {
+        let targets: TargetsMetadata = verify_role(bytes, &self.root.roles[&Role::Targets], &self.clock.now())?;
+
+        match self.snapshot {
+            Some(ref snapshot) if snapshot.targets_version == targets.version => {}
+            _ => return Err(TufError::Mismatch(Role::Targets)),
+        }
+
+        if let Some(ref old) = self.targets {
+            if targets.version < old.version {
+                return Err(TufError::Rollback(Role::Targets));
+            }
+        }
+
+        self.targets = Some(targets);
+        Ok(())
+    }
+
+    /// Checks that the file is a listed target, and that none of the trusted metadata has
+    /// expired since it was accepted.
+    pub fn verify_target(&self, name: &str, data: &[u8]) -> Result<(), TufError> {
+        let now = self.clock.now();
+
+        if self.root.expires <= now {
+            return Err(TufError::Expired(Role::Root));
+        }
+
+        let timestamp = self.timestamp.as_ref().ok_or(TufError::Mismatch(Role::Timestamp))?;
+        let snapshot = self.snapshot.as_ref().ok_or(TufError::Mismatch(Role::Snapshot))?;
+        let targets = self.targets.as_ref().ok_or(TufError::Mismatch(Role::Targets))?;
+
+        for &(role, expires) in &[(Role::Timestamp, &timestamp.expires),
+                                  (Role::Snapshot, &snapshot.expires),
+                                  (Role::Targets, &targets.expires)] {
+            if *expires <= now {
+                return Err(TufError::Expired(role));
+            }
+        }
+
+        match targets.targets.get(name) {
+            Some(info) if *info == TargetInfo::of(data) => Ok(()),
+            Some(_) => Err(TufError::TargetModified(name.to_string())),
+            None => Err(TufError::UnknownTarget(name.to_string())),
+        }
+    }
+}
+
+#[test]
+fn test_tuf_update() {
+    use chrono::Duration;
+    use edcert::ed25519;
+    use clock::ManualClock;
+
+    let keys: Vec<(Vec<u8>, Vec<u8>)> = (0..4).map(|_| ed25519::generate_keypair()).collect();
+    let quorum = |i: usize| Quorum::new(vec![keys[i].0.clone()], 1).unwrap();
+    let in_a_week = UTC::now() + Duration::days(7);
+
+    let mut roles = BTreeMap::new();
+    roles.insert(Role::Root, quorum(0));
+    roles.insert(Role::Targets, quorum(1));
+    roles.insert(Role::Snapshot, quorum(2));
+    roles.insert(Role::Timestamp, quorum(3));
+
+    let mut root = RootMetadata { version: 1, expires: in_a_week, roles }.into_letter();
+    root.sign(&keys[0].1).unwrap();
+
+    let firmware = b"firmware v2".to_vec();
+    let mut targets_list = BTreeMap::new();
+    targets_list.insert("firmware.bin".to_string(), TargetInfo::of(&firmware));
+    let mut targets = TargetsMetadata { version: 1, expires: in_a_week, targets: targets_list }.into_letter();
+    targets.sign(&keys[1].1).unwrap();
+    let mut snapshot = SnapshotMetadata { version: 1, expires: in_a_week, targets_version: 1 }.into_letter();
+    snapshot.sign(&keys[2].1).unwrap();
+    let snapshot = snapshot.to_bytes();
+    let mut timestamp = TimestampMetadata {
+        version: 1,
+        expires: UTC::now() + Duration::days(1),
+        snapshot_version: 1,
+        snapshot_sha512: sha512::hash(&snapshot).0.to_vec(),
+    }.into_letter();
+    timestamp.sign(&keys[3].1).unwrap();
+
+    let clock = ManualClock::new(UTC::now());
+    let mut client = TufClient::with_clock(&root.to_bytes(), &clock).unwrap();
+    assert_eq!(Err(TufError::Mismatch(Role::Snapshot)), client.update_snapshot(&snapshot));
+    client.update_timestamp(&timestamp.to_bytes()).unwrap();
+    client.update_snapshot(&snapshot).unwrap();
+    client.update_targets(&targets.to_bytes()).unwrap();
+
+    assert_eq!(Ok(()), client.verify_target("firmware.bin", &firmware));
+    assert_eq!(Err(TufError::TargetModified("firmware.bin".to_string())), client.verify_target("firmware.bin", b"evil"));
+
+    // The targets key can't sign a timestamp.
+    let mut forged = TimestampMetadata {
+        version: 2,
+        expires: in_a_week,
+        snapshot_version: 1,
+        snapshot_sha512: Vec::new(),
+    }.into_letter();
+    forged.sign(&keys[1].1).unwrap();
+    assert_eq!(Err(TufError::Signature(Role::Timestamp)), client.update_timestamp(&forged.to_bytes()));
+
+    clock.advance(Duration::days(2));
+    assert_eq!(Err(TufError::Expired(Role::Timestamp)), client.verify_target("firmware.bin", &firmware));
+}
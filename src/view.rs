@@ -0,0 +1,276 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+//! Zero-copy reading of serialized letters.
+//!
+//! `LetterView::parse` splits a serialized letter into slices of the input buffer without
+//! allocating, for hot paths that read letters out of network frames. The content can only be
+//! borrowed if it isn't compressed, so compressed letters are rejected with
+//! `DecodeError::UnsupportedCompression`; use `Letter::from_bytes` for those.
+
+use edcert::revoker::Revokable;
+use edcert::revoker::RevokeError;
+use edcert::revoker::Revoker;
+use edcert::signature::Signature;
+use edcert::validator::Validatable;
+use edcert::validator::ValidationError;
+use edcert::validator::Validator;
+
+use borrowed::LetterRef;
+use codec::Reader;
+use codec::Writer;
+use digest::HashAlgorithm;
+use format;
+use format::DecodeError;
+use format::FromFingerprint;
+use format::LetterFormatVersion;
+use format::MAGIC;
+use header::Header;
+use letter::Letter;
+use strict;
+
+/// The parts of a serialized letter, borrowed from the buffer it was read from.
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct LetterView<'a> {
+    hash_algorithm: HashAlgorithm,
+    header: &'a [u8],
+    content: &'a [u8],
+    signature: &'a [u8],
+    parent: Option<&'a [u8]>,
+}
+
+impl<'a> LetterView<'a> {
+    /// Splits a serialized letter into its parts. The header is only checked as far as needed to
+    /// find its end; `header()` parses it completely.
+    pub fn parse(bytes: &'a [u8]) -> Result<LetterView<'a>, DecodeError> {
+        let mut r = Reader::new(bytes);
+
+        if r.raw(MAGIC.len()).map_err(|_| DecodeError::InvalidMagic)? != MAGIC {
+            return Err(DecodeError::InvalidMagic);
+        }
+
+        match LetterFormatVersion::from_byte(r.u8()?)? {
+            LetterFormatVersion::V1 => {
+                let header = r.bytes()?;
+                let hash_algorithm = scan_header(header)?;
+                let content = r.bytes()?;
+                let signature = r.bytes()?;
+
+                let parent = match r.u8()? {
+                    0 => None,
+                    1 => Some(r.bytes()?),
+                    2 => return Err(DecodeError::DetachedChain),
+                    _ => return Err(DecodeError::InvalidCertificate),
+                };
+
+                if !r.is_empty() {
+                    return Err(DecodeError::TrailingBytes);
+                }
+
+                Ok(LetterView {
+                    hash_algorithm,
+                    header,
+                    content,
+                    signature,
+                    parent,
+                })
+            }
+        }
+    }
+
+    /// Returns the content bytes.
+    pub fn content(&self) -> &'a [u8] {
+        self.content
+    }
+
+    /// Returns the signature hash.
+    pub fn signature_hash(&self) -> &'a [u8] {
+        self.signature
+    }
+
+    /// Returns the encoded parent certificate, if the letter was signed by a certificate.
+    pub fn parent_bytes(&self) -> Option<&'a [u8]> {
+        self.parent
+    }
+
+    /// Parses the header.
+    pub fn header(&self) -> Result<Header, DecodeError> {
+        Header::from_bytes(self.header)
+    }
+
+    /// Parses the signature, including the parent certificate.
+    pub fn signature(&self) -> Result<Signature, DecodeError> {
+        match self.parent {
+            Some(parent) => {
+                let parent = format::decode_certificate(parent)?;
+                Ok(Signature::with_parent(Box::new(parent), self.signature.to_vec()))
+            }
+            None => Ok(Signature::new(self.signature.to_vec())),
+        }
+    }
+
+    /// Returns the bytes the signature of the letter is made over.
+    pub fn signed_bytes(&self) -> Vec<u8> {
+        // This is `Header::signed_bytes`, but over the header bytes as they were read.
+        let mut w = Writer::new();
+        w.raw(MAGIC);
+        w.u8(LetterFormatVersion::current().as_byte());
+        w.bytes(self.header);
+        w.bytes(&self.hash_algorithm.digest(self.content));
+        w.into_bytes()
+    }
+
+    /// Converts the view into a letter that borrows its content from the buffer.
+    pub fn to_letter_ref(&self) -> Result<LetterRef<'a, [u8]>, DecodeError> {
+        Ok(Letter::from_borrowed_parts(self.content, self.header()?, self.signature()?))
+    }
+
+    /// Converts the view into a letter that owns its content.
+    pub fn to_letter<T: FromFingerprint>(&self) -> Result<Letter<T>, DecodeError> {
+        Ok(Letter::from_parts(T::from_fingerprint(self.content)?, self.header()?, self.signature()?))
+    }
+}
+
+impl<'a> Validatable for LetterView<'a> {
+    fn self_validate<V: Validator>(&self, cv: &V) -> Result<(), ValidationError> {
+        let bytes = self.signed_bytes();
+
+        match self.parent {
+            None => {
+                strict::check_signature_bytes(self.signature, None)?;
+
+                if cv.is_signature_valid(&bytes, self.signature) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::SignatureInvalid)
+                }
+            }
+            Some(parent) => {
+                let parent = match format::decode_certificate(parent) {
+                    Ok(parent) => parent,
+                    Err(_) => return Err(ValidationError::ParentInvalid),
+                };
+
+                ::letter::check_parent_chain(Some(&parent), ::letter::MAX_CHAIN_DEPTH)?;
+                strict::check_signature_bytes(self.signature, Some(&parent))?;
+
+                if cv.is_valid(&parent).is_err() {
+                    Err(ValidationError::ParentInvalid)
+                } else if parent.verify(&bytes, self.signature) {
+                    Ok(())
+                } else {
+                    Err(ValidationError::SignatureInvalid)
+                }
+            }
+        }
+    }
+}
+
+impl<'a> Revokable for LetterView<'a> {
+    fn self_check_revoked<R: Revoker>(&self, revoker: &R) -> Result<(), RevokeError> {
+        let parent = match self.parent {
+            Some(parent) => format::decode_certificate(parent).map_err(|_| RevokeError::Other)?,
+            None => return Ok(()),
+        };
+
+        let mut cert = Some(&parent);
+        while let Some(c) = cert {
+            revoker.is_revoked(c)?;
+            cert = c.signature().and_then(|sig| sig.parent());
+        }
+
+        Ok(())
+    }
+}
+
+/// Walks the encoded header without allocating and returns its hash algorithm. Compressed
+/// content can't be borrowed, so compressed letters are rejected.
+fn scan_header(bytes: &[u8]) -> Result<HashAlgorithm, DecodeError> {
+    let mut r = Reader::new(bytes);
+    let hash_algorithm = HashAlgorithm::from_id(r.u8()?)?;
+
+    for _ in 0..r.u32()? {
+        r.bytes()?;
+        r.bytes()?;
+    }
+
+    r.u64()?;
+    r.u32()?;
+
+    if !r.is_empty() {
+        return Err(DecodeError::UnsupportedCompression(r.u8()?));
+    }
+
+    Ok(hash_algorithm)
+}
+
+#[test]
+fn test_view() {
+    use chrono::Duration;
+    use chrono::UTC;
+    use edcert::certificate::Certificate;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use edcert::fingerprint::Fingerprint;
+
+    use canonical::Fingerprintable;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+
+    let mut header = Header::new();
+    header.set_meta("purpose", "frame");
+    let letter = Letter::sign(Fingerprintable("hello".to_string()), header, &::signer::Signer::PrivateKey(&msk)).unwrap();
+    let bytes = letter.to_bytes();
+
+    let view = LetterView::parse(&bytes).unwrap();
+    assert_eq!(true, cv.is_valid(&view).is_ok());
+    assert_eq!(&letter.fingerprint()[..], view.content());
+    assert_eq!(letter.signed_bytes(), view.signed_bytes());
+    assert_eq!(letter, view.to_letter().unwrap());
+    assert_eq!(true, cv.is_valid(&view.to_letter_ref().unwrap()).is_ok());
+
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + Duration::days(1));
+    cert.sign_with_master(&msk);
+    let letter = Letter::sign(Fingerprintable("hello".to_string()), Header::new(), &::signer::Signer::Certificate(&cert)).unwrap();
+    let bytes = letter.to_bytes();
+    let view = LetterView::parse(&bytes).unwrap();
+    assert_eq!(true, cv.is_valid(&view).is_ok());
+
+    let mut tampered = bytes.clone();
+    let last = tampered.len() - 1;
+    tampered[last] ^= 1;
+    assert_eq!(false, LetterView::parse(&tampered).map(|v| cv.is_valid(&v).is_ok()).unwrap_or(false));
+
+    let mut trailing = bytes.clone();
+    trailing.push(0);
+    assert_eq!(Err(DecodeError::TrailingBytes), LetterView::parse(&trailing));
+
+    // Views are as strict as letters.
+    let malleated = Signature::with_parent(Box::new(cert.clone()), ::strict::malleate(letter.signature().hash()));
+    let malleated = Letter::from_parts(letter.get().clone(), Header::new(), malleated).to_bytes();
+    let view = LetterView::parse(&malleated).unwrap();
+    assert_eq!(Err(ValidationError::SignatureInvalid), cv.is_valid(&view));
+}
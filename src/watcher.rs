@@ -0,0 +1,176 @@
+// The MIT License (MIT)
+//
+// Copyright (c) 2016 Marvin Böcker
+//
+// Permission is hereby granted, free of charge, to any person obtaining a copy
+// of this software and associated documentation files (the "Software"), to deal
+// in the Software without restriction, including without limitation the rights
+// to use, copy, modify, merge, publish, distribute, sublicense, and/or sell
+// copies of the Software, and to permit persons to whom the Software is
+// furnished to do so, subject to the following conditions:
+//
+// The above copyright notice and this permission notice shall be included in all
+// copies or substantial portions of the Software.
+//
+// THE SOFTWARE IS PROVIDED "AS IS", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR
+// IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY,
+// FITNESS FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE
+// AUTHORS OR COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER
+// LIABILITY, WHETHER IN AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM,
+// OUT OF OR IN CONNECTION WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE
+// SOFTWARE.
+
+
+//! Reacting to revocations while sessions are running.
+//!
+//! A `RevocationWatcher` is fed revocation updates, for example from a channel filled by an HTTP
+//! poller or by a thread that reloads a revocation list file. For every newly revoked key it
+//! forgets the letters signed by that key in the registered `ValidationCache`s, so they are
+//! validated again on their next use, and notifies subscribers, so sessions that were
+//! authenticated with such a letter can be closed. The watcher is also a `Revoker` that rejects
+//! every key it has seen revoked.
+
+use std::path::Path;
+use std::sync::mpsc;
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::sync::RwLock;
+
+use edcert::certificate::Certificate;
+use edcert::revoker::RevokeError;
+use edcert::revoker::Revoker;
+
+use cache::ValidationCache;
+use revocation;
+use revocation::CrlError;
+use revocation::RevocationList;
+
+/// An update fed to a `RevocationWatcher`.
+#[derive(Clone, PartialEq, Debug)]
+pub enum RevocationUpdate {
+    /// A single public key has been revoked.
+    Revoked(Vec<u8>),
+    /// A complete revocation list. Keys missing from it aren't reinstated.
+    List(RevocationList),
+}
+
+/// Distributes revocations to caches and subscribers.
+#[derive(Default)]
+pub struct RevocationWatcher {
+    revoked: RwLock<RevocationList>,
+    caches: Mutex<Vec<Arc<ValidationCache>>>,
+    subscribers: Mutex<Vec<mpsc::Sender<Vec<u8>>>>,
+}
+
+impl RevocationWatcher {
+    /// Creates a watcher that doesn't know of any revocations.
+    pub fn new() -> RevocationWatcher {
+        RevocationWatcher::default()
+    }
+
+    /// Registers a cache whose letters are forgotten when their signer is revoked.
+    pub fn add_cache(&self, cache: Arc<ValidationCache>) {
+        self.caches.lock().unwrap().push(cache);
+    }
+
+    /// Returns a receiver of the public keys revoked from now on.
+    pub fn subscribe(&self) -> mpsc::Receiver<Vec<u8>> {
+        let (tx, rx) = mpsc::channel();
+        self.subscribers.lock().unwrap().push(tx);
+        rx
+    }
+
+    /// Revokes the public key. Returns false, if it was already revoked.
+    pub fn revoke(&self, public_key: &[u8]) -> bool {
+        {
+            let mut revoked = self.revoked.write().unwrap();
+            if revoked.contains(public_key) {
+                return false;
+            }
+            revoked.revoke(public_key);
+        }
+
+        for cache in self.caches.lock().unwrap().iter() {
+            let _forgotten = cache.invalidate_signer(public_key);
+            trace_event!(debug, key = %::trace::key_id(public_key), forgotten = _forgotten, "signer revoked");
+        }
+
+        // Subscribers that have gone away are dropped.
+        self.subscribers.lock().unwrap().retain(|tx| tx.send(public_key.to_vec()).is_ok());
+        true
+    }
+
+    /// Applies the update and returns the number of newly revoked keys.
+    pub fn apply(&self, update: &RevocationUpdate) -> usize {
+        match *update {
+            RevocationUpdate::Revoked(ref key) => self.revoke(key) as usize,
+            RevocationUpdate::List(ref list) => list.keys().iter().filter(|k| self.revoke(k)).count(),
+        }
+    }
+
+    /// Applies all updates from the channel until it is closed. Run this on its own thread.
+    pub fn follow(&self, updates: &mpsc::Receiver<RevocationUpdate>) {
+        for update in updates.iter() {
+            self.apply(&update);
+        }
+    }
+
+    /// Reads a signed revocation list file, like `FileRevoker` does, and applies it.
+    pub fn apply_file<P: AsRef<Path>>(&self, path: P, master_public_key: &[u8]) -> Result<usize, CrlError> {
+        let list = revocation::load_revocation_list(path.as_ref(), master_public_key)?;
+        Ok(self.apply(&RevocationUpdate::List(list.into_inner())))
+    }
+
+    /// Returns the keys revoked so far.
+    pub fn revoked(&self) -> RevocationList {
+        self.revoked.read().unwrap().clone()
+    }
+}
+
+impl Revoker for RevocationWatcher {
+    fn is_revoked(&self, cert: &Certificate) -> Result<(), RevokeError> {
+        self.revoked.read().unwrap().is_revoked(cert)
+    }
+}
+
+#[test]
+fn test_revocation_watcher() {
+    use std::thread;
+    use std::time::Duration;
+
+    use chrono::UTC;
+    use edcert::ed25519;
+    use edcert::meta::Meta;
+    use edcert::revoker::NoRevoker;
+    use edcert::root_validator::RootValidator;
+
+    use letter::Letter;
+
+    let (mpk, msk) = ed25519::generate_keypair();
+    let cv = RootValidator::new(&mpk, NoRevoker);
+    let mut cert = Certificate::generate_random(Meta::new_empty(), UTC::now() + ::chrono::Duration::days(1));
+    cert.sign_with_master(&msk);
+    let letter = Letter::with_certificate("hello", &cert).unwrap();
+
+    let cache = Arc::new(ValidationCache::new(16, Duration::from_secs(60)));
+    let watcher = Arc::new(RevocationWatcher::new());
+    watcher.add_cache(cache.clone());
+    let session = watcher.subscribe();
+
+    assert_eq!(Ok(()), cache.validate(&letter, &cv));
+    assert_eq!(1, cache.len());
+
+    let (tx, rx) = mpsc::channel();
+    let follower = {
+        let watcher = watcher.clone();
+        thread::spawn(move || watcher.follow(&rx))
+    };
+    tx.send(RevocationUpdate::Revoked(cert.public_key().clone())).unwrap();
+    drop(tx);
+    follower.join().unwrap();
+
+    assert_eq!(cert.public_key().clone(), session.recv().unwrap());
+    assert_eq!(0, cache.len());
+    assert_eq!(Err(RevokeError::Revoked), watcher.is_revoked(&cert));
+    assert_eq!(0, watcher.apply(&RevocationUpdate::Revoked(cert.public_key().clone())));
+}
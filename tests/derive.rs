@@ -0,0 +1,68 @@
+#![cfg(feature = "derive")]
+
+extern crate chrono;
+extern crate edcert;
+#[macro_use]
+extern crate edcert_letter;
+
+use chrono::DateTime;
+use chrono::UTC;
+use edcert::fingerprint::Fingerprint;
+use edcert_letter::canonical;
+
+#[derive(Fingerprint)]
+struct Handshake {
+    peer: String,
+    session: u64,
+    key: Vec<u8>,
+}
+
+#[derive(Fingerprint)]
+struct Wrapped(Handshake, Option<u32>);
+
+#[test]
+fn test_derive_encodes_fields_in_order() {
+    let h = Handshake {
+        peer: "alice".to_string(),
+        session: 7,
+        key: vec![1, 2, 3],
+    };
+
+    let expected = canonical::to_bytes(&("alice".to_string(), 7u64, vec![1u8, 2, 3]));
+    assert_eq!(expected, h.fingerprint());
+
+    let w = Wrapped(h, None);
+    let mut expected_w = expected.clone();
+    expected_w.push(0);
+    assert_eq!(expected_w, w.fingerprint());
+}
+
+#[derive(Fingerprint, Claims)]
+struct Session {
+    #[claim(issuer)]
+    iss: String,
+    #[claim(audience)]
+    aud: Option<String>,
+    #[claim(expires)]
+    exp: DateTime<UTC>,
+    roles: Vec<String>,
+}
+
+#[test]
+fn test_derive_claims() {
+    use edcert_letter::claims::Claims;
+
+    let session = Session {
+        iss: "login".to_string(),
+        aud: None,
+        exp: UTC::now(),
+        roles: vec!["admin".to_string()],
+    };
+
+    assert_eq!(Some("login"), session.issuer());
+    assert_eq!(None, session.audience());
+    assert_eq!(None, session.subject());
+    assert_eq!(Some(session.exp), session.expires());
+    assert_eq!(canonical::to_bytes(&(&session.iss, &session.aud, &session.exp, &session.roles)),
+               session.fingerprint());
+}